@@ -0,0 +1,66 @@
+//! Awaits video frames and UVC metadata concurrently in a tokio runtime.
+//!
+//! Requires the `tokio` feature: `cargo run --features tokio --example async-uvc`.
+
+use std::{env, path::Path};
+
+use anyhow::{anyhow, bail};
+use linuxvideo::{
+    format::{Format, MetaFormat, PixelFormat},
+    stream::AsyncStream,
+    uvc::UvcMetadata,
+    BufType, CapabilityFlags, Device,
+};
+
+fn usage() -> anyhow::Error {
+    anyhow!("usage: async-uvc <video device> <metadata device>")
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let mut args = env::args_os().skip(1);
+
+    let video_path = args.next().ok_or_else(usage)?;
+    let meta_path = args.next().ok_or_else(usage)?;
+
+    let video = Device::open(Path::new(&video_path))?;
+    let meta = Device::open(Path::new(&meta_path))?;
+    if !meta
+        .capabilities()?
+        .device_capabilities()
+        .contains(CapabilityFlags::META_CAPTURE)
+    {
+        bail!("metadata device does not support `META_CAPTURE` capability");
+    }
+
+    let Format::VideoCapture(fmt) = video.format(BufType::VIDEO_CAPTURE)? else {
+        unreachable!()
+    };
+    let capture = video.video_capture(fmt)?;
+    println!("negotiated format: {}", capture.format());
+
+    let mut video = AsyncStream::new(capture.into_stream()?)?;
+    let mut meta = AsyncStream::new(
+        meta.meta_capture(MetaFormat::new(PixelFormat::UVC))?
+            .into_stream()?,
+    )?;
+
+    println!("streams started, waiting for data");
+    loop {
+        tokio::select! {
+            frame = video.next_frame() => {
+                let frame = frame?;
+                println!("video #{}: {} bytes", frame.sequence(), frame.len());
+            }
+            res = meta.dequeue(|view| {
+                let count = UvcMetadata::parse_all(&view).count();
+                Ok((view.sequence(), count))
+            }) => {
+                let (sequence, count) = res?;
+                println!("meta  #{}: {} headers", sequence, count);
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Prints changes of a device's controls as they happen.
+//!
+//! Try changing the brightness from another terminal with
+//! `v4l2-ctl -d <device> --set-ctrl brightness=100`.
+
+use std::{env, path::Path};
+
+use anyhow::anyhow;
+use linuxvideo::controls::Cid;
+use linuxvideo::Device;
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let mut args = env::args_os().skip(1);
+    let path = args
+        .next()
+        .ok_or_else(|| anyhow!("usage: control-events <device> [all]"))?;
+    let all = args.next().is_some_and(|arg| arg == "all");
+
+    let mut device = Device::open(Path::new(&path))?;
+    if all {
+        device.subscribe_control_events(0)?;
+        println!("watching all controls");
+    } else {
+        device.subscribe_control_events(Cid::BRIGHTNESS)?;
+        println!(
+            "watching brightness (currently {})",
+            device.control(Cid::BRIGHTNESS)?
+        );
+    }
+
+    loop {
+        let event = device.dequeue_event()?;
+        println!(
+            "[{:?}] {:?}: value={} changes={:?} flags={:?}",
+            event.timestamp(),
+            event.id(),
+            event.value(),
+            event.changes(),
+            event.flags(),
+        );
+    }
+}
@@ -66,10 +66,10 @@ fn main() -> anyhow::Result<()> {
     match cid {
         Some(cid) => match value {
             Some(value) => {
-                device.write_control_raw(cid, value)?;
+                device.set_control(cid, value)?;
             }
             None => {
-                let value = device.read_control_raw(cid)?;
+                let value = device.control(cid)?;
                 println!("{:?} control value: {}", cid, value);
             }
         },
@@ -0,0 +1,134 @@
+//! Lists the controls of a device, similar to `v4l2-ctl --list-ctrls`.
+
+use std::{env, path::Path};
+
+use anyhow::anyhow;
+use linuxvideo::controls::{ControlDesc, CtrlClass, CtrlType};
+use linuxvideo::Device;
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let path = env::args_os()
+        .nth(1)
+        .ok_or_else(|| anyhow!("usage: ctrls <device>"))?;
+
+    let device = Device::open(Path::new(&path))?;
+    let mut class = None;
+    for desc in device.controls() {
+        let desc = desc?;
+        if class != Some(desc.class()) {
+            class = Some(desc.class());
+            // Drivers that only support `VIDIOC_QUERYCTRL` don't report class controls.
+            let title = match desc.control_type() {
+                CtrlType::CTRL_CLASS => desc.name().to_string(),
+                _ => class_title(desc.class()),
+            };
+            println!();
+            println!("{}", title);
+            println!();
+        }
+        if desc.control_type() == CtrlType::CTRL_CLASS {
+            continue;
+        }
+
+        let mut details = details(&desc);
+        if desc.control_type() != CtrlType::BUTTON {
+            // Compound and write-only controls can't be read as integers.
+            if let Ok(value) = device.control(desc.id()) {
+                details += &format!(" value={}", value);
+            }
+        }
+        println!(
+            "{:>32} {:#010x} ({:<8}): {}",
+            key(desc.name()),
+            u32::from(desc.id()),
+            type_name(desc.control_type()),
+            details,
+        );
+
+        if matches!(desc.control_type(), CtrlType::MENU | CtrlType::INTEGER_MENU) {
+            for item in device.menu_items(&desc) {
+                let item = item?;
+                println!("{:>40}: {}", item.index(), item);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Turns a control name into the lowercase identifier `v4l2-ctl` uses.
+fn key(name: &str) -> String {
+    let mut key = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            key.push(c.to_ascii_lowercase());
+        } else if !key.ends_with('_') {
+            key.push('_');
+        }
+    }
+    key.trim_matches('_').to_string()
+}
+
+/// Returns the name the kernel gives the class control of `class`.
+fn class_title(class: CtrlClass) -> String {
+    match class {
+        CtrlClass::USER => "User Controls".into(),
+        CtrlClass::CODEC => "Codec Controls".into(),
+        CtrlClass::CAMERA => "Camera Controls".into(),
+        CtrlClass::FLASH => "Flash Controls".into(),
+        CtrlClass::JPEG => "JPEG Compression Controls".into(),
+        CtrlClass::IMAGE_SOURCE => "Image Source Controls".into(),
+        CtrlClass::IMAGE_PROC => "Image Processing Controls".into(),
+        CtrlClass::DV => "Digital Video Controls".into(),
+        CtrlClass::DETECT => "Detection Controls".into(),
+        other => format!("{:?} Controls", other),
+    }
+}
+
+fn type_name(ty: CtrlType) -> String {
+    match ty {
+        CtrlType::INTEGER => "int".into(),
+        CtrlType::BOOLEAN => "bool".into(),
+        CtrlType::MENU => "menu".into(),
+        CtrlType::BUTTON => "button".into(),
+        CtrlType::INTEGER64 => "int64".into(),
+        CtrlType::STRING => "str".into(),
+        CtrlType::BITMASK => "bitmask".into(),
+        CtrlType::INTEGER_MENU => "intmenu".into(),
+        CtrlType::U8 => "u8".into(),
+        CtrlType::U16 => "u16".into(),
+        CtrlType::U32 => "u32".into(),
+        CtrlType::AREA => "area".into(),
+        CtrlType::RECT => "rect".into(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn details(desc: &ControlDesc) -> String {
+    let mut s = match desc.control_type() {
+        CtrlType::BUTTON => String::new(),
+        CtrlType::BOOLEAN => format!("default={}", desc.default_value()),
+        CtrlType::STRING => format!("min={} max={}", desc.minimum(), desc.maximum()),
+        CtrlType::BITMASK => format!(
+            "max={:#010x} default={:#010x}",
+            desc.maximum(),
+            desc.default_value()
+        ),
+        _ => format!(
+            "min={} max={} step={} default={}",
+            desc.minimum(),
+            desc.maximum(),
+            desc.step(),
+            desc.default_value()
+        ),
+    };
+    if desc.elems() > 1 || !desc.dims().is_empty() {
+        s += &format!(" elems={} dims={:?}", desc.elems(), desc.dims());
+    }
+    if !desc.flags().is_empty() {
+        s += &format!(" flags={:?}", desc.flags());
+    }
+    s
+}
@@ -30,29 +30,41 @@ fn main() -> anyhow::Result<()> {
         device.capabilities()?.device_capabilities()
     );
 
+    println!("supported formats:");
+    for desc in device.formats(BufType::VIDEO_CAPTURE) {
+        let desc = desc?;
+        println!("- [{}] {}", desc.pixel_format(), desc.description());
+        if !desc.flags().is_empty() {
+            println!("  {:?}", desc.flags());
+        }
+    }
+
     let Format::VideoCapture(fmt) = device.format(BufType::VIDEO_CAPTURE)? else {
         unreachable!()
     };
     let mut capture = device.video_capture(fmt)?;
-    println!("negotiated format: {:?}", capture.format());
+    println!("negotiated format: {}", capture.format());
     let size = capture.format().size_image() as usize;
     let mut buf = vec![0; size];
 
     println!("stream started, waiting for data");
     let mut frames = 0;
+    let mut bytes = 0;
     let mut time = Instant::now();
     loop {
-        capture.read(&mut buf)?;
+        let len = capture.read(&mut buf)?;
 
         frames += 1;
+        bytes += len;
         print!(".");
         std::io::stdout().flush().ok();
 
         if time.elapsed() >= Duration::from_secs(1) {
-            println!(" {} FPS", frames);
+            println!(" {} FPS, {} bytes/frame", frames, bytes / frames);
 
             time = Instant::now();
             frames = 0;
+            bytes = 0;
         }
     }
 }
@@ -30,11 +30,20 @@ fn main() -> anyhow::Result<()> {
         device.capabilities()?.device_capabilities()
     );
 
+    println!("supported formats:");
+    for desc in device.formats(BufType::VIDEO_CAPTURE) {
+        let desc = desc?;
+        println!("- [{}] {}", desc.pixel_format(), desc.description());
+        if !desc.flags().is_empty() {
+            println!("  {:?}", desc.flags());
+        }
+    }
+
     let Format::VideoCapture(fmt) = device.format(BufType::VIDEO_CAPTURE)? else {
         unreachable!()
     };
     let capture = device.video_capture(fmt)?;
-    println!("negotiated format: {:?}", capture.format());
+    println!("negotiated format: {}", capture.format());
 
     let mut stream = capture.into_stream()?;
 
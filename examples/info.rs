@@ -21,11 +21,7 @@ fn main() -> anyhow::Result<()> {
 
 fn list_device(device: Device) -> io::Result<()> {
     let caps = device.capabilities()?;
-    println!("card: {}", caps.card());
-    println!("driver: {}", caps.driver());
-    println!("bus info: {}", caps.bus_info());
-    println!("all capabilities:    {:?}", caps.all_capabilities());
-    println!("avail. capabilities: {:?}", caps.device_capabilities());
+    println!("{}", caps);
     for buf in device.supported_buf_types() {
         println!("- supported formats for {:?} buffers:", buf);
         for res in device.formats(buf) {
@@ -178,7 +174,7 @@ fn list_device(device: Device) -> io::Result<()> {
                         }
                         print!(", default={}]", desc.default_value());
                     }
-                    CtrlType::MENU => {
+                    CtrlType::MENU | CtrlType::INTEGER_MENU => {
                         print!(" [{}-{}]", desc.minimum(), desc.maximum());
                     }
                     _ => {}
@@ -189,12 +185,12 @@ fn list_device(device: Device) -> io::Result<()> {
                     println!("    {:?}", desc.flags());
                 }
 
-                if desc.control_type() == CtrlType::MENU {
+                if matches!(desc.control_type(), CtrlType::MENU | CtrlType::INTEGER_MENU) {
                     // Enumerate menu options.
-                    for res in device.enumerate_menu(&desc) {
+                    for res in device.menu_items(&desc) {
                         match res {
                             Ok(item) => {
-                                println!("    {}: {}", item.index(), item.name());
+                                println!("    {}: {}", item.index(), item);
                             }
                             Err(e) => {
                                 println!("    error: {}", e);
@@ -1,27 +1,27 @@
 use std::io;
 
-use linuxvideo::Device;
-
 fn main() -> io::Result<()> {
-    for res in linuxvideo::list()? {
-        match res.and_then(|device| list_device(device)) {
-            Ok(()) => {}
-            Err(e) => {
-                eprintln!("skipping device due to error: {}", e);
-            }
+    println!(
+        "{:<16} {:<12} {:<32} {:<24} CAPABILITIES",
+        "PATH", "DRIVER", "CARD", "BUS INFO"
+    );
+    for info in linuxvideo::list()? {
+        let path = info.path().display();
+        match info.capabilities() {
+            Some(caps) => println!(
+                "{:<16} {:<12} {:<32} {:<24} {:?}",
+                path,
+                caps.driver(),
+                caps.card(),
+                caps.bus_info(),
+                caps.device_capabilities(),
+            ),
+            None => println!("{:<16} error: {}", path, info.error().unwrap()),
+        }
+        for link in info.links() {
+            println!("  <- {}", link.display());
         }
     }
 
     Ok(())
 }
-
-fn list_device(device: Device) -> io::Result<()> {
-    let caps = device.capabilities()?;
-    println!("- {}: {}", device.path()?.display(), caps.card());
-    println!("  driver: {}", caps.driver());
-    println!("  bus info: {}", caps.bus_info());
-    println!("  all capabilities:    {:?}", caps.all_capabilities());
-    println!("  avail. capabilities: {:?}", caps.device_capabilities());
-
-    Ok(())
-}
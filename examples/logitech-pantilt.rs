@@ -0,0 +1,61 @@
+//! Moves the pan/tilt motors of a Logitech camera from the keyboard.
+//!
+//! Enter one of the following commands and press enter:
+//!
+//! - `w`/`a`/`s`/`d`: tilt up, pan left, tilt down, pan right
+//! - `r`: move back to the center position
+//! - `q`: quit
+
+use std::{
+    env,
+    io::{self, BufRead},
+    path::Path,
+};
+
+use anyhow::anyhow;
+use linuxvideo::{uvc::logitech::LogitechExt, Device};
+
+/// Movement per key press, in 1/64th of a degree.
+const STEP: i16 = 5 * 64;
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let mut args = env::args_os().skip(1);
+
+    let path = args
+        .next()
+        .ok_or_else(|| anyhow!("usage: logitech-pantilt <device>"))?;
+
+    let device = Device::open(Path::new(&path))?;
+    let logitech = LogitechExt::new(&device)?;
+    println!(
+        "motor control unit: {:?}, LED unit: {:?}",
+        logitech.motor_unit(),
+        logitech.led_unit(),
+    );
+    println!("commands: w/a/s/d = move, r = reset, q = quit");
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        for c in line.trim().chars() {
+            let res = match c {
+                'w' => logitech.pan_tilt_relative(0, STEP),
+                's' => logitech.pan_tilt_relative(0, -STEP),
+                'a' => logitech.pan_tilt_relative(-STEP, 0),
+                'd' => logitech.pan_tilt_relative(STEP, 0),
+                'r' => logitech.pan_tilt_reset(),
+                'q' => return Ok(()),
+                _ => {
+                    println!("unknown command '{}'", c);
+                    continue;
+                }
+            };
+            if let Err(e) = res {
+                println!("error: {}", e);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Streams a moving test pattern to a video output device (eg. `/dev/video10` created by
+//! `v4l2loopback`).
+//!
+//! The output can be viewed with any video player, eg. `ffplay /dev/video10`.
+
+use std::{
+    env, thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use linuxvideo::{
+    format::{PixFormat, PixelFormat},
+    CapabilityFlags, Device,
+};
+
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 480;
+const FRAME_TIME: Duration = Duration::from_micros(1_000_000 / 30);
+/// Width of the moving bar, in pixels.
+const BAR_WIDTH: u32 = 32;
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let mut args = env::args_os().skip(1);
+
+    let path = args
+        .next()
+        .ok_or_else(|| anyhow!("usage: output-pattern <device>"))?;
+
+    let device = Device::open(path)?;
+    let caps = device.capabilities()?.device_capabilities();
+    if !caps.contains(CapabilityFlags::VIDEO_OUTPUT | CapabilityFlags::STREAMING) {
+        bail!("device does not support streaming video output");
+    }
+
+    let output = device.video_output(PixFormat::new(WIDTH, HEIGHT, PixelFormat::YUYV))?;
+    let fmt = output.format();
+    println!("set format: {:?}", fmt);
+    if fmt.pixel_format() != PixelFormat::YUYV {
+        bail!("driver does not support YUYV output");
+    }
+    let (width, height, stride) = (fmt.width(), fmt.height(), fmt.bytes_per_line() as usize);
+    let frame_size = stride * height as usize;
+
+    let mut stream = output.into_stream()?;
+    // Fill all buffers before starting, so the driver does not underrun on the first frames.
+    stream.set_min_queued(stream.num_buffers());
+
+    println!("output started");
+    let start = Instant::now();
+    for frame in 0u32.. {
+        let bar = frame * 4 % width;
+        let luma_base = (frame % 256) as u8;
+        stream.enqueue(|mut buf| {
+            for (y, line) in buf[..frame_size].chunks_exact_mut(stride).enumerate() {
+                for (x, pixel) in line[..width as usize * 2].chunks_exact_mut(2).enumerate() {
+                    let x = x as u32;
+                    let in_bar = x.wrapping_sub(bar) % width < BAR_WIDTH;
+                    // Luma gradient that scrolls over time, with a bright moving bar.
+                    pixel[0] = if in_bar {
+                        0xeb
+                    } else {
+                        luma_base.wrapping_add((x + y as u32) as u8) / 2 + 0x10
+                    };
+                    // Alternating U (even pixels) and V (odd pixels) chroma samples.
+                    pixel[1] = if x.is_multiple_of(2) {
+                        0x80
+                    } else {
+                        (y * 255 / height as usize) as u8
+                    };
+                }
+            }
+            buf.set_bytes_used(frame_size);
+            buf.set_timestamp(start.elapsed());
+            Ok(())
+        })?;
+
+        let next = start + FRAME_TIME * (frame + 1);
+        thread::sleep(next.saturating_duration_since(Instant::now()));
+    }
+
+    Ok(())
+}
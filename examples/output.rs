@@ -17,7 +17,6 @@ use linuxvideo::{
 };
 
 // If `true`, streaming I/O will be used if supported. If `false`, read/write I/O will be used if supported.
-// TODO: does not seem to work with v4l2loopback
 const USE_STREAM: bool = false;
 
 const WIDTH: u32 = 120;
@@ -93,7 +92,7 @@ fn main() -> anyhow::Result<()> {
     loop {
         match &mut output {
             Output::Write(device) => {
-                device.write(&image)?;
+                device.write_frame(&image)?;
             }
             Output::Stream(stream) => {
                 stream.enqueue(|mut buf| {
@@ -14,7 +14,7 @@ use std::{
 use anyhow::{anyhow, bail};
 use linuxvideo::{
     format::{PixFormat, PixelFormat},
-    BufType, Device,
+    mjpeg, BufType, Device,
 };
 
 fn main() -> anyhow::Result<()> {
@@ -60,7 +60,7 @@ fn main() -> anyhow::Result<()> {
     };
 
     let capture = device.video_capture(PixFormat::new(u32::MAX, u32::MAX, format))?;
-    println!("negotiated format: {:?}", capture.format());
+    println!("negotiated format: {}", capture.format());
 
     let mut stream = capture.into_stream()?;
     println!("stream started, waiting for data");
@@ -83,10 +83,11 @@ fn main() -> anyhow::Result<()> {
             if buf.is_error() {
                 eprintln!("WARNING: error flag is set on buffer");
             }
-            file.write_all(&*buf)?;
+            let jpeg = mjpeg::extract_jpeg(&buf)?;
+            file.write_all(&jpeg)?;
             println!(
                 "wrote {} bytes to {} (raw buffer size: {} bytes)",
-                buf.len(),
+                jpeg.len(),
                 path.display(),
                 buf.raw_buffer().len(),
             );
@@ -1,4 +1,4 @@
-//! Captures BGRA video frames and encodes them as a PNG file (animated if more than one frame is
+//! Captures BGRA or YUYV video frames and encodes them as a PNG file (animated if more than one frame is
 //! captured).
 //!
 //! Uses the [`linuxvideo::stream::ReadStream`] returned by [`linuxvideo::VideoCaptureDevice::into_stream`]
@@ -7,15 +7,16 @@
 use std::{
     env,
     fs::File,
-    io::{self, stdout, BufWriter, Write},
+    io::{stdout, BufWriter, Write},
     path::Path,
     time::Instant,
 };
 
 use anyhow::{anyhow, bail};
 use linuxvideo::{
-    format::{PixFormat, PixelFormat},
-    BufType, Device,
+    convert::{self, YuvRange},
+    format::{Format, FormatPreferences, PixelFormat},
+    Device,
 };
 
 fn main() -> anyhow::Result<()> {
@@ -42,42 +43,40 @@ fn main() -> anyhow::Result<()> {
         bail!("'capture zero frames', statements dreamed up by the utterly deranged");
     }
 
-    let device = Device::open(Path::new(&device))?;
+    let mut device = Device::open(Path::new(&device))?;
     println!(
         "capabilities: {:?}",
         device.capabilities()?.device_capabilities()
     );
 
-    let formats = device
-        .formats(BufType::VIDEO_CAPTURE)
-        .map(|res| res.map(|f| f.pixel_format()))
-        .collect::<io::Result<Vec<_>>>()?;
-    let format = if formats.contains(&PixelFormat::ABGR32) {
-        PixelFormat::ABGR32
-    } else {
-        bail!(
-            "save-png does not support any of the device's formats (device supports {:?})",
-            formats
-        );
+    let prefs = FormatPreferences::new([PixelFormat::ABGR32, PixelFormat::YUYV]);
+    let Format::VideoCapture(format) = device.negotiate_capture(&prefs)? else {
+        unreachable!()
     };
-
-    let capture = device.video_capture(PixFormat::new(u32::MAX, u32::MAX, format))?;
-    println!("negotiated format: {:?}", capture.format());
+    let capture = device.video_capture(format)?;
+    let format = capture.format().pixel_format();
+    println!("negotiated format: {}", capture.format());
 
     let width = capture.format().width();
     let height = capture.format().height();
+    let stride = capture.format().bytes_per_line() as usize;
+    let range = YuvRange::from(capture.format().resolved_quantization());
+    let (color, bytes_per_pixel) = match format {
+        PixelFormat::ABGR32 => (png::ColorType::Rgba, 4),
+        _ => (png::ColorType::Rgb, 3),
+    };
     let mut stream = capture.into_stream()?;
     println!("stream started, waiting for data");
 
     let file = File::create(&file_path)?;
     let mut enc = png::Encoder::new(BufWriter::new(file), width, height);
     enc.set_animated(count, 0)?;
-    enc.set_color(png::ColorType::Rgba);
+    enc.set_color(color);
     enc.validate_sequence(true);
     // We're not using the stream writer since the basic `Writer` is already streaming on a per-frame
     // basis, which is enough for this.
     let mut writer = enc.write_header()?;
-    let mut cur_frame = vec![0; (width * height * 4) as usize];
+    let mut cur_frame = vec![0; (width * height * bytes_per_pixel) as usize];
     let mut prev_frame: Option<(Vec<u8>, _)> = None;
     for _ in 0..count + 1 {
         let arrival = stream.dequeue(|buf| {
@@ -88,14 +87,19 @@ fn main() -> anyhow::Result<()> {
 
             match format {
                 PixelFormat::ABGR32 => {
-                    // Source order: B G R A
-                    assert_eq!(cur_frame.len(), buf.len());
-                    for (dest, src) in cur_frame.chunks_exact_mut(4).zip(buf.chunks_exact(4)) {
-                        assert_eq!(dest.len(), 4);
-                        let &[b, g, r, a] = src else { unreachable!() };
-                        dest.copy_from_slice(&[r, g, b, a]);
+                    // Source order: B G R A. Lines may be padded, so use the negotiated stride.
+                    let line = width as usize * 4;
+                    for (dest, src) in cur_frame.chunks_exact_mut(line).zip(buf.chunks(stride)) {
+                        for (dest, src) in dest.chunks_exact_mut(4).zip(src[..line].chunks_exact(4))
+                        {
+                            let &[b, g, r, a] = src else { unreachable!() };
+                            dest.copy_from_slice(&[r, g, b, a]);
+                        }
                     }
                 }
+                PixelFormat::YUYV => {
+                    convert::yuyv_to_rgb24(&buf, stride, width, height, &mut cur_frame, range)?;
+                }
                 _ => unreachable!(),
             }
 
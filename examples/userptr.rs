@@ -0,0 +1,49 @@
+//! Captures video frames into buffers allocated by the application (`USERPTR` streaming).
+
+use std::{env, path::Path};
+
+use anyhow::anyhow;
+use linuxvideo::{format::Format, BufType, Device};
+
+const NUM_BUFFERS: usize = 4;
+const NUM_FRAMES: usize = 30;
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let mut args = env::args_os().skip(1);
+
+    let path = args
+        .next()
+        .ok_or_else(|| anyhow!("usage: userptr <device>"))?;
+
+    let device = Device::open(Path::new(&path))?;
+
+    let Format::VideoCapture(fmt) = device.format(BufType::VIDEO_CAPTURE)? else {
+        unreachable!()
+    };
+    let capture = device.video_capture(fmt)?;
+    println!("negotiated format: {}", capture.format());
+
+    let size = capture.format().size_image() as usize;
+    let buffers = (0..NUM_BUFFERS).map(|_| vec![0; size]).collect();
+    let mut stream = capture.into_userptr_stream(buffers)?;
+
+    println!("stream started, waiting for data");
+    for _ in 0..NUM_FRAMES {
+        stream.dequeue(|view| {
+            println!(
+                "frame #{} in buffer {} ({} bytes)",
+                view.sequence(),
+                view.index(),
+                view.len()
+            );
+            Ok(())
+        })?;
+    }
+
+    let buffers = stream.into_buffers()?;
+    println!("got {} buffers back", buffers.len());
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! Captures a still image while streaming video.
+//!
+//! Streams video from the video node, triggers a still image capture after a few frames, and uses
+//! the metadata node of the same camera to find the frame carrying the still image.
+
+use std::{env, fs, path::Path};
+
+use anyhow::{anyhow, bail};
+use linuxvideo::{
+    format::{Format, MetaFormat, PixelFormat},
+    uvc::{PairedStream, UvcExt},
+    BufType, CapabilityFlags, Device,
+};
+
+/// Number of video frames to capture before triggering the still image.
+const FRAMES_BEFORE_TRIGGER: u32 = 30;
+/// Number of frames to wait for the still image before giving up.
+const FRAMES_AFTER_TRIGGER: u32 = 60;
+
+fn usage() -> anyhow::Error {
+    anyhow!("usage: uvc-still <video device> <metadata device> <file>")
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let mut args = env::args_os().skip(1);
+
+    let video_path = args.next().ok_or_else(usage)?;
+    let meta_path = args.next().ok_or_else(usage)?;
+    let file_path = args.next().ok_or_else(usage)?;
+
+    let video = Device::open(Path::new(&video_path))?;
+    let meta = Device::open(Path::new(&meta_path))?;
+    if !meta
+        .capabilities()?
+        .device_capabilities()
+        .contains(CapabilityFlags::META_CAPTURE)
+    {
+        bail!("metadata device does not support `META_CAPTURE` capability");
+    }
+
+    // `video_capture` consumes the device, so keep a second handle for issuing UVC requests.
+    let uvc_device = video.try_clone()?;
+    let mut uvc = UvcExt::new(&uvc_device);
+    println!("still image format: {:?}", uvc.still_image_config()?);
+
+    let Format::VideoCapture(fmt) = video.format(BufType::VIDEO_CAPTURE)? else {
+        unreachable!()
+    };
+    let capture = video.video_capture(fmt)?;
+    println!("negotiated format: {}", capture.format());
+
+    let video_stream = capture.into_stream()?;
+    let meta_stream = meta
+        .meta_capture(MetaFormat::new(PixelFormat::UVC))?
+        .into_stream()?;
+    let mut stream = PairedStream::new(video_stream, meta_stream);
+
+    println!("streams started, waiting for data");
+    for i in 0..FRAMES_BEFORE_TRIGGER + FRAMES_AFTER_TRIGGER {
+        if i == FRAMES_BEFORE_TRIGGER {
+            println!("triggering still image");
+            uvc.trigger_still_image()?;
+        }
+
+        let found = stream.dequeue(|view, meta| {
+            let Some(meta) = meta else {
+                eprintln!("WARNING: no metadata for frame #{}", view.sequence());
+                return Ok(false);
+            };
+            if !meta.is_still_image() {
+                return Ok(false);
+            }
+
+            fs::write(&file_path, &*view)?;
+            println!(
+                "wrote still image (frame #{}, {} bytes) to {}",
+                view.sequence(),
+                view.len(),
+                Path::new(&file_path).display(),
+            );
+            Ok(true)
+        })?;
+        if found {
+            return Ok(());
+        }
+    }
+
+    bail!("did not receive a still image");
+}
@@ -1,4 +1,6 @@
 //! Queries UVC Extension Units (XUs).
+//!
+//! If no unit ID is given, lists the extension units of the device instead.
 
 use std::{env, path::Path};
 
@@ -6,7 +8,7 @@ use anyhow::anyhow;
 use linuxvideo::{uvc::UvcExt, Device};
 
 fn usage() -> anyhow::Error {
-    anyhow!("usage: uvc-xu <device> <extension unit ID>")
+    anyhow!("usage: uvc-xu <device> [<extension unit ID>]")
 }
 
 fn main() -> anyhow::Result<()> {
@@ -15,20 +17,56 @@ fn main() -> anyhow::Result<()> {
     let mut args = env::args_os().skip(1);
 
     let path = args.next().ok_or_else(usage)?;
-    let unit_id = args.next().ok_or_else(usage)?;
+    let device = Device::open(Path::new(&path))?;
+    let uvc = UvcExt::new(&device);
+
+    let unit_id = match args.next() {
+        Some(unit_id) => unit_id,
+        None => {
+            let units = uvc.extension_units()?;
+            if units.is_empty() {
+                println!("no extension units found");
+            }
+            for unit in units {
+                println!("{:?}", unit);
+            }
+            return Ok(());
+        }
+    };
     let unit_id: u8 = unit_id
         .to_str()
         .ok_or_else(|| anyhow!("unit ID must be an integer"))?
         .parse()?;
 
-    let device = Device::open(Path::new(&path))?;
-
-    let uvc = UvcExt::new(&device);
     let xu = uvc.extension_unit(unit_id);
 
-    for sel in 0..=0xff {
-        let res = xu.control_info(sel);
-        println!("{:#04x}: {:?}", sel, res);
+    let reports = xu.scan()?;
+    if reports.is_empty() {
+        println!("no controls found");
+    }
+    for report in reports {
+        let access = match (report.info.is_readable(), report.info.is_writable()) {
+            (true, true) => "read/write",
+            (true, false) => "read-only",
+            (false, true) => "write-only",
+            (false, false) => "no access",
+        };
+        println!("{:#04x}: {} ({:?})", report.selector, access, report.info);
+        match report.len {
+            Some(len) => println!("      len: {}", len),
+            None => println!("      len: -"),
+        }
+        for (name, value) in [
+            ("min", &report.min),
+            ("max", &report.max),
+            ("def", &report.default),
+            ("cur", &report.current),
+        ] {
+            match value {
+                Some(value) => println!("      {}: {:02x?}", name, value),
+                None => println!("      {}: -", name),
+            }
+        }
     }
 
     Ok(())
@@ -1,6 +1,6 @@
-//! Dumps UVC metadata frames.
+//! Prints a summary of every UVC payload header received via the metadata device.
 
-use std::{env, path::Path};
+use std::{env, path::Path, time::Duration};
 
 use anyhow::{anyhow, bail};
 use linuxvideo::{
@@ -9,6 +9,9 @@ use linuxvideo::{
     CapabilityFlags, Device,
 };
 
+/// Warn when the device does not produce any metadata for this long.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
 fn main() -> anyhow::Result<()> {
     env_logger::init();
 
@@ -32,10 +35,32 @@ fn main() -> anyhow::Result<()> {
 
     println!("stream started, waiting for data");
     loop {
-        stream.dequeue(|view| {
-            let meta = UvcMetadata::from_bytes(&view);
-            eprintln!("{:?}", meta);
+        let res = stream.dequeue_timeout(TIMEOUT, |view| {
+            for meta in UvcMetadata::parse_all(&view) {
+                let meta = meta?;
+                let info = meta.header_info();
+                print!(
+                    "#{}: {}, err={}, fid={}",
+                    view.sequence(),
+                    if info.is_end_of_frame() { "EOF" } else { "---" },
+                    info.has_error(),
+                    info.frame_id(),
+                );
+                if let Some(pts) = meta.presentation_time() {
+                    print!(", pts={}", pts);
+                }
+                if let Some(scr) = meta.source_clock() {
+                    print!(", stc={}, sof={}", scr.stc, scr.sof_counter);
+                }
+                if info.is_still_image() {
+                    print!(", still");
+                }
+                println!();
+            }
             Ok(())
         })?;
+        if res.is_none() {
+            eprintln!("WARNING: no metadata received for {:?}", TIMEOUT);
+        }
     }
 }
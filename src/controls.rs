@@ -1,20 +1,70 @@
 //! Device control enumeration and access.
 
+use std::ops::RangeInclusive;
 use std::{fmt, io, mem};
 
 use nix::errno::Errno;
 
-use crate::shared::CONTROL_FLAGS_NEXT_CTRL;
+use crate::raw::controls::CtrlWhich;
+use crate::shared::{CONTROL_FLAGS_NEXT_COMPOUND, CONTROL_FLAGS_NEXT_CTRL};
 use crate::{byte_array_to_str, raw, Device};
 
-pub use crate::raw::controls::Cid;
-pub use crate::shared::{ControlFlags, CtrlType};
+mod batch;
+mod event;
+pub(crate) mod exposure;
+pub(crate) mod focus;
+pub(crate) mod menu;
+pub mod ptz;
+mod range;
+mod reset;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod snapshot;
+mod value;
+pub(crate) mod white_balance;
+
+pub use crate::raw::controls::{Cid, CtrlClass};
+pub use crate::shared::{ControlChanges, ControlFlags, CtrlType};
+pub(crate) use batch::{read_batch, write_batch};
+pub use batch::{ControlBatch, ControlBatchError};
+pub use event::ControlEvent;
+pub(crate) use event::{
+    dequeue as dequeue_event, subscribe as subscribe_events, unsubscribe as unsubscribe_events,
+};
+pub use exposure::ExposureMode;
+pub use focus::AutoFocusStatus;
+pub use menu::{MenuControl, PowerLineFrequency};
+pub use range::RangeError;
+pub(crate) use range::{check_range, validate, write_clamped};
+pub use reset::ResetReport;
+pub(crate) use reset::{reset_all, reset_one};
+pub(crate) use snapshot::{apply as apply_snapshot, snapshot as control_snapshot};
+pub use snapshot::{ApplyReport, ControlSnapshot};
+pub(crate) use value::{press_button, read_integer, read_value, write_integer, write_value};
+pub use value::{Area, ControlValue};
+pub use white_balance::WhiteBalance;
+
+/// Selects one of the `VIDIOC_*_EXT_CTRLS` ioctls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExtCtrlsIoctl {
+    Get,
+    Set,
+    Try,
+}
 
 /// Iterator over the control descriptors of a device.
+///
+/// Controls are enumerated with `VIDIOC_QUERY_EXT_CTRL`, which also reports compound (array and
+/// structure) controls and 64-bit ranges. Drivers that don't support it are enumerated with the
+/// older `VIDIOC_QUERYCTRL` instead.
+///
+/// Disabled controls are included, with [`ControlFlags::DISABLED`] set in their
+/// [`ControlDesc::flags`].
 pub struct ControlIter<'a> {
     device: &'a Device,
     next_cid: Cid,
     finished: bool,
+    use_query_ext_ctrl: bool,
     use_ctrl_flag_next_ctrl: bool,
 }
 
@@ -22,8 +72,10 @@ impl<'a> ControlIter<'a> {
     pub(crate) fn new(device: &'a Device) -> Self {
         Self {
             device,
-            next_cid: Cid::BASE,
+            // `NEXT_CTRL` returns the first control with a larger ID, so start below all of them.
+            next_cid: Cid(0),
             finished: false,
+            use_query_ext_ctrl: true,
             use_ctrl_flag_next_ctrl: true,
         }
     }
@@ -42,50 +94,247 @@ impl Iterator for ControlIter<'_> {
                 return None;
             }
 
-            unsafe {
-                let mut id = self.next_cid.0;
-                if self.use_ctrl_flag_next_ctrl {
-                    id |= CONTROL_FLAGS_NEXT_CTRL;
+            let mut id = self.next_cid.0;
+            if self.use_ctrl_flag_next_ctrl {
+                id |= CONTROL_FLAGS_NEXT_CTRL;
+                if self.use_query_ext_ctrl {
+                    id |= CONTROL_FLAGS_NEXT_COMPOUND;
                 }
-                let mut raw = raw::QueryCtrl {
-                    id,
-                    ..mem::zeroed()
-                };
-                match raw::queryctrl(self.device.fd(), &mut raw) {
-                    Ok(_) => {
-                        if self.use_ctrl_flag_next_ctrl {
-                            self.next_cid.0 = raw.id;
-                        } else {
-                            self.next_cid.0 += 1;
-                        }
+            }
+            match query(self.device, id, self.use_query_ext_ctrl) {
+                Ok(raw) => {
+                    if self.use_ctrl_flag_next_ctrl {
+                        self.next_cid.0 = raw.id;
+                    } else {
+                        self.next_cid.0 += 1;
                     }
-                    Err(e) => {
-                        match e {
-                            Errno::EINVAL => {
-                                self.use_ctrl_flag_next_ctrl = false;
-                                self.next_cid.0 += 1;
-                                continue; // continue, because there might be gaps
-                            }
-                            e => {
-                                self.finished = true;
-                                return Some(Err(e.into()));
-                            }
+                    return Some(Ok(ControlDesc(raw)));
+                }
+                Err(Errno::ENOTTY) if self.use_query_ext_ctrl => {
+                    // Retry the same ID with `VIDIOC_QUERYCTRL`.
+                    self.use_query_ext_ctrl = false;
+                    continue;
+                }
+                Err(Errno::EINVAL) if self.use_query_ext_ctrl => {
+                    // `VIDIOC_QUERY_EXT_CTRL` always supports `NEXT_CTRL`, so this is the end.
+                    self.finished = true;
+                    return None;
+                }
+                Err(Errno::EINVAL) => {
+                    if self.use_ctrl_flag_next_ctrl {
+                        // Without `NEXT_CTRL`, only the user class controls can be walked.
+                        self.use_ctrl_flag_next_ctrl = false;
+                        if self.next_cid.0 < Cid::BASE.0 {
+                            self.next_cid = Cid::BASE;
+                            continue;
                         }
                     }
+                    self.next_cid.0 += 1;
+                    continue; // continue, because there might be gaps
                 }
-
-                if raw.flags.contains(ControlFlags::DISABLED) {
-                    continue;
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e.into()));
                 }
-
-                return Some(Ok(ControlDesc(raw)));
             }
         }
     }
 }
 
+/// Queries control `id` with `VIDIOC_QUERY_EXT_CTRL`, or `VIDIOC_QUERYCTRL` if `ext` is `false`.
+fn query(device: &Device, id: u32, ext: bool) -> Result<raw::QueryExtCtrl, Errno> {
+    unsafe {
+        if ext {
+            let mut raw = raw::QueryExtCtrl {
+                id,
+                ..mem::zeroed()
+            };
+            raw::query_ext_ctrl(device.fd(), &mut raw)?;
+            Ok(raw)
+        } else {
+            let mut raw = raw::QueryCtrl {
+                id,
+                ..mem::zeroed()
+            };
+            raw::queryctrl(device.fd(), &mut raw)?;
+            Ok(ext_from_legacy(&raw))
+        }
+    }
+}
+
+/// Returns the control class `cid` belongs to.
+pub(crate) fn class_of(cid: Cid) -> CtrlClass {
+    CtrlClass(cid.0 & 0x0fff0000)
+}
+
+/// Reads the default values of all controls in `class` with a single ioctl.
+pub(crate) fn read_class_defaults(
+    device: &Device,
+    class: CtrlClass,
+) -> Result<ControlBatch, ControlBatchError> {
+    let mut descs = Vec::new();
+    for desc in device.controls() {
+        let desc = desc.map_err(ControlBatchError::from_io)?;
+        if desc.class() == class
+            && !desc.flags().contains(ControlFlags::DISABLED)
+            && !matches!(desc.control_type(), CtrlType::BUTTON | CtrlType::CTRL_CLASS)
+        {
+            descs.push(desc);
+        }
+    }
+    batch::read_descs(device, &descs, CtrlWhich::DEF_VAL)
+}
+
+/// Returns the descriptor of the control `cid`.
+pub(crate) fn query_control(device: &Device, cid: Cid) -> io::Result<ControlDesc> {
+    let raw = match query(device, cid.0, true) {
+        Err(Errno::ENOTTY) => query(device, cid.0, false),
+        res => res,
+    };
+    raw.map(ControlDesc).map_err(|e| match e {
+        Errno::EINVAL => io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("device does not have control {:?}", cid),
+        ),
+        e => e.into(),
+    })
+}
+
+/// Returns the descriptor of the control `cid`, or `None` if the device doesn't have it or it is
+/// disabled.
+pub(crate) fn query_optional(device: &Device, cid: Cid) -> io::Result<Option<ControlDesc>> {
+    match query_control(device, cid) {
+        Ok(desc) if desc.flags().contains(ControlFlags::DISABLED) => Ok(None),
+        Ok(desc) => Ok(Some(desc)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns an error indicating that the device lacks the controls needed for `operation`.
+pub(crate) fn unsupported(operation: &str, cids: &[Cid]) -> io::Error {
+    let cids = cids
+        .iter()
+        .map(|cid| format!("{:?}", cid))
+        .collect::<Vec<_>>()
+        .join(" or ");
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "{} is not supported: device has no {} control",
+            operation, cids
+        ),
+    )
+}
+
+/// Returns the automatic mode control that makes `cid` inactive while it is enabled.
+fn auto_control(cid: Cid) -> Option<Cid> {
+    Some(match cid {
+        Cid::EXPOSURE | Cid::EXPOSURE_ABSOLUTE | Cid::IRIS_ABSOLUTE => Cid::EXPOSURE_AUTO,
+        Cid::GAIN => Cid::AUTOGAIN,
+        Cid::BRIGHTNESS => Cid::AUTOBRIGHTNESS,
+        Cid::HUE => Cid::HUE_AUTO,
+        Cid::RED_BALANCE | Cid::BLUE_BALANCE | Cid::WHITE_BALANCE_TEMPERATURE => {
+            Cid::AUTO_WHITE_BALANCE
+        }
+        Cid::FOCUS_ABSOLUTE | Cid::FOCUS_RELATIVE => Cid::FOCUS_AUTO,
+        _ => return None,
+    })
+}
+
+/// Like [`control_error`], but re-queries the control first, since its flags may have changed
+/// since `desc` was obtained.
+pub(crate) fn access_error(
+    device: &Device,
+    e: io::Error,
+    desc: &ControlDesc,
+    value: Option<i64>,
+) -> io::Error {
+    let flags = query_control(device, desc.id()).map_or(desc.flags(), |desc| desc.flags());
+    control_error(e, desc.id(), flags, value)
+}
+
+/// Adds context to errors returned when accessing the control `cid`.
+///
+/// `flags` are the current flags of the control, and `value` is the value that was attempted to
+/// be written, if any.
+pub(crate) fn control_error(
+    e: io::Error,
+    cid: Cid,
+    flags: ControlFlags,
+    value: Option<i64>,
+) -> io::Error {
+    let errno = e.raw_os_error().map(Errno::from_i32);
+    let inactive = matches!(errno, Some(Errno::EACCES | Errno::EINVAL | Errno::EPERM))
+        && flags.contains(ControlFlags::INACTIVE);
+    let (kind, msg) = match (errno, value) {
+        _ if inactive => (
+            io::ErrorKind::PermissionDenied,
+            match auto_control(cid) {
+                Some(auto) => format!("control {:?} is inactive while {:?} is enabled", cid, auto),
+                None => format!("control {:?} is inactive", cid),
+            },
+        ),
+        (Some(Errno::EINVAL), _) if flags.contains(ControlFlags::DISABLED) => (
+            io::ErrorKind::NotFound,
+            format!("control {:?} is disabled", cid),
+        ),
+        (Some(Errno::EACCES), Some(_)) if flags.contains(ControlFlags::READ_ONLY) => (
+            io::ErrorKind::PermissionDenied,
+            format!("control {:?} is read-only", cid),
+        ),
+        (Some(Errno::EACCES), None) if flags.contains(ControlFlags::WRITE_ONLY) => (
+            io::ErrorKind::PermissionDenied,
+            format!("control {:?} is write-only and cannot be read", cid),
+        ),
+        (Some(Errno::EBUSY), _) => (
+            e.kind(),
+            format!(
+                "control {:?} is busy (grabbed by another file handle, or locked while streaming)",
+                cid
+            ),
+        ),
+        (Some(Errno::ERANGE), Some(value)) => (
+            io::ErrorKind::InvalidInput,
+            format!("value {} is out of range for control {:?}", value, cid),
+        ),
+        (Some(Errno::ERANGE), None) => (
+            io::ErrorKind::InvalidInput,
+            format!("value of control {:?} is out of range", cid),
+        ),
+        _ => return e,
+    };
+    io::Error::new(kind, msg)
+}
+
+/// Converts the result of `VIDIOC_QUERYCTRL` to what `VIDIOC_QUERY_EXT_CTRL` would return.
+fn ext_from_legacy(legacy: &raw::QueryCtrl) -> raw::QueryExtCtrl {
+    let elem_size = match legacy.type_ {
+        CtrlType::BUTTON | CtrlType::CTRL_CLASS => 0,
+        CtrlType::INTEGER64 => 8,
+        CtrlType::STRING => legacy.maximum as u32 + 1,
+        _ => 4,
+    };
+    raw::QueryExtCtrl {
+        id: legacy.id,
+        type_: legacy.type_,
+        name: legacy.name,
+        minimum: legacy.minimum.into(),
+        maximum: legacy.maximum.into(),
+        step: legacy.step.max(0) as u64,
+        default_value: legacy.default_value.into(),
+        flags: legacy.flags,
+        elem_size,
+        elems: 1,
+        nr_of_dims: 0,
+        dims: [0; raw::CTRL_MAX_DIMS],
+        reserved: [0; 32],
+    }
+}
+
 /// Describes a device control.
-pub struct ControlDesc(raw::QueryCtrl);
+#[derive(Clone)]
+pub struct ControlDesc(raw::QueryExtCtrl);
 
 impl ControlDesc {
     /// The control's identifier.
@@ -99,36 +348,103 @@ impl ControlDesc {
         byte_array_to_str(&self.0.name)
     }
 
+    /// Returns the class the control belongs to.
+    ///
+    /// Drivers may define their own classes, which don't correspond to any of the [`CtrlClass`]
+    /// constants.
+    #[inline]
+    pub fn class(&self) -> CtrlClass {
+        class_of(self.id())
+    }
+
     /// Returns the type of value this control expects.
     #[inline]
     pub fn control_type(&self) -> CtrlType {
         self.0.type_
     }
 
+    /// Returns the minimum value of the control.
+    ///
+    /// For [`CtrlType::STRING`] controls, this is the minimum length of the string.
     #[inline]
-    pub fn minimum(&self) -> i32 {
+    pub fn minimum(&self) -> i64 {
         self.0.minimum
     }
 
+    /// Returns the maximum value of the control.
+    ///
+    /// For [`CtrlType::STRING`] controls, this is the maximum length of the string.
     #[inline]
-    pub fn maximum(&self) -> i32 {
+    pub fn maximum(&self) -> i64 {
         self.0.maximum
     }
 
+    /// Returns the step size between valid values of the control.
     #[inline]
-    pub fn step(&self) -> i32 {
+    pub fn step(&self) -> u64 {
         self.0.step
     }
 
     #[inline]
-    pub fn default_value(&self) -> i32 {
+    pub fn default_value(&self) -> i64 {
         self.0.default_value
     }
 
+    /// Returns the control's flags at the time it was queried.
+    ///
+    /// Some flags, like [`ControlFlags::INACTIVE`] and [`ControlFlags::GRABBED`], change while
+    /// the device is in use. [`Device::query_control`] returns a descriptor with the current
+    /// flags.
     #[inline]
     pub fn flags(&self) -> ControlFlags {
         self.0.flags
     }
+
+    /// Returns whether the control is a [`BUTTON`][CtrlType::BUTTON].
+    ///
+    /// Buttons have no value, and trigger an action when pressed with [`Device::press_button`].
+    /// User interfaces should show them as buttons rather than as sliders or input fields.
+    #[inline]
+    pub fn is_button(&self) -> bool {
+        self.control_type() == CtrlType::BUTTON
+    }
+
+    /// Returns whether the control can currently be changed.
+    ///
+    /// This is `false` for controls that are disabled, read-only, inactive (eg. the exposure time
+    /// while automatic exposure is enabled), or grabbed (eg. the pixel format while streaming),
+    /// and for control class headers.
+    pub fn is_actionable(&self) -> bool {
+        let blocked = ControlFlags::DISABLED
+            | ControlFlags::READ_ONLY
+            | ControlFlags::INACTIVE
+            | ControlFlags::GRABBED;
+        !self.flags().intersects(blocked) && self.control_type() != CtrlType::CTRL_CLASS
+    }
+
+    /// Returns the size of a single element of the control's value in bytes.
+    ///
+    /// For [`CtrlType::STRING`] controls, this is the size of the buffer needed to hold the
+    /// longest string, including the terminating NUL byte.
+    #[inline]
+    pub fn elem_size(&self) -> u32 {
+        self.0.elem_size
+    }
+
+    /// Returns the number of elements of the control's value.
+    ///
+    /// This is 1 for controls that aren't arrays.
+    #[inline]
+    pub fn elems(&self) -> u32 {
+        self.0.elems
+    }
+
+    /// Returns the dimensions of an array control, or an empty slice if the control isn't an
+    /// array.
+    pub fn dims(&self) -> &[u32] {
+        let count = (self.0.nr_of_dims as usize).min(raw::CTRL_MAX_DIMS);
+        &self.0.dims[..count]
+    }
 }
 
 impl fmt::Debug for ControlDesc {
@@ -136,65 +452,153 @@ impl fmt::Debug for ControlDesc {
         f.debug_struct("ControlDesc")
             .field("id", &self.id())
             .field("name", &self.name())
+            .field("class", &self.class())
             .field("control_type", &self.control_type())
             .field("minimum", &self.minimum())
             .field("maximum", &self.maximum())
             .field("step", &self.step())
             .field("default_value", &self.default_value())
             .field("flags", &self.flags())
+            .field("elem_size", &self.elem_size())
+            .field("elems", &self.elems())
+            .field("dims", &self.dims())
             .finish()
     }
 }
 
-/// An iterator over a menu control's valid choices.
+/// An iterator over the items of a menu control, returned by [`Device::menu_items`].
 ///
-/// Note that the returned [`TextMenuItem`]s might not have contiguous indices, since this iterator
+/// Note that the returned [`MenuItem`]s might not have contiguous indices, since this iterator
 /// automatically skips invalid indices.
-pub struct TextMenuIter<'a> {
+pub struct MenuIter<'a> {
     device: &'a Device,
     cid: Cid,
-    next_index: u32,
-    /// Highest allowed index.
-    max_index: u32,
+    integer: bool,
+    indices: RangeInclusive<u32>,
 }
 
-impl<'a> TextMenuIter<'a> {
+impl<'a> MenuIter<'a> {
     pub(crate) fn new(device: &'a Device, ctrl: &ControlDesc) -> Self {
-        assert_eq!(ctrl.control_type(), CtrlType::MENU, "menu control required");
+        let integer = match ctrl.control_type() {
+            CtrlType::MENU => false,
+            CtrlType::INTEGER_MENU => true,
+            _ => panic!("menu control required"),
+        };
+        let index = |value: i64| value.clamp(0, u32::MAX.into()) as u32;
 
         Self {
             device,
             cid: ctrl.id(),
-            next_index: ctrl.minimum() as _,
-            max_index: ctrl.maximum() as _,
+            integer,
+            indices: index(ctrl.minimum())..=index(ctrl.maximum()),
         }
     }
 }
 
-impl Iterator for TextMenuIter<'_> {
-    type Item = io::Result<TextMenuItem>;
+impl Iterator for MenuIter<'_> {
+    type Item = io::Result<MenuItem>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            if self.next_index > self.max_index {
-                return None;
-            } else {
-                unsafe {
-                    let mut raw = raw::QueryMenu {
-                        id: self.cid.0,
-                        index: self.next_index,
-                        ..mem::zeroed()
-                    };
-
-                    self.next_index += 1;
-                    match raw::querymenu(self.device.fd(), &mut raw) {
-                        Ok(_) => return Some(Ok(TextMenuItem { raw })),
-                        Err(Errno::EINVAL) => continue,
-                        Err(other) => return Some(Err(other.into())),
+        for index in self.indices.by_ref() {
+            unsafe {
+                let mut raw = raw::QueryMenu {
+                    id: self.cid.0,
+                    index,
+                    ..mem::zeroed()
+                };
+
+                match raw::querymenu(self.device.fd(), &mut raw) {
+                    Ok(_) => {
+                        return Some(Ok(MenuItem {
+                            raw,
+                            integer: self.integer,
+                        }))
                     }
+                    // Drivers may leave holes in the index range.
+                    Err(Errno::EINVAL) => continue,
+                    Err(other) => return Some(Err(other.into())),
                 }
             }
         }
+        None
+    }
+}
+
+/// An item of a [`CtrlType::MENU`] or [`CtrlType::INTEGER_MENU`] control.
+pub struct MenuItem {
+    raw: raw::QueryMenu,
+    integer: bool,
+}
+
+impl MenuItem {
+    /// The item's index. Setting the menu control to this value will choose this item.
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.raw.index
+    }
+
+    /// Returns the human-readable name of this item, or `None` if it belongs to an integer menu.
+    pub fn name(&self) -> Option<&str> {
+        if self.integer {
+            None
+        } else {
+            Some(byte_array_to_str(unsafe { &self.raw.name_or_value.name }))
+        }
+    }
+
+    /// Returns the value this item stands for, or `None` if it belongs to a text menu.
+    pub fn value(&self) -> Option<i64> {
+        if self.integer {
+            Some(unsafe { self.raw.name_or_value.value })
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for MenuItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.name(), self.value()) {
+            (Some(name), _) => f.write_str(name),
+            (None, Some(value)) => write!(f, "{}", value),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Debug for MenuItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("MenuItem");
+        s.field("index", &self.index());
+        match self.value() {
+            Some(value) => s.field("value", &value),
+            None => s.field("name", &self.name().unwrap()),
+        };
+        s.finish()
+    }
+}
+
+/// An iterator over a text menu control's valid choices.
+///
+/// Note that the returned [`TextMenuItem`]s might not have contiguous indices, since this iterator
+/// automatically skips invalid indices.
+pub struct TextMenuIter<'a>(MenuIter<'a>);
+
+impl<'a> TextMenuIter<'a> {
+    pub(crate) fn new(device: &'a Device, ctrl: &ControlDesc) -> Self {
+        assert_eq!(ctrl.control_type(), CtrlType::MENU, "menu control required");
+
+        Self(MenuIter::new(device, ctrl))
+    }
+}
+
+impl Iterator for TextMenuIter<'_> {
+    type Item = io::Result<TextMenuItem>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0
+            .next()
+            .map(|res| res.map(|item| TextMenuItem { raw: item.raw }))
     }
 }
 
@@ -215,3 +619,137 @@ impl TextMenuItem {
         byte_array_to_str(unsafe { &self.raw.name_or_value.name })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_query() {
+        let mut legacy: raw::QueryCtrl = unsafe { mem::zeroed() };
+        legacy.id = Cid::BRIGHTNESS.0;
+        legacy.type_ = CtrlType::INTEGER;
+        legacy.minimum = -128;
+        legacy.maximum = 127;
+        legacy.step = 1;
+        legacy.flags = ControlFlags::SLIDER;
+        let desc = ControlDesc(ext_from_legacy(&legacy));
+        assert_eq!(desc.id(), Cid::BRIGHTNESS);
+        assert_eq!(
+            (desc.minimum(), desc.maximum(), desc.step()),
+            (-128, 127, 1)
+        );
+        assert_eq!((desc.elem_size(), desc.elems()), (4, 1));
+        assert!(desc.dims().is_empty());
+
+        legacy.type_ = CtrlType::STRING;
+        legacy.maximum = 31;
+        assert_eq!(ext_from_legacy(&legacy).elem_size, 32);
+    }
+
+    #[test]
+    fn cid() {
+        assert_eq!(Cid::BRIGHTNESS.name(), Some("BRIGHTNESS"));
+        assert_eq!(Cid::from(0x009a0901), Cid::EXPOSURE_AUTO);
+        assert_eq!(u32::from(Cid::JPEG_COMPRESSION_QUALITY), 0x009d0903);
+        let vendor = Cid::from(0x0098f001);
+        assert_eq!(vendor.name(), None);
+        assert_eq!(format!("{:?}", vendor), "(unknown: 0x98f001)");
+        assert_eq!(class_of(vendor), CtrlClass::USER);
+        assert_eq!(class_of(Cid::EXPOSURE_AUTO), CtrlClass::CAMERA);
+        assert_eq!(class_of(Cid::JPEG_COMPRESSION_QUALITY), CtrlClass::JPEG);
+    }
+
+    #[test]
+    fn errors() {
+        let none = ControlFlags::empty();
+        let e = control_error(Errno::ERANGE.into(), Cid::BRIGHTNESS, none, Some(300));
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            e.to_string(),
+            "value 300 is out of range for control BRIGHTNESS"
+        );
+        let e = control_error(Errno::EBUSY.into(), Cid::BRIGHTNESS, none, Some(300));
+        assert_eq!(e.kind(), io::ErrorKind::ResourceBusy);
+        assert!(e.to_string().starts_with("control BRIGHTNESS is busy"));
+        let e = control_error(Errno::EIO.into(), Cid::BRIGHTNESS, none, None);
+        assert_eq!(e.raw_os_error(), Some(Errno::EIO as i32));
+
+        let inactive = ControlFlags::INACTIVE;
+        let e = control_error(
+            Errno::EACCES.into(),
+            Cid::EXPOSURE_ABSOLUTE,
+            inactive,
+            Some(5),
+        );
+        assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            e.to_string(),
+            "control EXPOSURE_ABSOLUTE is inactive while EXPOSURE_AUTO is enabled"
+        );
+        let e = control_error(Errno::EINVAL.into(), Cid::CONTRAST, inactive, Some(5));
+        assert_eq!(e.to_string(), "control CONTRAST is inactive");
+
+        let e = control_error(
+            Errno::EACCES.into(),
+            Cid::GAIN,
+            ControlFlags::READ_ONLY,
+            Some(1),
+        );
+        assert_eq!(e.to_string(), "control GAIN is read-only");
+        let write_only = ControlFlags::WRITE_ONLY;
+        let e = control_error(Errno::EACCES.into(), Cid::PAN_RESET, write_only, None);
+        assert_eq!(
+            e.to_string(),
+            "control PAN_RESET is write-only and cannot be read"
+        );
+        // Errors that the flags don't explain are passed through.
+        let e = control_error(Errno::EACCES.into(), Cid::GAIN, write_only, Some(1));
+        assert_eq!(e.raw_os_error(), Some(Errno::EACCES as i32));
+    }
+
+    #[test]
+    fn actionable() {
+        let mut raw = ext_from_legacy(&unsafe { mem::zeroed() });
+        raw.type_ = CtrlType::INTEGER;
+        assert!(ControlDesc(raw).is_actionable());
+        for flag in [ControlFlags::INACTIVE, ControlFlags::GRABBED] {
+            let mut raw = ext_from_legacy(&unsafe { mem::zeroed() });
+            raw.flags = flag | ControlFlags::SLIDER;
+            assert!(!ControlDesc(raw).is_actionable());
+        }
+        let mut raw = ext_from_legacy(&unsafe { mem::zeroed() });
+        raw.type_ = CtrlType::CTRL_CLASS;
+        assert!(!ControlDesc(raw).is_actionable());
+        raw.type_ = CtrlType::BUTTON;
+        assert!(ControlDesc(raw).is_button());
+    }
+
+    #[test]
+    fn menu_item() {
+        let mut name = [0; 32];
+        name[..5].copy_from_slice(b"50 Hz");
+        let raw = |name_or_value| raw::QueryMenu {
+            id: Cid::POWER_LINE_FREQUENCY.0,
+            index: 2,
+            name_or_value,
+            reserved: 0,
+        };
+        let item = MenuItem {
+            raw: raw(raw::QueryMenuUnion { name }),
+            integer: false,
+        };
+        assert_eq!((item.name(), item.value()), (Some("50 Hz"), None));
+        assert_eq!(item.to_string(), "50 Hz");
+
+        let item = MenuItem {
+            raw: raw(raw::QueryMenuUnion { value: -3 }),
+            integer: true,
+        };
+        assert_eq!(
+            (item.index(), item.name(), item.value()),
+            (2, None, Some(-3))
+        );
+        assert_eq!(format!("{:?}", item), "MenuItem { index: 2, value: -3 }");
+    }
+}
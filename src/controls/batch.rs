@@ -0,0 +1,323 @@
+//! Reading and writing several controls at once.
+
+use std::{fmt, io};
+
+use nix::errno::Errno;
+
+use super::value::RawControls;
+use super::{access_error, query_control, Cid, ControlDesc, ControlValue, ExtCtrlsIoctl};
+use crate::raw::controls::CtrlWhich;
+use crate::Device;
+
+/// A list of control values that are written with a single `VIDIOC_S_EXT_CTRLS` call.
+///
+/// Drivers apply all controls in a batch at once, so no frame is captured with only some of them
+/// changed (eg. a new exposure time, but the old gain). Batches are applied with
+/// [`Device::apply_controls`], and returned by [`Device::read_controls`].
+///
+/// Batches with controls of several [classes][ControlDesc::class] are applied with one call per
+/// class, so only the controls within each class are changed at once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ControlBatch {
+    controls: Vec<(Cid, ControlValue)>,
+}
+
+impl ControlBatch {
+    /// Creates an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an integer, boolean or menu control to set to `value`.
+    pub fn with(self, cid: impl Into<Cid>, value: i64) -> Self {
+        self.with_value(cid, ControlValue::Integer64(value))
+    }
+
+    /// Adds a control of any type to set to `value`.
+    pub fn with_value(mut self, cid: impl Into<Cid>, value: ControlValue) -> Self {
+        self.push(cid, value);
+        self
+    }
+
+    /// Adds a control to set to `value`.
+    pub fn push(&mut self, cid: impl Into<Cid>, value: ControlValue) {
+        self.controls.push((cid.into(), value));
+    }
+
+    /// Returns the number of controls in the batch.
+    pub fn len(&self) -> usize {
+        self.controls.len()
+    }
+
+    /// Returns whether the batch is empty.
+    pub fn is_empty(&self) -> bool {
+        self.controls.is_empty()
+    }
+
+    /// Returns the value of control `cid`, if the batch contains it.
+    pub fn get(&self, cid: impl Into<Cid>) -> Option<&ControlValue> {
+        let cid = cid.into();
+        self.controls
+            .iter()
+            .find(|(id, _)| *id == cid)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns an iterator over the controls in the batch, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (Cid, &ControlValue)> + '_ {
+        self.controls.iter().map(|(cid, value)| (*cid, value))
+    }
+}
+
+/// An error returned when reading or applying a [`ControlBatch`].
+#[derive(Debug)]
+pub struct ControlBatchError {
+    error: io::Error,
+    index: Option<usize>,
+    cid: Option<Cid>,
+    partially_applied: bool,
+}
+
+impl ControlBatchError {
+    fn new(error: io::Error, index: Option<usize>, cid: Option<Cid>) -> Self {
+        Self {
+            error,
+            index,
+            cid,
+            partially_applied: false,
+        }
+    }
+
+    /// Creates an error that is not related to a specific control.
+    pub(crate) fn from_io(error: io::Error) -> Self {
+        Self::new(error, None, None)
+    }
+
+    /// Returns the underlying error.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Returns the index of the control that caused the error, if known.
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    /// Returns the ID of the control that caused the error, if known.
+    pub fn cid(&self) -> Option<Cid> {
+        self.cid
+    }
+
+    /// Returns whether some of the controls before [`index`][Self::index] may have been applied.
+    ///
+    /// This happens when the driver fails to write a control to the hardware. When the batch is
+    /// rejected because a value is invalid, no controls are changed.
+    pub fn partially_applied(&self) -> bool {
+        self.partially_applied
+    }
+}
+
+impl fmt::Display for ControlBatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.index, self.cid) {
+            (Some(index), Some(cid)) => {
+                write!(f, "control {:?} (#{} in batch): {}", cid, index, self.error)?
+            }
+            _ => write!(f, "{}", self.error)?,
+        }
+        if self.partially_applied {
+            f.write_str(" (earlier controls in the batch may have been applied)")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ControlBatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<ControlBatchError> for io::Error {
+    fn from(e: ControlBatchError) -> Self {
+        io::Error::new(e.error.kind(), e)
+    }
+}
+
+/// Queries the descriptors of the controls in `cids`.
+fn query_all(
+    device: &Device,
+    cids: impl IntoIterator<Item = Cid>,
+) -> Result<Vec<ControlDesc>, ControlBatchError> {
+    cids.into_iter()
+        .enumerate()
+        .map(|(index, cid)| {
+            query_control(device, cid)
+                .map_err(|e| ControlBatchError::new(e, Some(index), Some(cid)))
+        })
+        .collect()
+}
+
+pub(crate) fn read_batch(
+    device: &Device,
+    cids: impl IntoIterator<Item = Cid>,
+    which: CtrlWhich,
+) -> Result<ControlBatch, ControlBatchError> {
+    let descs = query_all(device, cids)?;
+    read_descs(device, &descs, which)
+}
+
+/// Reads the controls described by `descs` with a single ioctl.
+pub(crate) fn read_descs(
+    device: &Device,
+    descs: &[ControlDesc],
+    which: CtrlWhich,
+) -> Result<ControlBatch, ControlBatchError> {
+    let mut raw = RawControls::with_capacity(descs.len());
+    for (index, desc) in descs.iter().enumerate() {
+        raw.push_read(desc)
+            .map_err(|e| ControlBatchError::new(e, Some(index), Some(desc.id())))?;
+    }
+
+    loop {
+        match device.ext_controls_raw(ExtCtrlsIoctl::Get, which, &mut raw.controls) {
+            Ok(()) => break,
+            Err((e, _)) if e.raw_os_error() == Some(Errno::ENOSPC as i32) && raw.grow() => {}
+            Err((e, index)) => return Err(batch_error(device, descs, e, index, None)),
+        }
+    }
+
+    let mut batch = ControlBatch::new();
+    for (index, desc) in descs.iter().enumerate() {
+        let value = raw
+            .value(index, desc)
+            .map_err(|e| ControlBatchError::new(e, Some(index), Some(desc.id())))?;
+        batch.push(desc.id(), value);
+    }
+    Ok(batch)
+}
+
+pub(crate) fn write_batch(device: &Device, batch: &ControlBatch) -> Result<(), ControlBatchError> {
+    let descs = query_all(device, batch.iter().map(|(cid, _)| cid))?;
+    let mut classes = Vec::new();
+    for desc in &descs {
+        if !classes.contains(&desc.class()) {
+            classes.push(desc.class());
+        }
+    }
+    if classes.len() <= 1 {
+        return write_descs(device, &descs, batch);
+    }
+
+    // Some drivers and older kernels require all controls of a `VIDIOC_S_EXT_CTRLS` call to
+    // belong to the same class, so mixed batches are applied one class at a time.
+    for (n, &class) in classes.iter().enumerate() {
+        let indices = (0..descs.len())
+            .filter(|&i| descs[i].class() == class)
+            .collect::<Vec<_>>();
+        let class_descs = indices
+            .iter()
+            .map(|&i| descs[i].clone())
+            .collect::<Vec<_>>();
+        let mut class_batch = ControlBatch::new();
+        for &i in &indices {
+            let (cid, value) = &batch.controls[i];
+            class_batch.push(*cid, value.clone());
+        }
+        write_class(device, &class_descs, &class_batch, CtrlWhich(class.0)).map_err(|mut e| {
+            e.index = e.index.map(|i| indices[i]);
+            e.partially_applied |= n > 0;
+            e
+        })?;
+    }
+    Ok(())
+}
+
+/// Writes `batch`, whose controls are described by `descs`, with a single ioctl.
+pub(crate) fn write_descs(
+    device: &Device,
+    descs: &[ControlDesc],
+    batch: &ControlBatch,
+) -> Result<(), ControlBatchError> {
+    write_class(device, descs, batch, CtrlWhich::CUR_VAL)
+}
+
+/// Writes `batch` with `which` set to `CUR_VAL` or the class all controls belong to.
+fn write_class(
+    device: &Device,
+    descs: &[ControlDesc],
+    batch: &ControlBatch,
+    which: CtrlWhich,
+) -> Result<(), ControlBatchError> {
+    let mut raw = RawControls::with_capacity(descs.len());
+    for (index, (desc, (_, value))) in descs.iter().zip(batch.iter()).enumerate() {
+        raw.push_write(desc, value)
+            .map_err(|e| ControlBatchError::new(e, Some(index), Some(desc.id())))?;
+    }
+
+    let (e, index) = match device.ext_controls_raw(ExtCtrlsIoctl::Set, which, &mut raw.controls) {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    if index < descs.len() {
+        let mut err = batch_error(device, descs, e, index, Some(batch));
+        err.partially_applied = index > 0;
+        return Err(err);
+    }
+
+    // When validation fails, `VIDIOC_S_EXT_CTRLS` doesn't tell us which control was at fault, but
+    // `VIDIOC_TRY_EXT_CTRLS` does.
+    let index = match device.ext_controls_raw(ExtCtrlsIoctl::Try, which, &mut raw.controls) {
+        Err((_, index)) => index,
+        Ok(()) => descs.len(),
+    };
+    Err(batch_error(device, descs, e, index, Some(batch)))
+}
+
+/// Attributes the error `e` of an ioctl to control `index`, if it is in range.
+fn batch_error(
+    device: &Device,
+    descs: &[ControlDesc],
+    e: io::Error,
+    index: usize,
+    batch: Option<&ControlBatch>,
+) -> ControlBatchError {
+    match descs.get(index) {
+        Some(desc) => {
+            let value = batch.and_then(|batch| batch.controls[index].1.as_i64());
+            let e = access_error(device, e, desc, value);
+            ControlBatchError::new(e, Some(index), Some(desc.id()))
+        }
+        None => ControlBatchError::new(e, None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch() {
+        let batch = ControlBatch::new()
+            .with(Cid::EXPOSURE_ABSOLUTE, 100)
+            .with_value(Cid::AUTOGAIN, ControlValue::Boolean(false));
+        assert_eq!(batch.len(), 2);
+        assert_eq!(
+            batch.get(Cid::EXPOSURE_ABSOLUTE),
+            Some(&ControlValue::Integer64(100))
+        );
+        assert_eq!(batch.get(Cid::GAIN), None);
+        let cids = batch.iter().map(|(cid, _)| cid).collect::<Vec<_>>();
+        assert_eq!(cids, [Cid::EXPOSURE_ABSOLUTE, Cid::AUTOGAIN]);
+    }
+
+    #[test]
+    fn errors() {
+        let mut e = ControlBatchError::new(Errno::EIO.into(), Some(1), Some(Cid::GAIN));
+        e.partially_applied = true;
+        assert!(e.to_string().starts_with("control GAIN (#1 in batch): "));
+        assert!(e.to_string().ends_with("may have been applied)"));
+        let e = io::Error::from(ControlBatchError::new(Errno::EIO.into(), None, None));
+        assert_eq!(e.to_string(), io::Error::from(Errno::EIO).to_string());
+    }
+}
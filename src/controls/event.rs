@@ -0,0 +1,225 @@
+//! Control change events (`V4L2_EVENT_CTRL`).
+
+use std::time::Duration;
+use std::{io, mem};
+
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+
+use super::{Cid, ControlFlags, CtrlType};
+use crate::shared::ControlChanges;
+use crate::{raw, Device};
+
+/// A change of a control, returned by [`Device::dequeue_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlEvent {
+    id: Cid,
+    control_type: CtrlType,
+    value: i64,
+    changes: ControlChanges,
+    flags: ControlFlags,
+    minimum: i64,
+    maximum: i64,
+    step: u64,
+    default_value: i64,
+    sequence: u32,
+    timestamp: Duration,
+}
+
+impl ControlEvent {
+    fn from_raw(raw: &raw::Event) -> Self {
+        let ctrl = unsafe { raw.u.ctrl };
+        let value = unsafe {
+            if ctrl.type_ == CtrlType::INTEGER64 {
+                ctrl.value.value64
+            } else {
+                ctrl.value.value.into()
+            }
+        };
+        Self {
+            id: Cid(raw.id),
+            control_type: ctrl.type_,
+            value,
+            changes: ctrl.changes,
+            flags: ctrl.flags,
+            minimum: ctrl.minimum.into(),
+            maximum: ctrl.maximum.into(),
+            step: ctrl.step.max(0) as u64,
+            default_value: ctrl.default_value.into(),
+            sequence: raw.sequence,
+            timestamp: Duration::new(raw.timestamp.tv_sec as u64, raw.timestamp.tv_nsec as u32),
+        }
+    }
+
+    /// Returns the ID of the control that changed.
+    #[inline]
+    pub fn id(&self) -> Cid {
+        self.id
+    }
+
+    #[inline]
+    pub fn control_type(&self) -> CtrlType {
+        self.control_type
+    }
+
+    /// Returns the new value of the control.
+    ///
+    /// This is only meaningful for integer, boolean and menu controls. The values of string and
+    /// array controls have to be read with [`Device::control_value`].
+    #[inline]
+    pub fn value(&self) -> i64 {
+        self.value
+    }
+
+    /// Returns what changed about the control.
+    #[inline]
+    pub fn changes(&self) -> ControlChanges {
+        self.changes
+    }
+
+    /// Returns the control's current flags.
+    #[inline]
+    pub fn flags(&self) -> ControlFlags {
+        self.flags
+    }
+
+    #[inline]
+    pub fn minimum(&self) -> i64 {
+        self.minimum
+    }
+
+    #[inline]
+    pub fn maximum(&self) -> i64 {
+        self.maximum
+    }
+
+    #[inline]
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+
+    #[inline]
+    pub fn default_value(&self) -> i64 {
+        self.default_value
+    }
+
+    /// Returns the sequence number of the event, which counts all events of the file handle.
+    #[inline]
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    /// Returns the time at which the event was queued, measured on the `CLOCK_MONOTONIC` clock.
+    #[inline]
+    pub fn timestamp(&self) -> Duration {
+        self.timestamp
+    }
+}
+
+fn subscription(type_: u32, cid: Cid) -> raw::EventSubscription {
+    raw::EventSubscription {
+        type_,
+        id: cid.0,
+        flags: 0,
+        reserved: [0; 5],
+    }
+}
+
+pub(crate) fn subscribe(device: &Device, cid: Cid) -> io::Result<()> {
+    if cid.0 != 0 {
+        unsafe {
+            raw::subscribe_event(device.fd(), &subscription(raw::EVENT_CTRL, cid))?;
+        }
+        return Ok(());
+    }
+
+    // The kernel only accepts subscriptions for individual controls.
+    for desc in device.controls() {
+        let desc = desc?;
+        if desc.control_type() != CtrlType::CTRL_CLASS {
+            subscribe(device, desc.id())?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn unsubscribe(device: &Device, cid: Cid) -> io::Result<()> {
+    let sub = if cid.0 == 0 {
+        subscription(raw::EVENT_ALL, cid)
+    } else {
+        subscription(raw::EVENT_CTRL, cid)
+    };
+    unsafe {
+        raw::unsubscribe_event(device.fd(), &sub)?;
+    }
+    Ok(())
+}
+
+/// Dequeues the next control event, waiting for one if `block` is `true`.
+pub(crate) fn dequeue(device: &Device, block: bool) -> io::Result<Option<ControlEvent>> {
+    loop {
+        // Events are signaled with `POLLPRI`, independent of whether the device is streaming.
+        let mut fds = [PollFd::new(device.fd(), PollFlags::POLLPRI)];
+        match poll(&mut fds, if block { -1 } else { 0 }) {
+            Ok(0) => return Ok(None),
+            Ok(_) => {}
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut event: raw::Event = unsafe { mem::zeroed() };
+        match unsafe { raw::dqevent(device.fd(), &mut event) } {
+            Ok(_) if event.type_ == raw::EVENT_CTRL => {
+                return Ok(Some(ControlEvent::from_raw(&event)))
+            }
+            Ok(_) => {
+                log::debug!("ignoring event of type {}", event.type_);
+                continue;
+            }
+            // Another thread dequeued the event first.
+            Err(Errno::ENOENT) if block => continue,
+            Err(Errno::ENOENT) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_layout() {
+        // Matches `struct v4l2_event` on 64-bit architectures.
+        if mem::size_of::<usize>() == 8 {
+            assert_eq!(mem::size_of::<raw::Event>(), 136);
+        }
+        assert_eq!(mem::size_of::<raw::EventCtrl>(), 40);
+    }
+
+    #[test]
+    fn from_raw() {
+        let mut event: raw::Event = unsafe { mem::zeroed() };
+        event.type_ = raw::EVENT_CTRL;
+        event.id = Cid::BRIGHTNESS.0;
+        event.sequence = 3;
+        event.timestamp.tv_sec = 5;
+        event.u.ctrl = raw::EventCtrl {
+            changes: ControlChanges::VALUE,
+            type_: CtrlType::INTEGER,
+            value: raw::EventCtrlValue { value: -20 },
+            flags: ControlFlags::SLIDER,
+            minimum: -128,
+            maximum: 127,
+            step: 1,
+            default_value: 0,
+        };
+        let ev = ControlEvent::from_raw(&event);
+        assert_eq!(ev.id(), Cid::BRIGHTNESS);
+        assert_eq!(ev.value(), -20);
+        assert_eq!(ev.changes(), ControlChanges::VALUE);
+        assert_eq!((ev.minimum(), ev.maximum(), ev.step()), (-128, 127, 1));
+        assert_eq!(ev.sequence(), 3);
+        assert_eq!(ev.timestamp(), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,135 @@
+//! Exposure mode and exposure time controls.
+
+use std::io;
+use std::time::Duration;
+
+use super::menu::{self, MenuControl};
+use super::{check_range, query_optional, read_integer, unsupported, write_integer, Cid};
+use crate::Device;
+
+/// Unit of `V4L2_CID_EXPOSURE_ABSOLUTE`.
+const EXPOSURE_UNIT_MICROS: u128 = 100;
+
+/// The automatic exposure mode of a camera (`V4L2_CID_EXPOSURE_AUTO`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExposureMode {
+    /// Exposure time and iris are adjusted automatically.
+    Auto,
+    /// Exposure time and iris are set manually.
+    Manual,
+    /// The exposure time is set manually, the iris is adjusted automatically.
+    ShutterPriority,
+    /// The iris is set manually, the exposure time is adjusted automatically.
+    AperturePriority,
+}
+
+impl MenuControl for ExposureMode {
+    const CID: Cid = Cid::EXPOSURE_AUTO;
+
+    fn from_index(index: i64) -> Option<Self> {
+        Some(match index {
+            0 => ExposureMode::Auto,
+            1 => ExposureMode::Manual,
+            2 => ExposureMode::ShutterPriority,
+            3 => ExposureMode::AperturePriority,
+            _ => return None,
+        })
+    }
+
+    fn index(self) -> i64 {
+        match self {
+            ExposureMode::Auto => 0,
+            ExposureMode::Manual => 1,
+            ExposureMode::ShutterPriority => 2,
+            ExposureMode::AperturePriority => 3,
+        }
+    }
+}
+
+impl ExposureMode {
+    /// Returns whether the exposure time is set manually in this mode.
+    pub fn is_exposure_manual(self) -> bool {
+        matches!(self, ExposureMode::Manual | ExposureMode::ShutterPriority)
+    }
+}
+
+fn to_units(exposure: Duration) -> i64 {
+    let units = (exposure.as_micros() + EXPOSURE_UNIT_MICROS / 2) / EXPOSURE_UNIT_MICROS;
+    units.try_into().unwrap_or(i64::MAX)
+}
+
+fn from_units(units: i64) -> Duration {
+    Duration::from_micros(units.max(0) as u64 * EXPOSURE_UNIT_MICROS as u64)
+}
+
+pub(crate) fn exposure(device: &Device) -> io::Result<Duration> {
+    if query_optional(device, Cid::EXPOSURE_ABSOLUTE)?.is_none() {
+        return Err(unsupported(
+            "reading the exposure time",
+            &[Cid::EXPOSURE_ABSOLUTE],
+        ));
+    }
+    Ok(from_units(read_integer(device, Cid::EXPOSURE_ABSOLUTE)?))
+}
+
+pub(crate) fn set_exposure(device: &Device, exposure: Duration) -> io::Result<()> {
+    let desc = query_optional(device, Cid::EXPOSURE_ABSOLUTE)?
+        .ok_or_else(|| unsupported("setting the exposure time", &[Cid::EXPOSURE_ABSOLUTE]))?;
+    let value = check_range(&desc, to_units(exposure))?;
+
+    // `EXPOSURE_ABSOLUTE` is inactive, and often rejects writes, until the exposure time is
+    // switched to manual.
+    if query_optional(device, Cid::EXPOSURE_AUTO)?.is_some() {
+        let mode = menu::get::<ExposureMode>(device)?;
+        if !mode.is_exposure_manual() {
+            log::debug!("switching exposure mode from {:?} to manual", mode);
+            menu::set(device, ExposureMode::Manual)?;
+        }
+    }
+    write_integer(device, Cid::EXPOSURE_ABSOLUTE, value)
+}
+
+pub(crate) fn auto_priority(device: &Device) -> io::Result<bool> {
+    if query_optional(device, Cid::EXPOSURE_AUTO_PRIORITY)?.is_none() {
+        return Err(unsupported(
+            "reading the exposure priority",
+            &[Cid::EXPOSURE_AUTO_PRIORITY],
+        ));
+    }
+    Ok(read_integer(device, Cid::EXPOSURE_AUTO_PRIORITY)? != 0)
+}
+
+pub(crate) fn set_auto_priority(device: &Device, enabled: bool) -> io::Result<()> {
+    if query_optional(device, Cid::EXPOSURE_AUTO_PRIORITY)?.is_none() {
+        return Err(unsupported(
+            "setting the exposure priority",
+            &[Cid::EXPOSURE_AUTO_PRIORITY],
+        ));
+    }
+    write_integer(device, Cid::EXPOSURE_AUTO_PRIORITY, enabled.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units() {
+        assert_eq!(to_units(Duration::from_millis(10)), 100);
+        assert_eq!(to_units(Duration::from_micros(149)), 1);
+        assert_eq!(to_units(Duration::from_micros(150)), 2);
+        assert_eq!(to_units(Duration::MAX), i64::MAX);
+        assert_eq!(from_units(333), Duration::from_micros(33_300));
+        assert_eq!(from_units(-1), Duration::ZERO);
+    }
+
+    #[test]
+    fn mode() {
+        for index in 0..4 {
+            assert_eq!(ExposureMode::from_index(index).unwrap().index(), index);
+        }
+        assert_eq!(ExposureMode::from_index(4), None);
+        assert!(ExposureMode::ShutterPriority.is_exposure_manual());
+        assert!(!ExposureMode::AperturePriority.is_exposure_manual());
+    }
+}
@@ -0,0 +1,88 @@
+//! Focus controls.
+
+use std::io;
+
+use bitflags::bitflags;
+
+use super::{check_range, query_optional, read_integer, unsupported, write_integer, Cid};
+use crate::Device;
+
+bitflags! {
+    /// The state of the automatic focus (`V4L2_CID_AUTO_FOCUS_STATUS`).
+    ///
+    /// If no flag is set, the automatic focus is idle.
+    pub struct AutoFocusStatus: u32 {
+        /// The automatic focus is currently adjusting the focus.
+        const BUSY    = 1 << 0;
+        /// The automatic focus has reached focus.
+        const REACHED = 1 << 1;
+        /// The automatic focus failed to reach focus.
+        const FAILED  = 1 << 2;
+    }
+}
+
+pub(crate) fn autofocus(device: &Device) -> io::Result<bool> {
+    if query_optional(device, Cid::FOCUS_AUTO)?.is_none() {
+        return Err(unsupported("automatic focus", &[Cid::FOCUS_AUTO]));
+    }
+    Ok(read_integer(device, Cid::FOCUS_AUTO)? != 0)
+}
+
+pub(crate) fn set_autofocus(device: &Device, enabled: bool) -> io::Result<()> {
+    if query_optional(device, Cid::FOCUS_AUTO)?.is_none() {
+        return Err(unsupported("automatic focus", &[Cid::FOCUS_AUTO]));
+    }
+    write_integer(device, Cid::FOCUS_AUTO, enabled.into())
+}
+
+/// Disables automatic focus, which makes the manual focus controls inactive.
+fn disable_autofocus(device: &Device) -> io::Result<()> {
+    if query_optional(device, Cid::FOCUS_AUTO)?.is_some()
+        && read_integer(device, Cid::FOCUS_AUTO)? != 0
+    {
+        log::debug!("disabling automatic focus");
+        write_integer(device, Cid::FOCUS_AUTO, 0)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn focus(device: &Device) -> io::Result<i64> {
+    if query_optional(device, Cid::FOCUS_ABSOLUTE)?.is_none() {
+        return Err(unsupported("reading the focus", &[Cid::FOCUS_ABSOLUTE]));
+    }
+    read_integer(device, Cid::FOCUS_ABSOLUTE)
+}
+
+pub(crate) fn set_focus(device: &Device, focus: i64) -> io::Result<()> {
+    let desc = query_optional(device, Cid::FOCUS_ABSOLUTE)?
+        .ok_or_else(|| unsupported("setting the focus", &[Cid::FOCUS_ABSOLUTE]))?;
+    let value = check_range(&desc, focus)?;
+    disable_autofocus(device)?;
+    write_integer(device, Cid::FOCUS_ABSOLUTE, value)
+}
+
+pub(crate) fn nudge_focus(device: &Device, delta: i64) -> io::Result<()> {
+    if let Some(desc) = query_optional(device, Cid::FOCUS_RELATIVE)? {
+        disable_autofocus(device)?;
+        return write_integer(device, Cid::FOCUS_RELATIVE, desc.clamp(delta));
+    }
+    match query_optional(device, Cid::FOCUS_ABSOLUTE)? {
+        Some(desc) => {
+            disable_autofocus(device)?;
+            let value = read_integer(device, Cid::FOCUS_ABSOLUTE)?.saturating_add(delta);
+            write_integer(device, Cid::FOCUS_ABSOLUTE, desc.clamp(value))
+        }
+        None => Err(unsupported(
+            "moving the focus",
+            &[Cid::FOCUS_RELATIVE, Cid::FOCUS_ABSOLUTE],
+        )),
+    }
+}
+
+pub(crate) fn autofocus_status(device: &Device) -> io::Result<Option<AutoFocusStatus>> {
+    if query_optional(device, Cid::AUTO_FOCUS_STATUS)?.is_none() {
+        return Ok(None);
+    }
+    let bits = read_integer(device, Cid::AUTO_FOCUS_STATUS)?;
+    Ok(Some(AutoFocusStatus::from_bits_truncate(bits as u32)))
+}
@@ -0,0 +1,113 @@
+//! Menu controls with typed values.
+
+use std::io;
+
+use super::{query_optional, read_integer, unsupported, validate, write_integer, Cid};
+use crate::Device;
+
+/// A type whose values are the items of a standard menu control.
+///
+/// Implementors can be read and written with [`Device::menu_control`] and
+/// [`Device::set_menu_control`], which take care of checking that the driver has the control and
+/// the item.
+pub trait MenuControl: Copy + Sized {
+    /// The menu control whose items this type represents.
+    const CID: Cid;
+
+    /// Returns the value corresponding to the menu item `index`, if there is one.
+    fn from_index(index: i64) -> Option<Self>;
+
+    /// Returns the menu index of `self`.
+    fn index(self) -> i64;
+}
+
+/// Mains frequency filter, which suppresses flicker under artificial lighting
+/// (`V4L2_CID_POWER_LINE_FREQUENCY`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerLineFrequency {
+    /// No flicker suppression.
+    Disabled,
+    /// Suppress flicker from 50 Hz mains.
+    Hz50,
+    /// Suppress flicker from 60 Hz mains.
+    Hz60,
+    /// Detect the mains frequency automatically.
+    Auto,
+}
+
+impl MenuControl for PowerLineFrequency {
+    const CID: Cid = Cid::POWER_LINE_FREQUENCY;
+
+    fn from_index(index: i64) -> Option<Self> {
+        Some(match index {
+            0 => PowerLineFrequency::Disabled,
+            1 => PowerLineFrequency::Hz50,
+            2 => PowerLineFrequency::Hz60,
+            3 => PowerLineFrequency::Auto,
+            _ => return None,
+        })
+    }
+
+    fn index(self) -> i64 {
+        match self {
+            PowerLineFrequency::Disabled => 0,
+            PowerLineFrequency::Hz50 => 1,
+            PowerLineFrequency::Hz60 => 2,
+            PowerLineFrequency::Auto => 3,
+        }
+    }
+}
+
+pub(crate) fn get<T: MenuControl>(device: &Device) -> io::Result<T> {
+    if query_optional(device, T::CID)?.is_none() {
+        return Err(unsupported("reading the menu control", &[T::CID]));
+    }
+    let index = read_integer(device, T::CID)?;
+    T::from_index(index).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "driver reported unknown item {} of control {:?}",
+                index,
+                T::CID
+            ),
+        )
+    })
+}
+
+pub(crate) fn set<T: MenuControl>(device: &Device, value: T) -> io::Result<()> {
+    let desc = query_optional(device, T::CID)?
+        .ok_or_else(|| unsupported("setting the menu control", &[T::CID]))?;
+    // Drivers commonly omit some of the standard items.
+    validate(device, &desc, value.index())?;
+    write_integer(device, T::CID, value.index())
+}
+
+pub(crate) fn supported<T: MenuControl>(device: &Device) -> io::Result<Vec<T>> {
+    let desc = match query_optional(device, T::CID)? {
+        Some(desc) => desc,
+        None => return Ok(Vec::new()),
+    };
+    let mut values = Vec::new();
+    for item in device.menu_items(&desc) {
+        if let Some(value) = T::from_index(item?.index().into()) {
+            values.push(value);
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_line_frequency() {
+        for index in 0..4 {
+            let freq = PowerLineFrequency::from_index(index).unwrap();
+            assert_eq!(freq.index(), index);
+        }
+        assert_eq!(PowerLineFrequency::from_index(-1), None);
+        assert_eq!(PowerLineFrequency::from_index(4), None);
+    }
+}
@@ -0,0 +1,257 @@
+//! Pan, tilt and zoom controls of cameras with motorized or digital PTZ.
+//!
+//! The V4L2 pan and tilt controls use arc-seconds as their unit. [`Ptz`] converts from and to
+//! degrees, and picks the controls the camera actually has.
+
+use std::io;
+use std::ops::RangeInclusive;
+
+use super::{
+    check_range, press_button, query_optional, read_integer, unsupported, write_integer, Cid,
+    ControlDesc,
+};
+use crate::Device;
+
+const ARC_SECONDS_PER_DEGREE: f64 = 3600.0;
+
+fn to_arc_seconds(degrees: f64) -> i64 {
+    (degrees * ARC_SECONDS_PER_DEGREE).round() as i64
+}
+
+fn to_degrees(arc_seconds: i64) -> f64 {
+    arc_seconds as f64 / ARC_SECONDS_PER_DEGREE
+}
+
+/// One of the two rotation axes.
+#[derive(Clone, Copy)]
+struct Axis {
+    name: &'static str,
+    absolute: Cid,
+    relative: Cid,
+    reset: Cid,
+}
+
+const PAN: Axis = Axis {
+    name: "pan",
+    absolute: Cid::PAN_ABSOLUTE,
+    relative: Cid::PAN_RELATIVE,
+    reset: Cid::PAN_RESET,
+};
+
+const TILT: Axis = Axis {
+    name: "tilt",
+    absolute: Cid::TILT_ABSOLUTE,
+    relative: Cid::TILT_RELATIVE,
+    reset: Cid::TILT_RESET,
+};
+
+/// Controls the pan, tilt and zoom of a camera.
+///
+/// Operations return an error of kind [`io::ErrorKind::Unsupported`] if the camera lacks the
+/// controls they need. When a camera has both absolute and relative controls for an axis, the
+/// absolute ones are used, since their position can be read back.
+///
+/// Angles are in degrees. Positive pan angles turn the camera to the right, positive tilt angles
+/// turn it up. Zoom values are driver-specific, with larger values zooming in further.
+///
+/// Use [`Device::ptz`] to create one.
+pub struct Ptz<'a> {
+    device: &'a mut Device,
+}
+
+impl<'a> Ptz<'a> {
+    /// Creates a `Ptz` for `device`.
+    ///
+    /// This doesn't check which controls the device has.
+    pub fn new(device: &'a mut Device) -> Self {
+        Self { device }
+    }
+
+    fn absolute(&self, axis: Axis, operation: &str) -> io::Result<ControlDesc> {
+        query_optional(self.device, axis.absolute)?
+            .ok_or_else(|| unsupported(operation, &[axis.absolute]))
+    }
+
+    fn get(&self, axis: Axis) -> io::Result<f64> {
+        self.absolute(axis, axis.name)?;
+        Ok(to_degrees(read_integer(self.device, axis.absolute)?))
+    }
+
+    fn set(&mut self, axis: Axis, degrees: f64) -> io::Result<()> {
+        let desc = self.absolute(axis, axis.name)?;
+        let value = check_range(&desc, to_arc_seconds(degrees))?;
+        write_integer(self.device, axis.absolute, value)
+    }
+
+    fn nudge(&mut self, axis: Axis, degrees: f64) -> io::Result<()> {
+        let delta = to_arc_seconds(degrees);
+        if let Some(desc) = query_optional(self.device, axis.absolute)? {
+            let value = read_integer(self.device, axis.absolute)?.saturating_add(delta);
+            return write_integer(self.device, axis.absolute, desc.clamp(value));
+        }
+        match query_optional(self.device, axis.relative)? {
+            // Relative controls are write-only, and start moving as soon as they are written.
+            Some(desc) => write_integer(self.device, axis.relative, desc.clamp(delta)),
+            None => Err(unsupported(axis.name, &[axis.absolute, axis.relative])),
+        }
+    }
+
+    fn range(&self, axis: Axis) -> io::Result<RangeInclusive<f64>> {
+        let desc = match query_optional(self.device, axis.absolute)? {
+            Some(desc) => desc,
+            None => query_optional(self.device, axis.relative)?
+                .ok_or_else(|| unsupported(axis.name, &[axis.absolute, axis.relative]))?,
+        };
+        Ok(to_degrees(desc.minimum())..=to_degrees(desc.maximum()))
+    }
+
+    /// Returns the current pan angle.
+    pub fn pan(&self) -> io::Result<f64> {
+        self.get(PAN)
+    }
+
+    /// Turns the camera to the pan angle `degrees`.
+    ///
+    /// The angle is rounded to the nearest angle the camera supports. Returns an error of kind
+    /// [`io::ErrorKind::InvalidInput`] if it is outside of [`Ptz::pan_range`].
+    pub fn set_pan(&mut self, degrees: f64) -> io::Result<()> {
+        self.set(PAN, degrees)
+    }
+
+    /// Turns the camera by `degrees` relative to its current pan angle.
+    ///
+    /// Moves that would exceed the pan range stop at its end.
+    pub fn nudge_pan(&mut self, degrees: f64) -> io::Result<()> {
+        self.nudge(PAN, degrees)
+    }
+
+    /// Returns the range of pan angles, or of relative pan moves if the camera only supports
+    /// those.
+    pub fn pan_range(&self) -> io::Result<RangeInclusive<f64>> {
+        self.range(PAN)
+    }
+
+    /// Returns the current tilt angle.
+    pub fn tilt(&self) -> io::Result<f64> {
+        self.get(TILT)
+    }
+
+    /// Turns the camera to the tilt angle `degrees`.
+    ///
+    /// Like [`Ptz::set_pan`], the angle is rounded to the nearest supported angle.
+    pub fn set_tilt(&mut self, degrees: f64) -> io::Result<()> {
+        self.set(TILT, degrees)
+    }
+
+    /// Turns the camera by `degrees` relative to its current tilt angle.
+    pub fn nudge_tilt(&mut self, degrees: f64) -> io::Result<()> {
+        self.nudge(TILT, degrees)
+    }
+
+    /// Returns the range of tilt angles, or of relative tilt moves if the camera only supports
+    /// those.
+    pub fn tilt_range(&self) -> io::Result<RangeInclusive<f64>> {
+        self.range(TILT)
+    }
+
+    /// Moves the camera back to its center pan and tilt position.
+    ///
+    /// This presses the pan and tilt reset buttons if the camera has them, and sets the absolute
+    /// controls to their default value otherwise.
+    pub fn reset(&mut self) -> io::Result<()> {
+        let mut supported = false;
+        for axis in [PAN, TILT] {
+            if query_optional(self.device, axis.reset)?.is_some() {
+                press_button(self.device, axis.reset)?;
+            } else if let Some(desc) = query_optional(self.device, axis.absolute)? {
+                write_integer(self.device, axis.absolute, desc.default_value())?;
+            } else {
+                continue;
+            }
+            supported = true;
+        }
+        if supported {
+            Ok(())
+        } else {
+            Err(unsupported(
+                "pan/tilt reset",
+                &[PAN.reset, TILT.reset, PAN.absolute, TILT.absolute],
+            ))
+        }
+    }
+
+    /// Returns the current zoom value.
+    pub fn zoom(&self) -> io::Result<i64> {
+        self.zoom_desc("zoom")?;
+        read_integer(self.device, Cid::ZOOM_ABSOLUTE)
+    }
+
+    fn zoom_desc(&self, operation: &str) -> io::Result<ControlDesc> {
+        query_optional(self.device, Cid::ZOOM_ABSOLUTE)?
+            .ok_or_else(|| unsupported(operation, &[Cid::ZOOM_ABSOLUTE]))
+    }
+
+    /// Sets the zoom value.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] if `zoom` is outside of
+    /// [`Ptz::zoom_range`].
+    pub fn set_zoom(&mut self, zoom: i64) -> io::Result<()> {
+        let desc = self.zoom_desc("zoom")?;
+        let value = check_range(&desc, zoom)?;
+        write_integer(self.device, Cid::ZOOM_ABSOLUTE, value)
+    }
+
+    /// Changes the zoom value by `delta`, stopping at the end of the zoom range.
+    pub fn nudge_zoom(&mut self, delta: i64) -> io::Result<()> {
+        if let Some(desc) = query_optional(self.device, Cid::ZOOM_ABSOLUTE)? {
+            let value = read_integer(self.device, Cid::ZOOM_ABSOLUTE)?.saturating_add(delta);
+            return write_integer(self.device, Cid::ZOOM_ABSOLUTE, desc.clamp(value));
+        }
+        match query_optional(self.device, Cid::ZOOM_RELATIVE)? {
+            Some(desc) => write_integer(self.device, Cid::ZOOM_RELATIVE, desc.clamp(delta)),
+            None => Err(unsupported(
+                "zoom",
+                &[Cid::ZOOM_ABSOLUTE, Cid::ZOOM_RELATIVE],
+            )),
+        }
+    }
+
+    /// Returns the range of zoom values.
+    pub fn zoom_range(&self) -> io::Result<RangeInclusive<i64>> {
+        let desc = self.zoom_desc("zoom")?;
+        Ok(desc.minimum()..=desc.maximum())
+    }
+
+    /// Starts zooming continuously at `speed`, or stops zooming if `speed` is 0.
+    ///
+    /// Positive speeds zoom in, negative speeds zoom out. The zoom stops at the end of the zoom
+    /// range, or when this is called again with a speed of 0.
+    pub fn zoom_continuous(&mut self, speed: i64) -> io::Result<()> {
+        let desc = query_optional(self.device, Cid::ZOOM_CONTINUOUS)?
+            .ok_or_else(|| unsupported("continuous zoom", &[Cid::ZOOM_CONTINUOUS]))?;
+        write_integer(self.device, Cid::ZOOM_CONTINUOUS, desc.clamp(speed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units() {
+        assert_eq!(to_arc_seconds(1.5), 5400);
+        assert_eq!(to_arc_seconds(-0.0001), 0);
+        assert_eq!(to_degrees(-36000), -10.0);
+        assert_eq!(to_degrees(to_arc_seconds(12.25)), 12.25);
+    }
+
+    #[test]
+    fn unsupported_error() {
+        let e = unsupported("pan", &[PAN.absolute, PAN.relative]);
+        assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(
+            e.to_string(),
+            "pan is not supported: device has no PAN_ABSOLUTE or PAN_RELATIVE control"
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! Client-side validation and clamping of integer control values.
+
+use std::{fmt, io};
+
+use super::value::check_integer;
+use super::{query_control, write_value, Cid, ControlDesc, ControlValue, CtrlType};
+use crate::Device;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reason {
+    OutOfRange,
+    Step,
+    MenuIndex,
+    Bitmask,
+}
+
+/// An error indicating that a value is not accepted by a control.
+///
+/// Returned by [`ControlDesc::validate`] and [`Device::validate_control`]. Converting it to an
+/// [`io::Error`] results in an error of kind [`io::ErrorKind::InvalidInput`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeError {
+    cid: Cid,
+    value: i64,
+    nearest: i64,
+    minimum: i64,
+    maximum: i64,
+    step: u64,
+    reason: Reason,
+}
+
+impl RangeError {
+    fn new(desc: &ControlDesc, value: i64, nearest: i64, reason: Reason) -> Self {
+        Self {
+            cid: desc.id(),
+            value,
+            nearest,
+            minimum: desc.minimum(),
+            maximum: desc.maximum(),
+            step: desc.step(),
+            reason,
+        }
+    }
+
+    /// Returns the ID of the control that rejects the value.
+    pub fn cid(&self) -> Cid {
+        self.cid
+    }
+
+    /// Returns the rejected value.
+    pub fn value(&self) -> i64 {
+        self.value
+    }
+
+    /// Returns the valid value closest to the rejected one.
+    ///
+    /// This is the value that [`ControlDesc::clamp`] (or, for menu controls,
+    /// [`Device::set_control_clamped`]) would use instead.
+    pub fn nearest(&self) -> i64 {
+        self.nearest
+    }
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            Reason::OutOfRange => write!(
+                f,
+                "value {} is out of range for control {:?} ({}..={})",
+                self.value, self.cid, self.minimum, self.maximum
+            ),
+            Reason::Step => write!(
+                f,
+                "value {} of control {:?} is not a multiple of the step size {} (nearest valid value is {})",
+                self.value, self.cid, self.step, self.nearest
+            ),
+            Reason::MenuIndex => write!(
+                f,
+                "value {} is not the index of an item of menu control {:?}",
+                self.value, self.cid
+            ),
+            Reason::Bitmask => write!(
+                f,
+                "value {:#x} sets bits that are not supported by control {:?} (supported: {:#x})",
+                self.value, self.cid, self.maximum
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+impl From<RangeError> for io::Error {
+    fn from(e: RangeError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    }
+}
+
+impl ControlDesc {
+    /// Checks whether `value` is a valid value of the control.
+    ///
+    /// Values have to be between [`minimum`][Self::minimum] and [`maximum`][Self::maximum], and
+    /// integer controls only accept values that are a multiple of [`step`][Self::step] away from
+    /// the default value. Bitmask controls only accept the bits set in the maximum value.
+    ///
+    /// Menu controls are only checked against their minimum and maximum, since the descriptor
+    /// doesn't know which indices in between have items. [`Device::validate_control`] also
+    /// checks that.
+    ///
+    /// Controls that don't have an integer value accept any value.
+    pub fn validate(&self, value: i64) -> Result<(), RangeError> {
+        let nearest = self.clamp(value);
+        if nearest == value {
+            return Ok(());
+        }
+        let reason = if self.control_type() == CtrlType::BITMASK {
+            Reason::Bitmask
+        } else if value < self.minimum() || value > self.maximum() {
+            Reason::OutOfRange
+        } else {
+            Reason::Step
+        };
+        Err(RangeError::new(self, value, nearest, reason))
+    }
+
+    /// Returns the valid value of the control that is closest to `value`.
+    ///
+    /// This clamps `value` to the control's range and rounds it to the nearest step, with the
+    /// same rules as [`ControlDesc::validate`].
+    pub fn clamp(&self, value: i64) -> i64 {
+        let (min, max) = (self.minimum(), self.maximum());
+        match self.control_type() {
+            CtrlType::BITMASK => value & max,
+            CtrlType::INTEGER | CtrlType::INTEGER64 => snap(
+                value.max(min).min(max),
+                min,
+                max,
+                self.step(),
+                self.default_value(),
+            ),
+            CtrlType::BOOLEAN | CtrlType::MENU | CtrlType::INTEGER_MENU => value.max(min).min(max),
+            _ => value,
+        }
+    }
+}
+
+/// Rounds `value`, which is in `min..=max`, to the nearest multiple of `step` away from `default`.
+fn snap(value: i64, min: i64, max: i64, step: u64, default: i64) -> i64 {
+    if step <= 1 {
+        return value;
+    }
+    let (value, min, max, step, default) = (
+        i128::from(value),
+        i128::from(min),
+        i128::from(max),
+        i128::from(step),
+        i128::from(default),
+    );
+    let steps = (value - default + step / 2).div_euclid(step);
+    let mut snapped = default + steps * step;
+    if snapped > max {
+        snapped -= step;
+    }
+    if snapped < min {
+        snapped += step;
+    }
+    if snapped > max {
+        // The range is smaller than a single step.
+        snapped = default.max(min).min(max);
+    }
+    snapped as i64
+}
+
+/// Checks that `value` is within the range of `desc`, and rounds it to the nearest step.
+///
+/// This is used by the helpers for specific controls, which take values in physical units that
+/// rarely line up exactly with the control's steps.
+pub(crate) fn check_range(desc: &ControlDesc, value: i64) -> io::Result<i64> {
+    match desc.validate(value) {
+        Err(e) if e.reason == Reason::OutOfRange || e.reason == Reason::Bitmask => Err(e.into()),
+        Err(e) => Ok(e.nearest()),
+        Ok(()) => Ok(value),
+    }
+}
+
+/// Returns the index of the menu item of `desc` that is closest to `index`.
+fn nearest_menu_index(device: &Device, desc: &ControlDesc, index: i64) -> io::Result<i64> {
+    nearest_index(
+        device
+            .menu_items(desc)
+            .map(|item| item.map(|item| i64::from(item.index()))),
+        index,
+    )
+}
+
+/// Returns the item index in `items` that is closest to `index`, or `index` if there are none.
+fn nearest_index(items: impl Iterator<Item = io::Result<i64>>, index: i64) -> io::Result<i64> {
+    let mut nearest = None;
+    for item in items {
+        let item = item?;
+        if item == index {
+            return Ok(index);
+        }
+        if nearest.is_none_or(|n: i64| (item - index).abs() < (n - index).abs()) {
+            nearest = Some(item);
+        }
+    }
+    Ok(nearest.unwrap_or(index))
+}
+
+fn is_menu(desc: &ControlDesc) -> bool {
+    matches!(desc.control_type(), CtrlType::MENU | CtrlType::INTEGER_MENU)
+}
+
+pub(crate) fn validate(device: &Device, desc: &ControlDesc, value: i64) -> io::Result<()> {
+    desc.validate(value)?;
+    if is_menu(desc) {
+        let nearest = nearest_menu_index(device, desc, value)?;
+        if nearest != value {
+            return Err(RangeError::new(desc, value, nearest, Reason::MenuIndex).into());
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn write_clamped(device: &Device, cid: Cid, value: i64) -> io::Result<Option<i64>> {
+    let desc = query_control(device, cid)?;
+    check_integer(&desc)?;
+    let mut clamped = desc.clamp(value);
+    if is_menu(&desc) {
+        clamped = nearest_menu_index(device, &desc, clamped)?;
+    }
+    write_value(device, &desc, &ControlValue::Integer64(clamped))?;
+    if clamped == value {
+        Ok(None)
+    } else {
+        log::debug!(
+            "clamped value {} of control {:?} to {}",
+            value,
+            cid,
+            clamped
+        );
+        Ok(Some(clamped))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw;
+
+    use std::mem;
+
+    fn test_desc(
+        type_: CtrlType,
+        minimum: i64,
+        maximum: i64,
+        step: u64,
+        default: i64,
+    ) -> ControlDesc {
+        let mut raw: raw::QueryExtCtrl = unsafe { mem::zeroed() };
+        raw.id = Cid::BRIGHTNESS.0;
+        raw.type_ = type_;
+        raw.minimum = minimum;
+        raw.maximum = maximum;
+        raw.step = step;
+        raw.default_value = default;
+        ControlDesc(raw)
+    }
+
+    #[test]
+    fn clamp() {
+        let desc = test_desc(CtrlType::INTEGER, -10, 100, 4, 2);
+        assert_eq!(desc.clamp(200), 98);
+        assert_eq!(desc.clamp(-100), -10);
+        assert_eq!(desc.clamp(7), 6);
+        assert_eq!(desc.clamp(8), 10);
+        assert_eq!(desc.clamp(-3), -2);
+        assert_eq!(desc.clamp(14), 14);
+
+        let narrow = test_desc(CtrlType::INTEGER, 1, 2, 10, 1);
+        assert_eq!(narrow.clamp(2), 1);
+        let bitmask = test_desc(CtrlType::BITMASK, 0, 0b1010, 0, 0);
+        assert_eq!(bitmask.clamp(0b1111), 0b1010);
+        let string = test_desc(CtrlType::STRING, 0, 31, 1, 0);
+        assert_eq!(string.clamp(100), 100);
+        let extreme = test_desc(CtrlType::INTEGER64, i64::MIN, i64::MAX, 1 << 62, 0);
+        assert_eq!(extreme.clamp(i64::MAX), 1 << 62);
+    }
+
+    #[test]
+    fn validate() {
+        let desc = test_desc(CtrlType::INTEGER, 0, 255, 2, 128);
+        assert_eq!(desc.validate(130), Ok(()));
+
+        let e = desc.validate(300).unwrap_err();
+        assert_eq!(
+            (e.cid(), e.value(), e.nearest()),
+            (Cid::BRIGHTNESS, 300, 254)
+        );
+        assert_eq!(
+            e.to_string(),
+            "value 300 is out of range for control BRIGHTNESS (0..=255)"
+        );
+        let e = desc.validate(129).unwrap_err();
+        assert_eq!(e.nearest(), 130);
+        assert!(e.to_string().contains("step size 2"), "{}", e);
+
+        let menu = test_desc(CtrlType::MENU, 1, 4, 1, 1);
+        assert_eq!(menu.validate(3), Ok(()));
+        assert_eq!(menu.validate(0).unwrap_err().nearest(), 1);
+        let e = io::Error::from(menu.validate(5).unwrap_err());
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn check_range() {
+        let desc = test_desc(CtrlType::INTEGER, 0, 100, 10, 50);
+        assert_eq!(super::check_range(&desc, 40).unwrap(), 40);
+        assert_eq!(super::check_range(&desc, 44).unwrap(), 40);
+        assert_eq!(super::check_range(&desc, 46).unwrap(), 50);
+        let e = super::check_range(&desc, 101).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+        let bitmask = test_desc(CtrlType::BITMASK, 0, 0b0110, 0, 0);
+        assert_eq!(super::check_range(&bitmask, 0b0100).unwrap(), 0b0100);
+        assert!(super::check_range(&bitmask, 0b0001).is_err());
+    }
+
+    #[test]
+    fn nearest_menu_item() {
+        let items = || [0, 1, 4, 5].into_iter().map(Ok);
+        assert_eq!(nearest_index(items(), 4).unwrap(), 4);
+        assert_eq!(nearest_index(items(), 2).unwrap(), 1);
+        assert_eq!(nearest_index(items(), 3).unwrap(), 4);
+        assert_eq!(nearest_index(items(), 9).unwrap(), 5);
+        assert_eq!(nearest_index(std::iter::empty(), 3).unwrap(), 3);
+
+        let failing = [Ok(0), Err(io::Error::other("failed"))].into_iter();
+        assert!(nearest_index(failing, 3).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Resetting controls to their default values.
+
+use std::{fmt, io};
+
+use super::batch::{read_descs, write_descs};
+use super::{query_control, Cid, ControlDesc, ControlFlags, CtrlType};
+use crate::raw::controls::CtrlWhich;
+use crate::Device;
+
+/// The outcome of [`Device::reset_controls`].
+#[derive(Debug, Default)]
+pub struct ResetReport {
+    reset: Vec<Cid>,
+    failed: Vec<(Cid, io::Error)>,
+}
+
+impl ResetReport {
+    /// Returns the controls that were reset to their default value.
+    pub fn reset(&self) -> &[Cid] {
+        &self.reset
+    }
+
+    /// Returns the controls that could not be reset, along with the reason.
+    pub fn failures(&self) -> &[(Cid, io::Error)] {
+        &self.failed
+    }
+
+    /// Returns whether all controls were reset successfully.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl fmt::Display for ResetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "reset {} controls", self.reset.len())?;
+        if !self.failed.is_empty() {
+            write!(f, ", {} failed", self.failed.len())?;
+            for (i, (cid, e)) in self.failed.iter().enumerate() {
+                let sep = if i == 0 { ": " } else { "; " };
+                write!(f, "{}{:?}: {}", sep, cid, e)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns whether `desc` is a control that [`reset_all`] should reset.
+fn is_resettable(desc: &ControlDesc) -> bool {
+    let skip = ControlFlags::DISABLED
+        | ControlFlags::READ_ONLY
+        | ControlFlags::INACTIVE
+        | ControlFlags::VOLATILE;
+    !desc.flags().intersects(skip)
+        && !matches!(desc.control_type(), CtrlType::BUTTON | CtrlType::CTRL_CLASS)
+}
+
+pub(crate) fn reset_all(device: &Device) -> io::Result<ResetReport> {
+    let mut classes: Vec<Vec<ControlDesc>> = Vec::new();
+    for desc in device.controls() {
+        let desc = desc?;
+        if !is_resettable(&desc) {
+            continue;
+        }
+        // Controls are enumerated ordered by ID, so each class is contiguous.
+        match classes.last_mut() {
+            Some(class) if class[0].class() == desc.class() => class.push(desc),
+            _ => classes.push(vec![desc]),
+        }
+    }
+
+    let mut report = ResetReport::default();
+    for descs in classes {
+        let res = read_descs(device, &descs, CtrlWhich::DEF_VAL)
+            .and_then(|defaults| write_descs(device, &descs, &defaults));
+        match res {
+            Ok(()) => report.reset.extend(descs.iter().map(ControlDesc::id)),
+            Err(e) => {
+                log::debug!(
+                    "resetting controls in batch failed ({}), resetting one by one",
+                    e
+                );
+                for desc in &descs {
+                    match reset_desc(device, desc) {
+                        Ok(()) => report.reset.push(desc.id()),
+                        Err(e) => report.failed.push((desc.id(), e)),
+                    }
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn reset_desc(device: &Device, desc: &ControlDesc) -> io::Result<()> {
+    let descs = std::slice::from_ref(desc);
+    let defaults = read_descs(device, descs, CtrlWhich::DEF_VAL)?;
+    write_descs(device, descs, &defaults)?;
+    Ok(())
+}
+
+pub(crate) fn reset_one(device: &Device, cid: Cid) -> io::Result<()> {
+    reset_desc(device, &query_control(device, cid)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw;
+
+    use std::mem;
+
+    #[test]
+    fn resettable() {
+        let mut raw: raw::QueryExtCtrl = unsafe { mem::zeroed() };
+        raw.type_ = CtrlType::INTEGER;
+        assert!(is_resettable(&ControlDesc(raw)));
+
+        for flag in [ControlFlags::INACTIVE, ControlFlags::READ_ONLY] {
+            let mut raw: raw::QueryExtCtrl = unsafe { mem::zeroed() };
+            raw.flags = flag;
+            assert!(!is_resettable(&ControlDesc(raw)));
+        }
+        let mut raw: raw::QueryExtCtrl = unsafe { mem::zeroed() };
+        raw.type_ = CtrlType::BUTTON;
+        assert!(!is_resettable(&ControlDesc(raw)));
+    }
+
+    #[test]
+    fn report() {
+        let report = ResetReport {
+            reset: vec![Cid::BRIGHTNESS, Cid::CONTRAST],
+            failed: vec![(Cid::EXPOSURE_ABSOLUTE, io::Error::other("inactive"))],
+        };
+        assert!(!report.is_complete());
+        assert_eq!(
+            report.to_string(),
+            "reset 2 controls, 1 failed: EXPOSURE_ABSOLUTE: inactive"
+        );
+    }
+}
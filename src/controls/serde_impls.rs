@@ -0,0 +1,107 @@
+//! `serde` support for control descriptions.
+
+use std::borrow::Cow;
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Cid, ControlDesc, ControlFlags, CtrlType};
+use crate::raw;
+use crate::serde_impls::{serde_via, str_to_array};
+
+#[derive(Serialize, Deserialize)]
+struct ControlDescRepr<'a> {
+    id: Cid,
+    name: Cow<'a, str>,
+    control_type: CtrlType,
+    minimum: i64,
+    maximum: i64,
+    step: u64,
+    default_value: i64,
+    flags: ControlFlags,
+    elem_size: u32,
+    elems: u32,
+    dims: Cow<'a, [u32]>,
+}
+
+impl<'a> From<&'a ControlDesc> for ControlDescRepr<'a> {
+    fn from(desc: &'a ControlDesc) -> Self {
+        Self {
+            id: desc.id(),
+            name: desc.name().into(),
+            control_type: desc.control_type(),
+            minimum: desc.minimum(),
+            maximum: desc.maximum(),
+            step: desc.step(),
+            default_value: desc.default_value(),
+            flags: desc.flags(),
+            elem_size: desc.elem_size(),
+            elems: desc.elems(),
+            dims: desc.dims().into(),
+        }
+    }
+}
+
+impl TryFrom<ControlDescRepr<'_>> for ControlDesc {
+    type Error = String;
+
+    fn try_from(repr: ControlDescRepr<'_>) -> Result<Self, String> {
+        if repr.dims.len() > raw::CTRL_MAX_DIMS {
+            return Err(format!(
+                "control has {} dimensions, at most {} are supported",
+                repr.dims.len(),
+                raw::CTRL_MAX_DIMS
+            ));
+        }
+        let mut raw: raw::QueryExtCtrl = unsafe { mem::zeroed() };
+        raw.id = repr.id.0;
+        raw.type_ = repr.control_type;
+        raw.name = str_to_array(&repr.name)?;
+        raw.minimum = repr.minimum;
+        raw.maximum = repr.maximum;
+        raw.step = repr.step;
+        raw.default_value = repr.default_value;
+        raw.flags = repr.flags;
+        raw.elem_size = repr.elem_size;
+        raw.elems = repr.elems;
+        raw.nr_of_dims = repr.dims.len() as u32;
+        raw.dims[..repr.dims.len()].copy_from_slice(&repr.dims);
+        Ok(ControlDesc(raw))
+    }
+}
+
+serde_via!(ControlDesc => ControlDescRepr);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_desc() {
+        let json = r#"{
+            "id": 9963776,
+            "name": "Brightness",
+            "control_type": 1,
+            "minimum": 0,
+            "maximum": 255,
+            "step": 1,
+            "default_value": 128,
+            "flags": 32,
+            "elem_size": 4,
+            "elems": 1,
+            "dims": []
+        }"#;
+        let desc: ControlDesc = serde_json::from_str(json).unwrap();
+        assert_eq!(desc.id(), Cid::BRIGHTNESS);
+        assert_eq!(desc.name(), "Brightness");
+        assert_eq!(desc.control_type(), CtrlType::INTEGER);
+        assert_eq!(desc.flags(), ControlFlags::SLIDER);
+
+        let value = serde_json::to_value(&desc).unwrap();
+        let desc: ControlDesc = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&desc).unwrap(), value);
+
+        let json = json.replace(r#""dims": []"#, "\"dims\": [1, 2, 3, 4, 5]");
+        assert!(serde_json::from_str::<ControlDesc>(&json).is_err());
+    }
+}
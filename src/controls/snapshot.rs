@@ -0,0 +1,302 @@
+//! Saving and restoring the values of all controls of a device.
+
+use std::collections::BTreeMap;
+use std::{fmt, io};
+
+use super::batch::{read_descs, write_descs};
+use super::{
+    query_optional, read_value, write_value, Cid, ControlBatch, ControlDesc, ControlFlags,
+    ControlValue, CtrlType,
+};
+use crate::raw::controls::CtrlWhich;
+use crate::Device;
+
+/// The values of the writable controls of a device, returned by [`Device::control_snapshot`].
+///
+/// Snapshots can be restored with [`Device::apply_snapshot`]. With the `serde` feature, they
+/// can be serialized, eg. to store a known-good camera profile as JSON. Controls are stored by
+/// their numeric ID, since driver-specific controls have no name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct ControlSnapshot {
+    controls: BTreeMap<u32, ControlValue>,
+}
+
+impl ControlSnapshot {
+    /// Creates an empty snapshot.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value of control `cid`, if the snapshot contains it.
+    pub fn get(&self, cid: impl Into<Cid>) -> Option<&ControlValue> {
+        self.controls.get(&cid.into().0)
+    }
+
+    /// Adds control `cid` to the snapshot, or replaces its value.
+    pub fn insert(&mut self, cid: impl Into<Cid>, value: ControlValue) {
+        self.controls.insert(cid.into().0, value);
+    }
+
+    /// Removes control `cid` from the snapshot, returning its value.
+    pub fn remove(&mut self, cid: impl Into<Cid>) -> Option<ControlValue> {
+        self.controls.remove(&cid.into().0)
+    }
+
+    /// Returns the number of controls in the snapshot.
+    pub fn len(&self) -> usize {
+        self.controls.len()
+    }
+
+    /// Returns whether the snapshot is empty.
+    pub fn is_empty(&self) -> bool {
+        self.controls.is_empty()
+    }
+
+    /// Returns an iterator over the controls in the snapshot, ordered by ID.
+    pub fn iter(&self) -> impl Iterator<Item = (Cid, &ControlValue)> + '_ {
+        self.controls.iter().map(|(&id, value)| (Cid(id), value))
+    }
+}
+
+/// The outcome of [`Device::apply_snapshot`].
+#[derive(Debug, Default)]
+pub struct ApplyReport {
+    applied: Vec<Cid>,
+    skipped: Vec<Cid>,
+    failed: Vec<(Cid, io::Error)>,
+}
+
+impl ApplyReport {
+    /// Returns the controls that were set to their value in the snapshot.
+    pub fn applied(&self) -> &[Cid] {
+        &self.applied
+    }
+
+    /// Returns the controls that were not set, because the device doesn't have them, or they
+    /// are read-only or inactive.
+    pub fn skipped(&self) -> &[Cid] {
+        &self.skipped
+    }
+
+    /// Returns the controls that could not be set, along with the reason.
+    pub fn failures(&self) -> &[(Cid, io::Error)] {
+        &self.failed
+    }
+
+    /// Returns whether no control failed to be set.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl fmt::Display for ApplyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "applied {} controls, skipped {}",
+            self.applied.len(),
+            self.skipped.len()
+        )?;
+        if !self.failed.is_empty() {
+            write!(f, ", {} failed", self.failed.len())?;
+            for (i, (cid, e)) in self.failed.iter().enumerate() {
+                let sep = if i == 0 { ": " } else { "; " };
+                write!(f, "{}{:?}: {}", sep, cid, e)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns whether `desc` is a control whose value a snapshot should contain.
+fn is_saved(desc: &ControlDesc) -> bool {
+    let skip = ControlFlags::DISABLED | ControlFlags::READ_ONLY | ControlFlags::WRITE_ONLY;
+    !desc.flags().intersects(skip)
+        && !matches!(desc.control_type(), CtrlType::BUTTON | CtrlType::CTRL_CLASS)
+}
+
+/// Returns whether `cid` switches other controls between automatic and manual operation.
+///
+/// These are restored first, since the controls they make inactive can only be written once
+/// automatic mode is disabled.
+fn is_auto_control(cid: Cid) -> bool {
+    matches!(
+        cid,
+        Cid::EXPOSURE_AUTO
+            | Cid::AUTOGAIN
+            | Cid::AUTOBRIGHTNESS
+            | Cid::HUE_AUTO
+            | Cid::AUTO_WHITE_BALANCE
+            | Cid::AUTO_N_PRESET_WHITE_BALANCE
+            | Cid::FOCUS_AUTO
+    )
+}
+
+pub(crate) fn snapshot(device: &Device) -> io::Result<ControlSnapshot> {
+    let mut classes: Vec<Vec<ControlDesc>> = Vec::new();
+    for desc in device.controls() {
+        let desc = desc?;
+        if !is_saved(&desc) {
+            continue;
+        }
+        match classes.last_mut() {
+            Some(class) if class[0].class() == desc.class() => class.push(desc),
+            _ => classes.push(vec![desc]),
+        }
+    }
+
+    let mut snapshot = ControlSnapshot::new();
+    for descs in classes {
+        match read_descs(device, &descs, CtrlWhich::CUR_VAL) {
+            Ok(batch) => {
+                for (cid, value) in batch.iter() {
+                    snapshot.insert(cid, value.clone());
+                }
+            }
+            Err(e) => {
+                log::debug!(
+                    "reading controls in batch failed ({}), reading one by one",
+                    e
+                );
+                for desc in &descs {
+                    match read_value(device, desc, CtrlWhich::CUR_VAL) {
+                        Ok(value) => snapshot.insert(desc.id(), value),
+                        Err(e) => log::debug!("skipping control {:?}: {}", desc.id(), e),
+                    }
+                }
+            }
+        }
+    }
+    Ok(snapshot)
+}
+
+pub(crate) fn apply(device: &Device, snapshot: &ControlSnapshot) -> io::Result<ApplyReport> {
+    let mut report = ApplyReport::default();
+
+    for (cid, value) in snapshot.iter().filter(|(cid, _)| is_auto_control(*cid)) {
+        match query_optional(device, cid) {
+            Ok(Some(desc)) if is_saved(&desc) => match write_value(device, &desc, value) {
+                Ok(()) => report.applied.push(cid),
+                Err(e) => report.failed.push((cid, e)),
+            },
+            Ok(_) => report.skipped.push(cid),
+            Err(e) => report.failed.push((cid, e)),
+        }
+    }
+
+    // The remaining controls are queried after the automatic modes were restored, so that the
+    // ones that are inactive now can be skipped.
+    let mut classes: Vec<(Vec<ControlDesc>, ControlBatch)> = Vec::new();
+    for (cid, value) in snapshot.iter().filter(|(cid, _)| !is_auto_control(*cid)) {
+        let desc = match query_optional(device, cid) {
+            Ok(Some(desc)) if is_saved(&desc) && !desc.flags().contains(ControlFlags::INACTIVE) => {
+                desc
+            }
+            Ok(_) => {
+                report.skipped.push(cid);
+                continue;
+            }
+            Err(e) => {
+                report.failed.push((cid, e));
+                continue;
+            }
+        };
+        match classes.last_mut() {
+            Some((descs, batch)) if descs[0].class() == desc.class() => {
+                descs.push(desc);
+                batch.push(cid, value.clone());
+            }
+            _ => {
+                let batch = ControlBatch::new().with_value(cid, value.clone());
+                classes.push((vec![desc], batch));
+            }
+        }
+    }
+
+    for (descs, batch) in classes {
+        match write_descs(device, &descs, &batch) {
+            Ok(()) => report.applied.extend(descs.iter().map(ControlDesc::id)),
+            Err(e) => {
+                log::debug!(
+                    "applying controls in batch failed ({}), applying one by one",
+                    e
+                );
+                for (desc, (cid, value)) in descs.iter().zip(batch.iter()) {
+                    match write_value(device, desc, value) {
+                        Ok(()) => report.applied.push(cid),
+                        Err(e) => report.failed.push((cid, e)),
+                    }
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot() {
+        let mut snapshot = ControlSnapshot::new();
+        snapshot.insert(Cid::CONTRAST, ControlValue::Integer(10));
+        snapshot.insert(Cid::BRIGHTNESS, ControlValue::Integer(20));
+        snapshot.insert(Cid::CONTRAST, ControlValue::Integer(30));
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(
+            snapshot.get(Cid::CONTRAST),
+            Some(&ControlValue::Integer(30))
+        );
+        let cids = snapshot.iter().map(|(cid, _)| cid).collect::<Vec<_>>();
+        assert_eq!(cids, [Cid::BRIGHTNESS, Cid::CONTRAST]);
+        assert_eq!(
+            snapshot.remove(Cid::BRIGHTNESS),
+            Some(ControlValue::Integer(20))
+        );
+        assert_eq!(snapshot.get(Cid::BRIGHTNESS), None);
+    }
+
+    #[test]
+    fn report() {
+        let report = ApplyReport {
+            applied: vec![Cid::EXPOSURE_AUTO],
+            skipped: vec![Cid::EXPOSURE_ABSOLUTE],
+            failed: vec![(Cid::GAIN, io::Error::other("busy"))],
+        };
+        assert!(!report.is_complete());
+        assert_eq!(
+            report.to_string(),
+            "applied 1 controls, skipped 1, 1 failed: GAIN: busy"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use super::super::Area;
+
+        let mut snapshot = ControlSnapshot::new();
+        snapshot.insert(Cid::BRIGHTNESS, ControlValue::Integer(128));
+        snapshot.insert(Cid::AUTOGAIN, ControlValue::Boolean(true));
+        snapshot.insert(
+            Cid::from(0x00f0f001),
+            ControlValue::Area(Area {
+                width: 1,
+                height: 2,
+            }),
+        );
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            json,
+            r#"{"9963776":{"Integer":128},"9963794":{"Boolean":true},"15790081":{"Area":{"width":1,"height":2}}}"#
+        );
+        let parsed: ControlSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, snapshot);
+    }
+}
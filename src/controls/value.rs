@@ -0,0 +1,502 @@
+//! Typed control values, including payload-based (string, array and compound) controls.
+
+use std::io;
+
+use nix::errno::Errno;
+
+use super::{access_error, control_error, query_control, Cid, ControlDesc, ControlFlags, CtrlType};
+use crate::raw::controls::{CtrlWhich, ExtControl, ExtControlValue};
+use crate::Device;
+
+/// The size of an image area, the payload of [`AREA`][CtrlType::AREA] controls (`struct v4l2_area`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Area {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Area {
+    const SIZE: usize = 8;
+
+    fn from_ne_bytes(b: &[u8]) -> Self {
+        Self {
+            width: u32::from_ne_bytes([b[0], b[1], b[2], b[3]]),
+            height: u32::from_ne_bytes([b[4], b[5], b[6], b[7]]),
+        }
+    }
+
+    fn to_ne_bytes(self) -> impl Iterator<Item = u8> {
+        self.width
+            .to_ne_bytes()
+            .into_iter()
+            .chain(self.height.to_ne_bytes())
+    }
+}
+
+/// The value of a control, as read by [`Device::control_value`].
+///
+/// Array controls are stored flattened, in row-major order. Their shape is given by
+/// [`ControlDesc::dims`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ControlValue {
+    /// The value of an [`INTEGER`][CtrlType::INTEGER], [`MENU`][CtrlType::MENU],
+    /// [`INTEGER_MENU`][CtrlType::INTEGER_MENU], [`BITMASK`][CtrlType::BITMASK] or
+    /// [`BUTTON`][CtrlType::BUTTON] control.
+    ///
+    /// For menu controls, this is the index of the selected item.
+    Integer(i32),
+    /// The value of an [`INTEGER64`][CtrlType::INTEGER64] control.
+    Integer64(i64),
+    /// The value of a [`BOOLEAN`][CtrlType::BOOLEAN] control.
+    Boolean(bool),
+    /// The value of a [`STRING`][CtrlType::STRING] control.
+    String(String),
+    /// The elements of a [`U8`][CtrlType::U8] control.
+    U8Array(Vec<u8>),
+    /// The elements of a [`U16`][CtrlType::U16] control.
+    U16Array(Vec<u16>),
+    /// The elements of a [`U32`][CtrlType::U32] control.
+    U32Array(Vec<u32>),
+    /// The value of an [`AREA`][CtrlType::AREA] control.
+    Area(Area),
+    /// The elements of an [`AREA`][CtrlType::AREA] array control.
+    AreaArray(Vec<Area>),
+    /// The payload of a compound control this crate has no dedicated type for, like
+    /// [`RECT`][CtrlType::RECT] controls and codec parameters.
+    ///
+    /// `data` holds the elements of the control back to back, each [`ControlDesc::elem_size`]
+    /// bytes large, in the layout of the corresponding kernel struct. When writing, `elem_size`
+    /// has to match the control's element size.
+    Compound { elem_size: u32, data: Vec<u8> },
+    /// The payload of any other control, like arrays of integers.
+    ///
+    /// This can also be used to write any control that has a payload.
+    Raw(Vec<u8>),
+}
+
+impl ControlValue {
+    /// Returns the value of a scalar integer or boolean control as an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            ControlValue::Integer(v) => Some(v.into()),
+            ControlValue::Integer64(v) => Some(v),
+            ControlValue::Boolean(v) => Some(v.into()),
+            _ => None,
+        }
+    }
+
+    /// Decodes the payload of a control of type `ty` with elements of `elem_size` bytes.
+    ///
+    /// `array` is whether the control is an array control.
+    fn from_payload(
+        ty: CtrlType,
+        elem_size: u32,
+        array: bool,
+        mut payload: Vec<u8>,
+    ) -> io::Result<Self> {
+        Ok(match ty {
+            CtrlType::STRING => {
+                if let Some(len) = payload.iter().position(|&b| b == 0) {
+                    payload.truncate(len);
+                }
+                let s = String::from_utf8(payload)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                ControlValue::String(s)
+            }
+            CtrlType::U8 => ControlValue::U8Array(payload),
+            CtrlType::U16 => ControlValue::U16Array(
+                payload
+                    .chunks_exact(2)
+                    .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+                    .collect(),
+            ),
+            CtrlType::U32 => ControlValue::U32Array(
+                payload
+                    .chunks_exact(4)
+                    .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect(),
+            ),
+            CtrlType::AREA if array => ControlValue::AreaArray(
+                payload
+                    .chunks_exact(Area::SIZE)
+                    .map(Area::from_ne_bytes)
+                    .collect(),
+            ),
+            CtrlType::AREA if payload.len() >= Area::SIZE => {
+                ControlValue::Area(Area::from_ne_bytes(&payload))
+            }
+            _ if has_payload_type(ty) && elem_size > 0 => ControlValue::Compound {
+                elem_size,
+                data: payload,
+            },
+            _ => ControlValue::Raw(payload),
+        })
+    }
+
+    /// Returns the payload to pass to the kernel, or `None` if `self` is not a payload value.
+    fn to_payload(&self) -> Option<Vec<u8>> {
+        Some(match self {
+            ControlValue::String(s) => {
+                let mut payload = Vec::with_capacity(s.len() + 1);
+                payload.extend_from_slice(s.as_bytes());
+                payload.push(0);
+                payload
+            }
+            ControlValue::U8Array(v) | ControlValue::Raw(v) => v.clone(),
+            ControlValue::Compound { data, .. } => data.clone(),
+            ControlValue::Area(area) => area.to_ne_bytes().collect(),
+            ControlValue::AreaArray(v) => v.iter().flat_map(|a| a.to_ne_bytes()).collect(),
+            ControlValue::U16Array(v) => v.iter().flat_map(|e| e.to_ne_bytes()).collect(),
+            ControlValue::U32Array(v) => v.iter().flat_map(|e| e.to_ne_bytes()).collect(),
+            _ => return None,
+        })
+    }
+
+    /// Returns whether `self` can be written to a control of type `ty`.
+    ///
+    /// `payload` is whether the control has a payload.
+    fn is_compatible(&self, ty: CtrlType, payload: bool) -> bool {
+        match self {
+            ControlValue::Integer(_) | ControlValue::Integer64(_) => {
+                !payload && ty != CtrlType::CTRL_CLASS
+            }
+            ControlValue::Boolean(_) => ty == CtrlType::BOOLEAN,
+            ControlValue::String(_) => ty == CtrlType::STRING,
+            ControlValue::U8Array(_) => ty == CtrlType::U8,
+            ControlValue::U16Array(_) => ty == CtrlType::U16,
+            ControlValue::U32Array(_) => ty == CtrlType::U32,
+            ControlValue::Area(_) | ControlValue::AreaArray(_) => ty == CtrlType::AREA,
+            ControlValue::Compound { .. } => payload && has_payload_type(ty),
+            ControlValue::Raw(_) => payload,
+        }
+    }
+}
+
+/// Returns whether values of type `ty` are always passed via a payload pointer.
+fn has_payload_type(ty: CtrlType) -> bool {
+    ty == CtrlType::STRING || ty.0 >= CtrlType::U8.0
+}
+
+fn has_payload(desc: &ControlDesc) -> bool {
+    desc.flags().contains(ControlFlags::HAS_PAYLOAD) || has_payload_type(desc.control_type())
+}
+
+/// Controls in the form passed to the `VIDIOC_*_EXT_CTRLS` ioctls, along with the buffers their
+/// payloads point to.
+pub(super) struct RawControls {
+    pub(super) controls: Vec<ExtControl>,
+    payloads: Vec<Vec<u8>>,
+}
+
+impl RawControls {
+    pub(super) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            controls: Vec::with_capacity(capacity),
+            payloads: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Adds a control to read the value of.
+    pub(super) fn push_read(&mut self, desc: &ControlDesc) -> io::Result<()> {
+        if desc.control_type() == CtrlType::CTRL_CLASS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "control {:?} is a control class and has no value",
+                    desc.id()
+                ),
+            ));
+        }
+
+        if has_payload(desc) {
+            // The kernel reports the required size with `ENOSPC` if this is not enough (eg. for
+            // dynamically sized arrays), see `grow`.
+            let size = desc.elem_size() as usize * desc.elems().max(1) as usize;
+            self.push_payload(desc.id(), vec![0; size.max(1)])
+        } else {
+            self.push_scalar(desc.id(), ExtControlValue { value64: 0 });
+            Ok(())
+        }
+    }
+
+    /// Adds a control to set to `value`.
+    pub(super) fn push_write(
+        &mut self,
+        desc: &ControlDesc,
+        value: &ControlValue,
+    ) -> io::Result<()> {
+        let cid = desc.id();
+        let ty = desc.control_type();
+        if !value.is_compatible(ty, has_payload(desc)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "cannot set control {:?} of type {:?} to {:?}",
+                    cid, ty, value
+                ),
+            ));
+        }
+
+        if let ControlValue::Compound { elem_size, .. } = value {
+            if *elem_size != desc.elem_size() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "control {:?} has {}-byte elements, but the value has {}-byte elements",
+                        cid,
+                        desc.elem_size(),
+                        elem_size
+                    ),
+                ));
+            }
+        }
+        if let Some(payload) = value.to_payload() {
+            return self.push_payload(cid, payload);
+        }
+        let value = value.as_i64().unwrap();
+        let raw = if ty == CtrlType::INTEGER64 {
+            ExtControlValue { value64: value }
+        } else {
+            match i32::try_from(value) {
+                Ok(value) => ExtControlValue { value },
+                Err(_) => {
+                    let e = Errno::ERANGE.into();
+                    return Err(control_error(e, cid, desc.flags(), Some(value)));
+                }
+            }
+        };
+        self.push_scalar(cid, raw);
+        Ok(())
+    }
+
+    fn push_scalar(&mut self, cid: Cid, value: ExtControlValue) {
+        self.controls.push(ExtControl {
+            id: cid,
+            size: 0,
+            reserved2: [0],
+            value,
+        });
+        self.payloads.push(Vec::new());
+    }
+
+    fn push_payload(&mut self, cid: Cid, mut payload: Vec<u8>) -> io::Result<()> {
+        let size = u32::try_from(payload.len()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "control payload too large")
+        })?;
+        // Moving the `Vec` into `self.payloads` doesn't move its heap buffer.
+        let ptr = payload.as_mut_ptr().cast();
+        self.controls.push(ExtControl {
+            id: cid,
+            size,
+            reserved2: [0],
+            value: ExtControlValue { ptr },
+        });
+        self.payloads.push(payload);
+        Ok(())
+    }
+
+    /// Grows the payload buffers the kernel reported as too small after failing with `ENOSPC`.
+    ///
+    /// Returns whether any buffer was grown.
+    pub(super) fn grow(&mut self) -> bool {
+        let mut grown = false;
+        for (control, payload) in self.controls.iter_mut().zip(&mut self.payloads) {
+            let required = control.size as usize;
+            if !payload.is_empty() && required > payload.len() {
+                payload.resize(required, 0);
+                control.value.ptr = payload.as_mut_ptr().cast();
+                grown = true;
+            }
+        }
+        grown
+    }
+
+    /// Returns the value of control `index`, which is described by `desc`.
+    pub(super) fn value(&self, index: usize, desc: &ControlDesc) -> io::Result<ControlValue> {
+        let control = &self.controls[index];
+        let payload = &self.payloads[index];
+        if !payload.is_empty() {
+            // Dynamic arrays report how much of the payload is used.
+            let used = (control.size as usize).min(payload.len());
+            let array = desc.elems() > 1 || !desc.dims().is_empty();
+            let payload = payload[..used].to_vec();
+            return ControlValue::from_payload(
+                desc.control_type(),
+                desc.elem_size(),
+                array,
+                payload,
+            );
+        }
+        Ok(unsafe {
+            match desc.control_type() {
+                CtrlType::INTEGER64 => ControlValue::Integer64(control.value.value64),
+                CtrlType::BOOLEAN => ControlValue::Boolean(control.value.value != 0),
+                _ => ControlValue::Integer(control.value.value),
+            }
+        })
+    }
+}
+
+/// Reads the value selected by `which` of the control described by `desc`.
+pub(crate) fn read_value(
+    device: &Device,
+    desc: &ControlDesc,
+    which: CtrlWhich,
+) -> io::Result<ControlValue> {
+    let mut raw = RawControls::with_capacity(1);
+    raw.push_read(desc)?;
+    loop {
+        match device.read_ext_controls_raw(which, &mut raw.controls) {
+            Ok(()) => return raw.value(0, desc),
+            Err(e) if e.raw_os_error() == Some(Errno::ENOSPC as i32) && raw.grow() => continue,
+            Err(e) => return Err(access_error(device, e, desc, None)),
+        }
+    }
+}
+
+/// Sets the control described by `desc` to `value`.
+pub(crate) fn write_value(
+    device: &Device,
+    desc: &ControlDesc,
+    value: &ControlValue,
+) -> io::Result<()> {
+    let mut raw = RawControls::with_capacity(1);
+    raw.push_write(desc, value)?;
+    device
+        .write_ext_controls_raw(&mut raw.controls)
+        .map_err(|e| access_error(device, e, desc, value.as_i64()))
+}
+
+/// Reads the current value of an integer, boolean, menu or 64-bit integer control.
+pub(crate) fn read_integer(device: &Device, cid: Cid) -> io::Result<i64> {
+    let desc = query_control(device, cid)?;
+    check_integer(&desc)?;
+    let value = read_value(device, &desc, CtrlWhich::CUR_VAL)?;
+    Ok(value.as_i64().unwrap())
+}
+
+/// Sets the value of an integer, boolean, menu or 64-bit integer control.
+pub(crate) fn write_integer(device: &Device, cid: Cid, value: i64) -> io::Result<()> {
+    let desc = query_control(device, cid)?;
+    check_integer(&desc)?;
+    write_value(device, &desc, &ControlValue::Integer64(value))
+}
+
+/// Triggers the action of a button control.
+pub(crate) fn press_button(device: &Device, cid: Cid) -> io::Result<()> {
+    let desc = query_control(device, cid)?;
+    if !desc.is_button() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "control {:?} is not a button control (it has type {:?})",
+                cid,
+                desc.control_type()
+            ),
+        ));
+    }
+    // The value is ignored, but the kernel requires a 32-bit one.
+    write_value(device, &desc, &ControlValue::Integer(0))
+}
+
+pub(super) fn check_integer(desc: &ControlDesc) -> io::Result<()> {
+    if has_payload(desc) || desc.control_type() == CtrlType::CTRL_CLASS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "control {:?} has type {:?}, which is not an integer type",
+                desc.id(),
+                desc.control_type()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payloads() {
+        let value = ControlValue::from_payload(CtrlType::STRING, 8, false, b"vivid\0\0\0".to_vec());
+        assert_eq!(value.unwrap(), ControlValue::String("vivid".into()));
+        assert!(ControlValue::from_payload(CtrlType::STRING, 2, false, vec![0xff, 0]).is_err());
+        assert_eq!(
+            ControlValue::String("ab".into()).to_payload().unwrap(),
+            b"ab\0"
+        );
+
+        let value = ControlValue::U16Array(vec![1, 0x1234]);
+        let payload = value.to_payload().unwrap();
+        assert_eq!(payload.len(), 4);
+        assert_eq!(
+            ControlValue::from_payload(CtrlType::U16, 2, true, payload).unwrap(),
+            value
+        );
+        let value = ControlValue::U32Array(vec![7, u32::MAX]);
+        let payload = value.to_payload().unwrap();
+        assert_eq!(
+            ControlValue::from_payload(CtrlType::U32, 4, true, payload).unwrap(),
+            value
+        );
+
+        let area = Area {
+            width: 640,
+            height: 480,
+        };
+        let payload = ControlValue::Area(area).to_payload().unwrap();
+        assert_eq!(payload.len(), 8);
+        assert_eq!(
+            ControlValue::from_payload(CtrlType::AREA, 8, false, payload.clone()).unwrap(),
+            ControlValue::Area(area)
+        );
+        let mut payload2 = payload.clone();
+        payload2.extend_from_slice(&payload);
+        assert_eq!(
+            ControlValue::from_payload(CtrlType::AREA, 8, true, payload2).unwrap(),
+            ControlValue::AreaArray(vec![area, area])
+        );
+        assert_eq!(
+            ControlValue::from_payload(CtrlType::RECT, 16, false, vec![1; 16]).unwrap(),
+            ControlValue::Compound {
+                elem_size: 16,
+                data: vec![1; 16]
+            }
+        );
+        assert_eq!(
+            ControlValue::from_payload(CtrlType::INTEGER, 4, true, vec![1; 8]).unwrap(),
+            ControlValue::Raw(vec![1; 8])
+        );
+
+        assert_eq!(ControlValue::Integer(3).to_payload(), None);
+        assert_eq!(ControlValue::Boolean(true).as_i64(), Some(1));
+        assert_eq!(ControlValue::Raw(vec![]).as_i64(), None);
+    }
+
+    #[test]
+    fn compatibility() {
+        assert!(ControlValue::Integer(1).is_compatible(CtrlType::MENU, false));
+        assert!(ControlValue::Integer(1).is_compatible(CtrlType::INTEGER64, false));
+        assert!(ControlValue::Integer64(1).is_compatible(CtrlType::INTEGER, false));
+        // Arrays of integers have a payload.
+        assert!(!ControlValue::Integer(1).is_compatible(CtrlType::INTEGER, true));
+        assert!(ControlValue::Raw(vec![]).is_compatible(CtrlType::INTEGER, true));
+        assert!(!ControlValue::Integer(1).is_compatible(CtrlType::STRING, true));
+        assert!(!ControlValue::U8Array(vec![]).is_compatible(CtrlType::U16, true));
+        assert!(ControlValue::Raw(vec![]).is_compatible(CtrlType::H264_SPS, true));
+        assert!(!ControlValue::Raw(vec![]).is_compatible(CtrlType::BOOLEAN, false));
+        let area = ControlValue::Area(Area::default());
+        assert!(area.is_compatible(CtrlType::AREA, true));
+        assert!(!area.is_compatible(CtrlType::U32, true));
+        let compound = ControlValue::Compound {
+            elem_size: 16,
+            data: vec![],
+        };
+        assert!(compound.is_compatible(CtrlType::RECT, true));
+        assert!(!compound.is_compatible(CtrlType::INTEGER, true));
+    }
+}
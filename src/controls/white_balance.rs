@@ -0,0 +1,160 @@
+//! White balance controls.
+
+use std::io;
+
+use super::{check_range, query_optional, read_integer, unsupported, write_integer, Cid};
+use crate::Device;
+
+/// `V4L2_WHITE_BALANCE_MANUAL`, the manual item of `V4L2_CID_AUTO_N_PRESET_WHITE_BALANCE`.
+const PRESET_MANUAL: i64 = 0;
+/// `V4L2_WHITE_BALANCE_AUTO`.
+const PRESET_AUTO: i64 = 1;
+
+/// A manual white balance setting.
+///
+/// Most cameras are set by color temperature, some (mostly sensors without an ISP) by the gains
+/// of the red and blue channels instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhiteBalance {
+    /// Color temperature in Kelvin (`V4L2_CID_WHITE_BALANCE_TEMPERATURE`).
+    Temperature(u32),
+    /// Red and blue channel gains (`V4L2_CID_RED_BALANCE` and `V4L2_CID_BLUE_BALANCE`), in
+    /// driver-specific units.
+    Gains { red: i64, blue: i64 },
+}
+
+/// Returns the control that enables automatic white balance, and the values that enable and
+/// disable it.
+fn auto_control(device: &Device) -> io::Result<Option<(Cid, i64, i64)>> {
+    if query_optional(device, Cid::AUTO_WHITE_BALANCE)?.is_some() {
+        return Ok(Some((Cid::AUTO_WHITE_BALANCE, 1, 0)));
+    }
+    // Cameras with white balance presets use this menu control instead.
+    if query_optional(device, Cid::AUTO_N_PRESET_WHITE_BALANCE)?.is_some() {
+        return Ok(Some((
+            Cid::AUTO_N_PRESET_WHITE_BALANCE,
+            PRESET_AUTO,
+            PRESET_MANUAL,
+        )));
+    }
+    Ok(None)
+}
+
+pub(crate) fn auto_white_balance(device: &Device) -> io::Result<bool> {
+    match auto_control(device)? {
+        Some((cid, auto, _)) => Ok(read_integer(device, cid)? == auto),
+        None => Err(unsupported(
+            "automatic white balance",
+            &[Cid::AUTO_WHITE_BALANCE, Cid::AUTO_N_PRESET_WHITE_BALANCE],
+        )),
+    }
+}
+
+pub(crate) fn set_auto_white_balance(device: &Device, enabled: bool) -> io::Result<()> {
+    match auto_control(device)? {
+        Some((cid, auto, manual)) => {
+            write_integer(device, cid, if enabled { auto } else { manual })
+        }
+        None => Err(unsupported(
+            "automatic white balance",
+            &[Cid::AUTO_WHITE_BALANCE, Cid::AUTO_N_PRESET_WHITE_BALANCE],
+        )),
+    }
+}
+
+/// Disables automatic white balance, which makes the manual controls inactive.
+fn disable_auto(device: &Device) -> io::Result<()> {
+    if let Some((cid, auto, manual)) = auto_control(device)? {
+        if read_integer(device, cid)? == auto {
+            log::debug!("disabling automatic white balance ({:?})", cid);
+            write_integer(device, cid, manual)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn temperature(device: &Device) -> io::Result<u32> {
+    if query_optional(device, Cid::WHITE_BALANCE_TEMPERATURE)?.is_none() {
+        return Err(unsupported(
+            "white balance temperature",
+            &[Cid::WHITE_BALANCE_TEMPERATURE],
+        ));
+    }
+    let value = read_integer(device, Cid::WHITE_BALANCE_TEMPERATURE)?;
+    Ok(value.clamp(0, u32::MAX.into()) as u32)
+}
+
+pub(crate) fn set_temperature(device: &Device, kelvin: u32) -> io::Result<u32> {
+    let desc = query_optional(device, Cid::WHITE_BALANCE_TEMPERATURE)?.ok_or_else(|| {
+        unsupported(
+            "white balance temperature",
+            &[Cid::WHITE_BALANCE_TEMPERATURE],
+        )
+    })?;
+    let value = check_range(&desc, kelvin.into())?;
+    disable_auto(device)?;
+    write_integer(device, Cid::WHITE_BALANCE_TEMPERATURE, value)?;
+    temperature(device)
+}
+
+fn gains(device: &Device) -> io::Result<(i64, i64)> {
+    Ok((
+        read_integer(device, Cid::RED_BALANCE)?,
+        read_integer(device, Cid::BLUE_BALANCE)?,
+    ))
+}
+
+fn has_gains(device: &Device) -> io::Result<bool> {
+    Ok(query_optional(device, Cid::RED_BALANCE)?.is_some()
+        && query_optional(device, Cid::BLUE_BALANCE)?.is_some())
+}
+
+fn no_white_balance() -> io::Error {
+    unsupported(
+        "manual white balance",
+        &[
+            Cid::WHITE_BALANCE_TEMPERATURE,
+            Cid::RED_BALANCE,
+            Cid::BLUE_BALANCE,
+        ],
+    )
+}
+
+pub(crate) fn white_balance(device: &Device) -> io::Result<WhiteBalance> {
+    if query_optional(device, Cid::WHITE_BALANCE_TEMPERATURE)?.is_some() {
+        return Ok(WhiteBalance::Temperature(temperature(device)?));
+    }
+    if has_gains(device)? {
+        let (red, blue) = gains(device)?;
+        return Ok(WhiteBalance::Gains { red, blue });
+    }
+    Err(no_white_balance())
+}
+
+pub(crate) fn set_white_balance(
+    device: &Device,
+    balance: WhiteBalance,
+) -> io::Result<WhiteBalance> {
+    match balance {
+        WhiteBalance::Temperature(kelvin) => {
+            set_temperature(device, kelvin).map(WhiteBalance::Temperature)
+        }
+        WhiteBalance::Gains { red, blue } => {
+            if !has_gains(device)? {
+                return Err(unsupported(
+                    "white balance gains",
+                    &[Cid::RED_BALANCE, Cid::BLUE_BALANCE],
+                ));
+            }
+            let red_desc = device.query_control(Cid::RED_BALANCE)?;
+            let blue_desc = device.query_control(Cid::BLUE_BALANCE)?;
+            let red = check_range(&red_desc, red)?;
+            let blue = check_range(&blue_desc, blue)?;
+            disable_auto(device)?;
+            write_integer(device, Cid::RED_BALANCE, red)?;
+            write_integer(device, Cid::BLUE_BALANCE, blue)?;
+            let (red, blue) = gains(device)?;
+            Ok(WhiteBalance::Gains { red, blue })
+        }
+    }
+}
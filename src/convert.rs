@@ -0,0 +1,379 @@
+//! Conversion of captured frames to RGB and greyscale images.
+//!
+//! The conversions operate on tightly packed destination buffers, and take the stride of the
+//! source image (see [`PixFormat::bytes_per_line`][crate::format::PixFormat::bytes_per_line]),
+//! since drivers can pad every line of a frame.
+
+use std::{fmt, io};
+
+use crate::format::{PixelFormat, Quantization};
+
+/// The range of the Y, U and V values of YUV data.
+///
+/// This corresponds to the quantization of the format negotiated with the driver, see
+/// [`PixFormat::resolved_quantization`][crate::format::PixFormat::resolved_quantization]. Most
+/// cameras produce limited range data, while JPEG images use the full range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum YuvRange {
+    /// Y values range from 16 to 235, U and V values from 16 to 240.
+    #[default]
+    Limited,
+    /// Y, U and V values use the full range from 0 to 255.
+    Full,
+}
+
+/// Fixed-point BT.601 conversion coefficients, scaled by `1 << SHIFT`.
+struct Coefficients {
+    y_offset: i32,
+    y: i32,
+    r_v: i32,
+    g_u: i32,
+    g_v: i32,
+    b_u: i32,
+}
+
+const SHIFT: u32 = 16;
+
+impl From<Quantization> for YuvRange {
+    /// Converts a resolved quantization. [`Quantization::DEFAULT`] is treated as limited range.
+    fn from(quantization: Quantization) -> Self {
+        match quantization {
+            Quantization::FULL_RANGE => YuvRange::Full,
+            _ => YuvRange::Limited,
+        }
+    }
+}
+
+impl YuvRange {
+    fn coefficients(self) -> Coefficients {
+        match self {
+            YuvRange::Limited => Coefficients {
+                y_offset: 16,
+                y: 76309,
+                r_v: 104597,
+                g_u: 25675,
+                g_v: 53279,
+                b_u: 132201,
+            },
+            YuvRange::Full => Coefficients {
+                y_offset: 0,
+                y: 1 << SHIFT,
+                r_v: 91881,
+                g_u: 22554,
+                g_v: 46802,
+                b_u: 116130,
+            },
+        }
+    }
+}
+
+/// Converts a [`YUYV`][crate::format::PixelFormat::YUYV] image to 24-bit RGB, using the BT.601
+/// color matrix.
+///
+/// `src` is the YUYV image, with lines `src_stride` bytes apart. The RGB image is written to the
+/// start of `dst`, which must be at least `width * height * 3` bytes large.
+///
+/// # Errors
+///
+/// Returns an error if `src_stride` is too small for a line of `width` pixels, or if `src` or
+/// `dst` are too small for the image.
+pub fn yuyv_to_rgb24(
+    src: &[u8],
+    src_stride: usize,
+    width: u32,
+    height: u32,
+    dst: &mut [u8],
+    range: YuvRange,
+) -> Result<(), ConvertError> {
+    let line = (width as usize).div_ceil(2) * 4;
+    let Some((width, height)) = check_sizes(src, src_stride, line, width, height, dst.len(), 3)?
+    else {
+        return Ok(());
+    };
+
+    let c = range.coefficients();
+    let round = 1 << (SHIFT - 1);
+    let pixel = |y: u8, r: i32, g: i32, b: i32, out: &mut [u8]| {
+        let y = (i32::from(y) - c.y_offset) * c.y + round;
+        out[0] = ((y + r) >> SHIFT).clamp(0, 255) as u8;
+        out[1] = ((y + g) >> SHIFT).clamp(0, 255) as u8;
+        out[2] = ((y + b) >> SHIFT).clamp(0, 255) as u8;
+    };
+
+    for (line, out) in src
+        .chunks(src_stride)
+        .zip(dst.chunks_exact_mut(width * 3))
+        .take(height)
+    {
+        let macropixels = line[..width.div_ceil(2) * 4].chunks_exact(4);
+        for (macropixel, out) in macropixels.zip(out.chunks_mut(6)) {
+            let &[y0, u, y1, v] = macropixel else {
+                unreachable!()
+            };
+            let (u, v) = (i32::from(u) - 128, i32::from(v) - 128);
+            let (r, g, b) = (c.r_v * v, -c.g_u * u - c.g_v * v, c.b_u * u);
+
+            pixel(y0, r, g, b, &mut out[..3]);
+            // The last macropixel of a line with an odd width only covers a single pixel.
+            if out.len() == 6 {
+                pixel(y1, r, g, b, &mut out[3..]);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Extracts the luma (Y) plane of a [`YUYV`][crate::format::PixelFormat::YUYV] image as an 8-bit
+/// greyscale image.
+///
+/// The Y values are copied without conversion, so for limited range data, black and white are 16
+/// and 235 instead of 0 and 255. Parameters are like for [`yuyv_to_rgb24`], except that `dst`
+/// only needs to be `width * height` bytes large.
+pub fn yuyv_to_grey(
+    src: &[u8],
+    src_stride: usize,
+    width: u32,
+    height: u32,
+    dst: &mut [u8],
+) -> Result<(), ConvertError> {
+    let line = (width as usize).div_ceil(2) * 4;
+    let Some((width, height)) = check_sizes(src, src_stride, line, width, height, dst.len(), 1)?
+    else {
+        return Ok(());
+    };
+
+    for (line, out) in src
+        .chunks(src_stride)
+        .zip(dst.chunks_exact_mut(width))
+        .take(height)
+    {
+        for (out, y) in out.iter_mut().zip(line.iter().step_by(2)) {
+            *out = *y;
+        }
+    }
+
+    Ok(())
+}
+
+/// Validates the buffer sizes for converting an image with `line` bytes per source line to an
+/// image with `dst_bpp` bytes per pixel, and returns the dimensions as `usize`s, or `None` if the
+/// image is empty.
+pub(crate) fn check_sizes(
+    src: &[u8],
+    src_stride: usize,
+    line: usize,
+    width: u32,
+    height: u32,
+    dst_len: usize,
+    dst_bpp: usize,
+) -> Result<Option<(usize, usize)>, ConvertError> {
+    let (width, height) = (width as usize, height as usize);
+    if width == 0 || height == 0 {
+        return Ok(None);
+    }
+
+    if src_stride < line {
+        return Err(ConvertError::StrideTooSmall {
+            stride: src_stride,
+            required: line,
+        });
+    }
+    let src_required = src_stride
+        .checked_mul(height - 1)
+        .and_then(|n| n.checked_add(line))
+        .ok_or(ConvertError::SourceTooSmall {
+            len: src.len(),
+            required: usize::MAX,
+        })?;
+    if src.len() < src_required {
+        return Err(ConvertError::SourceTooSmall {
+            len: src.len(),
+            required: src_required,
+        });
+    }
+    let dst_required = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(dst_bpp))
+        .unwrap_or(usize::MAX);
+    if dst_len < dst_required {
+        return Err(ConvertError::DestinationTooSmall {
+            len: dst_len,
+            required: dst_required,
+        });
+    }
+
+    Ok(Some((width, height)))
+}
+
+/// An error returned by the conversion functions when the buffers do not fit the image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConvertError {
+    /// The source stride is smaller than a line of the image.
+    StrideTooSmall { stride: usize, required: usize },
+    /// The source buffer is smaller than the image.
+    SourceTooSmall { len: usize, required: usize },
+    /// The destination buffer is smaller than the converted image.
+    DestinationTooSmall { len: usize, required: usize },
+    /// The conversion does not support the pixel format of the source image.
+    UnsupportedFormat(PixelFormat),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::StrideTooSmall { stride, required } => write!(
+                f,
+                "source stride of {} bytes is too small for a line of {} bytes",
+                stride, required
+            ),
+            ConvertError::SourceTooSmall { len, required } => write!(
+                f,
+                "source buffer of {} bytes is too small for the image ({} bytes)",
+                len, required
+            ),
+            ConvertError::DestinationTooSmall { len, required } => write!(
+                f,
+                "destination buffer of {} bytes is too small for the image ({} bytes)",
+                len, required
+            ),
+            ConvertError::UnsupportedFormat(format) => {
+                write!(f, "conversion from {} is not supported", format)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+impl From<ConvertError> for io::Error {
+    fn from(e: ConvertError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Straightforward floating-point BT.601 conversion.
+    fn reference(y: u8, u: u8, v: u8, range: YuvRange) -> [u8; 3] {
+        let (y, u, v) = (f64::from(y), f64::from(u) - 128.0, f64::from(v) - 128.0);
+        let (y, u, v) = match range {
+            YuvRange::Limited => (
+                (y - 16.0) * 255.0 / 219.0,
+                u * 255.0 / 224.0,
+                v * 255.0 / 224.0,
+            ),
+            YuvRange::Full => (y, u, v),
+        };
+        let r = y + 1.402 * v;
+        let g = y - 0.344136 * u - 0.714136 * v;
+        let b = y + 1.772 * u;
+        [r, g, b].map(|c| c.round().clamp(0.0, 255.0) as u8)
+    }
+
+    fn convert_pixel(y: u8, u: u8, v: u8, range: YuvRange) -> [u8; 3] {
+        let mut dst = [0; 6];
+        yuyv_to_rgb24(&[y, u, y, v], 4, 2, 1, &mut dst, range).unwrap();
+        assert_eq!(dst[..3], dst[3..]);
+        [dst[0], dst[1], dst[2]]
+    }
+
+    #[test]
+    fn reference_colors() {
+        let limited = YuvRange::Limited;
+        assert_eq!(convert_pixel(16, 128, 128, limited), [0, 0, 0]);
+        assert_eq!(convert_pixel(235, 128, 128, limited), [255, 255, 255]);
+        assert_eq!(convert_pixel(126, 128, 128, limited), [128, 128, 128]);
+        assert_eq!(convert_pixel(81, 90, 240, limited), [254, 0, 0]);
+        assert_eq!(convert_pixel(0, 128, 128, limited), [0, 0, 0]);
+        assert_eq!(convert_pixel(255, 128, 128, limited), [255, 255, 255]);
+
+        let full = YuvRange::Full;
+        assert_eq!(convert_pixel(0, 128, 128, full), [0, 0, 0]);
+        assert_eq!(convert_pixel(255, 128, 128, full), [255, 255, 255]);
+        assert_eq!(convert_pixel(76, 85, 255, full), [254, 0, 0]);
+    }
+
+    #[test]
+    fn matches_reference() {
+        for range in [YuvRange::Limited, YuvRange::Full] {
+            for y in (0..=255).step_by(5) {
+                for u in (0..=255).step_by(15) {
+                    for v in (0..=255).step_by(15) {
+                        let actual = convert_pixel(y, u, v, range);
+                        let expected = reference(y, u, v, range);
+                        for (a, e) in actual.into_iter().zip(expected) {
+                            assert!(a.abs_diff(e) <= 1, "{:?} != {:?}", actual, expected);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stride_and_odd_width() {
+        // 3x2 image with 2 bytes of padding after each line.
+        #[rustfmt::skip]
+        let src = [
+            16, 128, 235, 128, 126, 128, 0, 128, 0, 0,
+            235, 128, 16, 128, 16, 128, 0, 128,
+        ];
+        let mut dst = [1; 3 * 2 * 3 + 1];
+        yuyv_to_rgb24(&src, 10, 3, 2, &mut dst, YuvRange::Limited).unwrap();
+        #[rustfmt::skip]
+        assert_eq!(dst, [
+            0, 0, 0, 255, 255, 255, 128, 128, 128,
+            255, 255, 255, 0, 0, 0, 0, 0, 0,
+            1,
+        ]);
+
+        let mut grey = [0; 6];
+        yuyv_to_grey(&src, 10, 3, 2, &mut grey).unwrap();
+        assert_eq!(grey, [16, 235, 126, 235, 16, 16]);
+    }
+
+    #[test]
+    fn size_errors() {
+        let src = [0; 16];
+        let mut dst = [0; 24];
+        assert_eq!(
+            yuyv_to_rgb24(&src, 4, 4, 2, &mut dst, YuvRange::Limited),
+            Err(ConvertError::StrideTooSmall {
+                stride: 4,
+                required: 8
+            })
+        );
+        assert_eq!(
+            yuyv_to_rgb24(&src, 8, 4, 3, &mut dst, YuvRange::Limited),
+            Err(ConvertError::SourceTooSmall {
+                len: 16,
+                required: 24
+            })
+        );
+        assert_eq!(
+            yuyv_to_rgb24(&src, 8, 4, 2, &mut dst[..23], YuvRange::Limited),
+            Err(ConvertError::DestinationTooSmall {
+                len: 23,
+                required: 24
+            })
+        );
+        assert_eq!(
+            yuyv_to_grey(&src, 8, 4, 2, &mut dst[..7]),
+            Err(ConvertError::DestinationTooSmall {
+                len: 7,
+                required: 8
+            })
+        );
+        assert_eq!(yuyv_to_rgb24(&[], 0, 0, 0, &mut [], YuvRange::Full), Ok(()));
+
+        let error = io::Error::from(ConvertError::StrideTooSmall {
+            stride: 0,
+            required: 4,
+        });
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
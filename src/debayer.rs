@@ -0,0 +1,357 @@
+//! Demosaicing of raw Bayer sensor data.
+//!
+//! Raw sensor nodes deliver images in which every pixel only has one color sample, as determined
+//! by the color filter array (CFA) in front of the sensor. [`to_rgb24`] reconstructs the missing
+//! colors of every pixel by bilinear interpolation. This is simple and fast, but produces some
+//! color fringes at sharp edges, and does none of the other processing (black level, white
+//! balance, color correction) an ISP would do.
+
+use crate::convert::{check_sizes, ConvertError};
+use crate::format::PixelFormat;
+
+/// The arrangement of the color filters of a Bayer sensor.
+///
+/// Named after the colors of the top-left 2x2 pixels, row by row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CfaPattern {
+    Bggr,
+    Gbrg,
+    Grbg,
+    Rggb,
+}
+
+const R: usize = 0;
+const G: usize = 1;
+const B: usize = 2;
+
+impl CfaPattern {
+    /// Returns the color filter pattern of a Bayer pixel format, or `None` for other formats.
+    pub fn from_pixel_format(format: PixelFormat) -> Option<Self> {
+        Some(match format {
+            PixelFormat::SBGGR8
+            | PixelFormat::SBGGR10
+            | PixelFormat::SBGGR12
+            | PixelFormat::SBGGR10P
+            | PixelFormat::SBGGR12P => CfaPattern::Bggr,
+            PixelFormat::SGBRG8
+            | PixelFormat::SGBRG10
+            | PixelFormat::SGBRG12
+            | PixelFormat::SGBRG10P
+            | PixelFormat::SGBRG12P => CfaPattern::Gbrg,
+            PixelFormat::SGRBG8
+            | PixelFormat::SGRBG10
+            | PixelFormat::SGRBG12
+            | PixelFormat::SGRBG10P
+            | PixelFormat::SGRBG12P => CfaPattern::Grbg,
+            PixelFormat::SRGGB8
+            | PixelFormat::SRGGB10
+            | PixelFormat::SRGGB12
+            | PixelFormat::SRGGB10P
+            | PixelFormat::SRGGB12P => CfaPattern::Rggb,
+            _ => return None,
+        })
+    }
+
+    /// Returns the colors of the top-left 2x2 pixels, row by row.
+    fn colors(self) -> [usize; 4] {
+        match self {
+            CfaPattern::Bggr => [B, G, G, R],
+            CfaPattern::Gbrg => [G, B, R, G],
+            CfaPattern::Grbg => [G, R, B, G],
+            CfaPattern::Rggb => [R, G, G, B],
+        }
+    }
+}
+
+/// How the samples of a Bayer format are stored.
+#[derive(Clone, Copy)]
+enum Encoding {
+    /// One byte per sample.
+    Bits8,
+    /// The low `bits` bits of a 16-bit little-endian word per sample.
+    Unpacked { bits: u32 },
+    /// MIPI CSI-2 RAW10: 4 samples in 5 bytes.
+    Packed10,
+    /// MIPI CSI-2 RAW12: 2 samples in 3 bytes.
+    Packed12,
+}
+
+impl Encoding {
+    fn of(format: PixelFormat) -> Option<Self> {
+        Some(match format {
+            PixelFormat::SBGGR8
+            | PixelFormat::SGBRG8
+            | PixelFormat::SGRBG8
+            | PixelFormat::SRGGB8 => Encoding::Bits8,
+            PixelFormat::SBGGR10
+            | PixelFormat::SGBRG10
+            | PixelFormat::SGRBG10
+            | PixelFormat::SRGGB10 => Encoding::Unpacked { bits: 10 },
+            PixelFormat::SBGGR12
+            | PixelFormat::SGBRG12
+            | PixelFormat::SGRBG12
+            | PixelFormat::SRGGB12 => Encoding::Unpacked { bits: 12 },
+            PixelFormat::SBGGR10P
+            | PixelFormat::SGBRG10P
+            | PixelFormat::SGRBG10P
+            | PixelFormat::SRGGB10P => Encoding::Packed10,
+            PixelFormat::SBGGR12P
+            | PixelFormat::SGBRG12P
+            | PixelFormat::SGRBG12P
+            | PixelFormat::SRGGB12P => Encoding::Packed12,
+            _ => return None,
+        })
+    }
+
+    fn bits(self) -> u32 {
+        match self {
+            Encoding::Bits8 => 8,
+            Encoding::Unpacked { bits } => bits,
+            Encoding::Packed10 => 10,
+            Encoding::Packed12 => 12,
+        }
+    }
+
+    fn decode_line(self, line: &[u8], out: &mut [u16]) {
+        match self {
+            Encoding::Bits8 => {
+                for (out, &sample) in out.iter_mut().zip(line) {
+                    *out = sample.into();
+                }
+            }
+            Encoding::Unpacked { bits } => {
+                let mask = (1 << bits) - 1;
+                for (out, sample) in out.iter_mut().zip(line.chunks_exact(2)) {
+                    *out = u16::from_le_bytes([sample[0], sample[1]]) & mask;
+                }
+            }
+            Encoding::Packed10 => {
+                for (out, block) in out.chunks_mut(4).zip(line.chunks_exact(5)) {
+                    for (i, out) in out.iter_mut().enumerate() {
+                        *out = u16::from(block[i]) << 2 | u16::from(block[4] >> (2 * i)) & 0b11;
+                    }
+                }
+            }
+            Encoding::Packed12 => {
+                for (out, block) in out.chunks_mut(2).zip(line.chunks_exact(3)) {
+                    for (i, out) in out.iter_mut().enumerate() {
+                        *out = u16::from(block[i]) << 4 | u16::from(block[2] >> (4 * i)) & 0xf;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Demosaics a Bayer image to 24-bit RGB.
+///
+/// The CFA pattern, bit depth and packing of `src` are determined by `format`, which must be one
+/// of the Bayer formats (like [`PixelFormat::SRGGB8`], [`PixelFormat::SGRBG10`] or
+/// [`PixelFormat::SBGGR12P`]). Samples with more than 8 bits are scaled down to 8 bits.
+///
+/// Like for [`convert::yuyv_to_rgb24`][crate::convert::yuyv_to_rgb24], `src_stride` is the
+/// distance between the lines of `src`, and `dst` must be at least `width * height * 3` bytes
+/// large.
+///
+/// Every missing color of a pixel is the average of the samples of that color in the surrounding
+/// 3x3 pixels. At the edges of the image, only the neighbors within the image are used.
+///
+/// # Errors
+///
+/// Returns an error if `format` is not a Bayer format, or if the buffers are too small for the
+/// image.
+pub fn to_rgb24(
+    src: &[u8],
+    src_stride: usize,
+    width: u32,
+    height: u32,
+    format: PixelFormat,
+    dst: &mut [u8],
+) -> Result<(), ConvertError> {
+    let (Some(pattern), Some(encoding)) =
+        (CfaPattern::from_pixel_format(format), Encoding::of(format))
+    else {
+        return Err(ConvertError::UnsupportedFormat(format));
+    };
+    let line = format
+        .frame_size(width, 1)
+        .ok_or(ConvertError::SourceTooSmall {
+            len: src.len(),
+            required: usize::MAX,
+        })?;
+    let Some((width, height)) = check_sizes(src, src_stride, line, width, height, dst.len(), 3)?
+    else {
+        return Ok(());
+    };
+
+    let mut samples = vec![0; width * height];
+    for (line, out) in src
+        .chunks(src_stride)
+        .zip(samples.chunks_exact_mut(width))
+        .take(height)
+    {
+        encoding.decode_line(line, out);
+    }
+
+    let colors = pattern.colors();
+    let color_at = |x: usize, y: usize| colors[(y % 2) * 2 + x % 2];
+    let shift = encoding.bits() - 8;
+    for (y, out) in dst.chunks_exact_mut(width * 3).take(height).enumerate() {
+        for (x, out) in out.chunks_exact_mut(3).enumerate() {
+            let mut sum = [0u32; 3];
+            let mut count = [0u32; 3];
+            for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                    let color = color_at(nx, ny);
+                    sum[color] += u32::from(samples[ny * width + nx]);
+                    count[color] += 1;
+                }
+            }
+            // The color sampled by the pixel itself is not interpolated.
+            let color = color_at(x, y);
+            sum[color] = samples[y * width + x].into();
+            count[color] = 1;
+
+            for ((out, sum), count) in out.iter_mut().zip(sum).zip(count) {
+                let divisor = count << shift;
+                *out = match divisor {
+                    // Only possible for images that are a single pixel wide or high.
+                    0 => 0,
+                    _ => ((sum + divisor / 2) / divisor).min(255) as u8,
+                };
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an 8-bit Bayer image of `pattern` from a function returning the (R, G, B) value of
+    /// every pixel.
+    fn mosaic(
+        pattern: CfaPattern,
+        width: usize,
+        height: usize,
+        rgb: impl Fn(usize, usize) -> [u16; 3],
+    ) -> Vec<u16> {
+        let colors = pattern.colors();
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| rgb(x, y)[colors[(y % 2) * 2 + x % 2]])
+            .collect()
+    }
+
+    fn debayer(format: PixelFormat, src: &[u8], width: u32, height: u32) -> Vec<u8> {
+        let stride = format.frame_size(width, 1).unwrap();
+        let mut dst = vec![0; (width * height * 3) as usize];
+        to_rgb24(src, stride, width, height, format, &mut dst).unwrap();
+        dst
+    }
+
+    #[test]
+    fn uniform_color() {
+        for format in [
+            PixelFormat::SBGGR8,
+            PixelFormat::SGBRG8,
+            PixelFormat::SGRBG8,
+            PixelFormat::SRGGB8,
+        ] {
+            let pattern = CfaPattern::from_pixel_format(format).unwrap();
+            let src = mosaic(pattern, 5, 3, |_, _| [200, 100, 50]);
+            let src: Vec<u8> = src.into_iter().map(|s| s as u8).collect();
+            let rgb = debayer(format, &src, 5, 3);
+            for pixel in rgb.chunks_exact(3) {
+                assert_eq!(pixel, [200, 100, 50], "{}", format);
+            }
+        }
+    }
+
+    #[test]
+    fn interpolation() {
+        // A horizontal ramp is reproduced exactly, except for the outermost columns, where some
+        // neighbors are missing.
+        let src = mosaic(CfaPattern::Rggb, 6, 4, |x, _| [x as u16 * 10; 3]);
+        let src: Vec<u8> = src.into_iter().map(|s| s as u8).collect();
+        let rgb = debayer(PixelFormat::SRGGB8, &src, 6, 4);
+        for (y, line) in rgb.chunks_exact(6 * 3).enumerate() {
+            for (x, pixel) in line.chunks_exact(3).enumerate().skip(1).take(4) {
+                assert_eq!(pixel, [x as u8 * 10; 3], "pixel {}, {}", x, y);
+            }
+        }
+
+        // At the edges, only the neighbors within the image are averaged.
+        let rgb = debayer(PixelFormat::SRGGB8, &[10, 20, 40, 80], 2, 2);
+        assert_eq!(rgb, [10, 30, 80, 10, 20, 80, 10, 40, 80, 10, 30, 80]);
+    }
+
+    #[test]
+    fn bit_depths() {
+        let samples = mosaic(CfaPattern::Grbg, 6, 2, |x, y| {
+            [x as u16 * 150, 1000 - y as u16 * 300, 4095 - x as u16 * 50]
+        });
+        let unpacked: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut packed = Vec::new();
+        for pair in samples.chunks(2) {
+            packed.extend([(pair[0] >> 4) as u8, (pair[1] >> 4) as u8]);
+            packed.push((pair[0] & 0xf) as u8 | ((pair[1] & 0xf) << 4) as u8);
+        }
+        let expected = debayer(PixelFormat::SGRBG12, &unpacked, 6, 2);
+        assert_eq!(debayer(PixelFormat::SGRBG12P, &packed, 6, 2), expected);
+
+        // The samples are scaled down to 8 bits.
+        let eight: Vec<u8> = samples
+            .iter()
+            .map(|&s| ((s + 8) >> 4).min(255) as u8)
+            .collect();
+        let rgb = debayer(PixelFormat::SGRBG8, &eight, 6, 2);
+        for (a, b) in rgb.iter().zip(&expected) {
+            assert!(a.abs_diff(*b) <= 1);
+        }
+
+        let samples: Vec<u16> = samples.iter().map(|s| s >> 2).collect();
+        let unpacked: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut packed = Vec::new();
+        for block in samples.chunks(6).flat_map(|line| line.chunks(4)) {
+            let mut low = 0;
+            for (i, &s) in block.iter().enumerate() {
+                packed.push((s >> 2) as u8);
+                low |= ((s & 0b11) as u8) << (2 * i);
+            }
+            // The last block of a 6 pixel line only holds 2 pixels.
+            packed.resize(packed.len() + 4 - block.len(), 0);
+            packed.push(low);
+        }
+        assert_eq!(packed.len(), 20);
+        assert_eq!(
+            debayer(PixelFormat::SGRBG10P, &packed, 6, 2),
+            debayer(PixelFormat::SGRBG10, &unpacked, 6, 2)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut dst = [0; 12];
+        assert_eq!(
+            to_rgb24(&[0; 8], 4, 2, 2, PixelFormat::YUYV, &mut dst),
+            Err(ConvertError::UnsupportedFormat(PixelFormat::YUYV))
+        );
+        assert_eq!(
+            to_rgb24(&[0; 4], 2, 2, 2, PixelFormat::SRGGB10, &mut dst),
+            Err(ConvertError::StrideTooSmall {
+                stride: 2,
+                required: 4
+            })
+        );
+        assert_eq!(
+            to_rgb24(&[0; 4], 2, 2, 2, PixelFormat::SRGGB8, &mut dst[..11]),
+            Err(ConvertError::DestinationTooSmall {
+                len: 11,
+                required: 12
+            })
+        );
+    }
+}
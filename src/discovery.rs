@@ -0,0 +1,528 @@
+//! Enumeration of the V4L2 device nodes in `/dev`.
+
+use std::os::unix::prelude::*;
+use std::path::{Path, PathBuf};
+use std::{error, fmt, fs, io};
+
+use crate::{Capabilities, CapabilityFlags, Device};
+
+/// Name prefixes of the device nodes created by the V4L2 subsystem.
+const PREFIXES: &[&str] = &[
+    "video",
+    "vbi",
+    "radio",
+    "swradio",
+    "v4l-touch",
+    "v4l-subdev",
+];
+
+/// Directories containing udev's persistent symlinks to V4L2 device nodes.
+const LINK_DIRS: &[&str] = &["/dev/v4l/by-id", "/dev/v4l/by-path"];
+
+/// A V4L2 device node found by [`list`].
+#[derive(Debug)]
+pub struct DeviceInfo {
+    path: PathBuf,
+    links: Vec<PathBuf>,
+    capabilities: Result<Capabilities, io::Error>,
+}
+
+impl DeviceInfo {
+    /// Returns the path of the device node, eg. `/dev/video0`.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the symlinks in `/dev/v4l/by-id` and `/dev/v4l/by-path` that point to this node.
+    ///
+    /// Unlike the node's [`path`][Self::path], these stay the same across reboots and USB
+    /// re-enumeration.
+    pub fn links(&self) -> &[PathBuf] {
+        &self.links
+    }
+
+    /// Returns the capabilities of the device, or `None` if the node could not be queried.
+    ///
+    /// This contains the driver, card and bus info of the device.
+    pub fn capabilities(&self) -> Option<&Capabilities> {
+        self.capabilities.as_ref().ok()
+    }
+
+    /// Returns the error that prevented querying the node's capabilities.
+    ///
+    /// This is typically an error of kind [`io::ErrorKind::PermissionDenied`] when the current
+    /// user lacks access to the device node.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.capabilities.as_ref().err()
+    }
+
+    /// Returns what kind of data the node provides, or `None` if the node could not be queried.
+    pub fn kind(&self) -> Option<NodeKind> {
+        self.capabilities()
+            .map(|caps| NodeKind::from_capabilities(caps.device_capabilities()))
+    }
+
+    /// Opens the device node.
+    pub fn open(&self) -> io::Result<Device> {
+        Device::open(&self.path)
+    }
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        match &self.capabilities {
+            Ok(caps) => write!(
+                f,
+                " ({} node of \"{}\" at {})",
+                NodeKind::from_capabilities(caps.device_capabilities()),
+                caps.card(),
+                caps.bus_info()
+            ),
+            Err(e) => write!(f, " ({})", e),
+        }
+    }
+}
+
+/// The kind of data a V4L2 device node provides.
+///
+/// A single camera often exposes several nodes with the same card name and bus info, eg. a UVC
+/// webcam has a video node and a companion metadata node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// The node captures or outputs video frames.
+    Video,
+    /// The node only captures or outputs metadata.
+    Metadata,
+    /// Any other node, eg. a radio tuner or software defined radio.
+    Other,
+}
+
+impl NodeKind {
+    fn from_capabilities(caps: CapabilityFlags) -> Self {
+        let video = CapabilityFlags::VIDEO_CAPTURE
+            | CapabilityFlags::VIDEO_CAPTURE_MPLANE
+            | CapabilityFlags::VIDEO_OUTPUT
+            | CapabilityFlags::VIDEO_OUTPUT_MPLANE
+            | CapabilityFlags::VIDEO_M2M
+            | CapabilityFlags::VIDEO_M2M_MPLANE;
+        if caps.intersects(video) {
+            Self::Video
+        } else if caps.intersects(CapabilityFlags::META_CAPTURE | CapabilityFlags::META_OUTPUT) {
+            Self::Metadata
+        } else {
+            Self::Other
+        }
+    }
+}
+
+impl fmt::Display for NodeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Video => "video",
+            Self::Metadata => "metadata",
+            Self::Other => "other",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Matcher {
+    Card(String),
+    BusInfo(String),
+    Link(PathBuf),
+}
+
+/// Describes which device [`Device::open_by`] should open.
+///
+/// Unlike node paths like `/dev/video0`, the card name, bus info and the links in
+/// `/dev/v4l/by-id` don't depend on the order in which devices were enumerated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceSelector {
+    matcher: Matcher,
+    kind: Option<NodeKind>,
+}
+
+impl DeviceSelector {
+    /// Selects the video node of the device whose card name contains `name`.
+    pub fn card(name: impl Into<String>) -> Self {
+        Self {
+            matcher: Matcher::Card(name.into()),
+            kind: Some(NodeKind::Video),
+        }
+    }
+
+    /// Selects the video node of the device whose bus info is exactly `bus_info`.
+    ///
+    /// Bus info like `usb-0000:00:14.0-2` identifies the port a device is plugged into.
+    pub fn bus_info(bus_info: impl Into<String>) -> Self {
+        Self {
+            matcher: Matcher::BusInfo(bus_info.into()),
+            kind: Some(NodeKind::Video),
+        }
+    }
+
+    /// Selects the node a persistent symlink like `/dev/v4l/by-id/usb-…-video-index0` points to.
+    ///
+    /// A bare file name is looked up in `/dev/v4l/by-id`. Since the link already names a single
+    /// node, nodes of any [`NodeKind`] match.
+    pub fn link(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let path = if path.parent() == Some(Path::new("")) {
+            Path::new(LINK_DIRS[0]).join(path)
+        } else {
+            path.to_path_buf()
+        };
+        Self {
+            matcher: Matcher::Link(path),
+            kind: None,
+        }
+    }
+
+    /// Only matches nodes of the given kind.
+    ///
+    /// This is used to pick the metadata node of a camera instead of its video node.
+    pub fn kind(mut self, kind: NodeKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Matches nodes of any kind.
+    pub fn any_kind(mut self) -> Self {
+        self.kind = None;
+        self
+    }
+
+    fn matches(&self, info: &DeviceInfo) -> bool {
+        if self.kind.is_some() && info.kind() != self.kind {
+            return false;
+        }
+        match &self.matcher {
+            Matcher::Card(name) => info.capabilities().is_some_and(|c| c.card().contains(name)),
+            Matcher::BusInfo(bus) => info.capabilities().is_some_and(|c| c.bus_info() == bus),
+            Matcher::Link(path) => info.path() == path || info.links().contains(path),
+        }
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.matcher {
+            Matcher::Card(name) => write!(f, "card name containing \"{}\"", name)?,
+            Matcher::BusInfo(bus) => write!(f, "bus info \"{}\"", bus)?,
+            Matcher::Link(path) => write!(f, "link {}", path.display())?,
+        }
+        match self.kind {
+            Some(kind) => write!(f, " ({} node)", kind),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Error returned by [`Device::open_by`].
+#[derive(Debug)]
+pub enum OpenByError {
+    /// No device node matches the selector. `candidates` contains all device nodes found.
+    NoMatch {
+        selector: DeviceSelector,
+        candidates: Vec<DeviceInfo>,
+    },
+    /// More than one device node matches the selector. `candidates` contains the matching nodes.
+    MultipleMatches {
+        selector: DeviceSelector,
+        candidates: Vec<DeviceInfo>,
+    },
+    /// Listing the devices or opening the matching node failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for OpenByError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (candidates, label) = match self {
+            Self::NoMatch {
+                selector,
+                candidates,
+            } => {
+                write!(f, "no device matches {}", selector)?;
+                (candidates, "available devices")
+            }
+            Self::MultipleMatches {
+                selector,
+                candidates,
+            } => {
+                write!(f, "{} devices match {}", candidates.len(), selector)?;
+                (candidates, "candidates")
+            }
+            Self::Io(e) => return write!(f, "{}", e),
+        };
+        if candidates.is_empty() {
+            return write!(f, " (no devices found)");
+        }
+        write!(f, "; {}: ", label)?;
+        for (i, info) in candidates.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", info)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for OpenByError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for OpenByError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<OpenByError> for io::Error {
+    fn from(e: OpenByError) -> Self {
+        match e {
+            OpenByError::Io(e) => e,
+            OpenByError::NoMatch { .. } => io::Error::new(io::ErrorKind::NotFound, e),
+            OpenByError::MultipleMatches { .. } => io::Error::new(io::ErrorKind::InvalidInput, e),
+        }
+    }
+}
+
+/// Picks the single device in `devices` that matches `selector`.
+fn select(devices: Vec<DeviceInfo>, selector: &DeviceSelector) -> Result<DeviceInfo, OpenByError> {
+    let (mut matches, others): (Vec<_>, Vec<_>) =
+        devices.into_iter().partition(|info| selector.matches(info));
+    match matches.len() {
+        0 => Err(OpenByError::NoMatch {
+            selector: selector.clone(),
+            candidates: others,
+        }),
+        1 => Ok(matches.remove(0)),
+        _ => Err(OpenByError::MultipleMatches {
+            selector: selector.clone(),
+            candidates: matches,
+        }),
+    }
+}
+
+pub(crate) fn open_by(selector: &DeviceSelector) -> Result<Device, OpenByError> {
+    let info = select(list()?, selector)?;
+    Ok(info.open()?)
+}
+
+/// Lists all V4L2 device nodes in `/dev`.
+///
+/// Every node is opened briefly to query its capabilities, and closed again before this function
+/// returns. Nodes that cannot be opened or queried are still returned, with
+/// [`DeviceInfo::error`] describing the problem.
+///
+/// The devices are sorted by node name, with `/dev/video2` coming before `/dev/video10`.
+pub fn list() -> io::Result<Vec<DeviceInfo>> {
+    let mut nodes = Vec::new();
+    for entry in fs::read_dir("/dev")? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if !PREFIXES.iter().any(|p| name.starts_with(p)) {
+            continue;
+        }
+        if !entry.file_type()?.is_char_device() {
+            log::debug!("ignoring {}, which is not a character device", name);
+            continue;
+        }
+        nodes.push(entry.path());
+    }
+    nodes.sort_by(|a, b| node_key(a).cmp(&node_key(b)));
+
+    let links = persistent_links();
+    Ok(nodes
+        .into_iter()
+        .map(|path| {
+            // Open in non-blocking mode, since some drivers block in `open` otherwise.
+            let capabilities = Device::open_non_blocking(&path).and_then(|dev| dev.capabilities());
+            let links = links
+                .iter()
+                .filter(|(_, target)| *target == path)
+                .map(|(link, _)| link.clone())
+                .collect();
+            DeviceInfo {
+                path,
+                links,
+                capabilities,
+            }
+        })
+        .collect())
+}
+
+/// Returns all persistent symlinks, along with the device node they resolve to.
+fn persistent_links() -> Vec<(PathBuf, PathBuf)> {
+    let mut links = Vec::new();
+    for dir in LINK_DIRS {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                // The directories only exist if udev created at least one link.
+                if e.kind() != io::ErrorKind::NotFound {
+                    log::debug!("failed to read {}: {}", dir, e);
+                }
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let link = entry.path();
+            match fs::canonicalize(&link) {
+                Ok(target) => links.push((link, target)),
+                Err(e) => log::debug!("failed to resolve {}: {}", link.display(), e),
+            }
+        }
+    }
+    links.sort();
+    links
+}
+
+/// Sort key that orders node names by prefix first, and then by the numeric suffix.
+fn node_key(path: &Path) -> (&str, u64, &str) {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let prefix = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    let number = name[prefix.len()..].parse().unwrap_or(0);
+    (prefix, number, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw;
+
+    use std::mem;
+
+    #[test]
+    fn node_order() {
+        let mut nodes: Vec<_> = ["video10", "vbi0", "video2", "v4l-subdev0", "video0"]
+            .iter()
+            .map(|n| Path::new("/dev").join(n))
+            .collect();
+        nodes.sort_by(|a, b| node_key(a).cmp(&node_key(b)));
+        let names: Vec<_> = nodes
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            ["v4l-subdev0", "vbi0", "video0", "video2", "video10"]
+        );
+    }
+
+    fn device(node: &str, card: &str, bus_info: &str, caps: CapabilityFlags) -> DeviceInfo {
+        let mut raw: raw::Capabilities = unsafe { mem::zeroed() };
+        raw.card[..card.len()].copy_from_slice(card.as_bytes());
+        raw.bus_info[..bus_info.len()].copy_from_slice(bus_info.as_bytes());
+        raw.capabilities = caps.bits();
+        DeviceInfo {
+            path: Path::new("/dev").join(node),
+            links: Vec::new(),
+            capabilities: Ok(Capabilities(raw)),
+        }
+    }
+
+    fn devices() -> Vec<DeviceInfo> {
+        let meta = CapabilityFlags::META_CAPTURE | CapabilityFlags::STREAMING;
+        let video = CapabilityFlags::VIDEO_CAPTURE | CapabilityFlags::STREAMING;
+        let mut webcam = device("video0", "HD Webcam", "usb-0000:00:14.0-2", video);
+        webcam.links.push(PathBuf::from(
+            "/dev/v4l/by-id/usb-Vendor_HD_Webcam-video-index0",
+        ));
+        vec![
+            webcam,
+            device("video1", "HD Webcam", "usb-0000:00:14.0-2", meta),
+            device("video2", "HD Webcam", "usb-0000:00:14.0-3", video),
+            DeviceInfo {
+                path: PathBuf::from("/dev/video3"),
+                links: Vec::new(),
+                capabilities: Err(io::ErrorKind::PermissionDenied.into()),
+            },
+        ]
+    }
+
+    fn selected(selector: DeviceSelector) -> Result<PathBuf, OpenByError> {
+        select(devices(), &selector).map(|info| info.path)
+    }
+
+    #[test]
+    fn node_kind() {
+        let kinds: Vec<_> = devices().iter().map(DeviceInfo::kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                Some(NodeKind::Video),
+                Some(NodeKind::Metadata),
+                Some(NodeKind::Video),
+                None
+            ]
+        );
+        assert_eq!(
+            NodeKind::from_capabilities(CapabilityFlags::RADIO),
+            NodeKind::Other
+        );
+    }
+
+    #[test]
+    fn selectors() {
+        let bus = "usb-0000:00:14.0-2";
+        assert_eq!(
+            selected(DeviceSelector::bus_info(bus)).unwrap(),
+            Path::new("/dev/video0")
+        );
+        assert_eq!(
+            selected(DeviceSelector::bus_info(bus).kind(NodeKind::Metadata)).unwrap(),
+            Path::new("/dev/video1")
+        );
+        assert_eq!(
+            selected(DeviceSelector::link("usb-Vendor_HD_Webcam-video-index0")).unwrap(),
+            Path::new("/dev/video0")
+        );
+        assert_eq!(
+            selected(DeviceSelector::link("/dev/video3")).unwrap(),
+            Path::new("/dev/video3")
+        );
+
+        match selected(DeviceSelector::card("Webcam")) {
+            Err(OpenByError::MultipleMatches { candidates, .. }) => {
+                let paths: Vec<_> = candidates.iter().map(DeviceInfo::path).collect();
+                assert_eq!(paths, [Path::new("/dev/video0"), Path::new("/dev/video2")]);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+        match selected(DeviceSelector::bus_info("usb-0000:00:14.0")) {
+            Err(OpenByError::NoMatch { candidates, .. }) => assert_eq!(candidates.len(), 4),
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert!(selected(DeviceSelector::card("Webcam").any_kind()).is_err());
+    }
+
+    #[test]
+    fn error_message() {
+        let err = selected(DeviceSelector::card("Webcam")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "2 devices match card name containing \"Webcam\" (video node); candidates: \
+             /dev/video0 (video node of \"HD Webcam\" at usb-0000:00:14.0-2), \
+             /dev/video2 (video node of \"HD Webcam\" at usb-0000:00:14.0-3)"
+        );
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::InvalidInput);
+
+        let err = select(Vec::new(), &DeviceSelector::card("Webcam")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no device matches card name containing \"Webcam\" (video node) (no devices found)"
+        );
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,360 @@
+//! Recording captured frames to a file, and replaying them.
+//!
+//! A dump file stores frames exactly as the driver delivered them, together with the metadata
+//! needed to interpret them later. This is useful for archiving problematic camera output and for
+//! testing processing code offline.
+//!
+//! # File format
+//!
+//! All integers are little-endian. A dump starts with a header:
+//!
+//! | Size | Content                                    |
+//! |------|--------------------------------------------|
+//! | 6    | Magic bytes `LVDUMP`                       |
+//! | 2    | Format version (currently 1)               |
+//! | 4    | Pixel format (fourcc)                      |
+//! | 4    | Width in pixels                            |
+//! | 4    | Height in pixels                           |
+//! | 4    | Stride (bytes per line, 0 if not known)    |
+//!
+//! It is followed by any number of frames:
+//!
+//! | Size | Content                                          |
+//! |------|--------------------------------------------------|
+//! | 4    | Buffer index                                     |
+//! | 4    | Sequence number                                  |
+//! | 4    | Buffer flags ([`BufFlag`])                       |
+//! | 8    | Timestamp in nanoseconds (0 if there is none)    |
+//! | 4    | Length of the frame data (`bytesused`)           |
+//! | *n*  | Frame data                                       |
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::format::{PixFormat, PixelFormat};
+use crate::shared::BufFlag;
+use crate::stream::{ReadBufferView, SequenceTracker};
+
+const MAGIC: &[u8; 6] = b"LVDUMP";
+const VERSION: u16 = 1;
+
+/// Writes captured frames to a dump file.
+pub struct DumpWriter<W: Write = BufWriter<File>> {
+    writer: W,
+}
+
+impl DumpWriter {
+    /// Creates a dump file at `path` for frames of the given format.
+    ///
+    /// If the file already exists, it is truncated.
+    pub fn create<P: AsRef<Path>>(path: P, format: &PixFormat) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), format)
+    }
+}
+
+impl<W: Write> DumpWriter<W> {
+    /// Writes the dump header for frames of the given format to `writer`.
+    pub fn new(mut writer: W, format: &PixFormat) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        for value in [
+            u32::from_le_bytes(format.pixel_format().as_fourcc()),
+            format.width(),
+            format.height(),
+            format.bytes_per_line(),
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+
+        Ok(Self { writer })
+    }
+
+    /// Appends a frame to the dump.
+    ///
+    /// Only the used portion of the buffer is written. This is meant to be called from the
+    /// callback passed to [`ReadStream::dequeue`][crate::stream::ReadStream::dequeue].
+    pub fn write_frame(&mut self, view: &ReadBufferView<'_>) -> io::Result<()> {
+        let len = u32::try_from(view.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame is too large"))?;
+        let timestamp = view.timestamp().map_or(0, |ts| ts.as_nanos() as u64);
+
+        self.writer.write_all(&view.index().to_le_bytes())?;
+        self.writer.write_all(&view.sequence().to_le_bytes())?;
+        self.writer.write_all(&view.flags().bits().to_le_bytes())?;
+        self.writer.write_all(&timestamp.to_le_bytes())?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(view)?;
+        Ok(())
+    }
+
+    /// Flushes buffered data and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads frames from a dump file written by a [`DumpWriter`].
+pub struct DumpReader<R: Read = BufReader<File>> {
+    reader: R,
+    pixel_format: PixelFormat,
+    width: u32,
+    height: u32,
+    bytes_per_line: u32,
+    buf: Vec<u8>,
+    sequence: SequenceTracker,
+}
+
+impl DumpReader {
+    /// Opens the dump file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> DumpReader<R> {
+    /// Reads and validates the dump header from `reader`.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 6];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a frame dump (invalid magic bytes)",
+            ));
+        }
+        let mut version = [0; 2];
+        reader.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported frame dump version {}", version),
+            ));
+        }
+
+        let pixel_format = PixelFormat::from_fourcc(read_u32(&mut reader)?.to_le_bytes());
+        let width = read_u32(&mut reader)?;
+        let height = read_u32(&mut reader)?;
+        let bytes_per_line = read_u32(&mut reader)?;
+
+        Ok(Self {
+            reader,
+            pixel_format,
+            width,
+            height,
+            bytes_per_line,
+            buf: Vec::new(),
+            sequence: SequenceTracker::default(),
+        })
+    }
+
+    /// Returns the pixel format of the recorded frames.
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    /// Returns the width of the recorded frames in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of the recorded frames in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the stride of the recorded frames in bytes, or 0 if it is not known.
+    pub fn bytes_per_line(&self) -> u32 {
+        self.bytes_per_line
+    }
+
+    /// Reads the next frame, passes it to `cb`, and returns the result of `cb`.
+    ///
+    /// The view passed to `cb` reports the metadata that was recorded with the frame. Since only
+    /// the used portion of the buffer is recorded, [`ReadBufferView::raw_buffer`] returns the same
+    /// data as the view itself.
+    ///
+    /// Returns `None` at the end of the dump. If the dump ends in the middle of a frame, an error
+    /// of kind [`io::ErrorKind::UnexpectedEof`] is returned.
+    pub fn next_frame<T>(
+        &mut self,
+        cb: impl FnOnce(ReadBufferView<'_>) -> io::Result<T>,
+    ) -> io::Result<Option<T>> {
+        let mut index = [0; 4];
+        if !read_exact_or_eof(&mut self.reader, &mut index)? {
+            return Ok(None);
+        }
+        let index = u32::from_le_bytes(index);
+        let sequence = read_u32(&mut self.reader)?;
+        // Keep flags this version doesn't know about, like the driver-reported ones would be.
+        let flags = unsafe { BufFlag::from_bits_unchecked(read_u32(&mut self.reader)?) };
+        let mut timestamp = [0; 8];
+        self.reader.read_exact(&mut timestamp)?;
+        let timestamp = match u64::from_le_bytes(timestamp) {
+            0 => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        };
+        let len = read_u32(&mut self.reader)?;
+
+        // Don't trust the length before the data is actually there, it might be corrupted.
+        self.buf.clear();
+        let read = (&mut self.reader)
+            .take(len.into())
+            .read_to_end(&mut self.buf)?;
+        if read != len as usize {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let gap = self.sequence.observe(sequence);
+        let view = ReadBufferView::from_parts(index, flags, &self.buf, sequence, timestamp, gap);
+        cb(view).map(Some)
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Fills `buf`, returning `false` if the reader is at EOF before any byte was read.
+fn read_exact_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn synthetic_frame(i: u32) -> Vec<u8> {
+        (0..i * 7 + 3).map(|b| (b ^ i) as u8).collect()
+    }
+
+    #[test]
+    fn roundtrip() -> io::Result<()> {
+        let format = PixFormat::new(640, 480, PixelFormat::YUYV);
+        let mut writer = DumpWriter::new(Vec::new(), &format)?;
+        for i in 0..5 {
+            let data = synthetic_frame(i);
+            let flags = if i == 3 {
+                BufFlag::ERROR
+            } else {
+                BufFlag::TIMESTAMP_MONOTONIC
+            };
+            // Leave a gap in the sequence numbers after the second frame.
+            let sequence = if i < 2 { i } else { i + 1 };
+            let timestamp = (i != 0).then(|| Duration::from_micros(u64::from(i) * 33_333));
+            let view = ReadBufferView::from_parts(i % 2, flags, &data, sequence, timestamp, 0);
+            writer.write_frame(&view)?;
+        }
+        let dump = writer.finish()?;
+        assert_eq!(&dump[..6], b"LVDUMP");
+
+        let mut reader = DumpReader::new(&dump[..])?;
+        assert_eq!(reader.pixel_format(), PixelFormat::YUYV);
+        assert_eq!(reader.width(), 640);
+        assert_eq!(reader.height(), 480);
+        assert_eq!(reader.bytes_per_line(), format.bytes_per_line());
+
+        for i in 0..5 {
+            reader
+                .next_frame(|view| {
+                    assert_eq!(&*view, &synthetic_frame(i)[..]);
+                    assert_eq!(view.index(), i % 2);
+                    assert_eq!(view.is_error(), i == 3);
+                    assert_eq!(view.sequence(), if i < 2 { i } else { i + 1 });
+                    assert_eq!(view.gap_since_previous(), u32::from(i == 2));
+                    assert_eq!(
+                        view.timestamp(),
+                        (i != 0).then(|| Duration::from_micros(u64::from(i) * 33_333))
+                    );
+                    Ok(())
+                })?
+                .unwrap();
+        }
+        assert!(reader.next_frame(|_| Ok(()))?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_frame() -> io::Result<()> {
+        let format = PixFormat::new(4, 4, PixelFormat::YUYV);
+        let mut writer = DumpWriter::new(Vec::new(), &format)?;
+        let data = synthetic_frame(1);
+        writer.write_frame(&ReadBufferView::from_parts(
+            0,
+            BufFlag::empty(),
+            &data,
+            0,
+            None,
+            0,
+        ))?;
+        let mut dump = writer.finish()?;
+        dump.pop();
+
+        let mut reader = DumpReader::new(&dump[..])?;
+        let err = reader.next_frame(|_| Ok(())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        Ok(())
+    }
+
+    #[test]
+    fn corrupted_length() -> io::Result<()> {
+        let format = PixFormat::new(4, 4, PixelFormat::YUYV);
+        let data = synthetic_frame(1);
+        let mut writer = DumpWriter::new(Vec::new(), &format)?;
+        writer.write_frame(&ReadBufferView::from_parts(
+            0,
+            BufFlag::empty(),
+            &data,
+            0,
+            None,
+            0,
+        ))?;
+        let mut dump = writer.finish()?;
+
+        // Overwrite the length of the frame with the largest possible one.
+        let len_offset = dump.len() - data.len() - 4;
+        dump[len_offset..][..4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let mut reader = DumpReader::new(&dump[..])?;
+        let err = reader.next_frame(|_| Ok(())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(reader.buf.capacity() < 1 << 20);
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_flags() -> io::Result<()> {
+        let format = PixFormat::new(4, 4, PixelFormat::YUYV);
+        let flags = unsafe { BufFlag::from_bits_unchecked(BufFlag::ERROR.bits() | 1 << 31) };
+        let mut writer = DumpWriter::new(Vec::new(), &format)?;
+        writer.write_frame(&ReadBufferView::from_parts(0, flags, &[], 0, None, 0))?;
+        let dump = writer.finish()?;
+
+        let mut reader = DumpReader::new(&dump[..])?;
+        let bits = reader.next_frame(|view| Ok(view.flags().bits()))?;
+        assert_eq!(bits, Some(flags.bits()));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_magic() {
+        let err = DumpReader::new(&b"NOTADUMP"[..]).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
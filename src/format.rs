@@ -1,17 +1,28 @@
 //! Image and pixel formats.
 
+use std::iter::FusedIterator;
+use std::os::unix::prelude::*;
 use std::{fmt, io, mem};
 
 use nix::errno::Errno;
 
-use crate::shared::{FrmIvalType, FrmSizeType};
+use crate::shared::{FrmIvalType, FrmSizeType, PixFmtFlag};
 use crate::{byte_array_to_str, raw, BufType, Device, Fract};
 
-pub use crate::pixel_format::PixelFormat;
-pub use crate::shared::FormatFlags;
+mod negotiate;
+mod planar;
+#[cfg(feature = "serde")]
+mod serde_impls;
+
+pub use negotiate::FormatPreferences;
+pub use planar::{PlanarError, PlanarView, Plane};
+
+pub use crate::pixel_format::{ParsePixelFormatError, PixelFormat};
+pub use crate::shared::{Colorspace, Field, FormatFlags, Quantization, XferFunc, YcbcrEnc};
 
 /// Formats of all possible buffer types.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum Format {
     VideoCapture(PixFormat),
@@ -26,16 +37,26 @@ pub enum Format {
 
 /// Pixel format of a [`VIDEO_OUTPUT`][BufType::VIDEO_OUTPUT] or
 /// [`VIDEO_CAPTURE`][BufType::VIDEO_CAPTURE] buffer.
+#[derive(Clone)]
 pub struct PixFormat(raw::PixFormat);
 
+#[derive(Clone)]
 pub struct PixFormatMplane(raw::PixFormatMplane);
 
+#[derive(Clone)]
 pub struct Window(raw::Window);
 
+// Safety: the clip list and bitmap pointers of a window are only passed to the driver, and never
+// dereferenced by this library.
+unsafe impl Send for Window {}
+unsafe impl Sync for Window {}
+
+#[derive(Clone)]
 pub struct PlanePixFormat(raw::PlanePixFormat);
 
 /// Metadata format of a [`META_CAPTURE`][BufType::META_CAPTURE] or
 /// [`META_OUTPUT`][BufType::META_OUTPUT] buffer.
+#[derive(Clone)]
 pub struct MetaFormat(raw::MetaFormat);
 
 impl Format {
@@ -51,9 +72,79 @@ impl Format {
             }
             BufType::VIDEO_OVERLAY => Self::VideoOverlay(Window(raw.fmt.win)),
             BufType::META_CAPTURE => Self::MetaCapture(MetaFormat(raw.fmt.meta)),
+            BufType::META_OUTPUT => Self::MetaOutput(MetaFormat(raw.fmt.meta)),
             _ => return None,
         })
     }
+
+    /// Returns the buffer type this format applies to.
+    pub fn buf_type(&self) -> BufType {
+        match self {
+            Format::VideoCapture(_) => BufType::VIDEO_CAPTURE,
+            Format::VideoOutput(_) => BufType::VIDEO_OUTPUT,
+            Format::VideoCaptureMplane(_) => BufType::VIDEO_CAPTURE_MPLANE,
+            Format::VideoOutputMplane(_) => BufType::VIDEO_OUTPUT_MPLANE,
+            Format::VideoOverlay(_) => BufType::VIDEO_OVERLAY,
+            Format::MetaCapture(_) => BufType::META_CAPTURE,
+            Format::MetaOutput(_) => BufType::META_OUTPUT,
+        }
+    }
+
+    pub(crate) fn to_raw(&self) -> raw::Format {
+        let mut raw: raw::Format = unsafe { mem::zeroed() };
+        raw.type_ = self.buf_type();
+        match self {
+            Format::VideoCapture(f) | Format::VideoOutput(f) => raw.fmt.pix = f.to_raw(),
+            Format::VideoCaptureMplane(f) | Format::VideoOutputMplane(f) => {
+                raw.fmt.pix_mp = f.to_raw()
+            }
+            Format::VideoOverlay(f) => raw.fmt.win = f.to_raw(),
+            Format::MetaCapture(f) | Format::MetaOutput(f) => raw.fmt.meta = f.to_raw(),
+        }
+        raw
+    }
+
+    /// Returns whether this format, as returned by the driver, differs from the `requested`
+    /// format it was negotiated from.
+    ///
+    /// Only the values that were set in `requested` are compared: the dimensions and pixel format,
+    /// and the stride, image size, field order and colorimetry if they were not left to the driver.
+    /// Values that are always chosen by the driver, like the buffer size of a [`MetaFormat`], are
+    /// ignored.
+    pub fn is_adjusted(&self, requested: &Format) -> bool {
+        let differs = |requested: u32, actual: u32| requested != 0 && requested != actual;
+        match (self, requested) {
+            (Format::VideoCapture(a), Format::VideoCapture(r))
+            | (Format::VideoOutput(a), Format::VideoOutput(r)) => {
+                a.width() != r.width()
+                    || a.height() != r.height()
+                    || a.pixel_format() != r.pixel_format()
+                    || differs(r.bytes_per_line(), a.bytes_per_line())
+                    || differs(r.size_image(), a.size_image())
+                    || differs(r.field().0, a.field().0)
+                    || differs(r.colorspace().0, a.colorspace().0)
+                    || differs(r.ycbcr_enc().0, a.ycbcr_enc().0)
+                    || differs(r.quantization().0, a.quantization().0)
+                    || differs(r.xfer_func().0, a.xfer_func().0)
+            }
+            (Format::VideoCaptureMplane(a), Format::VideoCaptureMplane(r))
+            | (Format::VideoOutputMplane(a), Format::VideoOutputMplane(r)) => {
+                a.width() != r.width()
+                    || a.height() != r.height()
+                    || a.pixel_format() != r.pixel_format()
+                    || differs(r.num_planes() as u32, a.num_planes() as u32)
+                    || differs(r.field().0, a.field().0)
+                    || differs(r.colorspace().0, a.colorspace().0)
+                    || differs(r.ycbcr_enc().0, a.ycbcr_enc().0)
+                    || differs(r.quantization().0, a.quantization().0)
+                    || differs(r.xfer_func().0, a.xfer_func().0)
+            }
+            (Format::VideoOverlay(_), Format::VideoOverlay(_)) => false,
+            (Format::MetaCapture(a), Format::MetaCapture(r))
+            | (Format::MetaOutput(a), Format::MetaOutput(r)) => a.data_format() != r.data_format(),
+            _ => true,
+        }
+    }
 }
 
 impl PixFormat {
@@ -66,7 +157,7 @@ impl PixFormat {
         })
     }
 
-    pub(crate) fn to_raw(self) -> raw::PixFormat {
+    pub(crate) fn to_raw(&self) -> raw::PixFormat {
         self.0
     }
 
@@ -82,20 +173,206 @@ impl PixFormat {
         self.0.pixel_format
     }
 
+    /// Returns the distance between the starts of two lines of the image, in bytes.
+    ///
+    /// This can be larger than a line of pixels, so frames should always be addressed with the
+    /// stride of the negotiated format. For compressed formats, this is 0.
     pub fn bytes_per_line(&self) -> u32 {
         self.0.bytesperline
     }
 
+    /// Requests a stride (the distance between the starts of two lines, in bytes).
+    ///
+    /// Defaults to 0, which lets the driver choose. Drivers may honor a stride that is larger than
+    /// a line of pixels (for example, to meet alignment requirements of other hardware), but they
+    /// may also round it, or ignore it altogether. The stride the driver chose is reported by the
+    /// negotiated format.
+    pub fn with_bytes_per_line(mut self, bytes_per_line: u32) -> Self {
+        self.0.bytesperline = bytes_per_line;
+        self
+    }
+
+    /// Returns the size of a frame in bytes.
     pub fn size_image(&self) -> u32 {
         self.0.sizeimage
     }
+
+    /// Returns the field order of the image.
+    pub fn field(&self) -> Field {
+        self.0.field
+    }
+
+    /// Requests a field order.
+    ///
+    /// Defaults to [`Field::ANY`], which lets the driver choose. The field order the driver chose
+    /// is reported by the negotiated format.
+    pub fn with_field(mut self, field: Field) -> Self {
+        self.0.field = field;
+        self
+    }
+
+    /// Returns the colorspace of the image.
+    ///
+    /// Some drivers report [`Colorspace::DEFAULT`], see [`PixFormat::resolved_colorspace`].
+    pub fn colorspace(&self) -> Colorspace {
+        self.0.colorspace
+    }
+
+    /// Returns the Y'CbCr (or HSV) encoding of the image.
+    pub fn ycbcr_enc(&self) -> YcbcrEnc {
+        if self.has_ext_fields() {
+            self.0.enc
+        } else {
+            YcbcrEnc::DEFAULT
+        }
+    }
+
+    /// Returns the quantization range of the image.
+    pub fn quantization(&self) -> Quantization {
+        if self.has_ext_fields() {
+            self.0.quantization
+        } else {
+            Quantization::DEFAULT
+        }
+    }
+
+    /// Returns the transfer function of the image.
+    pub fn xfer_func(&self) -> XferFunc {
+        if self.has_ext_fields() {
+            self.0.xfer_func
+        } else {
+            XferFunc::DEFAULT
+        }
+    }
+
+    /// Requests a colorspace.
+    ///
+    /// Output devices take this as a description of the data the application provides. Capture
+    /// devices only honor it if the [`FormatDesc`] of the pixel format has the
+    /// [`FormatFlags::CSC_COLORSPACE`] flag, and otherwise report the colorspace they produce.
+    pub fn with_colorspace(mut self, colorspace: Colorspace) -> Self {
+        self.request_csc();
+        self.0.colorspace = colorspace;
+        self
+    }
+
+    /// Requests a Y'CbCr (or HSV) encoding.
+    ///
+    /// Capture devices only honor this if the pixel format has the
+    /// [`FormatFlags::CSC_YCBCR_ENC`] (or [`FormatFlags::CSC_HSV_ENC`]) flag.
+    pub fn with_ycbcr_enc(mut self, enc: YcbcrEnc) -> Self {
+        self.request_csc();
+        self.0.enc = enc;
+        self
+    }
+
+    /// Requests a quantization range.
+    ///
+    /// Capture devices only honor this if the pixel format has the
+    /// [`FormatFlags::CSC_QUANTIZATION`] flag.
+    pub fn with_quantization(mut self, quantization: Quantization) -> Self {
+        self.request_csc();
+        self.0.quantization = quantization;
+        self
+    }
+
+    /// Requests a transfer function.
+    ///
+    /// Capture devices only honor this if the pixel format has the
+    /// [`FormatFlags::CSC_XFER_FUNC`] flag.
+    pub fn with_xfer_func(mut self, xfer_func: XferFunc) -> Self {
+        self.request_csc();
+        self.0.xfer_func = xfer_func;
+        self
+    }
+
+    /// Returns the colorspace of the image, replacing [`Colorspace::DEFAULT`] with the default for
+    /// the image size ([`Colorspace::default_for_size`]).
+    pub fn resolved_colorspace(&self) -> Colorspace {
+        match self.colorspace() {
+            Colorspace::DEFAULT => Colorspace::default_for_size(self.width(), self.height()),
+            colorspace => colorspace,
+        }
+    }
+
+    /// Returns the Y'CbCr encoding of the image, replacing [`YcbcrEnc::DEFAULT`] with the default
+    /// of the [resolved colorspace][PixFormat::resolved_colorspace].
+    pub fn resolved_ycbcr_enc(&self) -> YcbcrEnc {
+        match self.ycbcr_enc() {
+            YcbcrEnc::DEFAULT => YcbcrEnc::default_for(self.resolved_colorspace()),
+            enc => enc,
+        }
+    }
+
+    /// Returns the quantization range of the image, replacing [`Quantization::DEFAULT`] with the
+    /// default for the pixel format and the [resolved colorspace][PixFormat::resolved_colorspace].
+    pub fn resolved_quantization(&self) -> Quantization {
+        match self.quantization() {
+            Quantization::DEFAULT => {
+                Quantization::default_for(self.resolved_colorspace(), self.pixel_format().is_rgb())
+            }
+            quantization => quantization,
+        }
+    }
+
+    /// Returns the transfer function of the image, replacing [`XferFunc::DEFAULT`] with the
+    /// default of the [resolved colorspace][PixFormat::resolved_colorspace].
+    pub fn resolved_xfer_func(&self) -> XferFunc {
+        match self.xfer_func() {
+            XferFunc::DEFAULT => XferFunc::default_for(self.resolved_colorspace()),
+            xfer_func => xfer_func,
+        }
+    }
+
+    /// The flags, encoding, quantization and transfer function fields are only valid if the
+    /// private field is set to the magic value.
+    fn has_ext_fields(&self) -> bool {
+        self.0.priv_ == raw::PIX_FMT_PRIV_MAGIC
+    }
+
+    fn request_csc(&mut self) {
+        if !self.has_ext_fields() {
+            self.0.priv_ = raw::PIX_FMT_PRIV_MAGIC;
+            self.0.flags = PixFmtFlag::empty();
+            self.0.enc = YcbcrEnc::DEFAULT;
+            self.0.quantization = Quantization::DEFAULT;
+            self.0.xfer_func = XferFunc::DEFAULT;
+        }
+        self.0.flags |= PixFmtFlag::SET_CSC;
+    }
 }
 
 impl PixFormatMplane {
-    pub(crate) fn to_raw(self) -> raw::PixFormatMplane {
+    pub fn new(width: u32, height: u32, pixel_format: PixelFormat) -> Self {
+        Self(raw::PixFormatMplane {
+            width,
+            height,
+            pixel_format,
+            ..unsafe { mem::zeroed() }
+        })
+    }
+
+    pub(crate) fn to_raw(&self) -> raw::PixFormatMplane {
         self.0
     }
 
+    pub fn width(&self) -> u32 {
+        self.0.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.0.height
+    }
+
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.0.pixel_format
+    }
+
+    /// Returns the number of memory planes of this format.
+    ///
+    /// Formats that store all color planes in a single buffer (like `NV12`) have a single memory
+    /// plane, while their non-contiguous variants (like `NV12M`) have one memory plane per color
+    /// plane.
     pub fn num_planes(&self) -> usize {
         self.0.num_planes.into()
     }
@@ -104,6 +381,105 @@ impl PixFormatMplane {
         // NB: this cannot return `&[PlanePixFormat]` because the underlying data is unaligned
         (0..self.num_planes()).map(move |i| PlanePixFormat(self.0.plane_fmt[i]))
     }
+
+    /// Returns the field order of the image.
+    pub fn field(&self) -> Field {
+        Field(self.0.field)
+    }
+
+    /// Requests a field order. See [`PixFormat::with_field`].
+    pub fn with_field(mut self, field: Field) -> Self {
+        self.0.field = field.0;
+        self
+    }
+
+    /// Returns the colorspace of the image.
+    pub fn colorspace(&self) -> Colorspace {
+        Colorspace(self.0.colorspace)
+    }
+
+    /// Returns the Y'CbCr (or HSV) encoding of the image.
+    pub fn ycbcr_enc(&self) -> YcbcrEnc {
+        YcbcrEnc(self.0.enc.into())
+    }
+
+    /// Returns the quantization range of the image.
+    pub fn quantization(&self) -> Quantization {
+        Quantization(self.0.quantization.into())
+    }
+
+    /// Returns the transfer function of the image.
+    pub fn xfer_func(&self) -> XferFunc {
+        XferFunc(self.0.xfer_func.into())
+    }
+
+    /// Requests a colorspace. See [`PixFormat::with_colorspace`].
+    pub fn with_colorspace(mut self, colorspace: Colorspace) -> Self {
+        self.request_csc();
+        self.0.colorspace = colorspace.0;
+        self
+    }
+
+    /// Requests a Y'CbCr (or HSV) encoding. See [`PixFormat::with_ycbcr_enc`].
+    pub fn with_ycbcr_enc(mut self, enc: YcbcrEnc) -> Self {
+        self.request_csc();
+        self.0.enc = enc.0 as u8;
+        self
+    }
+
+    /// Requests a quantization range. See [`PixFormat::with_quantization`].
+    pub fn with_quantization(mut self, quantization: Quantization) -> Self {
+        self.request_csc();
+        self.0.quantization = quantization.0 as u8;
+        self
+    }
+
+    /// Requests a transfer function. See [`PixFormat::with_xfer_func`].
+    pub fn with_xfer_func(mut self, xfer_func: XferFunc) -> Self {
+        self.request_csc();
+        self.0.xfer_func = xfer_func.0 as u8;
+        self
+    }
+
+    /// Returns the colorspace of the image, resolving [`Colorspace::DEFAULT`].
+    ///
+    /// See [`PixFormat::resolved_colorspace`].
+    pub fn resolved_colorspace(&self) -> Colorspace {
+        match self.colorspace() {
+            Colorspace::DEFAULT => Colorspace::default_for_size(self.width(), self.height()),
+            colorspace => colorspace,
+        }
+    }
+
+    /// Returns the Y'CbCr encoding of the image, resolving [`YcbcrEnc::DEFAULT`].
+    pub fn resolved_ycbcr_enc(&self) -> YcbcrEnc {
+        match self.ycbcr_enc() {
+            YcbcrEnc::DEFAULT => YcbcrEnc::default_for(self.resolved_colorspace()),
+            enc => enc,
+        }
+    }
+
+    /// Returns the quantization range of the image, resolving [`Quantization::DEFAULT`].
+    pub fn resolved_quantization(&self) -> Quantization {
+        match self.quantization() {
+            Quantization::DEFAULT => {
+                Quantization::default_for(self.resolved_colorspace(), self.pixel_format().is_rgb())
+            }
+            quantization => quantization,
+        }
+    }
+
+    /// Returns the transfer function of the image, resolving [`XferFunc::DEFAULT`].
+    pub fn resolved_xfer_func(&self) -> XferFunc {
+        match self.xfer_func() {
+            XferFunc::DEFAULT => XferFunc::default_for(self.resolved_colorspace()),
+            xfer_func => xfer_func,
+        }
+    }
+
+    fn request_csc(&mut self) {
+        self.0.flags |= PixFmtFlag::SET_CSC.bits() as u8;
+    }
 }
 
 impl PlanePixFormat {
@@ -117,7 +493,7 @@ impl PlanePixFormat {
 }
 
 impl Window {
-    pub(crate) fn to_raw(self) -> raw::Window {
+    pub(crate) fn to_raw(&self) -> raw::Window {
         self.0
     }
 }
@@ -130,15 +506,109 @@ impl MetaFormat {
         })
     }
 
+    /// Returns the format of the metadata.
+    pub fn data_format(&self) -> PixelFormat {
+        self.0.dataformat
+    }
+
+    /// Returns the maximum number of bytes the driver writes into a metadata buffer.
+    ///
+    /// This is 0 until the format has been negotiated with the driver.
     pub fn buffer_size(&self) -> u32 {
         self.0.buffersize
     }
 
-    pub(crate) fn to_raw(self) -> raw::MetaFormat {
+    pub(crate) fn to_raw(&self) -> raw::MetaFormat {
         self.0
     }
 }
 
+/// Formats the buffer type and the format, eg. `VIDEO_CAPTURE YUYV 1280x720, stride 2560, ...`.
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (buf_type, format): (_, &dyn fmt::Display) = match self {
+            Format::VideoCapture(fmt) => (BufType::VIDEO_CAPTURE, fmt),
+            Format::VideoOutput(fmt) => (BufType::VIDEO_OUTPUT, fmt),
+            Format::VideoCaptureMplane(fmt) => (BufType::VIDEO_CAPTURE_MPLANE, fmt),
+            Format::VideoOutputMplane(fmt) => (BufType::VIDEO_OUTPUT_MPLANE, fmt),
+            Format::VideoOverlay(win) => (BufType::VIDEO_OVERLAY, win),
+            Format::MetaCapture(fmt) => (BufType::META_CAPTURE, fmt),
+            Format::MetaOutput(fmt) => (BufType::META_OUTPUT, fmt),
+        };
+        write!(f, "{:?} {}", buf_type, format)
+    }
+}
+
+/// Formats the pixel format, size, stride, image size and colorspace, eg.
+/// `YUYV 1280x720, stride 2560, 1843200 bytes, SRGB`.
+///
+/// The field order is included if the image is interlaced.
+impl fmt::Display for PixFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}x{}, stride {}, {} bytes, {:?}",
+            self.pixel_format(),
+            self.width(),
+            self.height(),
+            self.bytes_per_line(),
+            self.size_image(),
+            self.colorspace(),
+        )?;
+        match self.field() {
+            Field::ANY | Field::NONE => Ok(()),
+            field => write!(f, ", {:?}", field),
+        }
+    }
+}
+
+/// Like the [`Display`][fmt::Display] impl of [`PixFormat`], but with the stride and size of
+/// every plane, eg. `NM12 1280x720, planes [stride 1280, 921600 bytes; stride 1280, 460800
+/// bytes], REC709`.
+impl fmt::Display for PixFormatMplane {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}x{}, planes [",
+            self.pixel_format(),
+            self.width(),
+            self.height()
+        )?;
+        for (i, plane) in self.plane_formats().enumerate() {
+            if i != 0 {
+                f.write_str("; ")?;
+            }
+
+            write!(
+                f,
+                "stride {}, {} bytes",
+                plane.bytes_per_line(),
+                plane.size_image()
+            )?;
+        }
+        write!(f, "], {:?}", self.colorspace())?;
+        match self.field() {
+            Field::ANY | Field::NONE => Ok(()),
+            field => write!(f, ", {:?}", field),
+        }
+    }
+}
+
+/// Formats the window rectangle, eg. `640x480 at (16, 16)`.
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let w = self.0.w;
+        write!(f, "{}x{} at ({}, {})", w.width, w.height, w.left, w.top)
+    }
+}
+
+/// Formats the data format and buffer size, eg. `UVCH, 1024 bytes`.
+impl fmt::Display for MetaFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {} bytes", self.data_format(), self.buffer_size())
+    }
+}
+
 impl fmt::Debug for PixFormat {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("PixFormat")
@@ -163,10 +633,14 @@ impl fmt::Debug for PixFormatMplane {
             .field("width", &{ self.0.width })
             .field("height", &{ self.0.height })
             .field("pixel_format", &{ self.0.pixel_format })
-            .field("field", &{ self.0.field })
-            .field("colorspace", &{ self.0.colorspace })
+            .field("field", &self.field())
+            .field("colorspace", &self.colorspace())
             .field("plane_fmt", &self.plane_formats().collect::<Vec<_>>())
             .field("num_planes", &self.0.num_planes)
+            .field("flags", &self.0.flags)
+            .field("enc", &self.ycbcr_enc())
+            .field("quantization", &self.quantization())
+            .field("xfer_func", &self.xfer_func())
             .finish()
     }
 }
@@ -204,17 +678,19 @@ impl fmt::Debug for MetaFormat {
 }
 
 /// Iterator over a device's supported [`FormatDesc`]s.
+///
+/// Returned by [`Device::formats`]. Enumeration stops after the first error.
 pub struct FormatDescIter<'a> {
-    device: &'a Device,
+    fd: BorrowedFd<'a>,
     buf_type: BufType,
     next_index: u32,
     finished: bool,
 }
 
 impl<'a> FormatDescIter<'a> {
-    pub(crate) fn new(device: &'a Device, buf_type: BufType) -> Self {
+    pub(crate) fn new(fd: BorrowedFd<'a>, buf_type: BufType) -> Self {
         Self {
-            device,
+            fd,
             buf_type,
             next_index: 0,
             finished: false,
@@ -237,7 +713,7 @@ impl Iterator for FormatDescIter<'_> {
                 mbus_code: 0,
                 ..mem::zeroed()
             };
-            match raw::enum_fmt(self.device.fd(), &mut desc) {
+            match raw::enum_fmt(self.fd.as_raw_fd(), &mut desc) {
                 Ok(_) => {}
                 Err(e) => {
                     self.finished = true;
@@ -255,17 +731,27 @@ impl Iterator for FormatDescIter<'_> {
     }
 }
 
+impl FusedIterator for FormatDescIter<'_> {}
+
+/// Description of a pixel format supported by a device (`VIDIOC_ENUM_FMT`).
 pub struct FormatDesc(raw::FmtDesc);
 
 impl FormatDesc {
+    /// Returns the format flags.
+    ///
+    /// [`FormatFlags::COMPRESSED`] is set for compressed formats like MJPEG, and
+    /// [`FormatFlags::EMULATED`] is set for formats that are converted in software (for example, by
+    /// libv4l) rather than produced by the hardware.
     pub fn flags(&self) -> FormatFlags {
         self.0.flags
     }
 
+    /// Returns the human-readable description of the format provided by the driver.
     pub fn description(&self) -> &str {
         byte_array_to_str(&self.0.description)
     }
 
+    /// Returns the pixel format (fourcc) this description refers to.
     pub fn pixel_format(&self) -> PixelFormat {
         self.0.pixel_format
     }
@@ -283,6 +769,8 @@ impl fmt::Debug for FormatDesc {
     }
 }
 
+/// The frame sizes supported for a pixel format, returned by [`Device::frame_sizes`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FrameSizes {
     Discrete(Vec<DiscreteFrameSize>),
     Stepwise(StepwiseFrameSizes),
@@ -364,8 +852,148 @@ impl FrameSizes {
             FrameSizes::Stepwise(sizes) | FrameSizes::Continuous(sizes) => sizes.max_height(),
         }
     }
+
+    /// Returns an iterator over all supported `(width, height)` pairs.
+    ///
+    /// Stepwise and continuous ranges are expanded lazily, in order of increasing width, then
+    /// height. Note that a continuous range of a large sensor can contain millions of sizes.
+    pub fn iter(&self) -> FrameSizeIter<'_> {
+        FrameSizeIter(match self {
+            FrameSizes::Discrete(sizes) => FrameSizeIterInner::Discrete(sizes.iter()),
+            FrameSizes::Stepwise(sizes) | FrameSizes::Continuous(sizes) => {
+                FrameSizeIterInner::Stepwise {
+                    sizes,
+                    next: Some((sizes.min_width(), sizes.min_height())),
+                }
+            }
+        })
+    }
+
+    /// Returns the supported frame size that is closest to `width`x`height`.
+    ///
+    /// For discrete sizes, this picks the size that minimizes the sum of the differences in width
+    /// and height, and returns `None` if there are none. For stepwise and continuous ranges, this
+    /// is [`StepwiseFrameSizes::clamp`].
+    pub fn closest(&self, width: u32, height: u32) -> Option<(u32, u32)> {
+        match self {
+            FrameSizes::Discrete(sizes) => sizes
+                .iter()
+                .map(|size| (size.width(), size.height()))
+                .min_by_key(|&(w, h)| w.abs_diff(width) + h.abs_diff(height)),
+            FrameSizes::Stepwise(sizes) | FrameSizes::Continuous(sizes) => {
+                Some(sizes.clamp(width, height))
+            }
+        }
+    }
+
+    /// Returns whether `width`x`height` is one of the supported frame sizes.
+    ///
+    /// For stepwise and continuous ranges, see [`StepwiseFrameSizes::contains`].
+    pub fn contains(&self, width: u32, height: u32) -> bool {
+        match self {
+            FrameSizes::Discrete(sizes) => sizes
+                .iter()
+                .any(|size| size.width() == width && size.height() == height),
+            FrameSizes::Stepwise(sizes) | FrameSizes::Continuous(sizes) => {
+                sizes.contains(width, height)
+            }
+        }
+    }
 }
 
+impl fmt::Display for FrameSizes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameSizes::Discrete(sizes) => {
+                for (i, size) in sizes.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+
+                    write!(f, "{}x{}", size.width(), size.height())?;
+                }
+
+                Ok(())
+            }
+            FrameSizes::Stepwise(sizes) => write!(
+                f,
+                "{}x{}-{}x{} (step {}x{})",
+                sizes.min_width(),
+                sizes.min_height(),
+                sizes.max_width(),
+                sizes.max_height(),
+                sizes.step_width(),
+                sizes.step_height(),
+            ),
+            FrameSizes::Continuous(sizes) => write!(
+                f,
+                "{}x{}-{}x{}",
+                sizes.min_width(),
+                sizes.min_height(),
+                sizes.max_width(),
+                sizes.max_height(),
+            ),
+        }
+    }
+}
+
+/// Returns whether `value` is one of `min, min + step, ..., max`.
+fn on_step(value: u32, min: u32, max: u32, step: u32) -> bool {
+    (min..=max).contains(&value) && (value - min).is_multiple_of(step.max(1))
+}
+
+/// Returns the value closest to `value` in `min, min + step, ..., max`.
+///
+/// Returns `min` if `max` is smaller than it.
+fn closest_step(value: u32, min: u32, max: u32, step: u32) -> u32 {
+    let step = step.max(1);
+    let max = max.max(min);
+    let value = value.clamp(min, max);
+    let below = value - (value - min) % step;
+    match below.checked_add(step) {
+        Some(above) if above <= max && above - value < value - below => above,
+        _ => below,
+    }
+}
+
+/// Iterator over the `(width, height)` pairs of [`FrameSizes`], returned by [`FrameSizes::iter`].
+pub struct FrameSizeIter<'a>(FrameSizeIterInner<'a>);
+
+enum FrameSizeIterInner<'a> {
+    Discrete(std::slice::Iter<'a, DiscreteFrameSize>),
+    Stepwise {
+        sizes: &'a StepwiseFrameSizes,
+        next: Option<(u32, u32)>,
+    },
+}
+
+impl Iterator for FrameSizeIter<'_> {
+    type Item = (u32, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            FrameSizeIterInner::Discrete(iter) => {
+                iter.next().map(|size| (size.width(), size.height()))
+            }
+            FrameSizeIterInner::Stepwise { sizes, next } => {
+                let (width, height) = (*next)?;
+                let step_height = sizes.step_height().max(1);
+                let step_width = sizes.step_width().max(1);
+                *next = match height.checked_add(step_height) {
+                    Some(h) if h <= sizes.max_height() => Some((width, h)),
+                    _ => match width.checked_add(step_width) {
+                        Some(w) if w <= sizes.max_width() => Some((w, sizes.min_height())),
+                        _ => None,
+                    },
+                };
+                Some((width, height))
+            }
+        }
+    }
+}
+
+impl FusedIterator for FrameSizeIter<'_> {}
+
 pub struct StepwiseFrameSizes(raw::FrmSizeStepwise);
 
 pub struct DiscreteFrameSize {
@@ -397,6 +1025,37 @@ impl StepwiseFrameSizes {
     pub fn step_height(&self) -> u32 {
         self.0.step_height
     }
+
+    /// Returns whether `width`x`height` lies within the range and on its step grid.
+    ///
+    /// Both dimensions must be a whole number of steps away from the minimum. For continuous
+    /// ranges, the step is 1, so any size within the range is contained.
+    pub fn contains(&self, width: u32, height: u32) -> bool {
+        on_step(width, self.min_width(), self.max_width(), self.step_width())
+            && on_step(
+                height,
+                self.min_height(),
+                self.max_height(),
+                self.step_height(),
+            )
+    }
+
+    /// Returns the size in the range that is closest to `width`x`height`.
+    ///
+    /// Each dimension is clamped to the range independently and rounded to the nearest step, so
+    /// the result is always [contained][Self::contains] in the range. If the driver reports a
+    /// maximum below the minimum, the minimum is used.
+    pub fn clamp(&self, width: u32, height: u32) -> (u32, u32) {
+        (
+            closest_step(width, self.min_width(), self.max_width(), self.step_width()),
+            closest_step(
+                height,
+                self.min_height(),
+                self.max_height(),
+                self.step_height(),
+            ),
+        )
+    }
 }
 
 impl DiscreteFrameSize {
@@ -413,6 +1072,9 @@ impl DiscreteFrameSize {
     }
 }
 
+/// The frame intervals supported for a pixel format and frame size, returned by
+/// [`Device::frame_intervals`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FrameIntervals {
     Discrete(Vec<DiscreteFrameInterval>),
     Stepwise(StepwiseFrameIntervals),
@@ -434,7 +1096,20 @@ impl FrameIntervals {
                 height,
                 ..mem::zeroed()
             };
-            raw::enum_frameintervals(device.fd(), &mut desc)?;
+            raw::enum_frameintervals(device.fd(), &mut desc).map_err(|e| match e {
+                Errno::EINVAL => io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "pixel format {} at {}x{} is not supported by the device",
+                        pixel_format, width, height
+                    ),
+                ),
+                Errno::ENOTTY => io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "device does not support frame interval enumeration",
+                ),
+                e => e.into(),
+            })?;
 
             match desc.type_ {
                 FrmIvalType::DISCRETE => {
@@ -501,6 +1176,36 @@ impl FrameIntervals {
             FrameIntervals::Stepwise(ivals) | FrameIntervals::Continuous(ivals) => *ivals.max(),
         }
     }
+
+    /// Returns whether the device can capture at a frame rate of `fps`.
+    ///
+    /// Frame rates are compared with a relative tolerance of 0.01%, so `29.97` matches an
+    /// interval of `1001/30000`, but not `1/30`. For stepwise ranges, the interval must be a whole
+    /// number of steps away from the minimum.
+    pub fn supports_fps(&self, fps: f64) -> bool {
+        const TOLERANCE: f64 = 1e-4;
+
+        let interval = 1.0 / fps;
+        if !interval.is_finite() || interval <= 0.0 {
+            return false;
+        }
+        let epsilon = interval * TOLERANCE;
+        let in_range = |ivals: &StepwiseFrameIntervals| {
+            interval >= ivals.min().as_f64() - epsilon && interval <= ivals.max().as_f64() + epsilon
+        };
+
+        match self {
+            FrameIntervals::Discrete(list) => list
+                .iter()
+                .any(|ival| (ival.fract().as_f64() - interval).abs() <= epsilon),
+            FrameIntervals::Stepwise(ivals) => {
+                let step = ivals.step().as_f64();
+                let steps = (interval - ivals.min().as_f64()) / step;
+                in_range(ivals) && (steps - steps.round()).abs() * step <= epsilon
+            }
+            FrameIntervals::Continuous(ivals) => in_range(ivals),
+        }
+    }
 }
 
 impl fmt::Display for FrameIntervals {
@@ -557,3 +1262,265 @@ impl StepwiseFrameIntervals {
         &self.0.step
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn discrete(sizes: &[(u32, u32)]) -> FrameSizes {
+        FrameSizes::Discrete(
+            sizes
+                .iter()
+                .enumerate()
+                .map(|(index, &(width, height))| DiscreteFrameSize {
+                    raw: raw::FrmSizeDiscrete { width, height },
+                    index: index as u32,
+                })
+                .collect(),
+        )
+    }
+
+    fn stepwise(width: (u32, u32, u32), height: (u32, u32, u32)) -> FrameSizes {
+        FrameSizes::Stepwise(StepwiseFrameSizes(raw::FrmSizeStepwise {
+            min_width: width.0,
+            max_width: width.1,
+            step_width: width.2,
+            min_height: height.0,
+            max_height: height.1,
+            step_height: height.2,
+        }))
+    }
+
+    #[test]
+    fn frame_size_iter() {
+        let sizes = discrete(&[(640, 480), (1280, 720)]);
+        assert_eq!(sizes.iter().collect::<Vec<_>>(), [(640, 480), (1280, 720)]);
+
+        let sizes = stepwise((16, 48, 16), (8, 10, 2));
+        assert_eq!(
+            sizes.iter().collect::<Vec<_>>(),
+            [(16, 8), (16, 10), (32, 8), (32, 10), (48, 8), (48, 10)]
+        );
+
+        // The maximum is not necessarily reachable in whole steps.
+        let sizes = stepwise((1, 6, 4), (1, 1, 0));
+        assert_eq!(sizes.iter().collect::<Vec<_>>(), [(1, 1), (5, 1)]);
+    }
+
+    #[test]
+    fn adjusted_format() {
+        let requested = Format::VideoCapture(PixFormat::new(1920, 1080, PixelFormat::YUYV));
+        let mut pix = PixFormat::new(1920, 1080, PixelFormat::YUYV).to_raw();
+        pix.bytesperline = 1920 * 2;
+        pix.sizeimage = 1920 * 1080 * 2;
+        // Filling in the values left at 0 is not an adjustment.
+        assert!(!Format::VideoCapture(PixFormat(pix)).is_adjusted(&requested));
+
+        pix.height = 1088;
+        assert!(Format::VideoCapture(PixFormat(pix)).is_adjusted(&requested));
+        assert!(Format::VideoOutput(PixFormat(pix)).is_adjusted(&requested));
+
+        let meta = Format::MetaCapture(MetaFormat::new(PixelFormat::UVC));
+        let actual = Format::MetaCapture(MetaFormat(raw::MetaFormat {
+            dataformat: PixelFormat::UVC,
+            buffersize: 1024,
+        }));
+        assert!(!actual.is_adjusted(&meta));
+    }
+
+    #[test]
+    fn colorimetry() {
+        let format = PixFormat::new(640, 480, PixelFormat::YUYV);
+        assert_eq!(format.field(), Field::ANY);
+        assert_eq!(format.quantization(), Quantization::DEFAULT);
+        assert_eq!(format.resolved_colorspace(), Colorspace::SMPTE170M);
+        assert_eq!(format.resolved_ycbcr_enc(), YcbcrEnc::_601);
+        assert_eq!(format.resolved_quantization(), Quantization::LIM_RANGE);
+        assert_eq!(format.resolved_xfer_func(), XferFunc::_709);
+
+        let mut raw = PixFormat::new(1280, 720, PixelFormat::RGB24).to_raw();
+        assert_eq!(PixFormat(raw).resolved_colorspace(), Colorspace::REC709);
+        assert_eq!(PixFormat(raw).resolved_ycbcr_enc(), YcbcrEnc::_709);
+        assert_eq!(
+            PixFormat(raw).resolved_quantization(),
+            Quantization::FULL_RANGE
+        );
+        // The extended fields are ignored unless the magic value is set.
+        raw.quantization = Quantization::LIM_RANGE;
+        assert_eq!(PixFormat(raw).quantization(), Quantization::DEFAULT);
+        raw.priv_ = raw::PIX_FMT_PRIV_MAGIC;
+        assert_eq!(PixFormat(raw).quantization(), Quantization::LIM_RANGE);
+
+        let requested = PixFormat::new(640, 480, PixelFormat::YUYV)
+            .with_field(Field::NONE)
+            .with_quantization(Quantization::FULL_RANGE)
+            .with_colorspace(Colorspace::SRGB);
+        let raw = requested.to_raw();
+        assert_eq!(raw.priv_, raw::PIX_FMT_PRIV_MAGIC);
+        assert!(raw.flags.contains(PixFmtFlag::SET_CSC));
+        assert_eq!(requested.field(), Field::NONE);
+        assert_eq!(requested.quantization(), Quantization::FULL_RANGE);
+        assert_eq!(requested.colorspace(), Colorspace::SRGB);
+        assert_eq!(requested.resolved_xfer_func(), XferFunc::SRGB);
+
+        // A driver that does not support changing the quantization reports what it produces.
+        let mut actual = requested.to_raw();
+        actual.quantization = Quantization::LIM_RANGE;
+        assert!(Format::VideoCapture(PixFormat(actual))
+            .is_adjusted(&Format::VideoCapture(requested.clone())));
+        assert!(
+            !Format::VideoCapture(requested.clone()).is_adjusted(&Format::VideoCapture(requested))
+        );
+
+        let mplane = PixFormatMplane::new(1920, 1080, PixelFormat::NV12)
+            .with_ycbcr_enc(YcbcrEnc::BT2020)
+            .with_field(Field::INTERLACED_TB);
+        assert_eq!(mplane.ycbcr_enc(), YcbcrEnc::BT2020);
+        assert_eq!(mplane.field(), Field::INTERLACED_TB);
+        assert_eq!(mplane.resolved_colorspace(), Colorspace::REC709);
+        assert_eq!(mplane.resolved_quantization(), Quantization::LIM_RANGE);
+    }
+
+    #[test]
+    fn supports_fps() {
+        let discrete = FrameIntervals::Discrete(
+            [
+                Fract::new(1, 30),
+                Fract::new(1001, 30000),
+                Fract::new(1, 15),
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(index, raw)| DiscreteFrameInterval {
+                index: index as u32,
+                raw,
+            })
+            .collect(),
+        );
+        assert!(discrete.supports_fps(30.0));
+        assert!(discrete.supports_fps(29.97));
+        assert!(discrete.supports_fps(15.0));
+        assert!(!discrete.supports_fps(60.0));
+        assert!(!discrete.supports_fps(29.9));
+        assert!(!discrete.supports_fps(0.0));
+
+        let range = raw::FrmIvalStepwise {
+            min: Fract::new(1, 60),
+            max: Fract::new(1, 10),
+            step: Fract::new(1, 60),
+        };
+        let stepwise = FrameIntervals::Stepwise(StepwiseFrameIntervals(range));
+        assert!(stepwise.supports_fps(60.0));
+        assert!(stepwise.supports_fps(30.0));
+        assert!(stepwise.supports_fps(12.0));
+        assert!(stepwise.supports_fps(10.0));
+        assert!(!stepwise.supports_fps(25.0));
+        assert!(!stepwise.supports_fps(120.0));
+        assert!(!stepwise.supports_fps(5.0));
+
+        let continuous = FrameIntervals::Continuous(StepwiseFrameIntervals(range));
+        assert!(continuous.supports_fps(25.0));
+        assert!(!continuous.supports_fps(5.0));
+    }
+
+    #[test]
+    fn closest_frame_size() {
+        let sizes = discrete(&[(640, 480), (1280, 720), (1920, 1080)]);
+        assert_eq!(sizes.closest(1280, 720), Some((1280, 720)));
+        assert_eq!(sizes.closest(1200, 800), Some((1280, 720)));
+        assert_eq!(sizes.closest(4000, 3000), Some((1920, 1080)));
+        assert_eq!(sizes.closest(0, 0), Some((640, 480)));
+
+        let sizes = stepwise((16, 1920, 16), (16, 1080, 8));
+        assert_eq!(sizes.closest(1280, 720), Some((1280, 720)));
+        assert_eq!(sizes.closest(1001, 701), Some((1008, 704)));
+        assert_eq!(sizes.closest(1, 1), Some((16, 16)));
+        assert_eq!(sizes.closest(u32::MAX, u32::MAX), Some((1920, 1080)));
+
+        assert_eq!(discrete(&[]).closest(640, 480), None);
+
+        // Broken drivers may report a maximum below the minimum.
+        let sizes = stepwise((640, 320, 16), (480, 240, 8));
+        assert_eq!(sizes.closest(1280, 720), Some((640, 480)));
+        assert_eq!(sizes.closest(0, 0), Some((640, 480)));
+    }
+
+    #[test]
+    fn contains_frame_size() {
+        let sizes = discrete(&[(640, 480), (1280, 720)]);
+        assert!(sizes.contains(640, 480));
+        assert!(!sizes.contains(640, 720));
+
+        let sizes = stepwise((16, 1920, 16), (16, 1080, 8));
+        assert!(sizes.contains(16, 16));
+        assert!(sizes.contains(1920, 1080));
+        assert!(sizes.contains(1280, 24));
+        assert!(!sizes.contains(1288, 24));
+        assert!(!sizes.contains(1280, 28));
+        assert!(!sizes.contains(1936, 1080));
+        assert!(!sizes.contains(0, 0));
+
+        // Width and height have independent ranges and steps.
+        let FrameSizes::Stepwise(range) = stepwise((2, 10, 4), (1, 3, 1)) else {
+            unreachable!()
+        };
+        assert!(range.contains(10, 3));
+        assert!(!range.contains(3, 10));
+        assert_eq!(range.clamp(9, 0), (10, 1));
+        // Ties round towards the minimum.
+        assert_eq!(range.clamp(8, 8), (6, 3));
+        for (width, height) in [(0, 0), (5, 2), (7, 2), (u32::MAX, 1)] {
+            let (w, h) = range.clamp(width, height);
+            assert!(range.contains(w, h), "{}x{} -> {}x{}", width, height, w, h);
+        }
+
+        let sizes = FrameSizes::Continuous(StepwiseFrameSizes(raw::FrmSizeStepwise {
+            min_width: 16,
+            max_width: 4096,
+            step_width: 1,
+            min_height: 16,
+            max_height: 2160,
+            step_height: 1,
+        }));
+        assert!(sizes.contains(1001, 999));
+        assert!(!sizes.contains(8, 999));
+        assert_eq!(sizes.to_string(), "16x16-4096x2160");
+    }
+
+    #[test]
+    fn display() {
+        let mut raw = PixFormat::new(1280, 720, PixelFormat::YUYV)
+            .with_field(Field::NONE)
+            .with_colorspace(Colorspace::SRGB)
+            .to_raw();
+        raw.bytesperline = 2560;
+        raw.sizeimage = 1843200;
+        assert_eq!(
+            Format::VideoCapture(PixFormat(raw)).to_string(),
+            "VIDEO_CAPTURE YUYV 1280x720, stride 2560, 1843200 bytes, SRGB"
+        );
+        raw.field = Field::INTERLACED;
+        assert_eq!(
+            PixFormat(raw).to_string(),
+            "YUYV 1280x720, stride 2560, 1843200 bytes, SRGB, INTERLACED"
+        );
+
+        let mut mplane = PixFormatMplane::new(1280, 720, PixelFormat::NV12M)
+            .with_colorspace(Colorspace::REC709)
+            .to_raw();
+        mplane.num_planes = 2;
+        mplane.plane_fmt[0].bytesperline = 1280;
+        mplane.plane_fmt[0].sizeimage = 921600;
+        mplane.plane_fmt[1].bytesperline = 1280;
+        mplane.plane_fmt[1].sizeimage = 460800;
+        assert_eq!(
+            PixFormatMplane(mplane).to_string(),
+            "NM12 1280x720, planes [stride 1280, 921600 bytes; stride 1280, 460800 bytes], REC709"
+        );
+
+        assert_eq!(
+            Format::MetaCapture(MetaFormat::new(PixelFormat::UVC)).to_string(),
+            "META_CAPTURE UVCH, 0 bytes"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! Preferences for [`Device::negotiate_capture`][crate::Device::negotiate_capture].
+
+use std::cmp::Ordering;
+
+use super::{FrameIntervals, FrameSizes, PixelFormat};
+use crate::Fract;
+
+/// Relative tolerance used when comparing frame rates.
+const FPS_TOLERANCE: f64 = 1e-4;
+
+/// Describes the capture formats an application can work with, for
+/// [`Device::negotiate_capture`][crate::Device::negotiate_capture].
+///
+/// Pixel formats are listed in order of priority: a format is only chosen if none of the formats
+/// before it can be negotiated. Among the frame sizes and intervals of a pixel format, the size
+/// closest to the target resolution is chosen, and the frame rate closest to the target frame
+/// rate.
+#[derive(Debug, Clone)]
+pub struct FormatPreferences {
+    pixel_formats: Vec<PixelFormat>,
+    resolution: Option<(u32, u32)>,
+    min_fps: Option<f64>,
+    target_fps: Option<f64>,
+}
+
+/// The frame size and interval picked for a pixel format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Candidate {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// The frame interval to set, or `None` to keep the driver's default.
+    pub(crate) interval: Option<Fract>,
+}
+
+impl FormatPreferences {
+    /// Creates preferences accepting the given pixel formats, in order of priority.
+    ///
+    /// Without a target resolution, the largest frame size is preferred.
+    pub fn new(pixel_formats: impl IntoIterator<Item = PixelFormat>) -> Self {
+        Self {
+            pixel_formats: pixel_formats.into_iter().collect(),
+            resolution: None,
+            min_fps: None,
+            target_fps: None,
+        }
+    }
+
+    /// Sets the resolution to pick the closest supported frame size to.
+    pub fn with_resolution(mut self, width: u32, height: u32) -> Self {
+        self.resolution = Some((width, height));
+        self
+    }
+
+    /// Sets the lowest acceptable frame rate.
+    ///
+    /// Frame sizes that cannot be captured at this rate are skipped. If the device does not
+    /// support frame interval enumeration, this cannot be checked and is ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fps` is not a positive, finite number.
+    pub fn with_min_fps(mut self, fps: f64) -> Self {
+        assert_fps(fps);
+        self.min_fps = Some(fps);
+        self
+    }
+
+    /// Sets the frame rate to configure, if the device supports it.
+    ///
+    /// The supported frame rate closest to `fps` is used. If neither this nor a minimum frame rate
+    /// is set, the driver's default frame interval is kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fps` is not a positive, finite number.
+    pub fn with_target_fps(mut self, fps: f64) -> Self {
+        assert_fps(fps);
+        self.target_fps = Some(fps);
+        self
+    }
+
+    /// Returns the accepted pixel formats, in order of priority.
+    pub fn pixel_formats(&self) -> &[PixelFormat] {
+        &self.pixel_formats
+    }
+
+    /// Returns the target resolution, if any.
+    pub fn resolution(&self) -> Option<(u32, u32)> {
+        self.resolution
+    }
+
+    /// Returns the lowest acceptable frame rate, if any.
+    pub fn min_fps(&self) -> Option<f64> {
+        self.min_fps
+    }
+
+    /// Returns the target frame rate, if any.
+    pub fn target_fps(&self) -> Option<f64> {
+        self.target_fps
+    }
+
+    /// Returns the frame sizes worth considering out of `sizes`.
+    ///
+    /// For stepwise and continuous ranges, only the size closest to the target is returned, since
+    /// expanding the range is not feasible.
+    pub(crate) fn candidate_sizes(&self, sizes: &FrameSizes) -> Vec<(u32, u32)> {
+        match (sizes, self.resolution) {
+            (FrameSizes::Discrete(_), _) => sizes.iter().collect(),
+            (_, Some((width, height))) => sizes.closest(width, height).into_iter().collect(),
+            (_, None) => vec![(sizes.max_width(), sizes.max_height())],
+        }
+    }
+
+    /// Picks the best of `sizes`.
+    ///
+    /// `intervals` is called to enumerate the frame intervals of each size, and returns `None` if
+    /// they are unknown. Returns `None` if no size reaches the minimum frame rate.
+    pub(crate) fn pick(
+        &self,
+        sizes: impl IntoIterator<Item = (u32, u32)>,
+        mut intervals: impl FnMut(u32, u32) -> Option<FrameIntervals>,
+    ) -> Option<Candidate> {
+        sizes
+            .into_iter()
+            .filter_map(|(width, height)| {
+                let (interval, fps_distance) = match intervals(width, height) {
+                    Some(ivals) => self.pick_interval(&ivals)?,
+                    None => (None, 0.0),
+                };
+                let candidate = Candidate {
+                    width,
+                    height,
+                    interval,
+                };
+                Some((self.size_distance(width, height), fps_distance, candidate))
+            })
+            .min_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .map(|(_, _, candidate)| candidate)
+    }
+
+    /// Returns how far a frame size is from the preferred one (lower is better).
+    fn size_distance(&self, width: u32, height: u32) -> u64 {
+        match self.resolution {
+            Some((w, h)) => u64::from(width.abs_diff(w)) + u64::from(height.abs_diff(h)),
+            None => u64::MAX - u64::from(width) * u64::from(height),
+        }
+    }
+
+    /// Picks the frame interval to set out of `ivals`, along with how far its frame rate is from
+    /// the target.
+    ///
+    /// Returns `None` if the minimum frame rate is not reached.
+    fn pick_interval(&self, ivals: &FrameIntervals) -> Option<(Option<Fract>, f64)> {
+        let fast_enough = |fps: f64| {
+            self.min_fps
+                .is_none_or(|min| fps >= min * (1.0 - FPS_TOLERANCE))
+        };
+        if !fast_enough(ivals.min().to_fps()) {
+            return None;
+        }
+        let Some(target) = self.target_fps.or(self.min_fps) else {
+            return Some((None, 0.0));
+        };
+
+        let (interval, fps) = match ivals {
+            FrameIntervals::Discrete(list) => list
+                .iter()
+                .map(|ival| (*ival.fract(), ival.fract().to_fps()))
+                .filter(|&(_, fps)| fast_enough(fps))
+                .min_by(|a, b| (a.1 - target).abs().total_cmp(&(b.1 - target).abs()))?,
+            FrameIntervals::Stepwise(ivals) | FrameIntervals::Continuous(ivals) => {
+                let fps = target.clamp(ivals.max().to_fps(), ivals.min().to_fps());
+                match fps.partial_cmp(&target) {
+                    // Use the exact bound, not an approximation of it.
+                    Some(Ordering::Less) => (*ivals.min(), fps),
+                    Some(Ordering::Greater) => (*ivals.max(), fps),
+                    _ => (Fract::from_fps(fps), fps),
+                }
+            }
+        };
+        Some((Some(interval), (fps - target).abs()))
+    }
+}
+
+fn assert_fps(fps: f64) {
+    assert!(
+        fps.is_finite() && fps > 0.0,
+        "frame rate must be positive and finite"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{DiscreteFrameInterval, StepwiseFrameIntervals};
+    use crate::raw;
+
+    fn discrete(fps: &[u32]) -> FrameIntervals {
+        FrameIntervals::Discrete(
+            fps.iter()
+                .enumerate()
+                .map(|(index, &fps)| DiscreteFrameInterval {
+                    index: index as u32,
+                    raw: Fract::new(1, fps),
+                })
+                .collect(),
+        )
+    }
+
+    fn sizes() -> [(u32, u32); 4] {
+        [(320, 240), (640, 480), (1280, 720), (1920, 1080)]
+    }
+
+    /// Intervals of a typical webcam, which gets slower at higher resolutions.
+    fn webcam(width: u32, _height: u32) -> Option<FrameIntervals> {
+        Some(match width {
+            ..=640 => discrete(&[30, 15]),
+            641..=1280 => discrete(&[10, 5]),
+            _ => discrete(&[5]),
+        })
+    }
+
+    #[test]
+    fn sizes_and_rates() {
+        let prefs = FormatPreferences::new([PixelFormat::YUYV]);
+        let best = prefs.pick(sizes(), webcam).unwrap();
+        assert_eq!((best.width, best.height, best.interval), (1920, 1080, None));
+
+        let prefs = prefs.with_resolution(1000, 700);
+        let best = prefs.pick(sizes(), webcam).unwrap();
+        assert_eq!((best.width, best.height), (1280, 720));
+
+        // The minimum frame rate rules out larger sizes.
+        let prefs = prefs.with_min_fps(15.0);
+        let best = prefs.pick(sizes(), webcam).unwrap();
+        assert_eq!((best.width, best.height), (640, 480));
+        assert_eq!(best.interval, Some(Fract::new(1, 15)));
+
+        let best = prefs.clone().with_target_fps(60.0).pick(sizes(), webcam);
+        assert_eq!(best.unwrap().interval, Some(Fract::new(1, 30)));
+
+        assert_eq!(prefs.with_min_fps(60.0).pick(sizes(), webcam), None);
+
+        // Unknown intervals are accepted.
+        let prefs = FormatPreferences::new([]).with_min_fps(60.0);
+        let best = prefs.pick([(640, 480)], |_, _| None).unwrap();
+        assert_eq!(best.interval, None);
+    }
+
+    #[test]
+    fn stepwise() {
+        let ivals = FrameIntervals::Continuous(StepwiseFrameIntervals(raw::FrmIvalStepwise {
+            min: Fract::new(1, 60),
+            max: Fract::new(1, 1),
+            step: Fract::new(1, 1),
+        }));
+        let pick = |fps| {
+            FormatPreferences::new([])
+                .with_target_fps(fps)
+                .pick_interval(&ivals)
+                .unwrap()
+        };
+        assert_eq!(pick(25.0), (Some(Fract::new(1, 25)), 0.0));
+        assert_eq!(pick(90.0), (Some(Fract::new(1, 60)), 30.0));
+        assert_eq!(pick(0.5), (Some(Fract::new(1, 1)), 0.5));
+
+        let sizes = FrameSizes::Stepwise(super::super::StepwiseFrameSizes(raw::FrmSizeStepwise {
+            min_width: 16,
+            max_width: 1920,
+            step_width: 16,
+            min_height: 16,
+            max_height: 1080,
+            step_height: 8,
+        }));
+        let prefs = FormatPreferences::new([]);
+        assert_eq!(prefs.candidate_sizes(&sizes), [(1920, 1080)]);
+        let prefs = prefs.with_resolution(1000, 1001);
+        assert_eq!(prefs.candidate_sizes(&sizes), [(992, 1000)]);
+    }
+}
@@ -0,0 +1,306 @@
+//! Access to the planes of frames in planar YUV formats.
+
+use std::{fmt, io};
+
+use super::{PixFormat, PixelFormat};
+
+/// A view of the planes of a frame captured from a single-planar queue.
+///
+/// Formats like [`NV12`][PixelFormat::NV12] and [`YUV420`][PixelFormat::YUV420] store the luma
+/// plane and the chroma plane(s) one after the other in the same buffer. This computes where each
+/// plane starts from the negotiated [`PixFormat`], and checks that the buffer is large enough for
+/// all of them.
+///
+/// Supported formats are [`NV12`][PixelFormat::NV12], [`NV21`][PixelFormat::NV21],
+/// [`YUV420`][PixelFormat::YUV420], [`YVU420`][PixelFormat::YVU420] and
+/// [`GREY`][PixelFormat::GREY] (which only has a luma plane).
+#[derive(Debug, Clone, Copy)]
+pub struct PlanarView<'a> {
+    planes: [Plane<'a>; 3],
+    count: usize,
+    /// The chroma planes, including the padding between them.
+    chroma: &'a [u8],
+}
+
+/// A single plane of a [`PlanarView`].
+#[derive(Debug, Clone, Copy)]
+pub struct Plane<'a> {
+    data: &'a [u8],
+    stride: usize,
+    row_len: usize,
+    rows: usize,
+}
+
+impl<'a> PlanarView<'a> {
+    /// Splits `data`, a frame in the given format, into its planes.
+    ///
+    /// `data` is usually a dequeued frame view. If `format` does not specify the stride, tightly
+    /// packed lines are assumed.
+    pub fn new(data: &'a [u8], format: &PixFormat) -> Result<Self, PlanarError> {
+        let pixel_format = format.pixel_format();
+        let width = format.width() as usize;
+        let height = format.height() as usize;
+        let (chroma_planes, chroma_row_len, chroma_stride_div) = match pixel_format {
+            PixelFormat::GREY => (0, 0, 1),
+            // One plane of interleaved Cb and Cr samples, with the same stride as the luma plane.
+            PixelFormat::NV12 | PixelFormat::NV21 => (1, width.div_ceil(2) * 2, 1),
+            // Separate Cb and Cr planes, with half the stride of the luma plane.
+            PixelFormat::YUV420 | PixelFormat::YVU420 => (2, width.div_ceil(2), 2),
+            _ => return Err(PlanarError::UnsupportedFormat(pixel_format)),
+        };
+
+        let stride = match format.bytes_per_line() as usize {
+            0 => width,
+            stride if stride < width => {
+                return Err(PlanarError::StrideTooSmall {
+                    stride,
+                    required: width,
+                })
+            }
+            stride => stride,
+        };
+        let chroma_stride = (stride / chroma_stride_div).max(chroma_row_len);
+        let chroma_rows = height.div_ceil(2);
+
+        let mut planes = [Plane {
+            data: &[],
+            stride: 0,
+            row_len: 0,
+            rows: 0,
+        }; 3];
+        let mut layout = [(stride, width, height); 3];
+        layout[1] = (chroma_stride, chroma_row_len, chroma_rows);
+        layout[2] = layout[1];
+
+        let required = required_size(stride, height, chroma_stride, chroma_planes);
+        let mut offset = 0;
+        for (plane, (stride, row_len, rows)) in
+            planes.iter_mut().zip(layout).take(1 + chroma_planes)
+        {
+            let end = stride
+                .checked_mul(rows)
+                .and_then(|size| size.checked_add(offset))
+                .filter(|&end| end <= data.len())
+                .ok_or(PlanarError::BufferTooSmall {
+                    len: data.len(),
+                    required,
+                })?;
+            *plane = Plane {
+                data: &data[offset..end],
+                stride,
+                row_len,
+                rows,
+            };
+            offset = end;
+        }
+
+        Ok(Self {
+            planes,
+            count: 1 + chroma_planes,
+            chroma: &data[planes[0].data.len()..offset],
+        })
+    }
+
+    /// Returns the number of planes of the frame.
+    pub fn plane_count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns plane `index` (0 being the luma plane), or `None` if the format has fewer planes.
+    pub fn plane(&self, index: usize) -> Option<Plane<'a>> {
+        self.planes[..self.count].get(index).copied()
+    }
+
+    /// Returns the luma (Y) plane, including any padding at the end of each line.
+    pub fn luma(&self) -> &'a [u8] {
+        self.planes[0].data
+    }
+
+    /// Returns the chroma data, including any padding at the end of each line.
+    ///
+    /// For [`NV12`][PixelFormat::NV12] and [`NV21`][PixelFormat::NV21], this is the plane of
+    /// interleaved chroma samples. For [`YUV420`][PixelFormat::YUV420] and
+    /// [`YVU420`][PixelFormat::YVU420], this contains both chroma planes, which can be accessed
+    /// individually with [`PlanarView::plane`]. For [`GREY`][PixelFormat::GREY], this is empty.
+    pub fn chroma(&self) -> &'a [u8] {
+        self.chroma
+    }
+
+    /// Returns the stride of the luma plane in bytes.
+    pub fn luma_stride(&self) -> usize {
+        self.planes[0].stride
+    }
+
+    /// Returns the stride of the chroma plane(s) in bytes, or `None` for formats without chroma.
+    pub fn chroma_stride(&self) -> Option<usize> {
+        self.plane(1).map(|plane| plane.stride)
+    }
+}
+
+impl<'a> Plane<'a> {
+    /// Returns the data of the plane, including any padding at the end of each line.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns the distance between the starts of two lines of the plane in bytes.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns the number of lines of the plane.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns line `y` of the plane, without padding, or `None` if `y` is out of bounds.
+    pub fn row(&self, y: usize) -> Option<&'a [u8]> {
+        if y >= self.rows {
+            return None;
+        }
+        let start = y * self.stride;
+        Some(&self.data[start..start + self.row_len])
+    }
+
+    /// Returns an iterator over the lines of the plane, without padding.
+    pub fn rows_iter(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let row_len = self.row_len;
+        self.data
+            .chunks(self.stride.max(1))
+            .take(self.rows)
+            .map(move |row| &row[..row_len])
+    }
+}
+
+fn required_size(
+    stride: usize,
+    height: usize,
+    chroma_stride: usize,
+    chroma_planes: usize,
+) -> usize {
+    let chroma = chroma_stride.saturating_mul(height.div_ceil(2));
+    stride
+        .saturating_mul(height)
+        .saturating_add(chroma.saturating_mul(chroma_planes))
+}
+
+/// An error returned by [`PlanarView::new`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PlanarError {
+    /// The pixel format is not a supported planar format.
+    UnsupportedFormat(PixelFormat),
+    /// The stride of the format is smaller than a line of the image.
+    StrideTooSmall { stride: usize, required: usize },
+    /// The buffer is too small for the planes of the format.
+    BufferTooSmall { len: usize, required: usize },
+}
+
+impl fmt::Display for PlanarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanarError::UnsupportedFormat(format) => {
+                write!(f, "{} is not a supported planar format", format)
+            }
+            PlanarError::StrideTooSmall { stride, required } => write!(
+                f,
+                "stride of {} bytes is too small for a line of {} bytes",
+                stride, required
+            ),
+            PlanarError::BufferTooSmall { len, required } => write!(
+                f,
+                "buffer of {} bytes is too small for the planes of the format ({} bytes)",
+                len, required
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PlanarError {}
+
+impl From<PlanarError> for io::Error {
+    fn from(e: PlanarError) -> Self {
+        let kind = match e {
+            PlanarError::UnsupportedFormat(_) => io::ErrorKind::Unsupported,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(width: u32, height: u32, pixel_format: PixelFormat, stride: u32) -> PixFormat {
+        let mut raw = PixFormat::new(width, height, pixel_format).to_raw();
+        raw.bytesperline = stride;
+        PixFormat(raw)
+    }
+
+    #[test]
+    fn nv12() {
+        // 4x3 image with 2 bytes of padding per line: 3 luma lines, 2 chroma lines.
+        let data: Vec<u8> = (0..6 * 5).collect();
+        let view = PlanarView::new(&data, &format(4, 3, PixelFormat::NV12, 6)).unwrap();
+        assert_eq!(view.plane_count(), 2);
+        assert_eq!(view.luma(), &data[..18]);
+        assert_eq!(view.chroma(), &data[18..]);
+        assert_eq!(view.luma_stride(), 6);
+        assert_eq!(view.chroma_stride(), Some(6));
+
+        let chroma = view.plane(1).unwrap();
+        assert_eq!(chroma.rows(), 2);
+        assert_eq!(chroma.row(1), Some(&[24, 25, 26, 27][..]));
+        assert_eq!(chroma.row(2), None);
+        assert_eq!(view.plane(0).unwrap().rows_iter().count(), 3);
+        assert!(view.plane(2).is_none());
+    }
+
+    #[test]
+    fn yuv420() {
+        // Odd dimensions round the chroma planes up.
+        let data = [0; 5 * 3 + 2 * 3 * 2 + 1];
+        let view = PlanarView::new(&data, &format(5, 3, PixelFormat::YUV420, 0)).unwrap();
+        assert_eq!(view.plane_count(), 3);
+        assert_eq!(view.luma().len(), 15);
+        assert_eq!(view.chroma().len(), 12);
+        assert_eq!(view.chroma_stride(), Some(3));
+        let cr = view.plane(2).unwrap();
+        assert_eq!(cr.row(1).unwrap().len(), 3);
+
+        let view = PlanarView::new(&[0; 8 * 2 + 4 * 2], &format(8, 2, PixelFormat::YVU420, 8));
+        assert_eq!(view.unwrap().chroma_stride(), Some(4));
+    }
+
+    #[test]
+    fn grey() {
+        let data = [1; 12];
+        let view = PlanarView::new(&data, &format(4, 3, PixelFormat::GREY, 0)).unwrap();
+        assert_eq!(view.plane_count(), 1);
+        assert_eq!(view.luma(), &data);
+        assert!(view.chroma().is_empty());
+        assert_eq!(view.chroma_stride(), None);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            PlanarView::new(&[0; 17], &format(4, 3, PixelFormat::NV21, 4)).unwrap_err(),
+            PlanarError::BufferTooSmall {
+                len: 17,
+                required: 20
+            }
+        );
+        assert_eq!(
+            PlanarView::new(&[0; 64], &format(4, 3, PixelFormat::NV12, 2)).unwrap_err(),
+            PlanarError::StrideTooSmall {
+                stride: 2,
+                required: 4
+            }
+        );
+        let err = PlanarView::new(&[0; 64], &format(4, 3, PixelFormat::YUYV, 0)).unwrap_err();
+        assert_eq!(err, PlanarError::UnsupportedFormat(PixelFormat::YUYV));
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::Unsupported);
+    }
+}
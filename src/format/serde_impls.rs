@@ -0,0 +1,521 @@
+//! `serde` support for the format types.
+
+use std::borrow::Cow;
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Colorspace, DiscreteFrameInterval, DiscreteFrameSize, Field, FormatDesc, FormatFlags,
+    MetaFormat, PixFormat, PixFormatMplane, PixelFormat, Quantization, StepwiseFrameIntervals,
+    StepwiseFrameSizes, Window, XferFunc, YcbcrEnc,
+};
+use crate::serde_impls::{serde_via, str_to_array};
+use crate::shared::PixFmtFlag;
+use crate::{raw, BufType, Fract};
+
+#[derive(Serialize, Deserialize)]
+struct PixFormatRepr {
+    width: u32,
+    height: u32,
+    pixel_format: PixelFormat,
+    field: Field,
+    bytes_per_line: u32,
+    size_image: u32,
+    colorspace: Colorspace,
+    flags: PixFmtFlag,
+    ycbcr_enc: YcbcrEnc,
+    quantization: Quantization,
+    xfer_func: XferFunc,
+}
+
+impl From<&PixFormat> for PixFormatRepr {
+    fn from(format: &PixFormat) -> Self {
+        Self {
+            width: format.width(),
+            height: format.height(),
+            pixel_format: format.pixel_format(),
+            field: format.field(),
+            bytes_per_line: format.bytes_per_line(),
+            size_image: format.size_image(),
+            colorspace: format.colorspace(),
+            flags: if format.has_ext_fields() {
+                format.0.flags
+            } else {
+                PixFmtFlag::empty()
+            },
+            ycbcr_enc: format.ycbcr_enc(),
+            quantization: format.quantization(),
+            xfer_func: format.xfer_func(),
+        }
+    }
+}
+
+impl TryFrom<PixFormatRepr> for PixFormat {
+    type Error = String;
+
+    fn try_from(repr: PixFormatRepr) -> Result<Self, String> {
+        Ok(PixFormat(raw::PixFormat {
+            width: repr.width,
+            height: repr.height,
+            pixel_format: repr.pixel_format,
+            field: repr.field,
+            bytesperline: repr.bytes_per_line,
+            sizeimage: repr.size_image,
+            colorspace: repr.colorspace,
+            priv_: raw::PIX_FMT_PRIV_MAGIC,
+            flags: repr.flags,
+            enc: repr.ycbcr_enc,
+            quantization: repr.quantization,
+            xfer_func: repr.xfer_func,
+        }))
+    }
+}
+
+serde_via!(PixFormat => PixFormatRepr);
+
+#[derive(Serialize, Deserialize)]
+struct PlaneRepr {
+    bytes_per_line: u32,
+    size_image: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PixFormatMplaneRepr {
+    width: u32,
+    height: u32,
+    pixel_format: PixelFormat,
+    field: Field,
+    colorspace: Colorspace,
+    planes: Vec<PlaneRepr>,
+    flags: u8,
+    ycbcr_enc: YcbcrEnc,
+    quantization: Quantization,
+    xfer_func: XferFunc,
+}
+
+impl From<&PixFormatMplane> for PixFormatMplaneRepr {
+    fn from(format: &PixFormatMplane) -> Self {
+        Self {
+            width: format.width(),
+            height: format.height(),
+            pixel_format: format.pixel_format(),
+            field: format.field(),
+            colorspace: format.colorspace(),
+            planes: format
+                .plane_formats()
+                .map(|plane| PlaneRepr {
+                    bytes_per_line: plane.bytes_per_line(),
+                    size_image: plane.size_image(),
+                })
+                .collect(),
+            flags: format.0.flags,
+            ycbcr_enc: format.ycbcr_enc(),
+            quantization: format.quantization(),
+            xfer_func: format.xfer_func(),
+        }
+    }
+}
+
+impl TryFrom<PixFormatMplaneRepr> for PixFormatMplane {
+    type Error = String;
+
+    fn try_from(repr: PixFormatMplaneRepr) -> Result<Self, String> {
+        if repr.planes.len() > raw::VIDEO_MAX_PLANES {
+            return Err(format!(
+                "{} planes exceed the maximum of {}",
+                repr.planes.len(),
+                raw::VIDEO_MAX_PLANES
+            ));
+        }
+        let narrow = |value: u32, name: &str| {
+            u8::try_from(value).map_err(|_| format!("{} {:#x} is out of range", name, value))
+        };
+
+        let mut raw: raw::PixFormatMplane = unsafe { mem::zeroed() };
+        raw.width = repr.width;
+        raw.height = repr.height;
+        raw.pixel_format = repr.pixel_format;
+        raw.field = repr.field.0;
+        raw.colorspace = repr.colorspace.0;
+        for (i, plane) in repr.planes.iter().enumerate() {
+            raw.plane_fmt[i].bytesperline = plane.bytes_per_line;
+            raw.plane_fmt[i].sizeimage = plane.size_image;
+        }
+        raw.num_planes = repr.planes.len() as u8;
+        raw.flags = repr.flags;
+        raw.enc = narrow(repr.ycbcr_enc.0, "Y'CbCr encoding")?;
+        raw.quantization = narrow(repr.quantization.0, "quantization")?;
+        raw.xfer_func = narrow(repr.xfer_func.0, "transfer function")?;
+        Ok(PixFormatMplane(raw))
+    }
+}
+
+serde_via!(PixFormatMplane => PixFormatMplaneRepr);
+
+#[derive(Serialize, Deserialize)]
+struct RectRepr {
+    left: i32,
+    top: i32,
+    width: u32,
+    height: u32,
+}
+
+/// The clip list and bitmap of a window are pointers, so they are not serialized.
+#[derive(Serialize, Deserialize)]
+struct WindowRepr {
+    rect: RectRepr,
+    field: Field,
+    chromakey: u32,
+    global_alpha: u8,
+}
+
+impl From<&Window> for WindowRepr {
+    fn from(window: &Window) -> Self {
+        let w = window.0.w;
+        Self {
+            rect: RectRepr {
+                left: w.left,
+                top: w.top,
+                width: w.width,
+                height: w.height,
+            },
+            field: window.0.field,
+            chromakey: window.0.chromakey,
+            global_alpha: window.0.global_alpha,
+        }
+    }
+}
+
+impl TryFrom<WindowRepr> for Window {
+    type Error = String;
+
+    fn try_from(repr: WindowRepr) -> Result<Self, String> {
+        let mut raw: raw::Window = unsafe { mem::zeroed() };
+        raw.w = raw::Rect {
+            left: repr.rect.left,
+            top: repr.rect.top,
+            width: repr.rect.width,
+            height: repr.rect.height,
+        };
+        raw.field = repr.field;
+        raw.chromakey = repr.chromakey;
+        raw.global_alpha = repr.global_alpha;
+        Ok(Window(raw))
+    }
+}
+
+serde_via!(Window => WindowRepr);
+
+#[derive(Serialize, Deserialize)]
+struct MetaFormatRepr {
+    data_format: PixelFormat,
+    buffer_size: u32,
+}
+
+impl From<&MetaFormat> for MetaFormatRepr {
+    fn from(format: &MetaFormat) -> Self {
+        Self {
+            data_format: format.data_format(),
+            buffer_size: format.buffer_size(),
+        }
+    }
+}
+
+impl TryFrom<MetaFormatRepr> for MetaFormat {
+    type Error = String;
+
+    fn try_from(repr: MetaFormatRepr) -> Result<Self, String> {
+        Ok(MetaFormat(raw::MetaFormat {
+            dataformat: repr.data_format,
+            buffersize: repr.buffer_size,
+        }))
+    }
+}
+
+serde_via!(MetaFormat => MetaFormatRepr);
+
+#[derive(Serialize, Deserialize)]
+struct FormatDescRepr<'a> {
+    index: u32,
+    buf_type: BufType,
+    flags: FormatFlags,
+    description: Cow<'a, str>,
+    pixel_format: PixelFormat,
+}
+
+impl<'a> From<&'a FormatDesc> for FormatDescRepr<'a> {
+    fn from(desc: &'a FormatDesc) -> Self {
+        Self {
+            index: desc.0.index,
+            buf_type: desc.0.type_,
+            flags: desc.flags(),
+            description: desc.description().into(),
+            pixel_format: desc.pixel_format(),
+        }
+    }
+}
+
+impl TryFrom<FormatDescRepr<'_>> for FormatDesc {
+    type Error = String;
+
+    fn try_from(repr: FormatDescRepr<'_>) -> Result<Self, String> {
+        let mut raw: raw::FmtDesc = unsafe { mem::zeroed() };
+        raw.index = repr.index;
+        raw.type_ = repr.buf_type;
+        raw.flags = repr.flags;
+        raw.description = str_to_array(&repr.description)?;
+        raw.pixel_format = repr.pixel_format;
+        Ok(FormatDesc(raw))
+    }
+}
+
+serde_via!(FormatDesc => FormatDescRepr);
+
+#[derive(Serialize, Deserialize)]
+struct DiscreteFrameSizeRepr {
+    index: u32,
+    width: u32,
+    height: u32,
+}
+
+impl From<&DiscreteFrameSize> for DiscreteFrameSizeRepr {
+    fn from(size: &DiscreteFrameSize) -> Self {
+        Self {
+            index: size.index(),
+            width: size.width(),
+            height: size.height(),
+        }
+    }
+}
+
+impl TryFrom<DiscreteFrameSizeRepr> for DiscreteFrameSize {
+    type Error = String;
+
+    fn try_from(repr: DiscreteFrameSizeRepr) -> Result<Self, String> {
+        Ok(DiscreteFrameSize {
+            raw: raw::FrmSizeDiscrete {
+                width: repr.width,
+                height: repr.height,
+            },
+            index: repr.index,
+        })
+    }
+}
+
+serde_via!(DiscreteFrameSize => DiscreteFrameSizeRepr);
+
+#[derive(Serialize, Deserialize)]
+struct StepwiseFrameSizesRepr {
+    min_width: u32,
+    max_width: u32,
+    step_width: u32,
+    min_height: u32,
+    max_height: u32,
+    step_height: u32,
+}
+
+impl From<&StepwiseFrameSizes> for StepwiseFrameSizesRepr {
+    fn from(sizes: &StepwiseFrameSizes) -> Self {
+        Self {
+            min_width: sizes.min_width(),
+            max_width: sizes.max_width(),
+            step_width: sizes.step_width(),
+            min_height: sizes.min_height(),
+            max_height: sizes.max_height(),
+            step_height: sizes.step_height(),
+        }
+    }
+}
+
+impl TryFrom<StepwiseFrameSizesRepr> for StepwiseFrameSizes {
+    type Error = String;
+
+    fn try_from(repr: StepwiseFrameSizesRepr) -> Result<Self, String> {
+        Ok(StepwiseFrameSizes(raw::FrmSizeStepwise {
+            min_width: repr.min_width,
+            max_width: repr.max_width,
+            step_width: repr.step_width,
+            min_height: repr.min_height,
+            max_height: repr.max_height,
+            step_height: repr.step_height,
+        }))
+    }
+}
+
+serde_via!(StepwiseFrameSizes => StepwiseFrameSizesRepr);
+
+#[derive(Serialize, Deserialize)]
+struct DiscreteFrameIntervalRepr {
+    index: u32,
+    interval: Fract,
+}
+
+impl From<&DiscreteFrameInterval> for DiscreteFrameIntervalRepr {
+    fn from(ival: &DiscreteFrameInterval) -> Self {
+        Self {
+            index: ival.index(),
+            interval: *ival.fract(),
+        }
+    }
+}
+
+impl TryFrom<DiscreteFrameIntervalRepr> for DiscreteFrameInterval {
+    type Error = String;
+
+    fn try_from(repr: DiscreteFrameIntervalRepr) -> Result<Self, String> {
+        Ok(DiscreteFrameInterval {
+            index: repr.index,
+            raw: repr.interval,
+        })
+    }
+}
+
+serde_via!(DiscreteFrameInterval => DiscreteFrameIntervalRepr);
+
+#[derive(Serialize, Deserialize)]
+struct StepwiseFrameIntervalsRepr {
+    min: Fract,
+    max: Fract,
+    step: Fract,
+}
+
+impl From<&StepwiseFrameIntervals> for StepwiseFrameIntervalsRepr {
+    fn from(ivals: &StepwiseFrameIntervals) -> Self {
+        Self {
+            min: *ivals.min(),
+            max: *ivals.max(),
+            step: *ivals.step(),
+        }
+    }
+}
+
+impl TryFrom<StepwiseFrameIntervalsRepr> for StepwiseFrameIntervals {
+    type Error = String;
+
+    fn try_from(repr: StepwiseFrameIntervalsRepr) -> Result<Self, String> {
+        Ok(StepwiseFrameIntervals(raw::FrmIvalStepwise {
+            min: repr.min,
+            max: repr.max,
+            step: repr.step,
+        }))
+    }
+}
+
+serde_via!(StepwiseFrameIntervals => StepwiseFrameIntervalsRepr);
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+
+    use super::super::{Format, FrameIntervals, FrameSizes};
+    use super::*;
+
+    /// Checks that `value` survives a round trip through JSON, and returns the JSON value.
+    fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> serde_json::Value {
+        let json = serde_json::to_string(value).unwrap();
+        let back: T = serde_json::from_str(&json).unwrap();
+        let value = serde_json::to_value(value).unwrap();
+        assert_eq!(serde_json::to_value(&back).unwrap(), value, "{}", json);
+        value
+    }
+
+    #[test]
+    fn formats() {
+        let format = PixFormat::new(1280, 720, PixelFormat::YUYV)
+            .with_field(Field::NONE)
+            .with_quantization(Quantization::FULL_RANGE);
+        let value = round_trip(&Format::VideoCapture(format));
+        assert_eq!(value["VideoCapture"]["pixel_format"], "YUYV");
+        assert_eq!(value["VideoCapture"]["width"], 1280);
+
+        let mut mplane = PixFormatMplane::new(640, 480, PixelFormat::NV12M);
+        mplane.0.num_planes = 2;
+        mplane.0.plane_fmt[1].bytesperline = 640;
+        let value = round_trip(&Format::VideoCaptureMplane(mplane));
+        assert_eq!(
+            value["VideoCaptureMplane"]["planes"][1]["bytes_per_line"],
+            640
+        );
+
+        round_trip(&Format::MetaCapture(MetaFormat::new(PixelFormat::UVC)));
+        let mut window: raw::Window = unsafe { mem::zeroed() };
+        window.w.width = 100;
+        window.global_alpha = 0x80;
+        round_trip(&Format::VideoOverlay(Window(window)));
+
+        let mut desc: raw::FmtDesc = unsafe { mem::zeroed() };
+        desc.type_ = BufType::VIDEO_CAPTURE;
+        desc.flags = FormatFlags::COMPRESSED;
+        desc.description = str_to_array("Motion-JPEG").unwrap();
+        desc.pixel_format = PixelFormat::MJPG;
+        let value = round_trip(&FormatDesc(desc));
+        assert_eq!(value["description"], "Motion-JPEG");
+
+        // Unknown fourccs deserialize as raw codes.
+        let json = r#"{"MetaCapture": {"data_format": "XYZW", "buffer_size": 1024}}"#;
+        let Format::MetaCapture(meta) = serde_json::from_str(json).unwrap() else {
+            panic!("wrong format variant");
+        };
+        assert_eq!(meta.data_format(), PixelFormat::from_fourcc(*b"XYZW"));
+        assert_eq!(meta.buffer_size(), 1024);
+
+        let mut mplane = serde_json::to_value(Format::VideoOutputMplane(PixFormatMplane::new(
+            1,
+            1,
+            PixelFormat::NV12M,
+        )))
+        .unwrap();
+        mplane["VideoOutputMplane"]["planes"] = serde_json::Value::Array(vec![
+            serde_json::json!({"bytes_per_line": 1, "size_image": 1});
+            9
+        ]);
+        assert!(serde_json::from_value::<Format>(mplane).is_err());
+    }
+
+    #[test]
+    fn frame_sizes_and_intervals() {
+        let sizes = FrameSizes::Discrete(vec![
+            DiscreteFrameSize {
+                raw: raw::FrmSizeDiscrete {
+                    width: 640,
+                    height: 480,
+                },
+                index: 0,
+            },
+            DiscreteFrameSize {
+                raw: raw::FrmSizeDiscrete {
+                    width: 1280,
+                    height: 720,
+                },
+                index: 1,
+            },
+        ]);
+        let value = round_trip(&sizes);
+        assert_eq!(value["Discrete"][1]["width"], 1280);
+
+        let sizes = FrameSizes::Stepwise(StepwiseFrameSizes(raw::FrmSizeStepwise {
+            min_width: 16,
+            max_width: 1920,
+            step_width: 2,
+            min_height: 16,
+            max_height: 1080,
+            step_height: 2,
+        }));
+        round_trip(&sizes);
+
+        let ivals = FrameIntervals::Discrete(vec![DiscreteFrameInterval {
+            index: 0,
+            raw: Fract::new(1001, 30000),
+        }]);
+        let value = round_trip(&ivals);
+        assert_eq!(value["Discrete"][0]["interval"]["denominator"], 30000);
+
+        let ivals = FrameIntervals::Continuous(StepwiseFrameIntervals(raw::FrmIvalStepwise {
+            min: Fract::new(1, 60),
+            max: Fract::new(1, 1),
+            step: Fract::new(1, 1),
+        }));
+        round_trip(&ivals);
+    }
+}
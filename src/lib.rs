@@ -2,36 +2,69 @@
 //!
 //! This library provides a (hopefully) convenient and high-level wrapper around the V4L2 ioctls,
 //! and allows accessing video devices (capture cards, webcams, etc.) on Linux systems.
+//!
+//! # Cargo features
+//!
+//! - `tokio`: enables `stream::AsyncStream`, which allows awaiting frames in a tokio runtime.
+//! - `serde`: implements `Serialize` and `Deserialize` for capabilities, formats, frame sizes and
+//!   intervals, control descriptions and values, and control snapshots.
 
 #[macro_use]
 mod macros;
 mod buf_type;
 pub mod controls;
+pub mod convert;
+pub mod debayer;
+mod discovery;
+pub mod dump;
 pub mod format;
+pub mod mjpeg;
+mod open;
 mod pixel_format;
 mod raw;
+#[cfg(feature = "serde")]
+mod serde_impls;
 mod shared;
 pub mod stream;
 pub mod uvc;
 
-use nix::{errno::Errno, libc};
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+};
 use pixel_format::PixelFormat;
 use std::{
     fmt,
-    fs::{self, File, OpenOptions},
+    fs::{self, File},
     io::{self, Read, Write},
     mem::{self, MaybeUninit},
-    os::unix::prelude::*,
+    os::{
+        fd::{AsFd, BorrowedFd, OwnedFd},
+        unix::prelude::*,
+    },
     path::{Path, PathBuf},
+    time::Duration,
 };
 
-use controls::{ControlDesc, ControlIter, TextMenuIter};
-use format::{Format, FormatDescIter, FrameIntervals, FrameSizes, MetaFormat, PixFormat};
-use raw::controls::Cid;
+use controls::{
+    ApplyReport, AutoFocusStatus, ControlBatch, ControlBatchError, ControlDesc, ControlEvent,
+    ControlIter, ControlSnapshot, ControlValue, ExposureMode, ExtCtrlsIoctl, MenuControl, MenuIter,
+    PowerLineFrequency, ResetReport, TextMenuIter, WhiteBalance,
+};
+use format::{
+    Format, FormatDescIter, FormatPreferences, FrameIntervals, FrameSizes, MetaFormat, PixFormat,
+    PixFormatMplane,
+};
+use raw::controls::{Cid, CtrlClass, CtrlWhich, ExtControl, ExtControls};
 use shared::{CaptureParamFlags, Memory, StreamParamCaps};
-use stream::{ReadStream, WriteStream, DEFAULT_BUFFER_COUNT};
+use stream::{
+    CaptureStream, DmabufStream, IoMethod, MplaneReadStream, ReadIoStream, ReadStream,
+    UserptrBuffer, UserptrStream, WriteStream, DEFAULT_BUFFER_COUNT,
+};
 
 pub use buf_type::*;
+pub use discovery::{list, DeviceInfo, DeviceSelector, NodeKind, OpenByError};
+pub use open::{AccessMode, OpenOptions};
 pub use shared::{
     AnalogStd, CapabilityFlags, Fract, InputCapabilities, InputStatus, InputType,
     OutputCapabilities, OutputType,
@@ -39,41 +72,6 @@ pub use shared::{
 
 pub use stream::Frame;
 
-/// Returns an iterator over all connected V4L2 devices.
-pub fn list() -> io::Result<impl Iterator<Item = io::Result<Device>>> {
-    Ok(fs::read_dir("/dev")?.flat_map(|file| {
-        let file = match file {
-            Ok(file) => file,
-            Err(e) => return Some(Err(e.into())),
-        };
-
-        match file.file_type() {
-            Ok(ty) => {
-                if !ty.is_char_device() {
-                    log::debug!("unexpected device file type {:?}", ty);
-                    return None;
-                }
-            }
-            Err(e) => return Some(Err(e.into())),
-        }
-
-        let name = file.file_name();
-        let prefixes: &[&[u8]] = &[
-            b"video",
-            b"vbi",
-            b"radio",
-            b"swradio",
-            b"v4l-touch",
-            b"v4l-subdev",
-        ];
-        if prefixes.iter().any(|p| name.as_bytes().starts_with(p)) {
-            Some(Device::open(&file.path()))
-        } else {
-            None
-        }
-    }))
-}
-
 /// A V4L2 device.
 #[derive(Debug)]
 pub struct Device {
@@ -86,7 +84,25 @@ impl Device {
     ///
     /// If the path does not refer to a V4L2 device node, an error will be returned.
     pub fn open<A: AsRef<Path>>(path: A) -> io::Result<Self> {
-        Self::open_impl(path.as_ref(), true)
+        Self::open_with(path, OpenOptions::new())
+    }
+
+    /// Opens a V4L2 device file from the given path, using the given [`OpenOptions`].
+    ///
+    /// The options can be retrieved again with [`Device::open_options`].
+    ///
+    /// If the path does not refer to a V4L2 device node, an error will be returned.
+    pub fn open_with<A: AsRef<Path>>(path: A, options: OpenOptions) -> io::Result<Self> {
+        let file = options.open(path.as_ref())?;
+        Self::from_file(file)
+    }
+
+    /// Opens the device node matching `selector`.
+    ///
+    /// This lists all devices like [`list`] does, and opens the one matching `selector`. It is
+    /// an error if no device, or more than one device matches.
+    pub fn open_by(selector: &DeviceSelector) -> Result<Self, OpenByError> {
+        discovery::open_by(selector)
     }
 
     pub fn try_clone(&self) -> io::Result<Self> {
@@ -96,21 +112,18 @@ impl Device {
         })
     }
 
-    /// Opens a V4L2 device file from the given path.
+    /// Opens a V4L2 device file from the given path in non-blocking mode (`O_NONBLOCK`).
+    ///
+    /// Streams created from a non-blocking device return an error of kind
+    /// [`io::ErrorKind::WouldBlock`] from `dequeue` instead of waiting for the next buffer.
+    /// [`ReadStream::try_dequeue`] can be used with both blocking and non-blocking devices.
     ///
     /// If the path does not refer to a V4L2 device node, an error will be returned.
     pub fn open_non_blocking<A: AsRef<Path>>(path: A) -> io::Result<Self> {
-        Self::open_impl(path.as_ref(), false)
+        Self::open_with(path, OpenOptions::new().with_non_blocking(true))
     }
 
-    fn open_impl(path: &Path, blocking: bool) -> io::Result<Self> {
-        let mut open_options = OpenOptions::new();
-        open_options.read(true).write(true);
-        if !blocking {
-            open_options.custom_flags(libc::O_NONBLOCK);
-        }
-        let file = open_options.open(path)?;
-
+    pub(crate) fn from_file(file: File) -> io::Result<Self> {
         let mut this = Self {
             file,
             available_capabilities: CapabilityFlags::empty(),
@@ -125,9 +138,34 @@ impl Device {
         self.file.as_raw_fd()
     }
 
+    /// Returns the options the device was opened with.
+    ///
+    /// These are determined from the file descriptor's current flags, so they also reflect
+    /// changes made through other handles to the same open file (eg. a [`Device::try_clone`]).
+    pub fn open_options(&self) -> io::Result<OpenOptions> {
+        OpenOptions::from_fd(self.file.as_fd())
+    }
+
+    /// Returns whether the device file descriptor is in non-blocking mode (`O_NONBLOCK`).
+    ///
+    /// Streams created from this device inherit this mode.
+    pub fn is_nonblocking(&self) -> io::Result<bool> {
+        is_nonblocking(self.file.as_fd())
+    }
+
     /// Returns the path to the V4L2 device.
     pub fn path(&self) -> io::Result<PathBuf> {
-        Ok(fs::read_link(format!("/proc/self/fd/{}", self.fd()))?)
+        fs::read_link(format!("/proc/self/fd/{}", self.fd()))
+    }
+
+    /// Returns the sysfs directory of the device (`/sys/dev/char/<major>:<minor>`).
+    pub(crate) fn sysfs_path(&self) -> io::Result<PathBuf> {
+        let rdev = self.file.metadata()?.rdev();
+        Ok(PathBuf::from(format!(
+            "/sys/dev/char/{}:{}",
+            nix::sys::stat::major(rdev),
+            nix::sys::stat::minor(rdev),
+        )))
     }
 
     pub fn capabilities(&self) -> io::Result<Capabilities> {
@@ -149,7 +187,7 @@ impl Device {
     /// `VIDEO_OUTPUT_MPLANE`, `VIDEO_OVERLAY`, `SDR_CAPTURE`, `SDR_OUTPUT`, `META_CAPTURE`, or
     /// `META_OUTPUT`.
     pub fn formats(&self, buf_type: BufType) -> FormatDescIter<'_> {
-        FormatDescIter::new(self, buf_type)
+        FormatDescIter::new(self.file.as_fd(), buf_type)
     }
 
     /// Returns the supported frame sizes for a given pixel format.
@@ -162,6 +200,14 @@ impl Device {
         FrameSizes::new(self, pixel_format)
     }
 
+    /// Returns the supported frame intervals for a given pixel format and frame size.
+    ///
+    /// # Errors
+    ///
+    /// If the device does not support the combination of `pixel_format`, `width`, and `height`,
+    /// an error of kind [`io::ErrorKind::InvalidInput`] is returned. If the device does not
+    /// support frame interval enumeration at all, the error is of kind
+    /// [`io::ErrorKind::Unsupported`].
     pub fn frame_intervals(
         &self,
         pixel_format: PixelFormat,
@@ -179,6 +225,27 @@ impl Device {
         }
     }
 
+    /// Returns the index of the currently selected video input.
+    pub fn input(&self) -> io::Result<u32> {
+        let mut index = 0;
+        unsafe {
+            raw::g_input(self.fd(), &mut index)?;
+        }
+        Ok(index)
+    }
+
+    /// Selects the video input with the given index (as enumerated by [`Device::inputs`]).
+    ///
+    /// The supported formats and frame sizes can depend on the selected input. Note that the
+    /// selection is kept by the driver after the device is closed.
+    pub fn set_input(&mut self, index: u32) -> io::Result<()> {
+        let mut index = index;
+        unsafe {
+            raw::s_input(self.fd(), &mut index)?;
+        }
+        Ok(())
+    }
+
     pub fn outputs(&self) -> OutputIter<'_> {
         OutputIter {
             device: self,
@@ -191,13 +258,404 @@ impl Device {
         ControlIter::new(self)
     }
 
+    /// Returns an iterator over the controls of the given class.
+    ///
+    /// This includes the [`CTRL_CLASS`][controls::CtrlType::CTRL_CLASS] control that names the
+    /// class, if the driver has one.
+    pub fn controls_in_class(
+        &self,
+        class: CtrlClass,
+    ) -> impl Iterator<Item = io::Result<ControlDesc>> + '_ {
+        self.controls().filter(move |desc| match desc {
+            Ok(desc) => desc.class() == class,
+            Err(_) => true,
+        })
+    }
+
+    /// Returns an iterator over the items of a [`MENU`][controls::CtrlType::MENU] or
+    /// [`INTEGER_MENU`][controls::CtrlType::INTEGER_MENU] control.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ctrl` is not a menu control.
+    pub fn menu_items(&self, ctrl: &ControlDesc) -> MenuIter<'_> {
+        MenuIter::new(self, ctrl)
+    }
+
     /// Returns an iterator over the valid values of a menu control.
+    ///
+    /// Only supports [`MENU`][controls::CtrlType::MENU] controls, [`Device::menu_items`] also
+    /// supports integer menus.
     pub fn enumerate_menu(&self, ctrl: &ControlDesc) -> TextMenuIter<'_> {
         TextMenuIter::new(self, ctrl)
     }
 
-    pub fn read_control_raw(&self, cid: Cid) -> io::Result<i32> {
-        let mut control = raw::controls::Control { id: cid, value: 0 };
+    /// Queries the current description of a control.
+    ///
+    /// Flags like [`ControlFlags::INACTIVE`][controls::ControlFlags::INACTIVE] change at runtime,
+    /// so this can be called again to find out whether a control is currently
+    /// [actionable][ControlDesc::is_actionable].
+    pub fn query_control(&self, cid: impl Into<Cid>) -> io::Result<ControlDesc> {
+        controls::query_control(self, cid.into())
+    }
+
+    /// Reads the current value of a control.
+    ///
+    /// Integer, 64-bit integer, boolean, menu, integer menu and bitmask controls are supported.
+    /// Boolean controls are 0 or 1, and menu controls return the index of the selected item.
+    pub fn control(&self, cid: impl Into<Cid>) -> io::Result<i64> {
+        controls::read_integer(self, cid.into())
+    }
+
+    /// Sets the value of a control.
+    ///
+    /// Supports the same control types as [`Device::control`]. Returns an error of kind
+    /// [`io::ErrorKind::InvalidInput`] if `value` is out of range, and of kind
+    /// [`io::ErrorKind::ResourceBusy`] if the control is grabbed by another file handle.
+    pub fn set_control(&mut self, cid: impl Into<Cid>, value: i64) -> io::Result<()> {
+        controls::write_integer(self, cid.into(), value)
+    }
+
+    /// Sets the value of a control, clamping it to the control's valid values first.
+    ///
+    /// Out-of-range values are clamped to the nearest valid value (see [`ControlDesc::clamp`]),
+    /// and menu controls are set to the existing item closest to `value`. Returns the value that
+    /// was written instead of `value`, or `None` if `value` was valid.
+    pub fn set_control_clamped(
+        &mut self,
+        cid: impl Into<Cid>,
+        value: i64,
+    ) -> io::Result<Option<i64>> {
+        controls::write_clamped(self, cid.into(), value)
+    }
+
+    /// Checks whether `value` is a valid value of a control, without setting it.
+    ///
+    /// In addition to the checks of [`ControlDesc::validate`], this checks that menu controls
+    /// have an item at index `value`. Returns an error of kind
+    /// [`io::ErrorKind::InvalidInput`] wrapping a [`RangeError`][controls::RangeError] if the
+    /// value is invalid.
+    pub fn validate_control(&self, desc: &ControlDesc, value: i64) -> io::Result<()> {
+        controls::validate(self, desc, value)
+    }
+
+    /// Returns the camera's exposure mode (`V4L2_CID_EXPOSURE_AUTO`).
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] if the device doesn't have an
+    /// exposure mode control.
+    pub fn exposure_mode(&self) -> io::Result<ExposureMode> {
+        self.menu_control()
+    }
+
+    /// Sets the camera's exposure mode.
+    ///
+    /// Cameras typically support only some of the modes. Returns an error of kind
+    /// [`io::ErrorKind::InvalidInput`] if `mode` is not one of them.
+    pub fn set_exposure_mode(&mut self, mode: ExposureMode) -> io::Result<()> {
+        self.set_menu_control(mode)
+    }
+
+    /// Returns the exposure time (`V4L2_CID_EXPOSURE_ABSOLUTE`).
+    ///
+    /// While the exposure time is controlled automatically, this may return the last manually set
+    /// value rather than the one in use.
+    pub fn exposure(&self) -> io::Result<Duration> {
+        controls::exposure::exposure(self)
+    }
+
+    /// Sets the exposure time.
+    ///
+    /// The exposure control only takes effect with a manual exposure mode, so this first
+    /// switches the camera to [`ExposureMode::Manual`] if it controls the exposure time
+    /// automatically. The exposure time is rounded to the control's unit of 100 µs and its step
+    /// size, and an error of kind [`io::ErrorKind::InvalidInput`] is returned if it is out of
+    /// range.
+    pub fn set_exposure(&mut self, exposure: Duration) -> io::Result<()> {
+        controls::exposure::set_exposure(self, exposure)
+    }
+
+    /// Returns whether the camera may lower the frame rate to increase the exposure time
+    /// (`V4L2_CID_EXPOSURE_AUTO_PRIORITY`).
+    pub fn exposure_auto_priority(&self) -> io::Result<bool> {
+        controls::exposure::auto_priority(self)
+    }
+
+    /// Sets whether the camera may lower the frame rate to increase the exposure time in
+    /// automatic exposure modes.
+    ///
+    /// Many webcams enable this by default, which makes the frame rate drop in low light even
+    /// though a fixed frame interval was requested.
+    pub fn set_exposure_auto_priority(&mut self, enabled: bool) -> io::Result<()> {
+        controls::exposure::set_auto_priority(self, enabled)
+    }
+
+    /// Returns whether automatic white balance is enabled.
+    ///
+    /// This uses `V4L2_CID_AUTO_WHITE_BALANCE`, or `V4L2_CID_AUTO_N_PRESET_WHITE_BALANCE` on
+    /// cameras with white balance presets.
+    pub fn auto_white_balance(&self) -> io::Result<bool> {
+        controls::white_balance::auto_white_balance(self)
+    }
+
+    /// Enables or disables automatic white balance.
+    pub fn set_auto_white_balance(&mut self, enabled: bool) -> io::Result<()> {
+        controls::white_balance::set_auto_white_balance(self, enabled)
+    }
+
+    /// Returns the white balance color temperature in Kelvin.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] if the camera has no
+    /// temperature control, in which case [`Device::white_balance`] may return its red and blue
+    /// gains instead.
+    pub fn white_balance_temperature(&self) -> io::Result<u32> {
+        controls::white_balance::temperature(self)
+    }
+
+    /// Sets the white balance color temperature in Kelvin, disabling automatic white balance
+    /// first.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] if `kelvin` is outside of the
+    /// control's range. Otherwise, returns the temperature the driver settled on, which may
+    /// differ from `kelvin` due to rounding.
+    pub fn set_white_balance_temperature(&mut self, kelvin: u32) -> io::Result<u32> {
+        controls::white_balance::set_temperature(self, kelvin)
+    }
+
+    /// Returns the manual white balance setting.
+    ///
+    /// This is the color temperature if the camera has a temperature control, and the red and
+    /// blue gains otherwise.
+    pub fn white_balance(&self) -> io::Result<WhiteBalance> {
+        controls::white_balance::white_balance(self)
+    }
+
+    /// Sets the white balance manually, disabling automatic white balance first.
+    ///
+    /// Returns the setting the driver settled on.
+    pub fn set_white_balance(&mut self, balance: WhiteBalance) -> io::Result<WhiteBalance> {
+        controls::white_balance::set_white_balance(self, balance)
+    }
+
+    /// Reads the value of the menu control represented by `T`.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] if the device doesn't have the
+    /// control, and of kind [`io::ErrorKind::InvalidData`] if the selected item is not one `T`
+    /// knows about.
+    pub fn menu_control<T: MenuControl>(&self) -> io::Result<T> {
+        controls::menu::get(self)
+    }
+
+    /// Selects the item `value` of the menu control represented by `T`.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] if the driver doesn't offer the
+    /// item.
+    pub fn set_menu_control<T: MenuControl>(&mut self, value: T) -> io::Result<()> {
+        controls::menu::set(self, value)
+    }
+
+    /// Returns the values of `T` that the driver offers as items of its menu control.
+    ///
+    /// Returns an empty list if the device doesn't have the control.
+    pub fn supported_menu_values<T: MenuControl>(&self) -> io::Result<Vec<T>> {
+        controls::menu::supported(self)
+    }
+
+    /// Returns the mains frequency filter setting (`V4L2_CID_POWER_LINE_FREQUENCY`).
+    pub fn power_line_frequency(&self) -> io::Result<PowerLineFrequency> {
+        self.menu_control()
+    }
+
+    /// Sets the mains frequency filter, which suppresses flicker under artificial lighting.
+    ///
+    /// Many cameras don't support [`PowerLineFrequency::Auto`], which is rejected with an error
+    /// of kind [`io::ErrorKind::InvalidInput`]. [`Device::supported_menu_values`] returns the
+    /// supported settings.
+    pub fn set_power_line_frequency(&mut self, frequency: PowerLineFrequency) -> io::Result<()> {
+        self.set_menu_control(frequency)
+    }
+
+    /// Returns whether continuous automatic focus is enabled (`V4L2_CID_FOCUS_AUTO`).
+    pub fn autofocus(&self) -> io::Result<bool> {
+        controls::focus::autofocus(self)
+    }
+
+    /// Enables or disables continuous automatic focus.
+    pub fn set_autofocus(&mut self, enabled: bool) -> io::Result<()> {
+        controls::focus::set_autofocus(self, enabled)
+    }
+
+    /// Returns the focus position (`V4L2_CID_FOCUS_ABSOLUTE`).
+    ///
+    /// The unit is driver-specific. Larger values move the focus further away.
+    pub fn focus(&self) -> io::Result<i64> {
+        controls::focus::focus(self)
+    }
+
+    /// Sets the focus position, disabling automatic focus first.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] if `focus` is outside of the
+    /// control's range.
+    pub fn set_focus(&mut self, focus: i64) -> io::Result<()> {
+        controls::focus::set_focus(self, focus)
+    }
+
+    /// Moves the focus by `delta`, disabling automatic focus first.
+    ///
+    /// This uses `V4L2_CID_FOCUS_RELATIVE` if the camera has it, and changes the absolute focus
+    /// position otherwise. Moves past the end of the focus range stop at its end.
+    pub fn nudge_focus(&mut self, delta: i64) -> io::Result<()> {
+        controls::focus::nudge_focus(self, delta)
+    }
+
+    /// Returns the state of the automatic focus, or `None` if the driver doesn't report it.
+    ///
+    /// This can be polled to wait for the focus to converge.
+    pub fn autofocus_status(&self) -> io::Result<Option<AutoFocusStatus>> {
+        controls::focus::autofocus_status(self)
+    }
+
+    /// Returns a [`Ptz`] that controls the pan, tilt and zoom of the camera.
+    ///
+    /// [`Ptz`]: controls::ptz::Ptz
+    pub fn ptz(&mut self) -> controls::ptz::Ptz<'_> {
+        controls::ptz::Ptz::new(self)
+    }
+
+    /// Presses a button control, triggering its action (eg. `V4L2_CID_PAN_RESET`).
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] if the control is not a
+    /// [`BUTTON`][controls::CtrlType::BUTTON].
+    pub fn press_button(&mut self, cid: impl Into<Cid>) -> io::Result<()> {
+        controls::press_button(self, cid.into())
+    }
+
+    /// Saves the current values of all writable controls.
+    ///
+    /// Read-only, write-only, disabled and button controls are not included. Inactive controls
+    /// are, since their value takes effect again once they become active.
+    pub fn control_snapshot(&self) -> io::Result<ControlSnapshot> {
+        controls::control_snapshot(self)
+    }
+
+    /// Restores the control values saved in `snapshot`.
+    ///
+    /// Controls that enable automatic modes (like `V4L2_CID_EXPOSURE_AUTO`) are restored first.
+    /// The remaining controls are then set one class at a time, skipping those that are inactive
+    /// in the restored mode and those the device doesn't have. Controls that cannot be set don't
+    /// abort the restore, but are listed in the returned [`ApplyReport`].
+    pub fn apply_snapshot(&mut self, snapshot: &ControlSnapshot) -> io::Result<ApplyReport> {
+        controls::apply_snapshot(self, snapshot)
+    }
+
+    /// Reads the current value of a control of any type.
+    ///
+    /// String, array and compound controls are read into a buffer of the size given by the
+    /// descriptor, which is grown if the driver reports that it needs more space.
+    pub fn control_value(&self, desc: &ControlDesc) -> io::Result<ControlValue> {
+        controls::read_value(self, desc, CtrlWhich::CUR_VAL)
+    }
+
+    /// Sets the value of a control of any type.
+    ///
+    /// The variant of `value` has to match the control's type, except that
+    /// [`ControlValue::Integer`] and [`ControlValue::Integer64`] can be used for any integer
+    /// control, and [`ControlValue::Raw`] for any control that has a payload.
+    pub fn set_control_value(
+        &mut self,
+        desc: &ControlDesc,
+        value: &ControlValue,
+    ) -> io::Result<()> {
+        controls::write_value(self, desc, value)
+    }
+
+    /// Reads the current values of several controls with a single `VIDIOC_G_EXT_CTRLS` call.
+    pub fn read_controls(
+        &self,
+        cids: impl IntoIterator<Item = impl Into<Cid>>,
+    ) -> Result<ControlBatch, ControlBatchError> {
+        controls::read_batch(self, cids.into_iter().map(Into::into), CtrlWhich::CUR_VAL)
+    }
+
+    /// Reads the default value of a control (`V4L2_CTRL_WHICH_DEF_VAL`).
+    ///
+    /// Unlike [`ControlDesc::default_value`], this also works for string, array and compound
+    /// controls. This doesn't change the control, and works for volatile and inactive controls,
+    /// whose current value may be meaningless.
+    pub fn control_default(&self, cid: impl Into<Cid>) -> io::Result<ControlValue> {
+        let desc = controls::query_control(self, cid.into())?;
+        controls::read_value(self, &desc, CtrlWhich::DEF_VAL)
+    }
+
+    /// Reads the default values of several controls with a single `VIDIOC_G_EXT_CTRLS` call.
+    pub fn read_control_defaults(
+        &self,
+        cids: impl IntoIterator<Item = impl Into<Cid>>,
+    ) -> Result<ControlBatch, ControlBatchError> {
+        controls::read_batch(self, cids.into_iter().map(Into::into), CtrlWhich::DEF_VAL)
+    }
+
+    /// Reads the default values of all controls in a control class with a single
+    /// `VIDIOC_G_EXT_CTRLS` call.
+    ///
+    /// Disabled and button controls are skipped, since they have no meaningful default value.
+    pub fn read_class_defaults(&self, class: CtrlClass) -> Result<ControlBatch, ControlBatchError> {
+        controls::read_class_defaults(self, class)
+    }
+
+    /// Atomically sets the controls in `batch` with a single `VIDIOC_S_EXT_CTRLS` call.
+    ///
+    /// If the batch is rejected, the returned error tells which control caused it, and whether
+    /// the driver may have applied some of the controls before it.
+    pub fn apply_controls(&mut self, batch: &ControlBatch) -> Result<(), ControlBatchError> {
+        controls::write_batch(self, batch)
+    }
+
+    /// Resets all writable controls to their default values.
+    ///
+    /// Read-only, inactive, volatile and button controls are skipped. The controls of each control
+    /// class are reset with a single `VIDIOC_S_EXT_CTRLS` call. If the driver rejects it, they are
+    /// reset one by one, and the controls that could not be reset are listed in the returned
+    /// report instead of aborting the reset.
+    pub fn reset_controls(&mut self) -> io::Result<ResetReport> {
+        controls::reset_all(self)
+    }
+
+    /// Resets the control `cid` to its default value.
+    pub fn reset_control(&mut self, cid: impl Into<Cid>) -> io::Result<()> {
+        controls::reset_one(self, cid.into())
+    }
+
+    /// Subscribes to change events of the control `cid` (`VIDIOC_SUBSCRIBE_EVENT`).
+    ///
+    /// Passing a raw ID of 0 subscribes to all controls of the device. Changes made through this
+    /// [`Device`] are not reported, only those made by the driver (eg. auto-exposure) or by other
+    /// file handles.
+    pub fn subscribe_control_events(&mut self, cid: impl Into<Cid>) -> io::Result<()> {
+        controls::subscribe_events(self, cid.into())
+    }
+
+    /// Cancels a subscription made with [`Device::subscribe_control_events`].
+    ///
+    /// Passing a raw ID of 0 cancels all subscriptions.
+    pub fn unsubscribe_control_events(&mut self, cid: impl Into<Cid>) -> io::Result<()> {
+        controls::unsubscribe_events(self, cid.into())
+    }
+
+    /// Blocks until a control event is available, and returns it.
+    pub fn dequeue_event(&self) -> io::Result<ControlEvent> {
+        controls::dequeue_event(self, true).map(Option::unwrap)
+    }
+
+    /// Returns the next control event, or `None` if no event is pending.
+    pub fn try_dequeue_event(&self) -> io::Result<Option<ControlEvent>> {
+        controls::dequeue_event(self, false)
+    }
+
+    pub fn read_control_raw(&self, cid: impl Into<Cid>) -> io::Result<i32> {
+        let mut control = raw::controls::Control {
+            id: cid.into(),
+            value: 0,
+        };
 
         unsafe {
             raw::g_ctrl(self.fd(), &mut control)?;
@@ -206,14 +664,107 @@ impl Device {
         Ok(control.value)
     }
 
-    pub fn write_control_raw(&mut self, cid: Cid, value: i32) -> io::Result<()> {
-        let mut control = raw::controls::Control { id: cid, value };
+    pub fn write_control_raw(&mut self, cid: impl Into<Cid>, value: i32) -> io::Result<()> {
+        let mut control = raw::controls::Control {
+            id: cid.into(),
+            value,
+        };
         unsafe {
             raw::s_ctrl(self.fd(), &mut control)?;
         }
         Ok(())
     }
 
+    /// Reads one of the values selected by `which` of several controls (`VIDIOC_G_EXT_CTRLS`).
+    pub(crate) fn read_ext_controls_raw(
+        &self,
+        which: CtrlWhich,
+        controls: &mut [ExtControl],
+    ) -> io::Result<()> {
+        self.ext_controls_raw(ExtCtrlsIoctl::Get, which, controls)
+            .map_err(|(e, _)| e)
+    }
+
+    /// Atomically sets the current value of several controls (`VIDIOC_S_EXT_CTRLS`).
+    pub(crate) fn write_ext_controls_raw(&self, controls: &mut [ExtControl]) -> io::Result<()> {
+        self.ext_controls_raw(ExtCtrlsIoctl::Set, CtrlWhich::CUR_VAL, controls)
+            .map_err(|(e, _)| e)
+    }
+
+    /// Performs one of the `VIDIOC_*_EXT_CTRLS` ioctls.
+    ///
+    /// On failure, the error is returned along with the `error_idx` reported by the kernel.
+    pub(crate) fn ext_controls_raw(
+        &self,
+        ioctl: ExtCtrlsIoctl,
+        which: CtrlWhich,
+        controls: &mut [ExtControl],
+    ) -> Result<(), (io::Error, usize)> {
+        let mut ext = ext_controls(which, controls).map_err(|e| (e, controls.len()))?;
+        let res = unsafe {
+            match ioctl {
+                ExtCtrlsIoctl::Get => raw::g_ext_ctrls(self.fd(), &mut ext),
+                ExtCtrlsIoctl::Set => raw::s_ext_ctrls(self.fd(), &mut ext),
+                ExtCtrlsIoctl::Try => raw::try_ext_ctrls(self.fd(), &mut ext),
+            }
+        };
+        match res {
+            Ok(_) => Ok(()),
+            Err(e) => Err((e.into(), ext.error_idx as usize)),
+        }
+    }
+
+    /// Reads the JPEG compression parameters of the device.
+    ///
+    /// This uses `VIDIOC_G_JPEGCOMP` if the driver supports it, and falls back to the
+    /// [`Cid::JPEG_COMPRESSION_QUALITY`] control otherwise. In the latter case, only the quality
+    /// is reported.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] if the driver supports neither.
+    pub fn jpeg_compression(&self) -> io::Result<mjpeg::JpegCompression> {
+        let mut raw = MaybeUninit::uninit();
+        match unsafe { raw::g_jpegcomp(self.fd(), raw.as_mut_ptr()) } {
+            Ok(_) => {
+                return Ok(mjpeg::JpegCompression::from_raw(unsafe {
+                    raw.assume_init()
+                }))
+            }
+            Err(Errno::ENOTTY) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let quality = self
+            .read_control_raw(Cid::JPEG_COMPRESSION_QUALITY)
+            .map_err(|e| match e.raw_os_error() {
+                Some(code) if code == Errno::EINVAL as i32 => jpeg_compression_unsupported(),
+                _ => e,
+            })?;
+        Ok(mjpeg::JpegCompression::new(quality.clamp(0, 100) as u8))
+    }
+
+    /// Sets the JPEG compression parameters of the device.
+    ///
+    /// Like [`Device::jpeg_compression`], this falls back to the
+    /// [`Cid::JPEG_COMPRESSION_QUALITY`] control if `VIDIOC_S_JPEGCOMP` is not supported. The
+    /// control can only configure the quality, so the segments and markers of `params` are
+    /// ignored in that case.
+    pub fn set_jpeg_compression(&mut self, params: &mjpeg::JpegCompression) -> io::Result<()> {
+        match unsafe { raw::s_jpegcomp(self.fd(), &params.to_raw()) } {
+            Ok(_) => return Ok(()),
+            Err(Errno::ENOTTY) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        if params.app_segment().is_some() || !params.comment().is_empty() {
+            log::warn!("driver does not support VIDIOC_S_JPEGCOMP, ignoring APPn and COM segments");
+        }
+        self.write_control_raw(Cid::JPEG_COMPRESSION_QUALITY, params.quality().into())
+            .map_err(|e| match e.raw_os_error() {
+                Some(code) if code == Errno::EINVAL as i32 => jpeg_compression_unsupported(),
+                _ => e,
+            })
+    }
+
     /// Reads the stream format in use by `buf_type`.
     ///
     /// The returned [`Format`] variant will match `buf_type`.
@@ -237,47 +788,147 @@ impl Device {
         }
     }
 
+    /// Asks the driver how it would adjust `format`, without changing the device's configuration
+    /// (`VIDIOC_TRY_FMT`).
+    ///
+    /// The buffer type is determined by the variant of `format`. The returned [`Format`] has the
+    /// same variant, and contains the values the driver would use if `format` was set, including
+    /// the driver-computed stride and image size. Use [`Format::is_adjusted`] to check whether the
+    /// driver would change the values that were requested.
+    ///
+    /// Like when setting a format, drivers may return `EINVAL` instead of adjusting unsupported
+    /// values.
+    pub fn try_format(&self, format: Format) -> io::Result<Format> {
+        let mut raw_format = format.to_raw();
+        unsafe {
+            raw::try_fmt(self.fd(), &mut raw_format)?;
+            Ok(Format::from_raw(raw_format).unwrap())
+        }
+    }
+
     /// Negotiates a stream's format.
     ///
     /// The driver will adjust the values in `format` to the closest values it supports (the variant
-    /// will not be changed). The modified `Format` is returned.
-    fn set_format_raw(&mut self, format: Format) -> io::Result<Format> {
+    /// will not be changed). The modified `Format` is returned, along with whether the driver
+    /// changed any of the requested values.
+    fn set_format_raw(&mut self, format: Format) -> io::Result<(Format, bool)> {
+        let mut raw_format = format.to_raw();
         unsafe {
-            let mut raw_format: raw::Format = mem::zeroed();
-            match format {
-                Format::VideoCapture(f) => {
-                    raw_format.type_ = BufType::VIDEO_CAPTURE;
-                    raw_format.fmt.pix = f.to_raw();
-                }
-                Format::VideoOutput(f) => {
-                    raw_format.type_ = BufType::VIDEO_OUTPUT;
-                    raw_format.fmt.pix = f.to_raw();
-                }
-                Format::VideoCaptureMplane(f) => {
-                    raw_format.type_ = BufType::VIDEO_CAPTURE_MPLANE;
-                    raw_format.fmt.pix_mp = f.to_raw();
-                }
-                Format::VideoOutputMplane(f) => {
-                    raw_format.type_ = BufType::VIDEO_OUTPUT_MPLANE;
-                    raw_format.fmt.pix_mp = f.to_raw();
-                }
-                Format::VideoOverlay(f) => {
-                    raw_format.type_ = BufType::VIDEO_OVERLAY;
-                    raw_format.fmt.win = f.to_raw();
+            raw::s_fmt(self.fd(), &mut raw_format)?;
+        }
+        let fmt = unsafe { Format::from_raw(raw_format).unwrap() };
+        let adjusted = fmt.is_adjusted(&format);
+        if adjusted {
+            log::debug!("driver adjusted format {:?} to {:?}", format, fmt);
+        }
+        Ok((fmt, adjusted))
+    }
+
+    /// Picks and sets the video capture format that best matches `prefs`.
+    ///
+    /// The preferred pixel formats are tried in order. For each one the device supports, the
+    /// frame sizes and intervals are enumerated, and the closest match to the preferences is
+    /// checked with [`Device::try_format`] before it is set. If a target or minimum frame rate is
+    /// given, the frame interval is set as well.
+    ///
+    /// The returned format can be passed to [`Device::video_capture`].
+    ///
+    /// # Errors
+    ///
+    /// If none of the preferred formats can be negotiated, an error of kind
+    /// [`io::ErrorKind::Unsupported`] is returned, describing the formats the device offers.
+    pub fn negotiate_capture(&mut self, prefs: &FormatPreferences) -> io::Result<Format> {
+        let offered = self
+            .formats(BufType::VIDEO_CAPTURE)
+            .map(|res| res.map(|desc| desc.pixel_format()))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut rejected = Vec::new();
+        for &pixel_format in prefs.pixel_formats() {
+            if !offered.contains(&pixel_format) {
+                continue;
+            }
+
+            let sizes = match self.frame_sizes(pixel_format) {
+                Ok(sizes) => prefs.candidate_sizes(&sizes),
+                Err(e) => {
+                    // Let the driver pick the size closest to the target instead.
+                    log::debug!("failed to enumerate frame sizes of {}: {}", pixel_format, e);
+                    vec![prefs.resolution().unwrap_or((u32::MAX, u32::MAX))]
                 }
-                Format::MetaCapture(f) => {
-                    raw_format.type_ = BufType::META_CAPTURE;
-                    raw_format.fmt.meta = f.to_raw();
+            };
+            let candidate = prefs.pick(sizes, |width, height| {
+                self.frame_intervals(pixel_format, width, height)
+                    .map_err(|e| {
+                        log::debug!(
+                            "failed to enumerate frame intervals of {} at {}x{}: {}",
+                            pixel_format,
+                            width,
+                            height,
+                            e
+                        )
+                    })
+                    .ok()
+            });
+            let Some(candidate) = candidate else {
+                rejected.push(format!(
+                    "{} does not reach {} fps",
+                    pixel_format,
+                    prefs.min_fps().unwrap_or_default()
+                ));
+                continue;
+            };
+
+            let format = Format::VideoCapture(PixFormat::new(
+                candidate.width,
+                candidate.height,
+                pixel_format,
+            ));
+            match self.try_format(format.clone()) {
+                Ok(Format::VideoCapture(fmt)) if fmt.pixel_format() == pixel_format => {}
+                Ok(Format::VideoCapture(fmt)) => {
+                    rejected.push(format!(
+                        "driver replaced {} with {}",
+                        pixel_format,
+                        fmt.pixel_format()
+                    ));
+                    continue;
                 }
-                Format::MetaOutput(f) => {
-                    raw_format.type_ = BufType::META_OUTPUT;
-                    raw_format.fmt.meta = f.to_raw();
+                Ok(_) => unreachable!(),
+                Err(e) if e.raw_os_error() == Some(Errno::EINVAL as i32) => {
+                    rejected.push(format!(
+                        "{} at {}x{} was rejected",
+                        pixel_format, candidate.width, candidate.height
+                    ));
+                    continue;
                 }
+                Err(e) => return Err(e),
             }
-            raw::s_fmt(self.fd(), &mut raw_format)?;
-            let fmt = Format::from_raw(raw_format).unwrap();
-            Ok(fmt)
+
+            let (format, _) = self.set_format_raw(format)?;
+            if let Some(interval) = candidate.interval {
+                let interval = set_capture_interval(self.fd(), interval)?;
+                log::debug!("negotiated frame interval {}", interval);
+            }
+            return Ok(format);
         }
+
+        let mut offers = Vec::new();
+        for &pixel_format in &offered {
+            match self.frame_sizes(pixel_format) {
+                Ok(sizes) => offers.push(format!("{} ({})", pixel_format, sizes)),
+                Err(_) => offers.push(pixel_format.to_string()),
+            }
+        }
+        let mut msg = format!(
+            "none of the preferred formats {:?} can be negotiated",
+            prefs.pixel_formats()
+        );
+        if !rejected.is_empty() {
+            msg.push_str(&format!(" ({})", rejected.join(", ")));
+        }
+        msg.push_str(&format!("; device offers {}", offers.join(", ")));
+        Err(io::Error::new(io::ErrorKind::Unsupported, msg))
     }
 
     /// Puts the device into video capture mode and negotiates a pixel format.
@@ -289,14 +940,37 @@ impl Device {
     /// However, it is not required to do so and may instead return `EINVAL` if the parameters are
     /// not supported. One example where this happens is with `v4l2loopback`.
     pub fn video_capture(mut self, format: PixFormat) -> io::Result<VideoCaptureDevice> {
-        let format = match self.set_format_raw(Format::VideoCapture(format))? {
-            Format::VideoCapture(fmt) => fmt,
+        let (format, format_adjusted) = match self.set_format_raw(Format::VideoCapture(format))? {
+            (Format::VideoCapture(fmt), adjusted) => (fmt, adjusted),
             _ => unreachable!(),
         };
 
         Ok(VideoCaptureDevice {
             file: self.file,
             format,
+            format_adjusted,
+        })
+    }
+
+    /// Puts the device into multi-planar video capture mode and negotiates a pixel format.
+    ///
+    /// This is required for devices that only support the
+    /// [`VIDEO_CAPTURE_MPLANE`][CapabilityFlags::VIDEO_CAPTURE_MPLANE] capability. Format
+    /// negotiation works like for [`Device::video_capture`].
+    pub fn video_capture_mplane(
+        mut self,
+        format: PixFormatMplane,
+    ) -> io::Result<VideoCaptureMplaneDevice> {
+        let (format, format_adjusted) =
+            match self.set_format_raw(Format::VideoCaptureMplane(format))? {
+                (Format::VideoCaptureMplane(fmt), adjusted) => (fmt, adjusted),
+                _ => unreachable!(),
+            };
+
+        Ok(VideoCaptureMplaneDevice {
+            file: self.file,
+            format,
+            format_adjusted,
         })
     }
 
@@ -309,20 +983,24 @@ impl Device {
     /// However, it is not required to do so and may instead return `EINVAL` if the parameters are
     /// not supported. One example where this happens is with `v4l2loopback`.
     pub fn video_output(mut self, format: PixFormat) -> io::Result<VideoOutputDevice> {
-        let format = match self.set_format_raw(Format::VideoOutput(format))? {
-            Format::VideoOutput(fmt) => fmt,
+        let (format, format_adjusted) = match self.set_format_raw(Format::VideoOutput(format))? {
+            (Format::VideoOutput(fmt), adjusted) => (fmt, adjusted),
             _ => unreachable!(),
         };
 
         Ok(VideoOutputDevice {
             file: self.file,
             format,
+            format_adjusted,
         })
     }
 
     /// Puts the device into metadata capture mode and negotiates a data format.
     pub fn meta_capture(mut self, format: MetaFormat) -> io::Result<MetaCaptureDevice> {
-        let format = match self.set_format_raw(Format::MetaCapture(format))? {
+        let (_, format_adjusted) = self.set_format_raw(Format::MetaCapture(format))?;
+        // Not all drivers report the buffer size in their `S_FMT` reply, so query the format that
+        // is actually in effect.
+        let format = match self.format(BufType::META_CAPTURE)? {
             Format::MetaCapture(fmt) => fmt,
             _ => unreachable!(),
         };
@@ -330,14 +1008,73 @@ impl Device {
         Ok(MetaCaptureDevice {
             file: self.file,
             format,
+            format_adjusted,
+        })
+    }
+
+    /// Puts the device into metadata output mode and negotiates a data format.
+    ///
+    /// Metadata output devices are used by ISP drivers (like `rkisp1` and `ipu3`) to receive
+    /// processing parameters from userspace.
+    pub fn meta_output(mut self, format: MetaFormat) -> io::Result<MetaOutputDevice> {
+        let (_, format_adjusted) = self.set_format_raw(Format::MetaOutput(format))?;
+        // See `meta_capture`.
+        let format = match self.format(BufType::META_OUTPUT)? {
+            Format::MetaOutput(fmt) => fmt,
+            _ => unreachable!(),
+        };
+
+        Ok(MetaOutputDevice {
+            file: self.file,
+            format,
+            format_adjusted,
         })
     }
 }
 
+/// Sets the frame interval of the video capture stream, returning the one chosen by the driver.
+fn set_capture_interval(fd: RawFd, interval: Fract) -> io::Result<Fract> {
+    unsafe {
+        let mut parm = raw::StreamParm {
+            type_: BufType::VIDEO_CAPTURE,
+            union: raw::StreamParmUnion {
+                capture: raw::CaptureParm {
+                    timeperframe: interval,
+                    capability: StreamParamCaps::TIMEPERFRAME,
+                    capturemode: CaptureParamFlags::empty(),
+                    extendedmode: 0,
+                    readbuffers: 0,
+                    reserved: [0; 4],
+                },
+            },
+        };
+        raw::s_parm(fd, &mut parm)?;
+        Ok(parm.union.capture.timeperframe)
+    }
+}
+
+/// The file descriptor of the device node.
+///
+/// This can be used to poll for device events, or to issue ioctls not wrapped by this library.
+impl AsRawFd for Device {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl AsFd for Device {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
 /// A video device configured for video capture.
 pub struct VideoCaptureDevice {
     file: File,
     format: PixFormat,
+    format_adjusted: bool,
 }
 
 impl VideoCaptureDevice {
@@ -348,6 +1085,13 @@ impl VideoCaptureDevice {
         &self.format
     }
 
+    /// Returns whether the driver adjusted any of the values of the requested format.
+    ///
+    /// See [`Format::is_adjusted`] for which values are compared.
+    pub fn format_adjusted(&self) -> bool {
+        self.format_adjusted
+    }
+
     /// Requests a change to the frame interval.
     ///
     /// Returns the actual frame interval chosen by the driver.
@@ -355,37 +1099,133 @@ impl VideoCaptureDevice {
     /// Supported frame intervals depend on the pixel format and video resolution and can be
     /// enumerated with [`Device::frame_intervals`].
     pub fn set_frame_interval(&self, interval: Fract) -> io::Result<Fract> {
-        unsafe {
-            let mut parm = raw::StreamParm {
-                type_: BufType::VIDEO_CAPTURE,
-                union: raw::StreamParmUnion {
-                    capture: raw::CaptureParm {
-                        timeperframe: interval,
-                        capability: StreamParamCaps::TIMEPERFRAME,
-                        capturemode: CaptureParamFlags::empty(),
-                        extendedmode: 0,
-                        readbuffers: 0,
-                        reserved: [0; 4],
-                    },
-                },
-            };
-            raw::s_parm(self.file.as_raw_fd(), &mut parm)?;
-            Ok(parm.union.capture.timeperframe)
-        }
+        set_capture_interval(self.file.as_raw_fd(), interval)
     }
 
     /// Initializes streaming I/O mode with the given number of buffers.
     pub fn into_stream(self) -> io::Result<ReadStream> {
-        Ok(ReadStream::new(
+        self.into_stream_num_buffers(DEFAULT_BUFFER_COUNT)
+    }
+
+    /// Initializes streaming I/O mode, requesting `num_buffers` buffers.
+    ///
+    /// If the driver requires a minimum number of buffers (via the `MIN_BUFFERS_FOR_CAPTURE`
+    /// control), `num_buffers` is raised to that minimum. The driver may also allocate more or
+    /// fewer buffers than requested. Use [`ReadStream::num_buffers`] to find out how many were
+    /// allocated.
+    pub fn into_stream_num_buffers(self, num_buffers: u32) -> io::Result<ReadStream> {
+        self.into_stream_min_buffers(num_buffers, 1)
+    }
+
+    /// Initializes streaming I/O mode, requesting `num_buffers` buffers, and failing if the driver
+    /// allocates fewer than `min_buffers`.
+    ///
+    /// Like [`VideoCaptureDevice::into_stream_num_buffers`], this raises `num_buffers` to the
+    /// driver's minimum.
+    pub fn into_stream_min_buffers(
+        self,
+        num_buffers: u32,
+        min_buffers: u32,
+    ) -> io::Result<ReadStream> {
+        let num_buffers = buffer_count(&self.file, Cid::MIN_BUFFERS_FOR_CAPTURE, num_buffers)?;
+        ReadStream::new(
             self.file,
-            BufType::VIDEO_CAPTURE,
+            Format::VideoCapture(self.format),
             Memory::MMAP,
-            DEFAULT_BUFFER_COUNT,
-        )?)
+            num_buffers,
+            min_buffers,
+        )
     }
 
-    pub fn into_stream_num_buffers(self, num_buffers: u32) -> io::Result<ReadStream> {
-        ReadStream::new(self.file, BufType::VIDEO_CAPTURE, Memory::MMAP, num_buffers)
+    /// Initializes streaming I/O mode with exactly `num_buffers` buffers.
+    ///
+    /// Unlike the other `into_stream` methods, this does not adjust the buffer count. It fails if
+    /// the driver requires more buffers (via the `MIN_BUFFERS_FOR_CAPTURE` control), or allocates a
+    /// different number of buffers.
+    pub fn into_stream_exact_buffer_count(self, num_buffers: u32) -> io::Result<ReadStream> {
+        let min = driver_min_buffers(&self.file, Cid::MIN_BUFFERS_FOR_CAPTURE)?;
+        if min > num_buffers {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "driver requires at least {} buffers, but {} were requested",
+                    min, num_buffers
+                ),
+            ));
+        }
+
+        let stream = ReadStream::new(
+            self.file,
+            Format::VideoCapture(self.format),
+            Memory::MMAP,
+            num_buffers,
+            num_buffers,
+        )?;
+        if stream.num_buffers() != num_buffers {
+            return Err(io::Error::other(format!(
+                "driver allocated {} buffers, but {} were requested",
+                stream.num_buffers(),
+                num_buffers
+            )));
+        }
+        Ok(stream)
+    }
+
+    /// Initializes capture using the given I/O method.
+    ///
+    /// With [`IoMethod::Auto`], streaming I/O is used if the device supports it, and `read(2)`
+    /// otherwise.
+    pub fn into_capture_stream(self, method: IoMethod) -> io::Result<CaptureStream> {
+        match resolve_io_method(&self.file, method)? {
+            IoMethod::Read => {
+                let compressed = is_compressed(
+                    &self.file,
+                    BufType::VIDEO_CAPTURE,
+                    self.format.pixel_format(),
+                )?;
+                let size = self.format.size_image() as usize;
+                Ok(CaptureStream::Read(ReadIoStream::new(
+                    self.file,
+                    Format::VideoCapture(self.format),
+                    size,
+                    !compressed,
+                )))
+            }
+            _ => Ok(CaptureStream::Streaming(self.into_stream()?)),
+        }
+    }
+
+    /// Initializes streaming I/O mode with buffers allocated by the application
+    /// (`USERPTR`).
+    ///
+    /// Every buffer must be at least [`PixFormat::size_image`] bytes large. Some drivers also
+    /// require the buffers to be aligned (for example to whole pages), and reject them when they
+    /// are enqueued otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] if the driver does not support
+    /// `USERPTR` streaming, and of kind [`io::ErrorKind::InvalidInput`] if the buffers are too
+    /// small.
+    pub fn into_userptr_stream<B: UserptrBuffer>(
+        self,
+        buffers: Vec<B>,
+    ) -> io::Result<UserptrStream<B>> {
+        let min_size = self.format.size_image() as usize;
+        UserptrStream::new(self.file, BufType::VIDEO_CAPTURE, buffers, min_size)
+    }
+
+    /// Initializes streaming I/O mode with imported DMA buffers (`DMABUF`).
+    ///
+    /// One buffer is used per file descriptor in `fds`. Each DMA buffer must be at least
+    /// [`PixFormat::size_image`] bytes large.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] if the driver does not support
+    /// `DMABUF` streaming.
+    pub fn into_dmabuf_stream(self, fds: Vec<OwnedFd>) -> io::Result<DmabufStream> {
+        DmabufStream::new(self.file, BufType::VIDEO_CAPTURE, fds)
     }
 }
 
@@ -399,10 +1239,55 @@ impl Read for VideoCaptureDevice {
     }
 }
 
+/// A video device configured for multi-planar video capture.
+pub struct VideoCaptureMplaneDevice {
+    file: File,
+    format: PixFormatMplane,
+    format_adjusted: bool,
+}
+
+impl VideoCaptureMplaneDevice {
+    /// Returns the pixel format the driver chose for capturing.
+    ///
+    /// This may (and usually will) differ from the format passed to
+    /// [`Device::video_capture_mplane`].
+    pub fn format(&self) -> &PixFormatMplane {
+        &self.format
+    }
+
+    /// Returns whether the driver adjusted any of the values of the requested format.
+    ///
+    /// See [`VideoCaptureDevice::format_adjusted`].
+    pub fn format_adjusted(&self) -> bool {
+        self.format_adjusted
+    }
+
+    /// Initializes streaming I/O mode.
+    pub fn into_stream(self) -> io::Result<MplaneReadStream> {
+        self.into_stream_num_buffers(DEFAULT_BUFFER_COUNT)
+    }
+
+    /// Initializes streaming I/O mode, requesting `num_buffers` buffers.
+    ///
+    /// `num_buffers` is raised to the minimum required by the driver, if any. The driver may also
+    /// allocate more or fewer buffers than requested. Use [`MplaneReadStream::num_buffers`] to find
+    /// out how many were allocated.
+    pub fn into_stream_num_buffers(self, num_buffers: u32) -> io::Result<MplaneReadStream> {
+        let num_buffers = buffer_count(&self.file, Cid::MIN_BUFFERS_FOR_CAPTURE, num_buffers)?;
+        MplaneReadStream::new(
+            self.file,
+            BufType::VIDEO_CAPTURE_MPLANE,
+            self.format,
+            num_buffers,
+        )
+    }
+}
+
 /// A video device configured for video output.
 pub struct VideoOutputDevice {
     file: File,
     format: PixFormat,
+    format_adjusted: bool,
 }
 
 impl VideoOutputDevice {
@@ -411,14 +1296,73 @@ impl VideoOutputDevice {
         &self.format
     }
 
+    /// Returns whether the driver adjusted any of the values of the requested format.
+    ///
+    /// See [`VideoCaptureDevice::format_adjusted`].
+    pub fn format_adjusted(&self) -> bool {
+        self.format_adjusted
+    }
+
     /// Initializes streaming I/O mode.
     pub fn into_stream(self) -> io::Result<WriteStream> {
-        Ok(WriteStream::new(
-            self.file,
-            BufType::VIDEO_CAPTURE,
-            Memory::MMAP,
-            DEFAULT_BUFFER_COUNT,
-        )?)
+        self.into_stream_num_buffers(DEFAULT_BUFFER_COUNT)
+    }
+
+    /// Initializes streaming I/O mode, requesting `num_buffers` buffers.
+    ///
+    /// `num_buffers` is raised to the minimum required by the driver (via the
+    /// `MIN_BUFFERS_FOR_OUTPUT` control), if any. The driver may also allocate more or fewer
+    /// buffers than requested. Use [`WriteStream::num_buffers`] to find out how many were
+    /// allocated.
+    pub fn into_stream_num_buffers(self, num_buffers: u32) -> io::Result<WriteStream> {
+        let num_buffers = buffer_count(&self.file, Cid::MIN_BUFFERS_FOR_OUTPUT, num_buffers)?;
+        WriteStream::new(self.file, BufType::VIDEO_OUTPUT, Memory::MMAP, num_buffers)
+    }
+
+    /// Writes a whole frame to the device with `write(2)`.
+    ///
+    /// This only works if the device advertises the `READWRITE` capability, and requires no
+    /// buffer setup. `frame` must be exactly [`PixFormat::size_image`] bytes long. Partial writes
+    /// are continued until the whole frame has been written.
+    ///
+    /// # Errors
+    ///
+    /// If the device was opened in non-blocking mode and cannot accept a frame right now, an error
+    /// of kind [`io::ErrorKind::WouldBlock`] is returned, and no data is written. Once part of the
+    /// frame has been written, this waits until the rest can be written, even in non-blocking
+    /// mode.
+    pub fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        let size = self.format.size_image() as usize;
+        if frame.len() != size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "frame size ({} bytes) does not match the negotiated format ({} bytes)",
+                    frame.len(),
+                    size
+                ),
+            ));
+        }
+
+        let mut written = 0;
+        while written < frame.len() {
+            match self.file.write(&frame[written..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => written += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock && written != 0 => {
+                    // Don't leave a partial frame behind, wait until the rest can be written.
+                    let mut fds = [PollFd::new(self.file.as_raw_fd(), PollFlags::POLLOUT)];
+                    match poll(&mut fds, -1) {
+                        Ok(_) | Err(Errno::EINTR) => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -443,22 +1387,85 @@ impl Write for VideoOutputDevice {
 pub struct MetaCaptureDevice {
     file: File,
     format: MetaFormat,
+    format_adjusted: bool,
 }
 
 impl MetaCaptureDevice {
     /// Returns the metadata format the driver chose.
+    ///
+    /// Its [`MetaFormat::buffer_size`] is the largest amount of data the driver writes into a
+    /// single buffer. For [`PixelFormat::UVC`], a buffer can contain several metadata records.
     pub fn format(&self) -> &MetaFormat {
         &self.format
     }
 
+    /// Returns whether the driver adjusted any of the values of the requested format.
+    ///
+    /// See [`VideoCaptureDevice::format_adjusted`].
+    pub fn format_adjusted(&self) -> bool {
+        self.format_adjusted
+    }
+
     /// Initializes streaming I/O mode.
     pub fn into_stream(self) -> io::Result<ReadStream> {
-        Ok(ReadStream::new(
+        self.into_stream_num_buffers(DEFAULT_BUFFER_COUNT)
+    }
+
+    /// Initializes streaming I/O mode, requesting `num_buffers` buffers.
+    ///
+    /// The driver may allocate more or fewer buffers than requested. Use
+    /// [`ReadStream::num_buffers`] to find out how many were allocated.
+    pub fn into_stream_num_buffers(self, num_buffers: u32) -> io::Result<ReadStream> {
+        self.into_stream_min_buffers(num_buffers, 1)
+    }
+
+    /// Initializes streaming I/O mode, requesting `num_buffers` buffers, and failing if the driver
+    /// allocates fewer than `min_buffers`.
+    pub fn into_stream_min_buffers(
+        self,
+        num_buffers: u32,
+        min_buffers: u32,
+    ) -> io::Result<ReadStream> {
+        let buffer_size = self.format.buffer_size();
+        let stream = ReadStream::new(
             self.file,
-            BufType::META_CAPTURE,
+            Format::MetaCapture(self.format),
             Memory::MMAP,
-            DEFAULT_BUFFER_COUNT,
-        )?)
+            num_buffers,
+            min_buffers,
+        )?;
+        for i in 0..stream.num_buffers() {
+            let length = stream.buffer_info(i)?.length();
+            if length < buffer_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "driver allocated a {} byte metadata buffer, but the format requires {} bytes",
+                        length, buffer_size
+                    ),
+                ));
+            }
+        }
+        Ok(stream)
+    }
+
+    /// Initializes capture using the given I/O method.
+    ///
+    /// With [`IoMethod::Auto`], streaming I/O is used if the device supports it, and `read(2)`
+    /// otherwise.
+    pub fn into_capture_stream(self, method: IoMethod) -> io::Result<CaptureStream> {
+        match resolve_io_method(&self.file, method)? {
+            IoMethod::Read => {
+                let size = self.format.buffer_size() as usize;
+                Ok(CaptureStream::Read(ReadIoStream::new(
+                    self.file,
+                    Format::MetaCapture(self.format),
+                    size,
+                    false,
+                )))
+            }
+            _ => Ok(CaptureStream::Streaming(self.into_stream()?)),
+        }
     }
 }
 
@@ -472,6 +1479,49 @@ impl Read for MetaCaptureDevice {
     }
 }
 
+/// A metadata output device.
+///
+/// Created with [`Device::meta_output`].
+pub struct MetaOutputDevice {
+    file: File,
+    format: MetaFormat,
+    format_adjusted: bool,
+}
+
+impl MetaOutputDevice {
+    /// Returns the metadata format the driver chose.
+    ///
+    /// Its [`MetaFormat::buffer_size`] is the amount of data the driver expects in each buffer.
+    pub fn format(&self) -> &MetaFormat {
+        &self.format
+    }
+
+    /// Returns whether the driver adjusted any of the values of the requested format.
+    ///
+    /// See [`VideoCaptureDevice::format_adjusted`].
+    pub fn format_adjusted(&self) -> bool {
+        self.format_adjusted
+    }
+
+    /// Initializes streaming I/O mode.
+    pub fn into_stream(self) -> io::Result<WriteStream> {
+        self.into_stream_num_buffers(DEFAULT_BUFFER_COUNT)
+    }
+
+    /// Initializes streaming I/O mode, requesting `num_buffers` buffers.
+    ///
+    /// The buffers passed to [`WriteStream::enqueue`] are [`MetaFormat::buffer_size`] bytes long.
+    /// Once every buffer has been enqueued, [`WriteStream::enqueue`] dequeues a buffer the driver
+    /// has consumed, so that it can be refilled with the parameters for the next frame.
+    pub fn into_stream_num_buffers(self, num_buffers: u32) -> io::Result<WriteStream> {
+        let buffer_size = self.format.buffer_size();
+        let mut stream =
+            WriteStream::new(self.file, BufType::META_OUTPUT, Memory::MMAP, num_buffers)?;
+        stream.set_payload_size(buffer_size)?;
+        Ok(stream)
+    }
+}
+
 /// Stores generic device information.
 ///
 /// Returned by [`Device::capabilities`].
@@ -508,28 +1558,69 @@ impl Capabilities {
     ///
     /// Some capabilities might be inaccessible through the opened device node and require opening a
     /// different one.
+    ///
+    /// Capability bits that this library doesn't know about are discarded, see
+    /// [`Capabilities::raw_capabilities`].
     pub fn all_capabilities(&self) -> CapabilityFlags {
-        self.0.capabilities
+        CapabilityFlags::from_bits_truncate(self.raw_capabilities())
     }
 
     /// Returns the capabilities available through the currently opened device node.
     pub fn device_capabilities(&self) -> CapabilityFlags {
-        if self.0.capabilities.contains(CapabilityFlags::DEVICE_CAPS) {
+        CapabilityFlags::from_bits_truncate(self.raw_device_capabilities())
+    }
+
+    /// Returns the `capabilities` field as reported by the driver, including unknown bits.
+    pub fn raw_capabilities(&self) -> u32 {
+        self.0.capabilities
+    }
+
+    /// Returns the raw capabilities of the currently opened device node, including unknown bits.
+    ///
+    /// This is the `device_caps` field if the driver sets [`CapabilityFlags::DEVICE_CAPS`], and
+    /// the `capabilities` field otherwise.
+    pub fn raw_device_capabilities(&self) -> u32 {
+        if self
+            .all_capabilities()
+            .contains(CapabilityFlags::DEVICE_CAPS)
+        {
             self.0.device_caps
         } else {
-            self.0.capabilities
+            self.raw_capabilities()
         }
     }
 }
 
+/// Formats the driver, card and bus info, and the decoded capability flags, on separate lines.
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = self.0.version;
+        writeln!(f, "driver: {}", self.driver())?;
+        writeln!(f, "card: {}", self.card())?;
+        writeln!(f, "bus info: {}", self.bus_info())?;
+        writeln!(
+            f,
+            "version: {}.{}.{}",
+            version >> 16,
+            (version >> 8) & 0xff,
+            version & 0xff
+        )?;
+        writeln!(f, "capabilities: {}", self.all_capabilities())?;
+        write!(f, "device capabilities: {}", self.device_capabilities())
+    }
+}
+
 impl fmt::Debug for Capabilities {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Capabilities")
             .field("driver", &self.driver())
             .field("card", &self.card())
             .field("bus_info", &self.bus_info())
-            .field("capabilities", &self.0.capabilities)
-            .field("device_caps", &self.0.device_caps)
+            .field("capabilities", &self.all_capabilities())
+            .field(
+                "device_caps",
+                &CapabilityFlags::from_bits_truncate(self.0.device_caps),
+            )
             .finish()
     }
 }
@@ -753,3 +1844,111 @@ fn byte_array_to_str(bytes: &[u8]) -> &str {
         .expect("missing NUL terminator");
     std::str::from_utf8(&bytes[..len]).unwrap()
 }
+
+/// Resolves [`IoMethod::Auto`] to the I/O method to use for the device.
+fn resolve_io_method(file: &File, method: IoMethod) -> io::Result<IoMethod> {
+    if method != IoMethod::Auto {
+        return Ok(method);
+    }
+
+    let caps = unsafe {
+        let mut caps = MaybeUninit::uninit();
+        raw::querycap(file.as_raw_fd(), caps.as_mut_ptr())?;
+        Capabilities(caps.assume_init())
+    };
+    let caps = caps.device_capabilities();
+    if caps.contains(CapabilityFlags::STREAMING) {
+        Ok(IoMethod::Streaming)
+    } else if caps.contains(CapabilityFlags::READWRITE) {
+        Ok(IoMethod::Read)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "device supports neither streaming nor read/write I/O",
+        ))
+    }
+}
+
+fn jpeg_compression_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "device supports neither VIDIOC_G/S_JPEGCOMP nor the JPEG compression quality control",
+    )
+}
+
+/// Returns whether the driver reports `pixel_format` to be a compressed format.
+fn is_compressed(file: &File, buf_type: BufType, pixel_format: PixelFormat) -> io::Result<bool> {
+    for desc in FormatDescIter::new(file.as_fd(), buf_type) {
+        let desc = desc?;
+        if desc.pixel_format() == pixel_format {
+            return Ok(desc.flags().contains(format::FormatFlags::COMPRESSED));
+        }
+    }
+
+    Ok(false)
+}
+
+/// Reads the minimum buffer count the driver requires (`MIN_BUFFERS_FOR_CAPTURE` or
+/// `MIN_BUFFERS_FOR_OUTPUT`), returning 0 if the driver does not have the control.
+fn driver_min_buffers(file: &File, cid: Cid) -> io::Result<u32> {
+    let mut control = raw::controls::Control { id: cid, value: 0 };
+    match unsafe { raw::g_ctrl(file.as_raw_fd(), &mut control) } {
+        Ok(_) => Ok(control.value.max(0) as u32),
+        Err(Errno::EINVAL) => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Raises `num_buffers` to the minimum the driver requires.
+fn buffer_count(file: &File, cid: Cid, num_buffers: u32) -> io::Result<u32> {
+    let min = driver_min_buffers(file, cid)?;
+    if min > num_buffers {
+        log::debug!(
+            "driver requires at least {} buffers ({} requested)",
+            min,
+            num_buffers
+        );
+    }
+    Ok(num_buffers.max(min))
+}
+
+/// Returns whether `O_NONBLOCK` is set on `fd`.
+pub(crate) fn is_nonblocking(fd: BorrowedFd<'_>) -> io::Result<bool> {
+    let flags = nix::fcntl::fcntl(fd.as_raw_fd(), nix::fcntl::FcntlArg::F_GETFL)?;
+    Ok(nix::fcntl::OFlag::from_bits_truncate(flags).contains(nix::fcntl::OFlag::O_NONBLOCK))
+}
+
+fn ext_controls(which: CtrlWhich, controls: &mut [ExtControl]) -> io::Result<ExtControls> {
+    let count = u32::try_from(controls.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many controls"))?;
+    Ok(ExtControls {
+        which,
+        count,
+        error_idx: 0,
+        request_fd: 0,
+        reserved: [0; 1],
+        controls: controls.as_mut_ptr(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_capability_bits() {
+        let mut raw: raw::Capabilities = unsafe { mem::zeroed() };
+        raw.capabilities = 0x4000_0008 | CapabilityFlags::DEVICE_CAPS.bits();
+        raw.device_caps = 0x4000_0000 | CapabilityFlags::VIDEO_CAPTURE.bits();
+        let caps = Capabilities(raw);
+
+        assert_eq!(caps.all_capabilities(), CapabilityFlags::DEVICE_CAPS);
+        assert_eq!(caps.raw_capabilities(), 0xc000_0008);
+        assert_eq!(caps.device_capabilities(), CapabilityFlags::VIDEO_CAPTURE);
+        assert_eq!(caps.raw_device_capabilities(), 0x4000_0001);
+
+        let debug = format!("{:?}", caps);
+        assert!(debug.contains("capabilities: DEVICE_CAPS,"), "{}", debug);
+        assert!(debug.contains("device_caps: VIDEO_CAPTURE }"), "{}", debug);
+    }
+}
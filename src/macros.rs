@@ -12,6 +12,11 @@ macro_rules! ffi_enum {
     ) => {
         $( #[$attrs] )*
         #[derive(Clone, Copy, PartialEq, Eq)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(transparent)
+        )]
         #[repr(transparent)]
         $v struct $name(pub(crate) $native);
 
@@ -20,17 +25,25 @@ macro_rules! ffi_enum {
                 $( #[$variant_attrs] )*
                 $v const $variant: Self = Self($value);
             )+
-        }
 
-        #[allow(unreachable_patterns)]
-        impl std::fmt::Debug for $name {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            /// Returns the name of the constant matching `self`, or `None` if there is none.
+            #[allow(dead_code, unreachable_patterns)]
+            pub(crate) fn variant_name(&self) -> Option<&'static str> {
                 match *self {
                     $(
-                        Self::$variant => f.write_str(stringify!($variant)),
+                        Self::$variant => Some(stringify!($variant)),
                     )+
 
-                    _ => write!(f, "(unknown: {:#x})", self.0),
+                    _ => None,
+                }
+            }
+        }
+
+        impl std::fmt::Debug for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self.variant_name() {
+                    Some(name) => f.write_str(name),
+                    None => write!(f, "(unknown: {:#x})", self.0),
                 }
             }
         }
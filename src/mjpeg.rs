@@ -0,0 +1,512 @@
+//! Turning Motion JPEG frames into standalone JPEG images.
+//!
+//! Frames captured in the [`MJPG`][crate::format::PixelFormat::MJPG] format are usually JPEG
+//! images without a DHT (Define Huffman Table) segment, since the format specifies that the
+//! default tables from the JPEG standard are used. Many JPEG decoders reject such images. Drivers
+//! also commonly deliver the frame followed by some padding after the EOI marker.
+//!
+//! [`extract_jpeg`] fixes both, so that the result can be passed to any JPEG decoder or written
+//! to a `.jpg` file.
+//!
+//! The quality of the images produced by the driver can be configured with a [`JpegCompression`].
+
+use std::borrow::Cow;
+use std::{fmt, io, mem};
+
+use crate::raw;
+
+pub use crate::shared::JpegMarkers;
+
+const SOI: u8 = 0xD8;
+const EOI: u8 = 0xD9;
+const SOS: u8 = 0xDA;
+const DHT: u8 = 0xC4;
+const TEM: u8 = 0x01;
+const RST0: u8 = 0xD0;
+const RST7: u8 = 0xD7;
+
+/// Extracts the JPEG image from a Motion JPEG frame.
+///
+/// This locates the SOI and EOI markers, discards any data before and after them, and inserts the
+/// default Huffman tables if the image does not define any. If no tables need to be inserted, a
+/// slice of `frame` is returned without copying.
+pub fn extract_jpeg(frame: &[u8]) -> Result<Cow<'_, [u8]>, MjpegError> {
+    let start = frame
+        .windows(2)
+        .position(|w| w == [0xFF, SOI])
+        .ok_or(MjpegError::MissingSoi)?;
+    let data = &frame[start..];
+
+    let mut has_dht = false;
+    let mut first_sos = None;
+    let mut pos = 2;
+    let end = loop {
+        if data.get(pos) != Some(&0xFF) {
+            return Err(if pos >= data.len() {
+                MjpegError::MissingEoi
+            } else {
+                MjpegError::InvalidMarker {
+                    offset: start + pos,
+                }
+            });
+        }
+        let marker_pos = pos;
+        // Markers can be preceded by any number of fill bytes.
+        while data.get(pos) == Some(&0xFF) {
+            pos += 1;
+        }
+        let marker = *data.get(pos).ok_or(MjpegError::MissingEoi)?;
+        pos += 1;
+
+        match marker {
+            EOI => break pos,
+            TEM | RST0..=RST7 => continue,
+            _ => {}
+        }
+
+        let len = match data.get(pos..pos + 2) {
+            Some(&[hi, lo]) => usize::from(u16::from_be_bytes([hi, lo])),
+            _ => return Err(MjpegError::MissingEoi),
+        };
+        if len < 2 {
+            return Err(MjpegError::InvalidMarker {
+                offset: start + marker_pos,
+            });
+        }
+        pos += len;
+        if pos > data.len() {
+            return Err(MjpegError::MissingEoi);
+        }
+
+        match marker {
+            DHT if first_sos.is_none() => has_dht = true,
+            SOS => {
+                first_sos.get_or_insert(marker_pos);
+                pos = skip_entropy_coded_data(data, pos).ok_or(MjpegError::MissingEoi)?;
+            }
+            _ => {}
+        }
+    };
+
+    let jpeg = &data[..end];
+    match first_sos {
+        Some(sos) if !has_dht => {
+            let mut fixed = Vec::with_capacity(jpeg.len() + DEFAULT_DHT.len());
+            fixed.extend_from_slice(&jpeg[..sos]);
+            fixed.extend_from_slice(&DEFAULT_DHT);
+            fixed.extend_from_slice(&jpeg[sos..]);
+            Ok(Cow::Owned(fixed))
+        }
+        _ => Ok(Cow::Borrowed(jpeg)),
+    }
+}
+
+/// Returns the position of the first marker after the entropy-coded data starting at `pos`.
+///
+/// Inside entropy-coded data, `0xFF` bytes are followed by a stuffed `0x00`, and restart markers
+/// can occur. Any other marker ends the data.
+fn skip_entropy_coded_data(data: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        pos += data.get(pos..)?.iter().position(|&b| b == 0xFF)?;
+        match *data.get(pos + 1)? {
+            0x00 | RST0..=RST7 => pos += 2,
+            // Fill bytes preceding a marker.
+            0xFF => pos += 1,
+            _ => return Some(pos),
+        }
+    }
+}
+
+/// An error that occurred while extracting a JPEG image from a Motion JPEG frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MjpegError {
+    /// The frame does not contain an SOI (Start Of Image) marker.
+    MissingSoi,
+    /// The frame ended before the EOI (End Of Image) marker.
+    ///
+    /// This usually means that the frame was truncated, for example because the driver dropped
+    /// some of its data.
+    MissingEoi,
+    /// The frame contains invalid data where a marker was expected.
+    InvalidMarker {
+        /// Offset of the invalid data in the frame.
+        offset: usize,
+    },
+}
+
+impl fmt::Display for MjpegError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MjpegError::MissingSoi => f.write_str("frame does not contain a JPEG image"),
+            MjpegError::MissingEoi => f.write_str("JPEG image is truncated"),
+            MjpegError::InvalidMarker { offset } => {
+                write!(f, "invalid JPEG marker at offset {}", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MjpegError {}
+
+impl From<MjpegError> for io::Error {
+    fn from(e: MjpegError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// JPEG compression parameters of a device.
+///
+/// Read with [`Device::jpeg_compression`][crate::Device::jpeg_compression] and applied with
+/// [`Device::set_jpeg_compression`][crate::Device::set_jpeg_compression].
+#[derive(Clone)]
+pub struct JpegCompression(raw::JpegCompression);
+
+impl JpegCompression {
+    /// Maximum length of the data of an APPn or COM segment.
+    pub const MAX_SEGMENT_LEN: usize = 60;
+
+    /// Creates compression parameters with the given quality, and no APPn or COM segment.
+    ///
+    /// `quality` is clamped to at most 100.
+    pub fn new(quality: u8) -> Self {
+        let mut this = Self(unsafe { mem::zeroed() });
+        this.set_quality(quality);
+        this
+    }
+
+    pub(crate) fn from_raw(raw: raw::JpegCompression) -> Self {
+        Self(raw)
+    }
+
+    pub(crate) fn to_raw(&self) -> raw::JpegCompression {
+        self.0
+    }
+
+    /// Returns the image quality, from 0 (smallest images) to 100 (best quality).
+    pub fn quality(&self) -> u8 {
+        self.0.quality.clamp(0, 100) as u8
+    }
+
+    /// Sets the image quality, clamped to at most 100.
+    pub fn set_quality(&mut self, quality: u8) {
+        self.0.quality = quality.min(100).into();
+    }
+
+    /// Returns the number and data of the APPn segment, if any.
+    pub fn app_segment(&self) -> Option<(u8, &[u8])> {
+        let data = segment_data(&self.0.app_data, self.0.app_len);
+        (!data.is_empty()).then_some((self.0.app_n as u8, data))
+    }
+
+    /// Sets the APPn segment to include in the images, and enables [`JpegMarkers::APP`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::ErrorKind::InvalidInput`] error if `n` is larger than 15 or `data` is
+    /// longer than [`JpegCompression::MAX_SEGMENT_LEN`].
+    pub fn set_app_segment(&mut self, n: u8, data: &[u8]) -> io::Result<()> {
+        if n > 15 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid APPn segment number {}", n),
+            ));
+        }
+        self.0.app_len = set_segment_data(&mut self.0.app_data, data)?;
+        self.0.app_n = n.into();
+        self.0.jpeg_markers |= JpegMarkers::APP;
+        Ok(())
+    }
+
+    /// Returns the data of the COM (comment) segment.
+    pub fn comment(&self) -> &[u8] {
+        segment_data(&self.0.com_data, self.0.com_len)
+    }
+
+    /// Sets the COM (comment) segment to include in the images, and enables [`JpegMarkers::COM`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::ErrorKind::InvalidInput`] error if `data` is longer than
+    /// [`JpegCompression::MAX_SEGMENT_LEN`].
+    pub fn set_comment(&mut self, data: &[u8]) -> io::Result<()> {
+        self.0.com_len = set_segment_data(&mut self.0.com_data, data)?;
+        self.0.jpeg_markers |= JpegMarkers::COM;
+        Ok(())
+    }
+
+    /// Returns the markers the driver includes in the images.
+    pub fn markers(&self) -> JpegMarkers {
+        self.0.jpeg_markers
+    }
+
+    /// Sets the markers the driver should include in the images.
+    pub fn set_markers(&mut self, markers: JpegMarkers) {
+        self.0.jpeg_markers = markers;
+    }
+}
+
+fn segment_data(data: &[u8; JpegCompression::MAX_SEGMENT_LEN], len: i32) -> &[u8] {
+    &data[..len.clamp(0, data.len() as i32) as usize]
+}
+
+fn set_segment_data(
+    dest: &mut [u8; JpegCompression::MAX_SEGMENT_LEN],
+    data: &[u8],
+) -> io::Result<i32> {
+    if data.len() > dest.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "JPEG segment data is too long ({} bytes, at most {} are supported)",
+                data.len(),
+                dest.len()
+            ),
+        ));
+    }
+    dest.fill(0);
+    dest[..data.len()].copy_from_slice(data);
+    Ok(data.len() as i32)
+}
+
+impl fmt::Debug for JpegCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JpegCompression")
+            .field("quality", &self.0.quality)
+            .field("app_segment", &self.app_segment())
+            .field("comment", &self.comment())
+            .field("markers", &self.0.jpeg_markers)
+            .finish()
+    }
+}
+
+/// Number of codes of each length (1 to 16 bits), followed by the symbols, for the default
+/// Huffman tables (ITU T.81, Annex K.3).
+const DC_LUMINANCE: ([u8; 16], [u8; 12]) = (
+    [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0],
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+);
+const DC_CHROMINANCE: ([u8; 16], [u8; 12]) = (
+    [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+);
+#[rustfmt::skip]
+const AC_LUMINANCE: ([u8; 16], [u8; 162]) = (
+    [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7D],
+    [
+        0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+        0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xA1, 0x08, 0x23, 0x42, 0xB1, 0xC1, 0x15, 0x52, 0xD1, 0xF0,
+        0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0A, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x25, 0x26, 0x27, 0x28,
+        0x29, 0x2A, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+        0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+        0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+        0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5, 0xA6, 0xA7,
+        0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3, 0xC4, 0xC5,
+        0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA, 0xE1, 0xE2,
+        0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+        0xF9, 0xFA,
+    ],
+);
+#[rustfmt::skip]
+const AC_CHROMINANCE: ([u8; 16], [u8; 162]) = (
+    [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77],
+    [
+        0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+        0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xA1, 0xB1, 0xC1, 0x09, 0x23, 0x33, 0x52, 0xF0,
+        0x15, 0x62, 0x72, 0xD1, 0x0A, 0x16, 0x24, 0x34, 0xE1, 0x25, 0xF1, 0x17, 0x18, 0x19, 0x1A, 0x26,
+        0x27, 0x28, 0x29, 0x2A, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+        0x49, 0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+        0x69, 0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+        0x88, 0x89, 0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5,
+        0xA6, 0xA7, 0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3,
+        0xC4, 0xC5, 0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA,
+        0xE2, 0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+        0xF9, 0xFA,
+    ],
+);
+
+const DHT_LEN: usize = 2 + 4 * (1 + 16) + 2 * 12 + 2 * 162;
+
+/// A DHT segment defining the default Huffman tables, with the luminance tables in slot 0 and the
+/// chrominance tables in slot 1.
+static DEFAULT_DHT: [u8; 2 + DHT_LEN] = default_dht();
+
+const fn default_dht() -> [u8; 2 + DHT_LEN] {
+    const fn table<const N: usize>(
+        out: &mut [u8; 2 + DHT_LEN],
+        mut pos: usize,
+        class_and_id: u8,
+        table: &([u8; 16], [u8; N]),
+    ) -> usize {
+        out[pos] = class_and_id;
+        pos += 1;
+        let mut i = 0;
+        while i < 16 {
+            out[pos] = table.0[i];
+            pos += 1;
+            i += 1;
+        }
+        let mut i = 0;
+        while i < N {
+            out[pos] = table.1[i];
+            pos += 1;
+            i += 1;
+        }
+        pos
+    }
+
+    let mut out = [0; 2 + DHT_LEN];
+    out[0] = 0xFF;
+    out[1] = DHT;
+    out[2] = (DHT_LEN >> 8) as u8;
+    out[3] = DHT_LEN as u8;
+    let pos = table(&mut out, 4, 0x00, &DC_LUMINANCE);
+    let pos = table(&mut out, pos, 0x10, &AC_LUMINANCE);
+    let pos = table(&mut out, pos, 0x01, &DC_CHROMINANCE);
+    let pos = table(&mut out, pos, 0x11, &AC_CHROMINANCE);
+    assert!(pos == out.len());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An 8x8 greyscale baseline JPEG with a single block of mid-grey, using the default Huffman
+    /// tables, but without a DHT segment (like Motion JPEG frames from UVC cameras).
+    #[rustfmt::skip]
+    const NO_DHT: &[u8] = &[
+        0xFF, 0xD8,
+        // DQT: table 0, all ones
+        0xFF, 0xDB, 0x00, 0x43, 0x00,
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+        // SOF0: 8 bits, 8x8, 1 component (id 1, 1x1 sampling, quantization table 0)
+        0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x08, 0x00, 0x08, 0x01, 0x01, 0x11, 0x00,
+        // SOS: 1 component (id 1, Huffman tables 0/0), spectral selection 0-63
+        0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00,
+        // DC difference 0 (`00`), EOB (`1010`), padded with 1 bits
+        0x2B,
+        0xFF, 0xD9,
+    ];
+
+    fn with_default_dht(jpeg: &[u8]) -> Vec<u8> {
+        let sos = jpeg.windows(2).position(|w| w == [0xFF, SOS]).unwrap();
+        [&jpeg[..sos], &DEFAULT_DHT[..], &jpeg[sos..]].concat()
+    }
+
+    #[test]
+    fn default_tables() {
+        for (bits, len) in [
+            (DC_LUMINANCE.0, DC_LUMINANCE.1.len()),
+            (DC_CHROMINANCE.0, DC_CHROMINANCE.1.len()),
+            (AC_LUMINANCE.0, AC_LUMINANCE.1.len()),
+            (AC_CHROMINANCE.0, AC_CHROMINANCE.1.len()),
+        ] {
+            assert_eq!(bits.iter().map(|&n| usize::from(n)).sum::<usize>(), len);
+        }
+        assert_eq!(DEFAULT_DHT[..4], [0xFF, 0xC4, 0x01, 0xA2]);
+    }
+
+    #[test]
+    fn inserts_missing_tables() {
+        let mut frame = NO_DHT.to_vec();
+        // Padding after the image.
+        frame.extend_from_slice(&[0; 100]);
+
+        let jpeg = extract_jpeg(&frame).unwrap();
+        assert!(matches!(jpeg, Cow::Owned(_)));
+        assert_eq!(&*jpeg, &with_default_dht(NO_DHT)[..]);
+    }
+
+    #[test]
+    fn camera_frame() {
+        // A 16x8 YUV 4:2:2 frame laid out like those of UVC cameras: an AVI1 APP0 segment, no DHT
+        // segment, and zero padding up to the end of the buffer.
+        const FRAME: &[u8] = include_bytes!("../tests/data/no-dht.mjpg");
+        const IMAGE_LEN: usize = 192;
+
+        let jpeg = extract_jpeg(FRAME).unwrap();
+        assert_eq!(jpeg.len(), IMAGE_LEN + DEFAULT_DHT.len());
+        assert_eq!(&*jpeg, &with_default_dht(&FRAME[..IMAGE_LEN])[..]);
+
+        // The result is a complete image, so extracting it again doesn't change it.
+        let again = extract_jpeg(&jpeg).unwrap();
+        assert!(matches!(again, Cow::Borrowed(_)));
+        assert_eq!(again, jpeg);
+    }
+
+    #[test]
+    fn borrows_complete_images() {
+        let complete = with_default_dht(NO_DHT);
+        let mut frame = vec![0x00, 0x12];
+        frame.extend_from_slice(&complete);
+        frame.extend_from_slice(&[0xFF, 0x00, 0x00]);
+
+        let jpeg = extract_jpeg(&frame).unwrap();
+        assert!(matches!(jpeg, Cow::Borrowed(_)));
+        assert_eq!(&*jpeg, &complete[..]);
+    }
+
+    #[test]
+    fn entropy_coded_data() {
+        // Stuffed bytes and restart markers do not end the scan, but fill bytes before the EOI
+        // are skipped.
+        let mut frame = NO_DHT[..NO_DHT.len() - 3].to_vec();
+        frame.extend_from_slice(&[0xFF, 0x00, 0x12, 0xFF, 0xD0, 0x34, 0xFF, 0xFF, 0xD9, 0x55]);
+
+        let jpeg = extract_jpeg(&frame).unwrap();
+        assert!(jpeg.ends_with(&[0x34, 0xFF, 0xFF, 0xD9]));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(extract_jpeg(&[]), Err(MjpegError::MissingSoi));
+        assert_eq!(extract_jpeg(&[0x00; 16]), Err(MjpegError::MissingSoi));
+        assert_eq!(
+            extract_jpeg(&NO_DHT[..NO_DHT.len() - 2]),
+            Err(MjpegError::MissingEoi)
+        );
+        assert_eq!(
+            extract_jpeg(&NO_DHT[..NO_DHT.len() - 20]),
+            Err(MjpegError::MissingEoi)
+        );
+        assert_eq!(
+            extract_jpeg(&[0xFF, 0xD8, 0x12, 0x34]),
+            Err(MjpegError::InvalidMarker { offset: 2 })
+        );
+
+        let error = io::Error::from(MjpegError::MissingEoi);
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn jpeg_compression_params() {
+        let mut params = JpegCompression::new(150);
+        assert_eq!(params.quality(), 100);
+        assert_eq!(params.app_segment(), None);
+        assert_eq!(params.comment(), b"");
+        assert_eq!(params.markers(), JpegMarkers::empty());
+
+        params.set_quality(75);
+        params.set_app_segment(14, b"Adobe").unwrap();
+        params.set_comment(b"frame").unwrap();
+        assert_eq!(params.quality(), 75);
+        assert_eq!(params.app_segment(), Some((14, &b"Adobe"[..])));
+        assert_eq!(params.comment(), b"frame");
+        assert_eq!(params.markers(), JpegMarkers::APP | JpegMarkers::COM);
+
+        let err = params.set_app_segment(16, b"").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = params.set_comment(&[0; 61]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(params.comment(), b"frame");
+
+        // Lengths reported by the driver are not trusted.
+        let mut raw = params.to_raw();
+        raw.com_len = 1000;
+        assert_eq!(JpegCompression::from_raw(raw).comment().len(), 60);
+    }
+}
@@ -0,0 +1,131 @@
+//! Options for opening V4L2 device nodes.
+
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::prelude::*;
+use std::path::Path;
+
+use nix::{
+    fcntl::{fcntl, FcntlArg, OFlag},
+    libc,
+};
+
+/// Whether a device node is opened for reading, writing, or both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccessMode {
+    /// Opens the node with `O_RDWR`. This is what [`Device::open`][crate::Device::open] uses.
+    #[default]
+    ReadWrite,
+    /// Opens the node with `O_RDONLY`.
+    ///
+    /// Buffers of streams created from a read-only device are mapped without write access.
+    ReadOnly,
+    /// Opens the node with `O_WRONLY`.
+    ///
+    /// Some drivers of output-only devices reject `O_RDWR`. The kernel does not allow memory
+    /// mapping write-only files though, so only `write(2)` based output via
+    /// [`VideoOutputDevice`][crate::VideoOutputDevice] and `USERPTR` or `DMABUF` streaming work
+    /// with such a device.
+    WriteOnly,
+}
+
+/// Options for [`Device::open_with`][crate::Device::open_with].
+///
+/// The default options open the device for reading and writing, in blocking mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OpenOptions {
+    access: AccessMode,
+    non_blocking: bool,
+}
+
+impl OpenOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the access mode to open the device with.
+    pub fn with_access(mut self, access: AccessMode) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Sets whether to open the device with `O_NONBLOCK`.
+    ///
+    /// Some drivers block in `open(2)` until the hardware has powered up, which this avoids as
+    /// well. See [`Device::open_non_blocking`][crate::Device::open_non_blocking] for how
+    /// non-blocking devices behave afterwards.
+    pub fn with_non_blocking(mut self, non_blocking: bool) -> Self {
+        self.non_blocking = non_blocking;
+        self
+    }
+
+    /// Returns the access mode.
+    pub fn access(&self) -> AccessMode {
+        self.access
+    }
+
+    /// Returns whether the device is opened with `O_NONBLOCK`.
+    pub fn is_non_blocking(&self) -> bool {
+        self.non_blocking
+    }
+
+    pub(crate) fn open(&self, path: &Path) -> io::Result<File> {
+        let mut options = fs::OpenOptions::new();
+        match self.access {
+            AccessMode::ReadWrite => options.read(true).write(true),
+            AccessMode::ReadOnly => options.read(true),
+            AccessMode::WriteOnly => options.write(true),
+        };
+        if self.non_blocking {
+            options.custom_flags(libc::O_NONBLOCK);
+        }
+        options.open(path)
+    }
+
+    /// Determines the options an already open file descriptor corresponds to.
+    pub(crate) fn from_fd(fd: BorrowedFd<'_>) -> io::Result<Self> {
+        let flags = fcntl(fd.as_raw_fd(), FcntlArg::F_GETFL)?;
+        Ok(Self {
+            access: AccessMode::from_flags(flags),
+            non_blocking: OFlag::from_bits_truncate(flags).contains(OFlag::O_NONBLOCK),
+        })
+    }
+}
+
+impl AccessMode {
+    fn from_flags(flags: libc::c_int) -> Self {
+        match flags & libc::O_ACCMODE {
+            libc::O_RDONLY => Self::ReadOnly,
+            libc::O_WRONLY => Self::WriteOnly,
+            _ => Self::ReadWrite,
+        }
+    }
+}
+
+/// Returns the access mode `fd` was opened with.
+pub(crate) fn access_mode(fd: RawFd) -> io::Result<AccessMode> {
+    Ok(AccessMode::from_flags(fcntl(fd, FcntlArg::F_GETFL)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_fd() {
+        for access in [
+            AccessMode::ReadWrite,
+            AccessMode::ReadOnly,
+            AccessMode::WriteOnly,
+        ] {
+            for non_blocking in [false, true] {
+                let options = OpenOptions::new()
+                    .with_access(access)
+                    .with_non_blocking(non_blocking);
+                let file = options.open(Path::new("/dev/null")).unwrap();
+                assert_eq!(OpenOptions::from_fd(file.as_fd()).unwrap(), options);
+            }
+        }
+    }
+}
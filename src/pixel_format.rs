@@ -1,9 +1,10 @@
 use std::fmt;
+use std::str::FromStr;
 
 /// Four character code (fourcc) defining the encoding of pixel data in an image buffer.
 ///
 /// fourcc codes are documented on <https://www.fourcc.org/>.
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(transparent)]
 pub struct PixelFormat(u32);
 
@@ -36,6 +37,15 @@ impl PixelFormat {
     /// Same as **`raw `**.
     pub const RGB3: Self = f(b"RGB3");
 
+    /// Same as [`Self::BGR3`] (the kernel's name for the format).
+    pub const BGR24: Self = Self::BGR3;
+
+    /// Same as [`Self::RGB3`] (the kernel's name for the format).
+    pub const RGB24: Self = Self::RGB3;
+
+    /// **`RGBP`**: `gggbbbbb rrrrrggg` (16 bits per pixel, little-endian)
+    pub const RGB565: Self = f(b"RGBP");
+
     /// **`AR24`**: `bbbbbbbb gggggggg rrrrrrrr aaaaaaaa`
     pub const ABGR32: Self = f(b"AR24");
 
@@ -90,11 +100,135 @@ impl PixelFormat {
     /// pixel's Y value, and `YYYYYYYY` is the right pixel's Y value.
     pub const YUYV: Self = f(b"YUYV");
 
+    /// **`YVYU`**: `yyyyyyyy vvvvvvvv YYYYYYYY uuuuuuuu`
+    ///
+    /// Like [`Self::YUYV`], with the chroma samples swapped.
+    pub const YVYU: Self = f(b"YVYU");
+
+    /// **`UYVY`**: `uuuuuuuu yyyyyyyy vvvvvvvv YYYYYYYY`
+    ///
+    /// Like [`Self::YUYV`], with the chroma samples first.
+    pub const UYVY: Self = f(b"UYVY");
+
+    /// **`VYUY`**: `vvvvvvvv yyyyyyyy uuuuuuuu YYYYYYYY`
+    pub const VYUY: Self = f(b"VYUY");
+
+    /// **`NV12`**: Semi-planar YUV/YCbCr data with 4:2:0 chroma subsampling.
+    ///
+    /// A plane of Y samples (one byte per pixel) is followed by a plane of interleaved U and V
+    /// samples, each shared by a 2x2 block of pixels.
+    pub const NV12: Self = f(b"NV12");
+
+    /// **`NV21`**: Like [`Self::NV12`], with the V sample before the U sample.
+    pub const NV21: Self = f(b"NV21");
+
+    /// **`NV16`**: Like [`Self::NV12`], but with 4:2:2 chroma subsampling (chroma samples are
+    /// shared by 2 horizontally neighboring pixels).
+    pub const NV16: Self = f(b"NV16");
+
+    /// **`NV61`**: Like [`Self::NV16`], with the V sample before the U sample.
+    pub const NV61: Self = f(b"NV61");
+
+    /// **`NM12`**: Like [`Self::NV12`], but the planes are stored in separate memory planes of a
+    /// multi-planar buffer.
+    pub const NV12M: Self = f(b"NM12");
+
+    /// **`YU12`**: Planar YUV/YCbCr data with 4:2:0 chroma subsampling.
+    ///
+    /// A plane of Y samples is followed by a plane of U samples and a plane of V samples, each
+    /// with a quarter of the resolution of the Y plane. Also known as I420.
+    pub const YUV420: Self = f(b"YU12");
+
+    /// **`YV12`**: Like [`Self::YUV420`], with the V plane before the U plane.
+    pub const YVU420: Self = f(b"YV12");
+
+    /// **`422P`**: Planar YUV/YCbCr data with 4:2:2 chroma subsampling.
+    pub const YUV422P: Self = f(b"422P");
+
+    /// **`GREY`**: `yyyyyyyy`
+    ///
+    /// Luminance only, 8 bits per pixel.
+    pub const GREY: Self = f(b"GREY");
+
+    /// **`Y10 `**: Luminance only, 10 bits stored in the low bits of a little-endian 16-bit word.
+    pub const Y10: Self = f(b"Y10 ");
+
+    /// **`Y12 `**: Luminance only, 12 bits stored in the low bits of a little-endian 16-bit word.
+    pub const Y12: Self = f(b"Y12 ");
+
+    /// **`Y16 `**: Luminance only, 16 bits per pixel (little-endian).
+    pub const Y16: Self = f(b"Y16 ");
+
+    /// **`BA81`**: 8-bit Bayer data with a `BGBG.. GRGR..` pattern.
+    pub const SBGGR8: Self = f(b"BA81");
+
+    /// **`GBRG`**: 8-bit Bayer data with a `GBGB.. RGRG..` pattern.
+    pub const SGBRG8: Self = f(b"GBRG");
+
+    /// **`GRBG`**: 8-bit Bayer data with a `GRGR.. BGBG..` pattern.
+    pub const SGRBG8: Self = f(b"GRBG");
+
+    /// **`RGGB`**: 8-bit Bayer data with a `RGRG.. GBGB..` pattern.
+    pub const SRGGB8: Self = f(b"RGGB");
+
+    /// **`BG10`**: 10-bit Bayer data (`BGBG.. GRGR..`), stored in 16-bit little-endian words.
+    pub const SBGGR10: Self = f(b"BG10");
+
+    /// **`GB10`**: 10-bit Bayer data (`GBGB.. RGRG..`), stored in 16-bit little-endian words.
+    pub const SGBRG10: Self = f(b"GB10");
+
+    /// **`BA10`**: 10-bit Bayer data (`GRGR.. BGBG..`), stored in 16-bit little-endian words.
+    pub const SGRBG10: Self = f(b"BA10");
+
+    /// **`RG10`**: 10-bit Bayer data (`RGRG.. GBGB..`), stored in 16-bit little-endian words.
+    pub const SRGGB10: Self = f(b"RG10");
+
+    /// **`BG12`**: 12-bit Bayer data (`BGBG.. GRGR..`), stored in 16-bit little-endian words.
+    pub const SBGGR12: Self = f(b"BG12");
+
+    /// **`GB12`**: 12-bit Bayer data (`GBGB.. RGRG..`), stored in 16-bit little-endian words.
+    pub const SGBRG12: Self = f(b"GB12");
+
+    /// **`BA12`**: 12-bit Bayer data (`GRGR.. BGBG..`), stored in 16-bit little-endian words.
+    pub const SGRBG12: Self = f(b"BA12");
+
+    /// **`RG12`**: 12-bit Bayer data (`RGRG.. GBGB..`), stored in 16-bit little-endian words.
+    pub const SRGGB12: Self = f(b"RG12");
+
+    /// **`pBAA`**: 10-bit Bayer data (`BGBG.. GRGR..`), packed as in MIPI CSI-2: every 4 pixels
+    /// are stored in 5 bytes, the high 8 bits of each pixel followed by a byte with the low 2 bits
+    /// of all 4.
+    pub const SBGGR10P: Self = f(b"pBAA");
+
+    /// **`pGAA`**: Packed 10-bit Bayer data (`GBGB.. RGRG..`), see [`PixelFormat::SBGGR10P`].
+    pub const SGBRG10P: Self = f(b"pGAA");
+
+    /// **`pgAA`**: Packed 10-bit Bayer data (`GRGR.. BGBG..`), see [`PixelFormat::SBGGR10P`].
+    pub const SGRBG10P: Self = f(b"pgAA");
+
+    /// **`pRAA`**: Packed 10-bit Bayer data (`RGRG.. GBGB..`), see [`PixelFormat::SBGGR10P`].
+    pub const SRGGB10P: Self = f(b"pRAA");
+
+    /// **`pBCC`**: 12-bit Bayer data (`BGBG.. GRGR..`), packed as in MIPI CSI-2: every 2 pixels
+    /// are stored in 3 bytes, the high 8 bits of each pixel followed by a byte with the low 4 bits
+    /// of both.
+    pub const SBGGR12P: Self = f(b"pBCC");
+
+    /// **`pGCC`**: Packed 12-bit Bayer data (`GBGB.. RGRG..`), see [`PixelFormat::SBGGR12P`].
+    pub const SGBRG12P: Self = f(b"pGCC");
+
+    /// **`pgCC`**: Packed 12-bit Bayer data (`GRGR.. BGBG..`), see [`PixelFormat::SBGGR12P`].
+    pub const SGRBG12P: Self = f(b"pgCC");
+
+    /// **`pRCC`**: Packed 12-bit Bayer data (`RGRG.. GBGB..`), see [`PixelFormat::SBGGR12P`].
+    pub const SRGGB12P: Self = f(b"pRCC");
+
     /// **`MJPG`**: Motion JPEG, a sequence of JPEG images with omitted huffman tables.
     ///
     /// The transmitted JPEG images lack the "DHT" frame (Define Huffman Table), and instead use a
-    /// predefined one. Most common JPEG decoders will handle this fine and don't need any extra
-    /// preprocessing.
+    /// predefined one. Frames may also be followed by padding. Some JPEG decoders reject such
+    /// frames, use [`mjpeg::extract_jpeg`][crate::mjpeg::extract_jpeg] to turn them into regular
+    /// JPEG images.
     pub const MJPG: Self = f(b"MJPG");
 
     /// **`JPEG`**: Data is a sequence of regular JFIF JPEG still images.
@@ -102,12 +236,234 @@ impl PixelFormat {
     /// Images can be decoded with any off-the-shelf JPEG decoder, no preprocessing is needed.
     pub const JPEG: Self = f(b"JPEG");
 
+    /// **`H264`**: H.264 (AVC) elementary stream with start codes.
+    pub const H264: Self = f(b"H264");
+
+    /// **`HEVC`**: H.265 (HEVC) elementary stream with start codes.
+    pub const HEVC: Self = f(b"HEVC");
+
     /// **`UVCH`**: UVC payload header metadata.
     ///
     /// Data is a stream of [`UvcMetadata`][crate::uvc::UvcMetadata] structures.
     pub const UVC: Self = f(b"UVCH");
 }
 
+/// How the samples of an uncompressed pixel format are laid out in memory.
+enum Layout {
+    /// All samples are stored in a single plane, with `bits` bits per pixel. Pixels are grouped
+    /// into blocks of `block` pixels, which share chroma samples (eg. 2 for YUYV).
+    Packed {
+        bits: u32,
+        block: u32,
+        subsampling: Option<(u32, u32)>,
+    },
+    /// A plane of 8-bit luma samples, followed by `planes - 1` planes containing two 8-bit chroma
+    /// samples per block of `subsampling` pixels (either interleaved or in separate planes).
+    Planar {
+        planes: u32,
+        subsampling: (u32, u32),
+    },
+    /// The data is compressed, so its size depends on the content.
+    Compressed,
+}
+
+const fn packed(bits: u32) -> Option<Layout> {
+    Some(Layout::Packed {
+        bits,
+        block: 1,
+        subsampling: None,
+    })
+}
+
+/// Pixels packed into blocks of `block` pixels with `bits` bits each (eg. MIPI CSI-2 RAW10).
+const fn packed_blocks(bits: u32, block: u32) -> Option<Layout> {
+    Some(Layout::Packed {
+        bits,
+        block,
+        subsampling: None,
+    })
+}
+
+const fn yuv422_packed() -> Option<Layout> {
+    Some(Layout::Packed {
+        bits: 16,
+        block: 2,
+        subsampling: Some((2, 1)),
+    })
+}
+
+const fn planar(planes: u32, subsampling: (u32, u32)) -> Option<Layout> {
+    Some(Layout::Planar {
+        planes,
+        subsampling,
+    })
+}
+
+/// Pixel format properties.
+impl PixelFormat {
+    /// Returns the memory layout of the format, or `None` if it is not known.
+    fn layout(self) -> Option<Layout> {
+        match self {
+            Self::GREY | Self::SBGGR8 | Self::SGBRG8 | Self::SGRBG8 | Self::SRGGB8 => packed(8),
+            Self::Y10
+            | Self::Y12
+            | Self::Y16
+            | Self::SBGGR10
+            | Self::SGBRG10
+            | Self::SGRBG10
+            | Self::SRGGB10
+            | Self::SBGGR12
+            | Self::SGBRG12
+            | Self::SGRBG12
+            | Self::SRGGB12
+            | Self::RGB565 => packed(16),
+            Self::SBGGR10P | Self::SGBRG10P | Self::SGRBG10P | Self::SRGGB10P => {
+                packed_blocks(10, 4)
+            }
+            Self::SBGGR12P | Self::SGBRG12P | Self::SGRBG12P | Self::SRGGB12P => {
+                packed_blocks(12, 2)
+            }
+            Self::BGR3 | Self::RGB3 => packed(24),
+            Self::ABGR32
+            | Self::XBGR32
+            | Self::BGRA32
+            | Self::BGRX32
+            | Self::RGBA32
+            | Self::RGBX32
+            | Self::ARGB32
+            | Self::XRGB32
+            | Self::BGR32
+            | Self::RGB32 => packed(32),
+            Self::YUYV | Self::YVYU | Self::UYVY | Self::VYUY => yuv422_packed(),
+            Self::NV12 | Self::NV21 | Self::NV12M => planar(2, (2, 2)),
+            Self::NV16 | Self::NV61 => planar(2, (2, 1)),
+            Self::YUV420 | Self::YVU420 => planar(3, (2, 2)),
+            Self::YUV422P => planar(3, (2, 1)),
+            Self::MJPG | Self::JPEG | Self::H264 | Self::HEVC => Some(Layout::Compressed),
+            _ => None,
+        }
+    }
+
+    /// Returns the average number of bits needed to store a pixel, across all planes.
+    ///
+    /// For example, this is 16 for [`PixelFormat::YUYV`] and 12 for [`PixelFormat::NV12`]. Returns
+    /// `None` for compressed formats and for formats this library does not know.
+    pub fn bits_per_pixel(self) -> Option<u32> {
+        match self.layout()? {
+            Layout::Packed { bits, .. } => Some(bits),
+            Layout::Planar {
+                subsampling: (h, v),
+                ..
+            } => Some(8 + 16 / (h * v)),
+            Layout::Compressed => None,
+        }
+    }
+
+    /// Returns the number of color planes of the format.
+    ///
+    /// This is 1 for packed formats, 2 for semi-planar formats like [`PixelFormat::NV12`], and 3
+    /// for fully planar formats like [`PixelFormat::YUV420`]. Compressed and unknown formats are
+    /// treated as having a single plane.
+    ///
+    /// Note that this is the number of *color* planes. A multi-planar buffer can store several
+    /// color planes in a single memory plane (see
+    /// [`PixFormatMplane::num_planes`][crate::format::PixFormatMplane::num_planes]).
+    pub fn plane_count(self) -> u32 {
+        match self.layout() {
+            Some(Layout::Planar { planes, .. }) => planes,
+            _ => 1,
+        }
+    }
+
+    /// Returns the horizontal and vertical chroma subsampling factors of a YUV format.
+    ///
+    /// For example, this is `(2, 1)` for 4:2:2 formats and `(2, 2)` for 4:2:0 formats. Returns
+    /// `None` for formats without separate chroma samples (like RGB or greyscale formats), as well
+    /// as for compressed and unknown formats.
+    pub fn chroma_subsampling(self) -> Option<(u32, u32)> {
+        match self.layout()? {
+            Layout::Packed { subsampling, .. } => subsampling,
+            Layout::Planar { subsampling, .. } => Some(subsampling),
+            Layout::Compressed => None,
+        }
+    }
+
+    /// Returns whether the format is compressed.
+    ///
+    /// The size of a compressed frame depends on its content, so the image size reported by the
+    /// driver is only an upper bound.
+    pub fn is_compressed(self) -> bool {
+        matches!(self.layout(), Some(Layout::Compressed))
+    }
+
+    /// Returns whether this is a known RGB or raw Bayer format.
+    ///
+    /// These formats default to full range quantization, see
+    /// [`Quantization::default_for`][crate::format::Quantization::default_for].
+    pub fn is_rgb(self) -> bool {
+        matches!(
+            self,
+            Self::BGR3
+                | Self::RGB3
+                | Self::RGB565
+                | Self::ABGR32
+                | Self::XBGR32
+                | Self::BGRA32
+                | Self::BGRX32
+                | Self::RGBA32
+                | Self::RGBX32
+                | Self::ARGB32
+                | Self::XRGB32
+                | Self::BGR32
+                | Self::RGB32
+                | Self::SBGGR8
+                | Self::SGBRG8
+                | Self::SGRBG8
+                | Self::SRGGB8
+                | Self::SBGGR10
+                | Self::SGBRG10
+                | Self::SGRBG10
+                | Self::SRGGB10
+                | Self::SBGGR12
+                | Self::SGBRG12
+                | Self::SGRBG12
+                | Self::SRGGB12
+                | Self::SBGGR10P
+                | Self::SGBRG10P
+                | Self::SGRBG10P
+                | Self::SRGGB10P
+                | Self::SBGGR12P
+                | Self::SGBRG12P
+                | Self::SGRBG12P
+                | Self::SRGGB12P
+        )
+    }
+
+    /// Returns the number of bytes of a `width`x`height` frame, without any padding between lines
+    /// or planes.
+    ///
+    /// Returns `None` for compressed and unknown formats, or if the size does not fit in a
+    /// `usize`.
+    pub fn frame_size(self, width: u32, height: u32) -> Option<usize> {
+        let (width, height) = (u64::from(width), u64::from(height));
+        let size = match self.layout()? {
+            Layout::Packed { bits, block, .. } => {
+                let line = width.div_ceil(u64::from(block)) * u64::from(block * bits) / 8;
+                line.checked_mul(height)?
+            }
+            Layout::Planar {
+                subsampling: (h, v),
+                ..
+            } => {
+                let chroma = width.div_ceil(u64::from(h)) * height.div_ceil(u64::from(v));
+                (width * height).checked_add(2 * chroma)?
+            }
+            Layout::Compressed => return None,
+        };
+        size.try_into().ok()
+    }
+}
+
 impl fmt::Display for PixelFormat {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let bytes = self.0.to_le_bytes();
@@ -122,6 +478,37 @@ impl fmt::Debug for PixelFormat {
     }
 }
 
+/// Parses a *fourcc* code like `"YUYV"`.
+///
+/// Codes shorter than 4 characters are padded with spaces, so `"Y16"` parses as **`Y16 `**. This
+/// accepts any code, not just those with a named constant, so the [`Display`][fmt::Display]
+/// output of any printable format parses back to the same format.
+impl FromStr for PixelFormat {
+    type Err = ParsePixelFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.len() > 4 || !s.bytes().all(|b| b.is_ascii_graphic() || b == b' ') {
+            return Err(ParsePixelFormatError(()));
+        }
+
+        let mut fourcc = [b' '; 4];
+        fourcc[..s.len()].copy_from_slice(s.as_bytes());
+        Ok(Self::from_fourcc(fourcc))
+    }
+}
+
+/// The error returned when parsing a [`PixelFormat`] fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePixelFormatError(());
+
+impl fmt::Display for ParsePixelFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("fourcc codes must consist of 1 to 4 printable ASCII characters")
+    }
+}
+
+impl std::error::Error for ParsePixelFormatError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,4 +517,77 @@ mod tests {
     fn simple() {
         assert_eq!(PixelFormat::RGBA32.to_string(), "AB24");
     }
+
+    #[test]
+    fn format_properties() {
+        // (format, bits per pixel, planes, subsampling, 640x480 frame size)
+        let table = [
+            (PixelFormat::YUYV, Some(16), 1, Some((2, 1)), Some(614400)),
+            (PixelFormat::UYVY, Some(16), 1, Some((2, 1)), Some(614400)),
+            (PixelFormat::NV12, Some(12), 2, Some((2, 2)), Some(460800)),
+            (PixelFormat::NV16, Some(16), 2, Some((2, 1)), Some(614400)),
+            (PixelFormat::YUV420, Some(12), 3, Some((2, 2)), Some(460800)),
+            (
+                PixelFormat::YUV422P,
+                Some(16),
+                3,
+                Some((2, 1)),
+                Some(614400),
+            ),
+            (PixelFormat::GREY, Some(8), 1, None, Some(307200)),
+            (PixelFormat::Y10, Some(16), 1, None, Some(614400)),
+            (PixelFormat::SRGGB8, Some(8), 1, None, Some(307200)),
+            (PixelFormat::SRGGB12, Some(16), 1, None, Some(614400)),
+            (PixelFormat::SRGGB10P, Some(10), 1, None, Some(384000)),
+            (PixelFormat::SGRBG12P, Some(12), 1, None, Some(460800)),
+            (PixelFormat::RGB565, Some(16), 1, None, Some(614400)),
+            (PixelFormat::RGB24, Some(24), 1, None, Some(921600)),
+            (PixelFormat::XRGB32, Some(32), 1, None, Some(1228800)),
+            (PixelFormat::MJPG, None, 1, None, None),
+            (PixelFormat::H264, None, 1, None, None),
+            (PixelFormat::UVC, None, 1, None, None),
+        ];
+        for (format, bpp, planes, subsampling, size) in table {
+            assert_eq!(format.bits_per_pixel(), bpp, "{}", format);
+            assert_eq!(format.plane_count(), planes, "{}", format);
+            assert_eq!(format.chroma_subsampling(), subsampling, "{}", format);
+            assert_eq!(format.frame_size(640, 480), size, "{}", format);
+        }
+
+        assert!(PixelFormat::MJPG.is_compressed());
+        assert!(PixelFormat::HEVC.is_compressed());
+        assert!(!PixelFormat::YUYV.is_compressed());
+        assert!(!PixelFormat::from_fourcc(*b"ZZ42").is_compressed());
+    }
+
+    #[test]
+    fn odd_frame_sizes() {
+        // Chroma samples are rounded up, like the kernel does.
+        assert_eq!(PixelFormat::YUYV.frame_size(3, 1), Some(8));
+        assert_eq!(PixelFormat::NV12.frame_size(3, 3), Some(9 + 8));
+        assert_eq!(PixelFormat::YUV420.frame_size(1, 1), Some(3));
+        assert_eq!(PixelFormat::SBGGR10P.frame_size(6, 1), Some(10));
+        assert_eq!(PixelFormat::SBGGR12P.frame_size(3, 2), Some(12));
+        assert_eq!(PixelFormat::XRGB32.frame_size(u32::MAX, u32::MAX), None);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("YUYV".parse(), Ok(PixelFormat::YUYV));
+        assert_eq!("Y16".parse(), Ok(PixelFormat::Y16));
+        assert_eq!("Y16 ".parse(), Ok(PixelFormat::Y16));
+        assert!("".parse::<PixelFormat>().is_err());
+        assert!("YUYV2".parse::<PixelFormat>().is_err());
+        assert!("Y\n".parse::<PixelFormat>().is_err());
+        assert!("\u{e4}".parse::<PixelFormat>().is_err());
+    }
+
+    #[test]
+    fn unknown_formats_roundtrip() {
+        let unknown = PixelFormat::from_fourcc(*b"ZZ42");
+        assert_eq!(unknown.as_fourcc(), *b"ZZ42");
+        assert_eq!(unknown.to_string().parse(), Ok(unknown));
+        assert_eq!(PixelFormat::Y10.to_string(), "Y10 ");
+        assert_eq!(PixelFormat::Y10.to_string().parse(), Ok(PixelFormat::Y10));
+    }
 }
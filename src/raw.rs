@@ -9,7 +9,7 @@ pub mod controls;
 use std::ffi::c_void;
 use std::os::raw::c_ulong;
 
-use nix::libc::timeval;
+use nix::libc::{timespec, timeval};
 use nix::{ioctl_read, ioctl_readwrite, ioctl_write_ptr};
 
 use crate::buf_type::BufType;
@@ -17,6 +17,9 @@ use crate::{shared::*, PixelFormat};
 
 pub const VIDEO_MAX_PLANES: usize = 8;
 
+/// Value of [`PixFormat::priv_`] that marks the extended fields as valid.
+pub const PIX_FMT_PRIV_MAGIC: u32 = 0xfeedcafe;
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct Capabilities {
@@ -24,8 +27,9 @@ pub struct Capabilities {
     pub card: [u8; 32],
     pub bus_info: [u8; 32],
     pub version: u32,
-    pub capabilities: CapabilityFlags,
-    pub device_caps: CapabilityFlags,
+    /// Raw [`CapabilityFlags`], possibly containing bits unknown to this library.
+    pub capabilities: u32,
+    pub device_caps: u32,
     reserved: [u32; 3],
 }
 
@@ -122,7 +126,7 @@ pub struct PixFormat {
     pub priv_: u32,
     // Below fields are only valid if `priv_` equals `V4L2_PIX_FMT_PRIV_MAGIC`.
     pub flags: PixFmtFlag,
-    pub enc: u32,
+    pub enc: YcbcrEnc,
     pub quantization: Quantization,
     pub xfer_func: XferFunc,
 }
@@ -199,6 +203,74 @@ pub struct QueryCtrl {
     pub reserved: [u32; 2],
 }
 
+pub const CTRL_MAX_DIMS: usize = 4;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct QueryExtCtrl {
+    pub id: u32,
+    pub type_: CtrlType,
+    pub name: [u8; 32],
+    pub minimum: i64,
+    pub maximum: i64,
+    pub step: u64,
+    pub default_value: i64,
+    pub flags: ControlFlags,
+    pub elem_size: u32,
+    pub elems: u32,
+    pub nr_of_dims: u32,
+    pub dims: [u32; CTRL_MAX_DIMS],
+    pub reserved: [u32; 32],
+}
+
+pub const EVENT_ALL: u32 = 0;
+pub const EVENT_CTRL: u32 = 3;
+
+#[repr(C)]
+pub struct EventSubscription {
+    pub type_: u32,
+    pub id: u32,
+    pub flags: u32,
+    pub reserved: [u32; 5],
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct EventCtrl {
+    pub changes: ControlChanges,
+    pub type_: CtrlType,
+    pub value: EventCtrlValue,
+    pub flags: ControlFlags,
+    pub minimum: i32,
+    pub maximum: i32,
+    pub step: i32,
+    pub default_value: i32,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub union EventCtrlValue {
+    pub value: i32,
+    pub value64: i64,
+}
+
+#[repr(C)]
+pub union EventUnion {
+    pub ctrl: EventCtrl,
+    pub data: [u8; 64],
+}
+
+#[repr(C)]
+pub struct Event {
+    pub type_: u32,
+    pub u: EventUnion,
+    pub pending: u32,
+    pub sequence: u32,
+    pub timestamp: timespec,
+    pub id: u32,
+    pub reserved: [u32; 8],
+}
+
 #[repr(C, packed)]
 pub struct QueryMenu {
     pub id: u32,
@@ -223,6 +295,44 @@ pub struct RequestBuffers {
     pub reserved: [u32; 1],
 }
 
+/// `v4l2_jpegcompression`
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct JpegCompression {
+    pub quality: i32,
+    /// Number of the APPn segment, 0..=15.
+    pub app_n: i32,
+    pub app_len: i32,
+    pub app_data: [u8; 60],
+    pub com_len: i32,
+    pub com_data: [u8; 60],
+    pub jpeg_markers: JpegMarkers,
+}
+
+#[repr(C)]
+pub struct CreateBuffers {
+    /// The index of the first created buffer, set by the driver.
+    pub index: u32,
+    pub count: u32,
+    pub memory: Memory,
+    pub format: Format,
+    pub capabilities: BufCap,
+    pub flags: u32,
+    pub max_num_buffers: u32,
+    pub reserved: [u32; 5],
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct ExportBuffer {
+    pub type_: BufType,
+    pub index: u32,
+    pub plane: u32,
+    pub flags: ExportFlags,
+    pub fd: i32,
+    pub reserved: [u32; 11],
+}
+
 #[repr(C)]
 pub struct Timecode {
     pub type_: TimecodeType,
@@ -265,6 +375,7 @@ pub union BufferTailUnion {
     pub reserved: u32,
 }
 
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub struct Plane {
     pub bytesused: u32,
@@ -274,6 +385,7 @@ pub struct Plane {
     pub reserved: [u32; 11],
 }
 
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub union PlaneMemoryUnion {
     pub mem_offset: u32,
@@ -378,18 +490,32 @@ ioctl_read!(querycap, 'V', 0, Capabilities);
 ioctl_readwrite!(enum_fmt, 'V', 2, FmtDesc);
 ioctl_readwrite!(enuminput, 'V', 26, Input);
 ioctl_readwrite!(enumoutput, 'V', 48, Output);
+ioctl_read!(g_input, 'V', 38, u32);
+ioctl_readwrite!(s_input, 'V', 39, u32);
 ioctl_readwrite!(g_fmt, 'V', 4, Format);
 ioctl_readwrite!(s_fmt, 'V', 5, Format);
 ioctl_readwrite!(queryctrl, 'V', 36, QueryCtrl);
 ioctl_readwrite!(querymenu, 'V', 37, QueryMenu);
+ioctl_readwrite!(query_ext_ctrl, 'V', 103, QueryExtCtrl);
+ioctl_readwrite!(try_fmt, 'V', 64, Format);
 ioctl_readwrite!(reqbufs, 'V', 8, RequestBuffers);
 ioctl_readwrite!(querybuf, 'V', 9, Buffer);
 ioctl_readwrite!(qbuf, 'V', 15, Buffer);
+ioctl_readwrite!(expbuf, 'V', 16, ExportBuffer);
 ioctl_readwrite!(dqbuf, 'V', 17, Buffer);
 ioctl_write_ptr!(streamon, 'V', 18, BufType);
 ioctl_write_ptr!(streamoff, 'V', 19, BufType);
 ioctl_readwrite!(s_parm, 'V', 22, StreamParm);
 ioctl_readwrite!(g_ctrl, 'V', 27, controls::Control);
 ioctl_readwrite!(s_ctrl, 'V', 28, controls::Control);
+ioctl_readwrite!(g_ext_ctrls, 'V', 71, controls::ExtControls);
+ioctl_readwrite!(s_ext_ctrls, 'V', 72, controls::ExtControls);
+ioctl_readwrite!(try_ext_ctrls, 'V', 73, controls::ExtControls);
+ioctl_read!(dqevent, 'V', 89, Event);
+ioctl_write_ptr!(subscribe_event, 'V', 90, EventSubscription);
+ioctl_write_ptr!(unsubscribe_event, 'V', 91, EventSubscription);
+ioctl_read!(g_jpegcomp, 'V', 61, JpegCompression);
+ioctl_write_ptr!(s_jpegcomp, 'V', 62, JpegCompression);
 ioctl_readwrite!(enum_framesizes, 'V', 74, FrmSizeEnum);
 ioctl_readwrite!(enum_frameintervals, 'V', 75, FrmIvalEnum);
+ioctl_readwrite!(create_bufs, 'V', 92, CreateBuffers);
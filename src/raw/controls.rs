@@ -20,9 +20,10 @@ ffi_enum! {
     /// Identifies a device control.
     ///
     /// This type has associated constants to refer to standard controls with predefined meanings,
-    /// but drivers can add their own driver-specific controls as well.
+    /// but drivers can add their own driver-specific controls as well. Those can be referred to
+    /// by converting their raw `u32` ID with [`Cid::from`].
     pub enum Cid: u32 {
-        BRIGHTNESS                  = Self::BASE.0 + 0, // comes first so it shows up in debug output
+        BRIGHTNESS                  = Self::BASE.0, // comes first so it shows up in debug output
         BASE                        = CtrlClass::USER.0 | 0x900,
 
         /// User-class control base ID.
@@ -124,10 +125,23 @@ ffi_enum! {
 
         CAMERA_ORIENTATION          = Self::CAMERA_CLASS_BASE.0 + 34,
         CAMERA_SENSOR_ROTATION      = Self::CAMERA_CLASS_BASE.0 + 35,
+
+        CAMERA_UVC_BASE             = Self::CAMERA_CLASS_BASE.0 + 0x1000,
+        UVC_REGION_OF_INTEREST_RECT = Self::CAMERA_UVC_BASE.0 + 1,
+        UVC_REGION_OF_INTEREST_AUTO = Self::CAMERA_UVC_BASE.0 + 2,
+
+        /// JPEG-class control base ID.
+        JPEG_CLASS_BASE             = CtrlClass::JPEG.0 | 0x900,
+        JPEG_CLASS                  = CtrlClass::JPEG.0 | 1,
+        JPEG_CHROMA_SUBSAMPLING     = Self::JPEG_CLASS_BASE.0 + 1,
+        JPEG_RESTART_INTERVAL       = Self::JPEG_CLASS_BASE.0 + 2,
+        JPEG_COMPRESSION_QUALITY    = Self::JPEG_CLASS_BASE.0 + 3,
+        JPEG_ACTIVE_MARKER          = Self::JPEG_CLASS_BASE.0 + 4,
     }
 }
 
 ffi_enum! {
+    #[allow(dead_code)] // currently unused
     pub enum PowerLineFrequency: u32 {
         DISABLED  = 0,
         FREQ_50HZ = 1,
@@ -137,6 +151,7 @@ ffi_enum! {
 }
 
 ffi_enum! {
+    #[allow(dead_code)] // currently unused
     pub enum ColorFx: u32 {
         NONE         = 0,
         BW           = 1,
@@ -162,3 +177,64 @@ pub struct Control {
     pub id: Cid,
     pub value: i32,
 }
+
+impl Cid {
+    /// Returns the name of a standard control ID, without the `V4L2_CID_` prefix.
+    ///
+    /// Returns `None` for driver-specific controls and other unknown IDs.
+    pub fn name(&self) -> Option<&'static str> {
+        self.variant_name()
+    }
+}
+
+impl From<u32> for Cid {
+    #[inline]
+    fn from(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
+impl From<Cid> for u32 {
+    #[inline]
+    fn from(cid: Cid) -> Self {
+        cid.0
+    }
+}
+
+ffi_enum! {
+    /// Selects which value of the controls `VIDIOC_G_EXT_CTRLS` returns.
+    pub enum CtrlWhich: u32 {
+        CUR_VAL     = 0,
+        DEF_VAL     = 0x0f000000,
+        REQUEST_VAL = 0x0f010000,
+        MIN_VAL     = 0x0f020000,
+        MAX_VAL     = 0x0f030000,
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub union ExtControlValue {
+    pub value: i32,
+    pub value64: i64,
+    pub ptr: *mut std::ffi::c_void,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+pub struct ExtControl {
+    pub id: Cid,
+    pub size: u32,
+    pub reserved2: [u32; 1],
+    pub value: ExtControlValue,
+}
+
+#[repr(C)]
+pub struct ExtControls {
+    pub which: CtrlWhich,
+    pub count: u32,
+    pub error_idx: u32,
+    pub request_fd: i32,
+    pub reserved: [u32; 1],
+    pub controls: *mut ExtControl,
+}
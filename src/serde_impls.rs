@@ -0,0 +1,223 @@
+//! `serde` support for types defined at the crate root, and helpers for the other modules.
+//!
+//! Types with private FFI representations are (de)serialized through plain mirror structs, so
+//! that reserved fields and pointers never end up in the serialized data.
+
+use std::borrow::Cow;
+use std::{fmt, mem};
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{raw, Capabilities, Fract, PixelFormat};
+
+/// Implements `Serialize` and `Deserialize` for a type by converting it to and from a mirror
+/// type.
+///
+/// The mirror type must implement `From<&Type>`, and `Type` must implement `TryFrom<Mirror>`.
+macro_rules! serde_via {
+    ($ty:ty => $repr:ident) => {
+        impl serde::Serialize for $ty {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serde::Serialize::serialize(&$repr::from(self), serializer)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $ty {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let repr: $repr = serde::Deserialize::deserialize(deserializer)?;
+                repr.try_into().map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+pub(crate) use serde_via;
+
+/// Copies `s` into a NUL-terminated byte array, as used by the kernel for strings.
+pub(crate) fn str_to_array<const N: usize>(s: &str) -> Result<[u8; N], String> {
+    if s.len() >= N || s.bytes().any(|b| b == 0) {
+        return Err(format!("string {:?} does not fit into {} bytes", s, N));
+    }
+    let mut array = [0; N];
+    array[..s.len()].copy_from_slice(s.as_bytes());
+    Ok(array)
+}
+
+/// Pixel formats are serialized as their *fourcc* string (eg. `"YUYV"`).
+///
+/// Codes that contain non-printable characters (like those with the big-endian flag set) are
+/// serialized as their raw `u32` value in hexadecimal instead (eg. `"0xa0363159"`), which is too
+/// long to be a fourcc. Both forms are accepted when deserializing, and unknown codes are
+/// preserved.
+impl Serialize for PixelFormat {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let fourcc = self.as_fourcc();
+        if fourcc.iter().all(|&b| b.is_ascii_graphic() || b == b' ') {
+            serializer.serialize_str(std::str::from_utf8(&fourcc).unwrap())
+        } else {
+            serializer.serialize_str(&format!("0x{:08x}", u32::from_le_bytes(fourcc)))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PixelFormat {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PixelFormatVisitor;
+
+        impl Visitor<'_> for PixelFormatVisitor {
+            type Value = PixelFormat;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a fourcc string or a hexadecimal u32")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<PixelFormat, E> {
+                match v.strip_prefix("0x") {
+                    Some(hex) if hex.len() == 8 => {
+                        let v = u32::from_str_radix(hex, 16).map_err(E::custom)?;
+                        Ok(PixelFormat::from_fourcc(v.to_le_bytes()))
+                    }
+                    _ => v.parse().map_err(E::custom),
+                }
+            }
+        }
+
+        deserializer.deserialize_str(PixelFormatVisitor)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CapabilitiesRepr<'a> {
+    driver: Cow<'a, str>,
+    card: Cow<'a, str>,
+    bus_info: Cow<'a, str>,
+    version: u32,
+    capabilities: u32,
+    device_caps: u32,
+}
+
+impl<'a> From<&'a Capabilities> for CapabilitiesRepr<'a> {
+    fn from(caps: &'a Capabilities) -> Self {
+        Self {
+            driver: caps.driver().into(),
+            card: caps.card().into(),
+            bus_info: caps.bus_info().into(),
+            version: caps.0.version,
+            capabilities: caps.0.capabilities,
+            device_caps: caps.0.device_caps,
+        }
+    }
+}
+
+impl TryFrom<CapabilitiesRepr<'_>> for Capabilities {
+    type Error = String;
+
+    fn try_from(repr: CapabilitiesRepr<'_>) -> Result<Self, String> {
+        let mut raw: raw::Capabilities = unsafe { mem::zeroed() };
+        raw.driver = str_to_array(&repr.driver)?;
+        raw.card = str_to_array(&repr.card)?;
+        raw.bus_info = str_to_array(&repr.bus_info)?;
+        raw.version = repr.version;
+        raw.capabilities = repr.capabilities;
+        raw.device_caps = repr.device_caps;
+        Ok(Capabilities(raw))
+    }
+}
+
+serde_via!(Capabilities => CapabilitiesRepr);
+
+#[derive(Serialize, Deserialize)]
+struct FractRepr {
+    numerator: u32,
+    denominator: u32,
+}
+
+impl From<&Fract> for FractRepr {
+    fn from(fract: &Fract) -> Self {
+        Self {
+            numerator: fract.numerator(),
+            denominator: fract.denominator(),
+        }
+    }
+}
+
+impl TryFrom<FractRepr> for Fract {
+    type Error = &'static str;
+
+    fn try_from(repr: FractRepr) -> Result<Self, &'static str> {
+        if repr.denominator == 0 {
+            return Err("fraction denominator must not be zero");
+        }
+        Ok(Fract::new(repr.numerator, repr.denominator))
+    }
+}
+
+serde_via!(Fract => FractRepr);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CapabilityFlags;
+
+    #[test]
+    fn pixel_format() {
+        let json = serde_json::to_string(&PixelFormat::YUYV).unwrap();
+        assert_eq!(json, r#""YUYV""#);
+        let format: PixelFormat = serde_json::from_str(&json).unwrap();
+        assert_eq!(format, PixelFormat::YUYV);
+
+        // Unknown codes are preserved.
+        let format: PixelFormat = serde_json::from_str(r#""ABCD""#).unwrap();
+        assert_eq!(format, PixelFormat::from_fourcc(*b"ABCD"));
+        let format: PixelFormat = serde_json::from_str(r#""Y16""#).unwrap();
+        assert_eq!(format, PixelFormat::Y16);
+
+        let be = PixelFormat::from_fourcc([b'Y', b'1', b'6', b' ' | 0x80]);
+        let json = serde_json::to_string(&be).unwrap();
+        assert_eq!(json, r#""0xa0363159""#);
+        assert_eq!(serde_json::from_str::<PixelFormat>(&json).unwrap(), be);
+        assert!(serde_json::from_str::<PixelFormat>("2687906137").is_err());
+        assert!(serde_json::from_str::<PixelFormat>(r#""0xa03631""#).is_err());
+        assert!(serde_json::from_str::<PixelFormat>(r#""0xa036315g""#).is_err());
+
+        assert!(serde_json::from_str::<PixelFormat>(r#""TOOLONG""#).is_err());
+    }
+
+    #[test]
+    fn capabilities() {
+        let json = r#"{
+            "driver": "vivid",
+            "card": "vivid",
+            "bus_info": "platform:vivid-000",
+            "version": 395776,
+            "capabilities": 2216693777,
+            "device_caps": 69206033
+        }"#;
+        let caps: Capabilities = serde_json::from_str(json).unwrap();
+        assert_eq!(caps.bus_info(), "platform:vivid-000");
+        assert!(caps
+            .device_capabilities()
+            .contains(CapabilityFlags::VIDEO_CAPTURE | CapabilityFlags::STREAMING));
+
+        assert_eq!(caps.raw_capabilities(), 2216693777);
+
+        let value = serde_json::to_value(&caps).unwrap();
+        let caps: Capabilities = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&caps).unwrap(), value);
+
+        let json = json.replace("vivid-000", "a-bus-info-string-that-is-way-too-long");
+        assert!(serde_json::from_str::<Capabilities>(&json).is_err());
+    }
+
+    #[test]
+    fn fract() {
+        let json = serde_json::to_string(&Fract::new(1001, 30000)).unwrap();
+        assert_eq!(json, r#"{"numerator":1001,"denominator":30000}"#);
+        let fract: Fract = serde_json::from_str(&json).unwrap();
+        assert_eq!(fract, Fract::new(1001, 30000));
+
+        let err = serde_json::from_str::<Fract>(r#"{"numerator":1,"denominator":0}"#).unwrap_err();
+        assert!(err.to_string().contains("denominator"), "{}", err);
+    }
+}
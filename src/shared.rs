@@ -1,19 +1,27 @@
 //! FFI-compatible types that may also be exposed to Rust code.
 
 use std::fmt;
+use std::hash::{Hash, Hasher};
 
 // This macro enforces that all `bitflags!` types in here are marked
-// `#[repr(transparent)]` and thus FFI-safe.
+// `#[repr(transparent)]` and thus FFI-safe. With the `serde` feature, they are serialized as their
+// underlying integer, preserving unknown bits.
 macro_rules! bitflags {
     ($($t:tt)*) => {
         bitflags::bitflags! {
             #[repr(transparent)]
+            #[cfg_attr(
+                feature = "serde",
+                derive(serde::Serialize, serde::Deserialize),
+                serde(transparent)
+            )]
             $($t)*
         }
     };
 }
 
 ffi_enum! {
+    #[allow(dead_code)] // currently unused
     pub enum TunerType: u32 {
         RADIO      = 1,
         ANALOG_TV  = 2,
@@ -48,61 +56,186 @@ ffi_enum! {
 }
 
 ffi_enum! {
+    /// The colorspace of an image, defining its chromaticities and default encoding parameters.
     pub enum Colorspace: u32 {
+        /// Lets the driver choose. Drivers should never report this value, but some do; see
+        /// [`Colorspace::default_for_size`].
         DEFAULT        = 0,
+        /// SMPTE 170M, used by most standard definition sources (NTSC and PAL).
         SMPTE170M      = 1,
+        /// SMPTE 240M, an obsolete HDTV standard.
         SMPTE240M      = 2,
+        /// Rec. 709, used by HDTV and most high definition sources.
         REC709         = 3,
+        /// Colorspace of the BT.878 chip (deprecated).
         BT878          = 4,
+        /// NTSC 1953 (deprecated).
         _470_SYSTEM_M  = 5,
+        /// EBU Tech. 3213 (deprecated).
         _470_SYSTEM_BG = 6,
+        /// Shorthand for sRGB primaries with a full range BT.601 encoding, as used by JPEG.
         JPEG           = 7,
+        /// sRGB, used by most webcams and computer graphics.
         SRGB           = 8,
+        /// opRGB (formerly Adobe RGB).
         OPRGB          = 9,
+        /// Rec. 2020, used by UHDTV.
         BT2020         = 10,
+        /// Raw sensor data, without a defined colorspace.
         RAW            = 11,
+        /// DCI-P3, used by digital cinema.
         DCI_P3         = 12,
     }
 }
 
 ffi_enum! {
+    /// The Y'CbCr encoding of an image, defining how R'G'B' values are converted to Y'CbCr.
+    ///
+    /// For HSV formats, this instead holds the HSV encoding ([`YcbcrEnc::HSV_180`] or
+    /// [`YcbcrEnc::HSV_256`]).
+    pub enum YcbcrEnc: u32 {
+        /// Use the default encoding of the colorspace (see [`YcbcrEnc::default_for`]).
+        DEFAULT          = 0,
+        /// ITU-R BT.601.
+        _601             = 1,
+        /// Rec. 709.
+        _709             = 2,
+        /// Extended gamut BT.601 (xvYCC 601).
+        XV601            = 3,
+        /// Extended gamut Rec. 709 (xvYCC 709).
+        XV709            = 4,
+        /// Same as `_601` (deprecated).
+        SYCC             = 5,
+        /// Rec. 2020 non-constant luminance.
+        BT2020           = 6,
+        /// Rec. 2020 constant luminance.
+        BT2020_CONST_LUM = 7,
+        /// SMPTE 240M.
+        SMPTE240M        = 8,
+        /// Hue is mapped to 0-179.
+        HSV_180          = 128,
+        /// Hue is mapped to 0-255.
+        HSV_256          = 129,
+    }
+}
+
+ffi_enum! {
+    /// The range of the encoded values of an image.
     pub enum Quantization: u32 {
+        /// Use the default quantization of the colorspace and pixel format (see
+        /// [`Quantization::default_for`]).
         DEFAULT    = 0,
+        /// Values use the full range of the data type (eg. 0-255 for 8-bit values).
         FULL_RANGE = 1,
+        /// Values use a limited range (eg. 16-235 for 8-bit Y' values). Treating limited range
+        /// data as full range produces washed-out images.
         LIM_RANGE  = 2,
     }
 }
 
 ffi_enum! {
+    /// The transfer function of an image, which maps linear to non-linear (gamma corrected) values.
     pub enum XferFunc: u32 {
+        /// Use the default transfer function of the colorspace (see [`XferFunc::default_for`]).
         DEFAULT   = 0,
+        /// Rec. 709.
         _709      = 1,
+        /// sRGB.
         SRGB      = 2,
+        /// opRGB.
         OPRGB     = 3,
+        /// SMPTE 240M.
         SMPTE240M = 4,
+        /// The values are linear.
         NONE      = 5,
+        /// DCI-P3.
         DCI_P3    = 6,
+        /// SMPTE ST 2084, used by HDR content.
         SMPTE2084 = 7,
     }
 }
 
 ffi_enum! {
+    /// The field order of an image, describing whether and how it is interlaced.
     pub enum Field: u32 {
         /// Lets the driver choose.
         ANY           = 0,
         /// Don't use fields.
         NONE          = 1,
+        /// Only the top field is transmitted.
         TOP           = 2,
+        /// Only the bottom field is transmitted.
         BOTTOM        = 3,
+        /// Both fields are interleaved line by line, in an unspecified temporal order.
         INTERLACED    = 4,
+        /// Both fields are stored one after the other, the top field first.
         SEQ_TB        = 5,
+        /// Both fields are stored one after the other, the bottom field first.
         SEQ_BT        = 6,
+        /// The fields are transmitted in separate buffers, alternating between top and bottom.
         ALTERNATE     = 7,
+        /// Both fields are interleaved line by line, the top field is transmitted first.
         INTERLACED_TB = 8,
+        /// Both fields are interleaved line by line, the bottom field is transmitted first.
         INTERLACED_BT = 9,
     }
 }
 
+impl Colorspace {
+    /// Returns the colorspace to assume for images of the given size when a driver reports
+    /// [`Colorspace::DEFAULT`].
+    ///
+    /// As specified by V4L2, standard definition images (up to 720x576) use
+    /// [`Colorspace::SMPTE170M`], and larger images use [`Colorspace::REC709`].
+    pub fn default_for_size(width: u32, height: u32) -> Self {
+        if width <= 720 && height <= 576 {
+            Self::SMPTE170M
+        } else {
+            Self::REC709
+        }
+    }
+}
+
+impl YcbcrEnc {
+    /// Returns the default Y'CbCr encoding of `colorspace` (`V4L2_MAP_YCBCR_ENC_DEFAULT`).
+    pub fn default_for(colorspace: Colorspace) -> Self {
+        match colorspace {
+            Colorspace::REC709 | Colorspace::DCI_P3 => Self::_709,
+            Colorspace::BT2020 => Self::BT2020,
+            Colorspace::SMPTE240M => Self::SMPTE240M,
+            _ => Self::_601,
+        }
+    }
+}
+
+impl Quantization {
+    /// Returns the default quantization of `colorspace` (`V4L2_MAP_QUANTIZATION_DEFAULT`).
+    ///
+    /// RGB (and HSV) formats and the [`Colorspace::JPEG`] colorspace default to full range, every
+    /// other Y'CbCr format to limited range.
+    pub fn default_for(colorspace: Colorspace, is_rgb: bool) -> Self {
+        if is_rgb || colorspace == Colorspace::JPEG {
+            Self::FULL_RANGE
+        } else {
+            Self::LIM_RANGE
+        }
+    }
+}
+
+impl XferFunc {
+    /// Returns the default transfer function of `colorspace` (`V4L2_MAP_XFER_FUNC_DEFAULT`).
+    pub fn default_for(colorspace: Colorspace) -> Self {
+        match colorspace {
+            Colorspace::OPRGB => Self::OPRGB,
+            Colorspace::SMPTE240M => Self::SMPTE240M,
+            Colorspace::DCI_P3 => Self::DCI_P3,
+            Colorspace::RAW => Self::NONE,
+            Colorspace::SRGB | Colorspace::JPEG => Self::SRGB,
+            _ => Self::_709,
+        }
+    }
+}
+
 ffi_enum! {
     /// Data types supported by a device control.
     pub enum CtrlType: u32 {
@@ -121,6 +254,7 @@ ffi_enum! {
         U16                 = 0x0101,
         U32                 = 0x0102,
         AREA                = 0x0106,
+        RECT                = 0x0107,
 
         H264_SPS            = 0x0200,
         H264_PPS            = 0x0201,
@@ -208,10 +342,27 @@ bitflags! {
 
         // Used internally, but not of interest to users of this library.
         //const NEXT_CTRL        = 0x80000000;
+        //const NEXT_COMPOUND    = 0x40000000;
     }
 }
 
 pub(crate) const CONTROL_FLAGS_NEXT_CTRL: u32 = 0x80000000;
+pub(crate) const CONTROL_FLAGS_NEXT_COMPOUND: u32 = 0x40000000;
+
+bitflags! {
+    /// Describes what changed about a control, in a
+    /// [`ControlEvent`][crate::controls::ControlEvent].
+    pub struct ControlChanges: u32 {
+        /// The control's value changed.
+        const VALUE      = 0x0001;
+        /// The control's [`ControlFlags`] changed.
+        const FLAGS      = 0x0002;
+        /// The control's minimum, maximum, step or default value changed.
+        const RANGE      = 0x0004;
+        /// The dimensions of an array control changed.
+        const DIMENSIONS = 0x0008;
+    }
+}
 
 bitflags! {
     pub struct FormatFlags: u32 {
@@ -336,6 +487,13 @@ bitflags! {
     }
 }
 
+/// Formats the names of the set flags, eg. `VIDEO_CAPTURE | STREAMING`.
+impl fmt::Display for CapabilityFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
 bitflags! {
     /// Bitflags describing the current status of a device [`Input`][crate::Input].
     pub struct InputStatus: u32 {
@@ -393,6 +551,23 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// JPEG markers a driver includes in the images it produces.
+    pub struct JpegMarkers: u32 {
+        /// Define Huffman Tables.
+        const DHT = 1 << 3;
+        /// Define Quantization Tables.
+        const DQT = 1 << 4;
+        /// Define Restart Interval.
+        const DRI = 1 << 5;
+        /// Comment segment.
+        const COM = 1 << 6;
+        /// App segment, whose type is set with
+        /// [`JpegCompression::set_app_segment`][crate::mjpeg::JpegCompression::set_app_segment].
+        const APP = 1 << 7;
+    }
+}
+
 bitflags! {
     pub struct PixFmtFlag: u32 {
         const PREMUL_ALPHA = 0x00000001;
@@ -413,32 +588,73 @@ bitflags! {
 }
 
 bitflags! {
+    /// Flags describing the state and contents of a stream buffer.
+    ///
+    /// For captured buffers, the meaningful flags are [`BufFlag::ERROR`] (the frame may be
+    /// corrupted), [`BufFlag::KEYFRAME`], [`BufFlag::PFRAME`] and [`BufFlag::BFRAME`] (set by
+    /// encoders producing compressed video), [`BufFlag::LAST`] (the last buffer before the driver
+    /// stops producing data), and the timestamp type and source bits. The remaining flags describe
+    /// the buffer's position in the driver's queues.
     pub struct BufFlag: u32 {
+        /// The buffer is mapped into the application's address space.
         const MAPPED               = 0x00000001;
+        /// The buffer is in the driver's incoming queue.
         const QUEUED               = 0x00000002;
+        /// The buffer is in the driver's outgoing queue, and can be dequeued.
         const DONE                 = 0x00000004;
+        /// The buffer contains a compressed keyframe (I-frame).
         const KEYFRAME             = 0x00000008;
+        /// The buffer contains a compressed predicted frame (P-frame).
         const PFRAME               = 0x00000010;
+        /// The buffer contains a compressed bidirectionally predicted frame (B-frame).
         const BFRAME               = 0x00000020;
+        /// An error occurred while filling the buffer, so the data may be corrupted.
         const ERROR                = 0x00000040;
         const IN_REQUEST           = 0x00000080;
+        /// The buffer's `timecode` field is valid.
         const TIMECODE             = 0x00000100;
         const M2M_HOLD_CAPTURE_BUF = 0x00000200;
         const PREPARED             = 0x00000400;
         const NO_CACHE_INVALIDATE  = 0x00000800;
         const NO_CACHE_CLEAN       = 0x00001000;
+        /// Mask of the timestamp type bits.
         const TIMESTAMP_MASK       = 0x0000e000;
         const TIMESTAMP_UNKNOWN    = 0x00000000;
+        /// The timestamp was taken from `CLOCK_MONOTONIC`.
         const TIMESTAMP_MONOTONIC  = 0x00002000;
+        /// The timestamp was copied from the corresponding output buffer (for mem-to-mem devices).
         const TIMESTAMP_COPY       = 0x00004000;
+        /// Mask of the timestamp source bits.
         const TIMESTAMP_SRC_MASK   = 0x00070000;
+        /// The timestamp was taken at the end of the frame.
         const TIMESTAMP_SRC_EOF    = 0x00000000;
+        /// The timestamp was taken at the start of exposure.
         const TIMESTAMP_SRC_SOE    = 0x00010000;
+        /// This is the last buffer produced by the driver before it stops streaming.
         const LAST                 = 0x00100000;
         const REQUEST_FD           = 0x00800000;
     }
 }
 
+bitflags! {
+    /// Flags for the file descriptor created when exporting a buffer as a DMA buffer.
+    pub struct ExportFlags: u32 {
+        /// Close the file descriptor when executing a new program.
+        const CLOEXEC = nix::libc::O_CLOEXEC as u32;
+        /// Open the DMA buffer for writing only.
+        const WRONLY  = nix::libc::O_WRONLY as u32;
+        /// Open the DMA buffer for reading and writing.
+        const RDWR    = nix::libc::O_RDWR as u32;
+    }
+}
+
+impl Default for ExportFlags {
+    /// Returns [`ExportFlags::CLOEXEC`].
+    fn default() -> Self {
+        Self::CLOEXEC
+    }
+}
+
 bitflags! {
     pub struct TimecodeFlags: u32 {
         const DROPFRAME            = 0x0001;
@@ -462,7 +678,7 @@ bitflags! {
 }
 
 /// A fractional value (`numerator / denominator`).
-#[derive(Clone, Copy, Hash)]
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub struct Fract {
     numerator: u32,
@@ -494,6 +710,43 @@ impl Fract {
     pub fn as_f32(&self) -> f32 {
         self.numerator as f32 / self.denominator as f32
     }
+
+    /// Returns this fraction as an `f64`.
+    #[inline]
+    pub fn as_f64(&self) -> f64 {
+        f64::from(self.numerator) / f64::from(self.denominator)
+    }
+
+    /// Returns the frame interval (in seconds) corresponding to a frame rate of `fps`.
+    ///
+    /// The interval is rounded to a multiple of 1/1000 frames per second, so `29.97` yields
+    /// `100/2997`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fps` is not a positive, finite number, or rounds to 0.
+    pub fn from_fps(fps: f64) -> Self {
+        assert!(
+            fps.is_finite() && fps > 0.0,
+            "frame rate must be positive and finite"
+        );
+        let millis = (fps * 1000.0).round();
+        assert!(
+            millis >= 1.0 && millis <= f64::from(u32::MAX),
+            "frame rate {} is out of range",
+            fps
+        );
+        let millis = millis as u32;
+        let divisor = gcd(1000, millis);
+        Self::new(1000 / divisor, millis / divisor)
+    }
+
+    /// Interprets this fraction as a frame interval in seconds, and returns the corresponding
+    /// frame rate.
+    #[inline]
+    pub fn to_fps(&self) -> f64 {
+        f64::from(self.denominator) / f64::from(self.numerator)
+    }
 }
 
 impl fmt::Display for Fract {
@@ -510,24 +763,47 @@ impl fmt::Debug for Fract {
 
 impl PartialEq for Fract {
     fn eq(&self, other: &Self) -> bool {
-        let [a, b] = same_denom(*self, *other);
-        a.numerator == b.numerator
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
 }
 
 impl Eq for Fract {}
 
+impl Hash for Fract {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Equal fractions must hash the same, so hash the reduced form. Drivers may report 0/0,
+        // which can't be reduced.
+        let divisor = gcd(self.numerator, self.denominator).max(1);
+        (self.numerator / divisor).hash(state);
+        (self.denominator / divisor).hash(state);
+    }
+}
+
 impl PartialOrd for Fract {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        let [a, b] = same_denom(*self, *other);
-        a.numerator.partial_cmp(&b.numerator)
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Fract {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        let [a, b] = same_denom(*self, *other);
-        a.numerator.cmp(&b.numerator)
+        // `same_denom` divides by the denominators. Like `Hash`, treat all n/0 as equal to 1/0
+        // (sorting after every other fraction), and 0/0 as a value of its own (sorting first).
+        fn rank(fract: &Fract) -> u8 {
+            match (fract.numerator, fract.denominator) {
+                (0, 0) => 0,
+                (_, 0) => 2,
+                _ => 1,
+            }
+        }
+
+        match (rank(self), rank(other)) {
+            (1, 1) => {
+                let [a, b] = same_denom(*self, *other);
+                a.numerator.cmp(&b.numerator)
+            }
+            (a, b) => a.cmp(&b),
+        }
     }
 }
 
@@ -580,6 +856,17 @@ mod tests {
         assert_eq!(lcm(5, 3), 15);
     }
 
+    #[test]
+    fn fps_conversion() {
+        assert_eq!(Fract::from_fps(30.0), Fract::new(1, 30));
+        assert_eq!(Fract::from_fps(7.5), Fract::new(2, 15));
+        let ntsc = Fract::from_fps(29.97);
+        assert_eq!((ntsc.numerator(), ntsc.denominator()), (100, 2997));
+        assert!((ntsc.to_fps() - 29.97).abs() < 1e-9);
+        assert_eq!(Fract::new(1001, 30000).to_fps(), 30000.0 / 1001.0);
+        assert_eq!(Fract::new(1, 4).as_f64(), 0.25);
+    }
+
     #[test]
     fn test_same_denom() {
         let a = Fract::new(2, 3);
@@ -590,4 +877,53 @@ mod tests {
         assert_eq!(y.numerator, 9);
         assert_eq!(y.denominator, 15);
     }
+
+    #[test]
+    fn fract_hash() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn hash(fract: Fract) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            fract.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        assert_eq!(hash(Fract::new(1, 2)), hash(Fract::new(2, 4)));
+        assert_ne!(hash(Fract::new(1, 2)), hash(Fract::new(1, 3)));
+
+        // Drivers may report a `timeperframe` of 0/0.
+        let zero = Fract {
+            numerator: 0,
+            denominator: 0,
+        };
+        assert_eq!(hash(zero), hash(zero));
+    }
+
+    #[test]
+    fn fract_zero_denominator() {
+        let zero = Fract {
+            numerator: 0,
+            denominator: 0,
+        };
+        let infinite = Fract {
+            numerator: 5,
+            denominator: 0,
+        };
+        assert_eq!(zero, zero);
+        assert_eq!(
+            infinite,
+            Fract {
+                numerator: 1,
+                denominator: 0
+            }
+        );
+        assert_ne!(zero, Fract::new(0, 1));
+        assert_ne!(zero, infinite);
+        assert!(zero < Fract::new(0, 1));
+        assert!(Fract::new(1000, 1) < infinite);
+        assert_eq!(
+            Fract::new(1, 2).cmp(&Fract::new(2, 4)),
+            std::cmp::Ordering::Equal
+        );
+    }
 }
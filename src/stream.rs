@@ -4,26 +4,121 @@ use std::ffi::c_void;
 use std::fs::File;
 use std::mem;
 use std::num::NonZeroUsize;
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Range};
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
 use std::os::raw::c_int;
-use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::os::unix::prelude::{AsRawFd, FromRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::{io, slice};
+use std::time::{Duration, Instant};
+use std::{fmt, io, slice};
 
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
 use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
 
 use crate::buf_type::BufType;
+use crate::format::Format;
 use crate::raw;
-use crate::shared::{BufFlag, Memory};
+use crate::shared::Memory;
+use crate::{AccessMode, Device};
+use stats::StatsCollector;
+
+/// Defines the accessors for the metadata the driver attaches to a dequeued buffer.
+///
+/// Expects the implementing type to have `flags`, `sequence` and `timestamp` fields.
+macro_rules! buffer_metadata {
+    () => {
+        /// Returns the flags the driver set on this buffer.
+        ///
+        /// See [`BufFlag`][crate::stream::BufFlag] for which flags are meaningful for captured
+        /// frames.
+        #[inline]
+        pub fn flags(&self) -> crate::stream::BufFlag {
+            self.flags
+        }
+
+        /// Returns whether the error flag for this buffer is set.
+        ///
+        /// If this returns `true`, the application should expect data corruption in the buffer
+        /// data.
+        #[inline]
+        pub fn is_error(&self) -> bool {
+            self.flags.contains(crate::stream::BufFlag::ERROR)
+        }
+
+        /// Returns the sequence number of the frame stored in this buffer.
+        ///
+        /// The driver increments the sequence number for every captured frame, so gaps indicate
+        /// dropped frames. The video and metadata streams of a UVC device use the same sequence
+        /// numbers for corresponding frames.
+        #[inline]
+        pub fn sequence(&self) -> u32 {
+            self.sequence
+        }
+
+        /// Returns the time at which the driver captured this frame.
+        ///
+        /// Returns `None` if the driver did not record a timestamp. Otherwise, the clock the
+        /// timestamp was taken from is indicated by the timestamp type in
+        /// [`flags`][Self::flags]: usually
+        /// [`BufFlag::TIMESTAMP_MONOTONIC`][crate::stream::BufFlag::TIMESTAMP_MONOTONIC], meaning
+        /// `CLOCK_MONOTONIC`, or [`BufFlag::TIMESTAMP_COPY`][crate::stream::BufFlag::TIMESTAMP_COPY]
+        /// for timestamps copied from an output buffer.
+        #[inline]
+        pub fn timestamp(&self) -> Option<std::time::Duration> {
+            self.timestamp
+        }
+
+        /// Returns the time at which the driver captured this frame, along with the clock it was
+        /// taken from.
+        ///
+        /// Returns `None` if the driver did not record a timestamp. Use
+        /// [`FrameTimestamp::to_system_time`][crate::stream::FrameTimestamp::to_system_time] to
+        /// estimate the wall-clock time of the frame.
+        #[inline]
+        pub fn frame_timestamp(&self) -> Option<crate::stream::FrameTimestamp> {
+            self.timestamp
+                .map(|ts| crate::stream::FrameTimestamp::from_flags(self.flags, ts))
+        }
+    };
+}
+
+#[cfg(feature = "tokio")]
+mod async_stream;
+mod frames;
+mod mplane;
+mod read_io;
+mod set;
+mod sharing;
+mod stats;
+mod timestamp;
+
+pub use crate::shared::{BufFlag, ExportFlags};
+#[cfg(feature = "tokio")]
+pub use async_stream::AsyncStream;
+pub use frames::Frames;
+pub use mplane::{MplaneBufferView, MplaneReadStream};
+pub use read_io::{CaptureStream, IoMethod, ReadIoStream};
+pub use set::{StreamSet, StreamSetError};
+pub use sharing::{FrameBuffer, SharedStream};
+pub use stats::StreamStats;
+pub use timestamp::{FrameTimestamp, TimestampSource};
 
 enum AllocType {
     /// The buffer was `mmap`ped into our address space, use `munmap` to free it.
     Mmap,
 }
 
+/// A dequeued (filled) buffer, returned by [`FrameProvider::fetch_frame`].
+///
+/// Dereferences to the used portion of the buffer. The buffer is enqueued again when the frame is
+/// dropped.
 pub struct Frame {
     index: u32,
     buffer: Buffer,
+    flags: BufFlag,
+    bytesused: usize,
     stream: Arc<Mutex<(u32, ReadStream)>>,
 }
 
@@ -34,6 +129,36 @@ impl Deref for Frame {
     type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
+        &self.buffer[..self.bytesused]
+    }
+}
+
+impl Frame {
+    /// Returns the flags the driver set on this buffer.
+    #[inline]
+    pub fn flags(&self) -> BufFlag {
+        self.flags
+    }
+
+    /// Returns whether the error flag for this buffer is set.
+    ///
+    /// If this returns `true`, the application should expect data corruption in the buffer data.
+    #[inline]
+    pub fn is_error(&self) -> bool {
+        self.flags.contains(BufFlag::ERROR)
+    }
+
+    /// Returns the total size of the backing buffer in bytes.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.buffer.length as usize
+    }
+
+    /// Returns a reference to the *entire* backing buffer.
+    ///
+    /// See [`ReadBufferView::raw_buffer`] for details.
+    #[inline]
+    pub fn raw_buffer(&self) -> &[u8] {
         &self.buffer
     }
 }
@@ -65,12 +190,14 @@ impl FrameProvider {
             ));
         }
 
-        let (index, buffer) = guard.1.dequeue_buf()?;
+        let (buf, buffer) = guard.1.dequeue_buf()?;
         let buffer = buffer.clone();
         guard.0 -= 1;
 
         Ok(Frame {
-            index,
+            index: buf.index,
+            bytesused: bytes_used(&buf, &buffer),
+            flags: buf.flags,
             buffer,
             stream: self.stream.clone(),
         })
@@ -85,13 +212,41 @@ impl FrameProvider {
     }
 }
 
-#[derive(Clone)]
 pub(crate) struct Buffer {
     /// Pointer in our address space where this buffer is mapped or allocated.
     ptr: *mut c_void,
     /// Size of the buffer in bytes.
     length: u32,
-    queued: bool,
+    /// Whether the buffer is currently enqueued with the driver.
+    ///
+    /// This is atomic because [`FrameGuard`]s dequeue and enqueue buffers through a shared
+    /// reference to the stream.
+    queued: AtomicBool,
+}
+
+impl Clone for Buffer {
+    fn clone(&self) -> Self {
+        Self {
+            ptr: self.ptr,
+            length: self.length,
+            queued: AtomicBool::new(self.queued.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Buffer {
+    fn is_queued(&self) -> bool {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    fn set_queued(&self, queued: bool) {
+        self.queued.store(queued, Ordering::Relaxed);
+    }
+}
+
+/// Returns how many of `buffers` are enqueued with the driver.
+fn queued_count(buffers: &[Buffer]) -> u32 {
+    buffers.iter().filter(|b| b.is_queued()).count() as u32
 }
 
 impl Deref for Buffer {
@@ -121,6 +276,7 @@ impl Buffers {
         buf_type: BufType,
         mem_type: Memory,
         mut buffer_count: u32,
+        min_buffer_count: u32,
     ) -> io::Result<Self> {
         let alloc_type = match mem_type {
             Memory::MMAP => AllocType::Mmap,
@@ -138,97 +294,220 @@ impl Buffers {
 
         log::debug!("{:?}", req_bufs);
 
+        if req_bufs.count < min_buffer_count {
+            return Err(io::Error::other(format!(
+                "driver only allocated {} buffers, but at least {} are required",
+                req_bufs.count, min_buffer_count
+            )));
+        }
         if req_bufs.count < buffer_count {
             log::trace!("failed to allocate {buffer_count} buffers (driver only allocated {0}), using {0} instead", req_bufs.count);
             buffer_count = req_bufs.count;
         }
 
         // Query the buffer locations and map them into our process.
-        let mut buffers = Vec::with_capacity(buffer_count as usize);
-        for i in 0..buffer_count {
-            let mut buf: raw::Buffer = unsafe { mem::zeroed() };
-            buf.type_ = buf_type;
-            buf.memory = mem_type;
-            buf.index = i;
+        let buffers = (0..buffer_count)
+            .map(|i| map_buffer(fd, buf_type, mem_type, i))
+            .collect::<io::Result<Vec<_>>>()?;
 
-            unsafe {
-                raw::querybuf(fd, &mut buf)?;
-            }
+        Ok(Self {
+            ty: alloc_type,
+            buffers,
+        })
+    }
+}
 
-            // NB: buffer sizes are usually `PixFormat::size_image(_)` rounded up to whole pages
-            let ptr = unsafe {
-                mmap(
-                    None,
-                    NonZeroUsize::try_from(buf.length as usize)
-                        .expect("V4L2 returned buffer size of 0"),
-                    // XXX is PROT_WRITE allowed for `ReadStream`s?
-                    ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
-                    MapFlags::MAP_SHARED,
-                    fd,
-                    buf.m.offset.into(),
-                )?
-            };
+/// Returns the protection to map buffers of `fd` with.
+///
+/// Shared writable mappings require a file that was opened for writing.
+fn mmap_protection(fd: c_int) -> io::Result<ProtFlags> {
+    Ok(match crate::open::access_mode(fd)? {
+        AccessMode::ReadOnly => ProtFlags::PROT_READ,
+        AccessMode::ReadWrite | AccessMode::WriteOnly => {
+            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE
+        }
+    })
+}
 
-            assert_eq!(buf.index, i);
-            assert_eq!(buf.index as usize, buffers.len());
+/// Queries the location of the buffer with the given index and maps it into our process.
+fn map_buffer(fd: c_int, buf_type: BufType, mem_type: Memory, index: u32) -> io::Result<Buffer> {
+    let mut buf: raw::Buffer = unsafe { mem::zeroed() };
+    buf.type_ = buf_type;
+    buf.memory = mem_type;
+    buf.index = index;
 
-            buffers.push(Buffer {
-                ptr,
-                length: buf.length,
-                queued: false,
-            });
+    unsafe {
+        raw::querybuf(fd, &mut buf)?;
+    }
+
+    // NB: buffer sizes are usually `PixFormat::size_image(_)` rounded up to whole pages
+    let ptr = unsafe {
+        mmap(
+            None,
+            NonZeroUsize::try_from(buf.length as usize).expect("V4L2 returned buffer size of 0"),
+            mmap_protection(fd)?,
+            MapFlags::MAP_SHARED,
+            fd,
+            buf.m.offset.into(),
+        )?
+    };
+
+    assert_eq!(buf.index, index);
+
+    Ok(Buffer {
+        ptr,
+        length: buf.length,
+        queued: AtomicBool::new(false),
+    })
+}
+
+impl Buffers {
+    /// Allocates `count` additional buffers with the current format of `buf_type`
+    /// (`VIDIOC_CREATE_BUFS`) and maps them.
+    ///
+    /// Returns the range of indices of the new buffers.
+    fn create(
+        &mut self,
+        fd: c_int,
+        buf_type: BufType,
+        mem_type: Memory,
+        count: u32,
+    ) -> io::Result<Range<u32>> {
+        let mut create: raw::CreateBuffers = unsafe { mem::zeroed() };
+        create.count = count;
+        create.memory = mem_type;
+        create.format.type_ = buf_type;
+
+        unsafe {
+            raw::g_fmt(fd, &mut create.format)?;
+            raw::create_bufs(fd, &mut create).map_err(|e| match e {
+                Errno::ENOTTY => io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "driver does not support creating buffers (VIDIOC_CREATE_BUFS)",
+                ),
+                e => e.into(),
+            })?;
         }
 
-        Ok(Self {
-            ty: alloc_type,
-            buffers,
-        })
+        log::debug!(
+            "created {} buffers starting at index {}",
+            create.count,
+            create.index
+        );
+
+        let indices = created_indices(&create, self.buffers.len())?;
+        for index in indices.clone() {
+            let buffer = map_buffer(fd, buf_type, mem_type, index)?;
+            self.buffers.push(buffer);
+        }
+
+        Ok(indices)
+    }
+
+    /// Unmaps or frees all buffers, returning the first error that occurs.
+    fn release(&mut self) -> io::Result<()> {
+        let mut res = Ok(());
+        for buffer in self.buffers.drain(..) {
+            let r = match self.ty {
+                AllocType::Mmap => unsafe { munmap(buffer.ptr, buffer.length as usize) },
+            };
+            if let (Ok(()), Err(e)) = (&res, r) {
+                res = Err(e.into());
+            }
+        }
+        res
+    }
+}
+
+/// Returns the indices of the buffers reported by `VIDIOC_CREATE_BUFS`, given that `existing`
+/// buffers were mapped before.
+fn created_indices(create: &raw::CreateBuffers, existing: usize) -> io::Result<Range<u32>> {
+    // If mapping a previously created buffer failed, the indices no longer match ours.
+    if create.index as usize != existing {
+        return Err(io::Error::other(format!(
+            "driver created buffers starting at index {}, expected {}",
+            create.index, existing
+        )));
     }
+    Ok(create.index..create.index + create.count)
 }
 
 impl Drop for Buffers {
     fn drop(&mut self) {
-        for buffer in &self.buffers {
-            match self.ty {
-                AllocType::Mmap => unsafe {
-                    munmap(buffer.ptr, buffer.length as usize).ok();
-                },
-            }
+        if let Err(e) = self.release() {
+            log::error!("failed to unmap buffers: {}", e);
         }
     }
 }
 
+/// Tells the driver to free all buffers of `buf_type` (`VIDIOC_REQBUFS` with a count of 0).
+///
+/// For memory-mapped buffers, this must happen after they were unmapped, otherwise the driver can
+/// refuse to free them.
+fn free_buffers(file: &File, buf_type: BufType, memory: Memory) -> io::Result<()> {
+    let mut req_bufs: raw::RequestBuffers = unsafe { mem::zeroed() };
+    req_bufs.count = 0;
+    req_bufs.type_ = buf_type;
+    req_bufs.memory = memory;
+    unsafe {
+        raw::reqbufs(file.as_raw_fd(), &mut req_bufs)?;
+    }
+
+    Ok(())
+}
+
+/// Turns a stream's file back into a [`Device`] after the stream has been torn down.
+///
+/// The stream type implements `Drop`, so its file cannot be moved out. Instead, the file descriptor
+/// is duplicated. Both descriptors refer to the same open file, so the new [`Device`] keeps
+/// ownership of the device node.
+fn file_into_device(file: &File) -> io::Result<Device> {
+    Device::from_file(file.try_clone()?)
+}
+
 /// A stream that reads data from a V4L2 device.
 pub struct ReadStream {
     file: File,
     buffers: Buffers,
+    /// The format in effect when the stream was created.
+    format: Format,
     buf_type: BufType,
     mem_type: Memory,
+    sequence: Mutex<SequenceTracker>,
+    /// `None` unless enabled with [`ReadStream::enable_stats`].
+    stats: Mutex<Option<StatsCollector>>,
+    /// Whether `VIDIOC_STREAMON` was issued (and not followed by `VIDIOC_STREAMOFF`).
+    streaming: bool,
 }
 
 impl ReadStream {
     pub(crate) fn new(
         file: File,
-        buf_type: BufType,
+        format: Format,
         mem_type: Memory,
         buffer_count: u32,
+        min_buffer_count: u32,
     ) -> io::Result<Self> {
         let fd = file.as_raw_fd();
-        let buffers = Buffers::allocate(fd, buf_type, mem_type, buffer_count)?;
+        let buf_type = format.buf_type();
+        let buffers = Buffers::allocate(fd, buf_type, mem_type, buffer_count, min_buffer_count)?;
 
         let mut this = Self {
             file,
             buffers,
+            format,
             buf_type,
             mem_type,
+            sequence: Mutex::new(SequenceTracker::default()),
+            stats: Mutex::new(None),
+            streaming: false,
         };
-        this.enqueue_all()?;
         this.stream_on()?;
 
         Ok(this)
     }
 
-    fn enqueue(&mut self, index: u32) -> io::Result<()> {
+    fn enqueue(&self, index: u32) -> io::Result<()> {
         let mut buf: raw::Buffer = unsafe { mem::zeroed() };
         buf.type_ = self.buf_type;
         buf.memory = self.mem_type;
@@ -238,7 +517,7 @@ impl ReadStream {
             raw::qbuf(self.file.as_raw_fd(), &mut buf)?;
         }
 
-        self.buffers.buffers[index as usize].queued = true;
+        self.buffers.buffers[index as usize].set_queued(true);
 
         Ok(())
     }
@@ -249,46 +528,371 @@ impl ReadStream {
 
     fn enqueue_all(&mut self) -> io::Result<()> {
         for i in 0..self.buffers.buffers.len() {
-            if !self.buffers.buffers[i].queued {
+            if !self.buffers.buffers[i].is_queued() {
                 self.enqueue(i as u32)?;
             }
         }
         Ok(())
     }
 
-    /// Starts streaming.
+    /// Starts streaming, after all buffers have been enqueued.
+    ///
+    /// Streams are started when they are created, so this only needs to be called to resume a
+    /// stream after [`ReadStream::stream_off`]. Calling it on a running stream has no effect.
     ///
     /// This function can potentially block for a noticeable amount of time.
-    fn stream_on(&mut self) -> io::Result<()> {
+    pub fn stream_on(&mut self) -> io::Result<()> {
+        if self.streaming {
+            // Keep the sequence tracker and statistics of the running stream.
+            return Ok(());
+        }
+
+        self.enqueue_all()?;
+        // Drivers restart the sequence numbers when streaming is started.
+        self.sequence.get_mut().unwrap().reset();
+        if let Some(stats) = self.stats.get_mut().unwrap() {
+            *stats = StatsCollector::default();
+        }
+
         unsafe {
             raw::streamon(self.file.as_raw_fd(), &self.buf_type)?;
         }
+        self.streaming = true;
 
         Ok(())
     }
 
-    // XXX to publicly expose this, we have to handle the fact that it dequeues all buffers
-    fn stream_off(&mut self) -> io::Result<()> {
+    /// Stops streaming, without releasing the stream's buffers.
+    ///
+    /// This makes the driver discard all filled and empty buffers, so no frames can be dequeued
+    /// until streaming is resumed with [`ReadStream::stream_on`]. Attempting to dequeue from a
+    /// stopped stream returns an error.
+    ///
+    /// Since this requires exclusive access to the stream, it cannot be called while any
+    /// [`FrameGuard`] is outstanding, so the driver never reclaims a buffer the application is
+    /// still reading.
+    pub fn stream_off(&mut self) -> io::Result<()> {
         unsafe {
             raw::streamoff(self.file.as_raw_fd(), &self.buf_type)?;
         }
+        self.streaming = false;
+
+        // `VIDIOC_STREAMOFF` removes all buffers from the driver's queues.
+        for b in &self.buffers.buffers {
+            b.set_queued(false);
+        }
+        self.sequence.get_mut().unwrap().reset();
+
+        Ok(())
+    }
+
+    /// Returns the number of frames the driver dropped since streaming was (re)started.
+    ///
+    /// Frames are dropped when the application does not dequeue buffers fast enough, so the driver
+    /// runs out of empty buffers to fill. This is detected by gaps in the sequence numbers of the
+    /// dequeued buffers, so it is only accurate for drivers that count dropped frames in their
+    /// sequence numbers (which most do).
+    pub fn dropped_frames(&self) -> u64 {
+        self.sequence.lock().unwrap().dropped
+    }
+
+    /// Enables collection of the per-frame parts of [`StreamStats`].
+    ///
+    /// Collection is disabled by default. When enabled, it costs a few arithmetic operations and
+    /// two reads of the monotonic clock per frame, but no allocations or system calls.
+    pub fn enable_stats(&mut self) {
+        self.stats
+            .get_mut()
+            .unwrap()
+            .get_or_insert_with(StatsCollector::default);
+    }
+
+    /// Returns statistics about this stream.
+    ///
+    /// The frame rate, latency, and frame count are only available if collection was enabled with
+    /// [`ReadStream::enable_stats`]. The dropped frames and buffer counts are always available.
+    pub fn stats(&self) -> StreamStats {
+        let mut stats = self
+            .stats
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(StatsCollector::to_stats)
+            .unwrap_or_default();
+        stats.dropped_frames = self.dropped_frames();
+        stats.buffers_in_driver = queued_count(&self.buffers.buffers);
+        stats.buffers_in_userspace = self.held_buffers();
+        stats
+    }
+
+    /// Records a dequeued buffer in the statistics, if enabled, and returns when it was dequeued.
+    fn stats_dequeued(&self, buf: &raw::Buffer) -> Option<Instant> {
+        let mut stats = self.stats.lock().unwrap();
+        Some(stats.as_mut()?.frame_dequeued(buffer_timestamp(buf)))
+    }
+
+    /// Records that a buffer returned by [`ReadStream::stats_dequeued`] was enqueued again.
+    fn stats_requeued(&self, dequeued_at: Option<Instant>) {
+        if let (Some(dequeued_at), Some(stats)) = (dequeued_at, &mut *self.stats.lock().unwrap()) {
+            stats.frame_requeued(dequeued_at);
+        }
+    }
 
-        for b in &mut self.buffers.buffers {
-            b.queued = false;
+    /// Returns the number of buffers that are currently held by the application, for example by
+    /// [`FrameGuard`]s.
+    ///
+    /// Returns 0 while the stream is stopped with [`ReadStream::stream_off`].
+    pub fn held_buffers(&self) -> u32 {
+        if !self.streaming {
+            return 0;
         }
+        self.num_buffers() - queued_count(&self.buffers.buffers)
+    }
 
+    /// Enqueues all buffers that are not currently enqueued in the driver.
+    ///
+    /// Since this requires exclusive access to the stream, no [`FrameGuard`] can be outstanding,
+    /// so this only has an effect if a guard was leaked (eg. with [`mem::forget`]), or failed to
+    /// enqueue its buffer when it was dropped.
+    pub fn requeue_all(&mut self) -> io::Result<()> {
+        if self.streaming {
+            self.enqueue_all()?;
+        }
         Ok(())
     }
 
+    /// Returns the format that was in effect when the stream was created.
+    ///
+    /// This is a [`Format::VideoCapture`] or [`Format::MetaCapture`], depending on the device the
+    /// stream was created from. The format cannot change while the stream exists.
+    pub fn format(&self) -> &Format {
+        &self.format
+    }
+
+    /// Returns the number of buffers the driver allocated for this stream.
+    ///
+    /// This can differ from the number of buffers that was requested when creating the stream, and
+    /// includes the buffers added with [`ReadStream::create_buffers`].
+    pub fn num_buffers(&self) -> u32 {
+        self.buffers.buffers.len() as u32
+    }
+
+    /// Allocates `count` additional buffers for the stream (`VIDIOC_CREATE_BUFS`), without
+    /// stopping it.
+    ///
+    /// The buffers are sized for the current format. If the stream is running, they are enqueued
+    /// right away. Returns the range of indices of the new buffers, which can contain fewer than
+    /// `count` buffers (or none at all) if the driver limits the number of buffers.
+    ///
+    /// The new buffers are freed together with the rest of the stream's buffers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] if the driver does not support
+    /// `VIDIOC_CREATE_BUFS`.
+    pub fn create_buffers(&mut self, count: u32) -> io::Result<Range<u32>> {
+        let fd = self.file.as_raw_fd();
+        let indices = self
+            .buffers
+            .create(fd, self.buf_type, self.mem_type, count)?;
+        if self.streaming {
+            for index in indices.clone() {
+                self.enqueue(index)?;
+            }
+        }
+        Ok(indices)
+    }
+
+    /// Queries information about the buffer with the given index (`VIDIOC_QUERYBUF`).
+    pub fn buffer_info(&self, index: u32) -> io::Result<BufferInfo> {
+        let mut buf: raw::Buffer = unsafe { mem::zeroed() };
+        buf.type_ = self.buf_type;
+        buf.memory = self.mem_type;
+        buf.index = index;
+
+        unsafe {
+            raw::querybuf(self.file.as_raw_fd(), &mut buf)?;
+            Ok(BufferInfo {
+                length: buf.length,
+                offset: buf.m.offset,
+                flags: buf.flags,
+            })
+        }
+    }
+
+    /// Returns whether the stream's file descriptor is in non-blocking mode (`O_NONBLOCK`).
+    ///
+    /// This is inherited from the [`crate::Device`] the stream was created from.
+    pub fn is_nonblocking(&self) -> io::Result<bool> {
+        crate::is_nonblocking(self.file.as_fd())
+    }
+
+    /// Exports a buffer of this stream as a DMA buffer file descriptor (`VIDIOC_EXPBUF`).
+    ///
+    /// `index` is the buffer index (as returned by [`ReadBufferView::index`]), `plane` is the plane
+    /// index (always 0 for single-planar formats). The returned file
+    /// descriptor can be imported into a GPU or display API, or into another V4L2 device via
+    /// `DMABUF` streaming.
+    ///
+    /// Exporting is allowed while the stream is running or stopped. The memory mappings used by
+    /// [`ReadStream::dequeue`] stay valid: the exported DMA buffer refers to the same memory, and
+    /// keeps it alive even after the stream is dropped, until the returned file descriptor is
+    /// closed.
+    pub fn export_buffer(&self, index: u32, plane: u32, flags: ExportFlags) -> io::Result<OwnedFd> {
+        let mut exp: raw::ExportBuffer = unsafe { mem::zeroed() };
+        exp.type_ = self.buf_type;
+        exp.index = index;
+        exp.plane = plane;
+        exp.flags = flags;
+
+        unsafe {
+            raw::expbuf(self.file.as_raw_fd(), &mut exp)?;
+            Ok(OwnedFd::from_raw_fd(exp.fd))
+        }
+    }
+
+    /// Exports all buffers of this stream as DMA buffer file descriptors, in buffer index order.
+    ///
+    /// Only the first plane of every buffer is exported, using [`ExportFlags::default`].
+    pub fn export_all(&self) -> io::Result<Vec<OwnedFd>> {
+        (0..self.buffers.buffers.len() as u32)
+            .map(|index| self.export_buffer(index, 0, ExportFlags::default()))
+            .collect()
+    }
+
     /// Dequeues a buffer, passes it to `cb`, then enqueues it again.
     ///
     /// If `cb` returns an error, this function will still try to enqueue the buffer again. If that
     /// fails, the error that occurred during enqueuing will be returned, if it succeeds, the error
     /// returned by `cb` will be returned.
+    ///
+    /// If the device was opened in non-blocking mode (see [`Device::open_non_blocking`]), this
+    /// returns an error of kind [`io::ErrorKind::WouldBlock`] when no filled buffer is available.
+    ///
+    /// [`Device::open_non_blocking`]: crate::Device::open_non_blocking
     pub fn dequeue<T>(
         &mut self,
         cb: impl FnOnce(ReadBufferView<'_>) -> io::Result<T>,
     ) -> io::Result<T> {
+        let buf = self.dqbuf()?;
+        self.process_dequeued(buf, cb)
+    }
+
+    /// Dequeues a buffer if one is available, without blocking.
+    ///
+    /// If a filled buffer is available, it is passed to `cb` and enqueued again afterwards, like
+    /// [`ReadStream::dequeue`] does, and the result of `cb` is returned as `Some`. If no buffer is
+    /// available, `cb` is not called and `Ok(None)` is returned immediately.
+    ///
+    /// This works regardless of whether the device was opened in blocking or non-blocking mode, so
+    /// the blocking behavior of [`ReadStream::dequeue`] is unaffected.
+    pub fn try_dequeue<T>(
+        &mut self,
+        cb: impl FnOnce(ReadBufferView<'_>) -> io::Result<T>,
+    ) -> io::Result<Option<T>> {
+        self.dequeue_timeout(Duration::ZERO, cb)
+    }
+
+    /// Dequeues a buffer, waiting at most `timeout` for one to become available.
+    ///
+    /// If a filled buffer becomes available in time, it is passed to `cb` and enqueued again
+    /// afterwards, like [`ReadStream::dequeue`] does, and the result of `cb` is returned as `Some`.
+    /// If the timeout elapses first, `cb` is not called and `Ok(None)` is returned.
+    ///
+    /// If the wait is interrupted by a signal, it is resumed with the remaining time.
+    pub fn dequeue_timeout<T>(
+        &mut self,
+        timeout: Duration,
+        cb: impl FnOnce(ReadBufferView<'_>) -> io::Result<T>,
+    ) -> io::Result<Option<T>> {
+        if !poll_readable(self.file.as_raw_fd(), timeout)? {
+            return Ok(None);
+        }
+
+        match self.try_dqbuf()? {
+            Some(buf) => self.process_dequeued(buf, cb).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Dequeues a buffer, returning `None` if the device is non-blocking and no buffer is ready.
+    fn try_dqbuf(&mut self) -> io::Result<Option<raw::Buffer>> {
+        match self.dqbuf() {
+            Ok(buf) => Ok(Some(buf)),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Turns this stream into a [`SharedStream`], which hands out frames as reference-counted
+    /// [`FrameBuffer`]s that can be shared between threads without copying.
+    pub fn into_shared(self) -> SharedStream {
+        SharedStream::new(self)
+    }
+
+    /// Returns an iterator over the captured frames.
+    ///
+    /// Every frame is copied out of its buffer, which is enqueued again right away. See [`Frames`]
+    /// for details.
+    pub fn frames(&mut self) -> Frames<'_> {
+        Frames::new(self)
+    }
+
+    /// Dequeues a buffer and returns a guard that enqueues it again when dropped.
+    ///
+    /// Unlike [`ReadStream::dequeue`], this does not require processing the buffer inside a
+    /// callback. Multiple guards can be held at the same time, but every held guard keeps one
+    /// buffer away from the driver, which can then capture fewer frames in the meantime.
+    ///
+    /// # Errors
+    ///
+    /// If every buffer of the stream is held by a [`FrameGuard`], this returns an
+    /// [`AllBuffersHeld`] error (of kind [`io::ErrorKind::WouldBlock`]) instead of waiting for a
+    /// buffer that can never arrive.
+    pub fn dequeue_frame(&self) -> io::Result<FrameGuard<'_>> {
+        let buf = self.dqbuf_held()?;
+        let buffer = &self.buffers.buffers[buf.index as usize];
+        let data =
+            unsafe { slice::from_raw_parts(buffer.ptr as *const u8, buffer.length as usize) };
+
+        Ok(FrameGuard {
+            stream: self,
+            index: buf.index,
+            flags: buf.flags,
+            data,
+            bytesused: bytes_used(&buf, buffer),
+            sequence: buf.sequence,
+            timestamp: buffer_timestamp(&buf),
+            gap: self.sequence.lock().unwrap().observe(buf.sequence),
+            dequeued_at: self.stats_dequeued(&buf),
+            requeued: false,
+        })
+    }
+
+    /// Dequeues a buffer that the application will hold on to, through a shared reference.
+    ///
+    /// Fails with [`AllBuffersHeld`] instead of blocking forever if no buffer is enqueued.
+    fn dqbuf_held(&self) -> io::Result<raw::Buffer> {
+        if self.streaming && self.held_buffers() == self.num_buffers() {
+            return Err(AllBuffersHeld {
+                buffers: self.num_buffers(),
+            }
+            .into());
+        }
+
+        let mut buf: raw::Buffer = unsafe { mem::zeroed() };
+        buf.type_ = self.buf_type;
+        buf.memory = self.mem_type;
+
+        unsafe {
+            raw::dqbuf(self.file.as_raw_fd(), &mut buf)?;
+        }
+
+        self.buffers.buffers[buf.index as usize].set_queued(false);
+        Ok(buf)
+    }
+
+    fn dqbuf(&mut self) -> io::Result<raw::Buffer> {
         let mut buf: raw::Buffer = unsafe { mem::zeroed() };
         buf.type_ = self.buf_type;
         buf.memory = self.mem_type;
@@ -297,25 +901,40 @@ impl ReadStream {
             raw::dqbuf(self.file.as_raw_fd(), &mut buf)?;
         }
 
+        Ok(buf)
+    }
+
+    fn process_dequeued<T>(
+        &mut self,
+        buf: raw::Buffer,
+        cb: impl FnOnce(ReadBufferView<'_>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let gap = self.sequence.get_mut().unwrap().observe(buf.sequence);
+        let dequeued_at = self.stats_dequeued(&buf);
         let buffer = &mut self.buffers.buffers[buf.index as usize];
-        buffer.queued = false;
+        buffer.set_queued(false);
         let data =
             unsafe { slice::from_raw_parts(buffer.ptr as *const u8, buffer.length as usize) };
         let view = ReadBufferView {
+            index: buf.index,
             flags: buf.flags,
             data,
-            bytesused: buf.bytesused as usize,
+            bytesused: bytes_used(&buf, buffer),
+            sequence: buf.sequence,
+            timestamp: buffer_timestamp(&buf),
+            gap,
         };
 
         let res = cb(view);
         // XXX not sure if we should short-circuit here
 
         self.enqueue(buf.index)?;
+        self.stats_requeued(dequeued_at);
 
         res
     }
 
-    pub(crate) fn dequeue_buf(&mut self) -> io::Result<(u32, &mut Buffer)> {
+    pub(crate) fn dequeue_buf(&mut self) -> io::Result<(raw::Buffer, &mut Buffer)> {
         let mut buf: raw::Buffer = unsafe { mem::zeroed() };
         buf.type_ = self.buf_type;
         buf.memory = self.mem_type;
@@ -325,9 +944,9 @@ impl ReadStream {
         }
 
         let buffer = &mut self.buffers.buffers[buf.index as usize];
-        buffer.queued = false;
+        buffer.set_queued(false);
 
-        Ok((buf.index, buffer))
+        Ok((buf, buffer))
     }
 
     pub fn into_frame_provider(self) -> FrameProvider {
@@ -363,14 +982,53 @@ impl ReadStream {
     }
 }
 
+impl ReadStream {
+    /// Stops streaming and frees all buffers, reporting any errors.
+    ///
+    /// Dropping the stream does the same, but can only log errors.
+    pub fn close(mut self) -> io::Result<()> {
+        self.teardown()
+    }
+
+    /// Stops streaming, frees all buffers, and returns the [`Device`] the stream was created from.
+    ///
+    /// The device can then be reconfigured, for example to create a new stream with a different
+    /// format.
+    pub fn into_device(mut self) -> io::Result<Device> {
+        self.teardown()?;
+        file_into_device(&self.file)
+    }
+
+    /// Turns off the stream, unmaps all buffers, and makes the driver free them.
+    ///
+    /// All steps are attempted, even if an earlier one fails. The first error is returned.
+    fn teardown(&mut self) -> io::Result<()> {
+        if self.buffers.buffers.is_empty() {
+            return Ok(());
+        }
+
+        // Turn off the stream to dequeue all buffers. The driver must not access the buffers
+        // after they are unmapped.
+        let res = self.stream_off();
+        let res = res.and(self.buffers.release());
+        res.and(free_buffers(&self.file, self.buf_type, self.mem_type))
+    }
+}
+
 impl Drop for ReadStream {
     fn drop(&mut self) {
-        // Turn off the stream to dequeue all buffers.
-        // This must be done before `Buffers` can be dropped safely, at least for userptr I/O.
-        self.stream_off().ok();
+        if let Err(e) = self.teardown() {
+            log::error!("failed to tear down capture stream: {}", e);
+        }
     }
 }
 
+/// The file descriptor of the stream's device.
+///
+/// The file descriptor becomes readable when a filled buffer can be dequeued. If it is in
+/// non-blocking mode (see [`ReadStream::is_nonblocking`]), [`ReadStream::dequeue`] is then
+/// guaranteed not to block, so the stream can be registered with an external event loop (eg.
+/// `epoll`).
 impl AsRawFd for ReadStream {
     #[inline]
     fn as_raw_fd(&self) -> RawFd {
@@ -378,22 +1036,294 @@ impl AsRawFd for ReadStream {
     }
 }
 
-/// Immutable view into a dequeued (filled) read buffer.
+impl AsFd for ReadStream {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+/// Waits until `fd` becomes readable, or `timeout` elapses.
 ///
-/// Dereferences to a byte slice.
-pub struct ReadBufferView<'a> {
-    flags: BufFlag,
-    data: &'a [u8],
-    bytesused: usize,
+/// Returns whether the file descriptor is readable (or has an error condition, which will be
+/// reported by the following `VIDIOC_DQBUF`).
+fn poll_readable(fd: RawFd, timeout: Duration) -> io::Result<bool> {
+    let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+    Ok(poll_timeout(&mut fds, timeout)? != 0)
 }
 
-impl<'a> ReadBufferView<'a> {
-    /// Returns whether the error flag for this buffer is set.
+/// Calls `poll(2)` on `fds`, waiting at most `timeout`, and returns the number of ready fds.
+///
+/// If the wait is interrupted by a signal, it is resumed with the remaining time.
+fn poll_timeout(fds: &mut [PollFd], timeout: Duration) -> io::Result<usize> {
+    let deadline = Instant::now() + timeout;
+    let mut remaining = timeout;
+    loop {
+        // Round up, so that we never return before the deadline.
+        let millis = remaining.as_nanos().div_ceil(1_000_000);
+        let millis = c_int::try_from(millis).unwrap_or(c_int::MAX);
+        match poll(fds, millis) {
+            Ok(n) => return Ok(n as usize),
+            Err(Errno::EINTR) => {
+                remaining = deadline.saturating_duration_since(Instant::now());
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Information about a stream buffer, returned by [`ReadStream::buffer_info`].
+#[derive(Debug, Clone, Copy)]
+pub struct BufferInfo {
+    length: u32,
+    offset: u32,
+    flags: BufFlag,
+}
+
+impl BufferInfo {
+    /// Returns the size of the buffer in bytes.
     ///
-    /// If this returns `true`, the application should expect data corruption in the buffer data.
+    /// This is usually [`PixFormat::size_image`][crate::format::PixFormat::size_image], rounded up
+    /// to a whole number of pages.
     #[inline]
-    pub fn is_error(&self) -> bool {
-        self.flags.contains(BufFlag::ERROR)
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// Returns the offset of the buffer in the device's memory, as passed to `mmap`.
+    #[inline]
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Returns the current state flags of the buffer.
+    #[inline]
+    pub fn flags(&self) -> BufFlag {
+        self.flags
+    }
+}
+
+/// Returns the number of bytes of `buffer` the driver filled, according to the dequeued `buf`.
+///
+/// This is clamped to the size of the buffer, in case a driver reports a bogus value.
+fn bytes_used(buf: &raw::Buffer, buffer: &Buffer) -> usize {
+    buf.bytesused.min(buffer.length) as usize
+}
+
+/// Tracks the sequence numbers of dequeued buffers to detect dropped frames.
+#[derive(Debug, Default)]
+pub(crate) struct SequenceTracker {
+    last: Option<u32>,
+    dropped: u64,
+}
+
+impl SequenceTracker {
+    /// Records a dequeued sequence number and returns the number of frames dropped before it.
+    pub(crate) fn observe(&mut self, sequence: u32) -> u32 {
+        let gap = match self.last {
+            // A sequence number that did not advance (or went backwards) is not a drop.
+            Some(last) => match sequence.wrapping_sub(last) {
+                0 => 0,
+                diff if diff > u32::MAX / 2 => 0,
+                diff => diff - 1,
+            },
+            None => 0,
+        };
+        self.last = Some(sequence);
+        self.dropped += u64::from(gap);
+        gap
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Returns the timestamp the driver recorded for a buffer, or `None` if it did not record one.
+fn buffer_timestamp(buf: &raw::Buffer) -> Option<Duration> {
+    let ts = buf.timestamp;
+    if ts.tv_sec == 0 && ts.tv_usec == 0 {
+        return None;
+    }
+    Some(Duration::new(ts.tv_sec as u64, ts.tv_usec as u32 * 1000))
+}
+
+/// A dequeued (filled) buffer of a [`ReadStream`], returned by [`ReadStream::dequeue_frame`].
+///
+/// Dereferences to the used portion of the buffer. The buffer is enqueued again when the guard is
+/// dropped, or when [`FrameGuard::requeue`] is called.
+pub struct FrameGuard<'a> {
+    stream: &'a ReadStream,
+    index: u32,
+    flags: BufFlag,
+    data: &'a [u8],
+    bytesused: usize,
+    sequence: u32,
+    timestamp: Option<Duration>,
+    gap: u32,
+    dequeued_at: Option<Instant>,
+    requeued: bool,
+}
+
+impl FrameGuard<'_> {
+    /// Returns the index of the buffer in the stream.
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    buffer_metadata!();
+
+    /// Returns the number of frames the driver dropped between the previously dequeued frame and
+    /// this one.
+    ///
+    /// See [`ReadBufferView::gap_since_previous`].
+    #[inline]
+    pub fn gap_since_previous(&self) -> u32 {
+        self.gap
+    }
+
+    /// Returns the total size of the backing buffer in bytes.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns a reference to the *entire* backing buffer.
+    ///
+    /// See [`ReadBufferView::raw_buffer`] for details.
+    #[inline]
+    pub fn raw_buffer(&self) -> &[u8] {
+        self.data
+    }
+
+    /// Enqueues the buffer again, reporting any errors.
+    ///
+    /// Dropping the guard also enqueues the buffer, but can only log errors. If enqueuing fails,
+    /// the buffer stays held until [`ReadStream::requeue_all`] is called.
+    pub fn requeue(mut self) -> io::Result<()> {
+        self.requeued = true;
+        self.stream.enqueue(self.index)?;
+        self.stream.stats_requeued(self.dequeued_at);
+        Ok(())
+    }
+}
+
+impl Deref for FrameGuard<'_> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.data[..self.bytesused]
+    }
+}
+
+impl Drop for FrameGuard<'_> {
+    fn drop(&mut self) {
+        if !self.requeued {
+            match self.stream.enqueue(self.index) {
+                Ok(()) => self.stream.stats_requeued(self.dequeued_at),
+                Err(e) => log::error!("failed to enqueue buffer {}: {}", self.index, e),
+            }
+        }
+    }
+}
+
+/// Error returned by [`ReadStream::dequeue_frame`] and [`SharedStream::dequeue`] when every buffer
+/// is held by the application.
+///
+/// This is returned wrapped in an [`io::Error`] of kind [`io::ErrorKind::WouldBlock`]. Use
+/// [`AllBuffersHeld::from_io_error`] to retrieve it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllBuffersHeld {
+    buffers: u32,
+}
+
+impl AllBuffersHeld {
+    /// Returns the [`AllBuffersHeld`] error wrapped in `error`, if any.
+    pub fn from_io_error(error: &io::Error) -> Option<&AllBuffersHeld> {
+        error.get_ref()?.downcast_ref()
+    }
+
+    /// Returns the number of buffers of the stream, all of which are held.
+    pub fn buffers(&self) -> u32 {
+        self.buffers
+    }
+}
+
+impl fmt::Display for AllBuffersHeld {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "all {} buffers are held by the application",
+            self.buffers
+        )
+    }
+}
+
+impl std::error::Error for AllBuffersHeld {}
+
+impl From<AllBuffersHeld> for io::Error {
+    fn from(e: AllBuffersHeld) -> Self {
+        io::Error::new(io::ErrorKind::WouldBlock, e)
+    }
+}
+
+/// Immutable view into a dequeued (filled) read buffer.
+///
+/// Dereferences to the portion of the buffer the driver filled with data for this frame. When the
+/// driver failed to capture a frame, this is typically empty and [`ReadBufferView::is_error`]
+/// returns `true`; the stale contents of the buffer are never exposed through the view itself.
+pub struct ReadBufferView<'a> {
+    index: u32,
+    flags: BufFlag,
+    data: &'a [u8],
+    bytesused: usize,
+    sequence: u32,
+    timestamp: Option<Duration>,
+    gap: u32,
+}
+
+impl<'a> ReadBufferView<'a> {
+    /// Creates a view of a frame that was not captured from a stream (eg. read from a dump).
+    pub(crate) fn from_parts(
+        index: u32,
+        flags: BufFlag,
+        data: &'a [u8],
+        sequence: u32,
+        timestamp: Option<Duration>,
+        gap: u32,
+    ) -> Self {
+        Self {
+            index,
+            flags,
+            data,
+            bytesused: data.len(),
+            sequence,
+            timestamp,
+            gap,
+        }
+    }
+
+    /// Returns the index of the buffer in the stream.
+    ///
+    /// This is the index to pass to [`ReadStream::export_buffer`] to get a DMA buffer referring to
+    /// this buffer.
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    buffer_metadata!();
+
+    /// Returns the number of frames the driver dropped between the previously dequeued frame and
+    /// this one.
+    ///
+    /// This is 0 for the first frame after streaming was (re)started. See
+    /// [`ReadStream::dropped_frames`] for the total number of dropped frames.
+    #[inline]
+    pub fn gap_since_previous(&self) -> u32 {
+        self.gap
     }
 
     /// Returns a reference to the *entire* backing buffer.
@@ -409,6 +1339,26 @@ impl<'a> ReadBufferView<'a> {
     pub fn raw_buffer(&self) -> &'a [u8] {
         self.data
     }
+
+    /// Returns the total size of the backing buffer in bytes.
+    ///
+    /// This is the length of [`ReadBufferView::raw_buffer`], while the length of the view itself is
+    /// the number of bytes the driver filled.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Copies the used portion of the buffer into an [`OwnedFrame`].
+    pub fn to_owned_frame(&self) -> OwnedFrame {
+        OwnedFrame {
+            index: self.index,
+            flags: self.flags,
+            sequence: self.sequence,
+            timestamp: self.timestamp,
+            data: self.to_vec(),
+        }
+    }
 }
 
 impl Deref for ReadBufferView<'_> {
@@ -420,13 +1370,62 @@ impl Deref for ReadBufferView<'_> {
     }
 }
 
+/// A frame whose data has been copied out of the stream's buffer.
+///
+/// Unlike [`ReadBufferView`], this does not borrow from the stream, so the buffer it was copied
+/// from can be enqueued again immediately.
+#[derive(Debug, Clone)]
+pub struct OwnedFrame {
+    index: u32,
+    flags: BufFlag,
+    sequence: u32,
+    timestamp: Option<Duration>,
+    data: Vec<u8>,
+}
+
+impl OwnedFrame {
+    /// Returns the index of the stream buffer this frame was copied from.
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    buffer_metadata!();
+
+    /// Returns the frame data.
+    #[inline]
+    pub fn into_vec(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl Deref for OwnedFrame {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
 /// A stream that writes to a V4L2 device.
+///
+/// Buffers can be filled and enqueued before streaming starts. By default, streaming starts as soon
+/// as the first buffer is enqueued. To avoid underruns at the start of the stream, use
+/// [`WriteStream::set_min_queued`] to start streaming only once several buffers are enqueued, or
+/// [`WriteStream::set_auto_start`] to start it manually with [`WriteStream::stream_on`].
 pub struct WriteStream {
     file: File,
     buffers: Buffers,
     next_unqueued_buffer: Option<usize>,
     buf_type: BufType,
     mem_type: Memory,
+    streaming: bool,
+    /// Whether streaming should start once `min_queued` buffers are enqueued.
+    start_requested: bool,
+    min_queued: u32,
+    /// Size of the data the driver expects in each buffer, if smaller than the buffers.
+    payload_size: Option<usize>,
 }
 
 impl WriteStream {
@@ -437,7 +1436,7 @@ impl WriteStream {
         buffer_count: u32,
     ) -> io::Result<Self> {
         let fd = file.as_raw_fd();
-        let buffers = Buffers::allocate(fd, buf_type, mem_type, buffer_count)?;
+        let buffers = Buffers::allocate(fd, buf_type, mem_type, buffer_count, 1)?;
 
         Ok(Self {
             file,
@@ -445,34 +1444,150 @@ impl WriteStream {
             next_unqueued_buffer: Some(0),
             buf_type,
             mem_type,
+            streaming: false,
+            start_requested: true,
+            min_queued: 1,
+            payload_size: None,
         })
     }
 
-    fn enqueue_buffer(&mut self, index: u32) -> io::Result<()> {
+    /// Limits the buffers handed out by [`WriteStream::enqueue`] to `size` bytes.
+    ///
+    /// Fails if the driver allocated a buffer smaller than `size`.
+    pub(crate) fn set_payload_size(&mut self, size: u32) -> io::Result<()> {
+        if let Some(buffer) = self.buffers.buffers.iter().find(|b| b.length < size) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "driver allocated a {} byte buffer, but the format requires {} bytes",
+                    buffer.length, size
+                ),
+            ));
+        }
+        self.payload_size = Some(size as usize);
+        Ok(())
+    }
+
+    /// Returns the number of buffers the driver allocated for this stream.
+    pub fn num_buffers(&self) -> u32 {
+        self.buffers.buffers.len() as u32
+    }
+
+    /// Returns the number of buffers that are currently enqueued in the driver.
+    pub fn queued_buffers(&self) -> u32 {
+        queued_count(&self.buffers.buffers)
+    }
+
+    /// Returns whether the stream has been started.
+    pub fn is_streaming(&self) -> bool {
+        self.streaming
+    }
+
+    /// Sets whether streaming is started automatically once enough buffers are enqueued.
+    ///
+    /// This is enabled by default. If disabled, [`WriteStream::stream_on`] has to be called to
+    /// start streaming, after enqueuing at most [`WriteStream::num_buffers`] buffers.
+    pub fn set_auto_start(&mut self, auto_start: bool) {
+        if !self.streaming {
+            self.start_requested = auto_start;
+        }
+    }
+
+    /// Sets the number of buffers that have to be enqueued before streaming starts.
+    ///
+    /// This defaults to 1, and is clamped to the number of buffers of the stream. Filling all
+    /// buffers before streaming starts gives the driver something to output while the next frame
+    /// is being prepared, which avoids underruns at the start of the stream.
+    pub fn set_min_queued(&mut self, min_queued: u32) {
+        self.min_queued = min_queued.clamp(1, self.num_buffers());
+    }
+
+    /// Starts streaming.
+    ///
+    /// If fewer buffers than set with [`WriteStream::set_min_queued`] are enqueued, streaming is
+    /// started once enough buffers are enqueued instead. Calling this on a running stream has no
+    /// effect.
+    pub fn stream_on(&mut self) -> io::Result<()> {
+        self.start_requested = true;
+        self.start_if_ready()
+    }
+
+    /// Stops streaming.
+    ///
+    /// This makes the driver discard all enqueued buffers. Streaming starts again according to
+    /// [`WriteStream::set_auto_start`] and [`WriteStream::set_min_queued`], or when
+    /// [`WriteStream::stream_on`] is called.
+    pub fn stream_off(&mut self) -> io::Result<()> {
+        unsafe {
+            raw::streamoff(self.file.as_raw_fd(), &self.buf_type)?;
+        }
+        self.streaming = false;
+
+        // `VIDIOC_STREAMOFF` removes all buffers from the driver's queue.
+        for b in &self.buffers.buffers {
+            b.set_queued(false);
+        }
+        self.next_unqueued_buffer = Some(0);
+
+        Ok(())
+    }
+
+    fn start_if_ready(&mut self) -> io::Result<()> {
+        if !self.streaming && self.start_requested && self.queued_buffers() >= self.min_queued {
+            unsafe {
+                raw::streamon(self.file.as_raw_fd(), &self.buf_type)?;
+            }
+            self.streaming = true;
+        }
+        Ok(())
+    }
+
+    fn enqueue_buffer(&mut self, index: u32, meta: &OutputBufferMeta) -> io::Result<()> {
         let mut buf: raw::Buffer = unsafe { mem::zeroed() };
         buf.type_ = self.buf_type;
         buf.memory = self.mem_type;
         buf.index = index;
+        buf.bytesused = meta.bytesused as u32;
+        if let Some(ts) = meta.timestamp {
+            buf.timestamp.tv_sec = ts.as_secs() as _;
+            buf.timestamp.tv_usec = ts.subsec_micros() as _;
+        }
 
         unsafe {
             raw::qbuf(self.file.as_raw_fd(), &mut buf)?;
         }
 
-        self.buffers.buffers[index as usize].queued = true;
+        self.buffers.buffers[index as usize].set_queued(true);
 
         Ok(())
     }
 
     /// Passes a non-queued buffer to `cb` to fill it with data, then enqueues it for outputting.
     ///
-    /// If no unqueued buffer is available, one is dequeued first (which may block until one is
-    /// available).
+    /// If no unqueued buffer is available, one is dequeued first (which may block until the driver
+    /// is done outputting one).
+    ///
+    /// By default, the whole buffer is output. If the frame is smaller than the buffer, `cb` has to
+    /// call [`WriteBufferView::set_bytes_used`]. For metadata output streams, the buffer is limited
+    /// to the [`MetaFormat::buffer_size`][crate::format::MetaFormat::buffer_size] of the format.
+    /// See [`WriteStream`] for when streaming starts.
+    ///
+    /// # Errors
+    ///
+    /// If all buffers are enqueued, but streaming has not been started yet, none of them can ever
+    /// be dequeued, so an error of kind [`io::ErrorKind::WouldBlock`] is returned.
     pub fn enqueue<T>(
         &mut self,
         cb: impl FnOnce(WriteBufferView<'_>) -> io::Result<T>,
     ) -> io::Result<T> {
         let buf_index = match self.next_unqueued_buffer {
             Some(i) => i,
+            None if !self.streaming => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "all buffers are enqueued, but streaming has not been started",
+                ));
+            }
             None => {
                 // All buffers are enqueued with the driver. Dequeue one.
                 let mut buf: raw::Buffer = unsafe { mem::zeroed() };
@@ -484,19 +1599,27 @@ impl WriteStream {
                 }
 
                 let buf_index = buf.index as usize;
-                self.buffers.buffers[buf_index].queued = false;
+                self.buffers.buffers[buf_index].set_queued(false);
                 buf_index
             }
         };
 
         let buffer = &mut self.buffers.buffers[buf_index];
-        assert!(!buffer.queued);
-
-        let data =
-            unsafe { slice::from_raw_parts_mut(buffer.ptr as *mut u8, buffer.length as usize) };
-        let view = WriteBufferView { data };
+        assert!(!buffer.is_queued());
+
+        let len = buffer.length as usize;
+        let len = self.payload_size.map_or(len, |size| size.min(len));
+        let data = unsafe { slice::from_raw_parts_mut(buffer.ptr as *mut u8, len) };
+        let mut meta = OutputBufferMeta {
+            bytesused: data.len(),
+            timestamp: None,
+        };
+        let view = WriteBufferView {
+            data,
+            meta: &mut meta,
+        };
         match cb(view) {
-            Ok(val) => match self.enqueue_buffer(buf_index as u32) {
+            Ok(val) => match self.enqueue_buffer(buf_index as u32, &meta) {
                 Ok(()) => {
                     match self.next_unqueued_buffer {
                         Some(i) => {
@@ -512,6 +1635,7 @@ impl WriteStream {
                             // Do nothing, next call will dequeue.
                         }
                     }
+                    self.start_if_ready()?;
                     Ok(val)
                 }
                 Err(e) => {
@@ -529,11 +1653,107 @@ impl WriteStream {
     }
 }
 
+impl WriteStream {
+    /// Stops streaming and frees all buffers, reporting any errors.
+    ///
+    /// Dropping the stream does the same, but can only log errors.
+    pub fn close(mut self) -> io::Result<()> {
+        self.teardown()
+    }
+
+    /// Stops streaming, frees all buffers, and returns the [`Device`] the stream was created from.
+    ///
+    /// See [`ReadStream::into_device`].
+    pub fn into_device(mut self) -> io::Result<Device> {
+        self.teardown()?;
+        file_into_device(&self.file)
+    }
+
+    /// Turns off the stream, unmaps all buffers, and makes the driver free them.
+    ///
+    /// All steps are attempted, even if an earlier one fails. The first error is returned.
+    fn teardown(&mut self) -> io::Result<()> {
+        if self.buffers.buffers.is_empty() {
+            return Ok(());
+        }
+
+        // Stop streaming, so that the driver no longer accesses the buffers.
+        let mut res = Ok(());
+        if self.streaming {
+            self.streaming = false;
+            if let Err(e) = unsafe { raw::streamoff(self.file.as_raw_fd(), &self.buf_type) } {
+                res = Err(e.into());
+            }
+        }
+        let res = res.and(self.buffers.release());
+        res.and(free_buffers(&self.file, self.buf_type, self.mem_type))
+    }
+}
+
+impl Drop for WriteStream {
+    fn drop(&mut self) {
+        if let Err(e) = self.teardown() {
+            log::error!("failed to tear down output stream: {}", e);
+        }
+    }
+}
+
+/// The file descriptor of the stream's device.
+///
+/// The file descriptor becomes writable when an empty buffer can be dequeued.
+impl AsRawFd for WriteStream {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl AsFd for WriteStream {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
 /// Mutable view into an unqueued write buffer.
 ///
 /// Dereferences to a byte slice.
 pub struct WriteBufferView<'a> {
     data: &'a mut [u8],
+    meta: &'a mut OutputBufferMeta,
+}
+
+/// Properties of an output buffer, set via [`WriteBufferView`].
+struct OutputBufferMeta {
+    bytesused: usize,
+    timestamp: Option<Duration>,
+}
+
+impl WriteBufferView<'_> {
+    /// Sets the number of bytes of the buffer that contain frame data.
+    ///
+    /// Defaults to the length of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_used` exceeds the length of the buffer.
+    pub fn set_bytes_used(&mut self, bytes_used: usize) {
+        assert!(
+            bytes_used <= self.data.len(),
+            "bytes used ({}) exceeds buffer length ({})",
+            bytes_used,
+            self.data.len()
+        );
+        self.meta.bytesused = bytes_used;
+    }
+
+    /// Sets the timestamp of the frame in this buffer.
+    ///
+    /// Depending on the driver, the timestamp is passed on to the consumer of the output (for
+    /// example with `v4l2loopback`), or ignored.
+    pub fn set_timestamp(&mut self, timestamp: Duration) {
+        self.meta.timestamp = Some(timestamp);
+    }
 }
 
 impl Deref for WriteBufferView<'_> {
@@ -552,10 +1772,574 @@ impl DerefMut for WriteBufferView<'_> {
     }
 }
 
+/// Requests `count` buffers of a memory type whose storage is provided by the application
+/// ([`Memory::USERPTR`] or [`Memory::DMABUF`]).
+///
+/// Fails if the driver does not support the memory type, or cannot use all `count` buffers.
+fn request_app_buffers(
+    file: &File,
+    buf_type: BufType,
+    memory: Memory,
+    count: u32,
+) -> io::Result<()> {
+    let mut req_bufs: raw::RequestBuffers = unsafe { mem::zeroed() };
+    req_bufs.count = count;
+    req_bufs.type_ = buf_type;
+    req_bufs.memory = memory;
+    unsafe {
+        raw::reqbufs(file.as_raw_fd(), &mut req_bufs).map_err(|e| match e {
+            Errno::EINVAL => io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("memory type {:?} unsupported by driver", memory),
+            ),
+            e => e.into(),
+        })?;
+    }
+    log::debug!("{:?}", req_bufs);
+    if req_bufs.count < count {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "driver only supports {} buffers, but {} were provided",
+                req_bufs.count, count
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Stops streaming and makes the driver release all buffers of the given memory type.
+fn release_app_buffers(file: &File, buf_type: BufType, memory: Memory) -> io::Result<()> {
+    unsafe {
+        raw::streamoff(file.as_raw_fd(), &buf_type)?;
+    }
+
+    free_buffers(file, buf_type, memory)
+}
+
+/// Buffers that can be filled by the driver in `USERPTR` streaming mode.
+///
+/// # Safety
+///
+/// [`UserptrBuffer::as_slice`] and [`UserptrBuffer::as_mut_slice`] must always return the same
+/// region of memory, and that region must stay valid for as long as the value exists, even when it
+/// is moved. This holds for heap-allocated buffers like [`Vec<u8>`] and [`Box<[u8]>`], but not for
+/// types that store their data inline (like arrays).
+pub unsafe trait UserptrBuffer: Send {
+    fn as_slice(&self) -> &[u8];
+    fn as_mut_slice(&mut self) -> &mut [u8];
+}
+
+unsafe impl UserptrBuffer for Vec<u8> {
+    fn as_slice(&self) -> &[u8] {
+        self
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+unsafe impl UserptrBuffer for Box<[u8]> {
+    fn as_slice(&self) -> &[u8] {
+        self
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+/// A stream that reads data from a V4L2 device into buffers supplied by the application.
+///
+/// The stream takes ownership of the buffers, so that they cannot be accessed or freed while the
+/// driver might write to them. Filled buffers are lent out by [`UserptrStream::dequeue`], and
+/// [`UserptrStream::into_buffers`] returns them once streaming has been stopped.
+pub struct UserptrStream<B: UserptrBuffer> {
+    file: File,
+    buf_type: BufType,
+    buffers: Vec<B>,
+}
+
+impl<B: UserptrBuffer> UserptrStream<B> {
+    pub(crate) fn new(
+        file: File,
+        buf_type: BufType,
+        buffers: Vec<B>,
+        min_size: usize,
+    ) -> io::Result<Self> {
+        if buffers.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "at least one buffer is required",
+            ));
+        }
+        for (i, buffer) in buffers.iter().enumerate() {
+            let data = buffer.as_slice();
+            if data.len() < min_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "buffer {} is too small for the negotiated format ({} bytes, {} required)",
+                        i,
+                        data.len(),
+                        min_size
+                    ),
+                ));
+            }
+        }
+
+        request_app_buffers(&file, buf_type, Memory::USERPTR, buffers.len() as u32)?;
+
+        let mut this = Self {
+            file,
+            buf_type,
+            buffers,
+        };
+        for i in 0..this.buffers.len() {
+            this.enqueue(i)?;
+        }
+
+        unsafe {
+            raw::streamon(this.file.as_raw_fd(), &this.buf_type)?;
+        }
+
+        Ok(this)
+    }
+
+    fn enqueue(&mut self, index: usize) -> io::Result<()> {
+        let data = self.buffers[index].as_mut_slice();
+        let mut buf: raw::Buffer = unsafe { mem::zeroed() };
+        buf.type_ = self.buf_type;
+        buf.memory = Memory::USERPTR;
+        buf.index = index as u32;
+        buf.m.userptr = data.as_mut_ptr() as std::os::raw::c_ulong;
+        buf.length = data.len() as u32;
+
+        unsafe {
+            raw::qbuf(self.file.as_raw_fd(), &mut buf)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of buffers used by the stream.
+    pub fn num_buffers(&self) -> usize {
+        self.buffers.len()
+    }
+
+    /// Dequeues a buffer, passes it to `cb`, then enqueues it again.
+    ///
+    /// If `cb` returns an error, this function will still try to enqueue the buffer again. If that
+    /// fails, the error that occurred during enqueuing will be returned, if it succeeds, the error
+    /// returned by `cb` will be returned.
+    pub fn dequeue<T>(
+        &mut self,
+        cb: impl FnOnce(UserptrBufferView<'_, B>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut buf: raw::Buffer = unsafe { mem::zeroed() };
+        buf.type_ = self.buf_type;
+        buf.memory = Memory::USERPTR;
+
+        unsafe {
+            raw::dqbuf(self.file.as_raw_fd(), &mut buf)?;
+        }
+
+        let index = buf.index as usize;
+        let view = UserptrBufferView {
+            index,
+            buffer: &self.buffers[index],
+            flags: buf.flags,
+            bytesused: (buf.bytesused as usize).min(self.buffers[index].as_slice().len()),
+            sequence: buf.sequence,
+            timestamp: buffer_timestamp(&buf),
+        };
+
+        let res = cb(view);
+
+        self.enqueue(index)?;
+
+        res
+    }
+
+    /// Stops streaming and returns the buffers, in the order they were passed in.
+    ///
+    /// If stopping the stream fails, the driver might still write to the buffers, so they are
+    /// leaked instead of being returned.
+    pub fn into_buffers(mut self) -> io::Result<Vec<B>> {
+        self.release()
+    }
+
+    /// Stops streaming, which makes the driver release all buffers, and takes them out of `self`.
+    ///
+    /// The buffers are leaked if this fails, since the driver might still access them.
+    fn release(&mut self) -> io::Result<Vec<B>> {
+        let buffers = mem::take(&mut self.buffers);
+        match release_app_buffers(&self.file, self.buf_type, Memory::USERPTR) {
+            Ok(()) => Ok(buffers),
+            Err(e) => {
+                mem::forget(buffers);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl<B: UserptrBuffer> Drop for UserptrStream<B> {
+    fn drop(&mut self) {
+        // The driver must not access the buffers after they are freed.
+        if !self.buffers.is_empty() {
+            if let Err(e) = self.release() {
+                log::error!("failed to stop USERPTR stream, leaking its buffers: {}", e);
+            }
+        }
+    }
+}
+
+impl<B: UserptrBuffer> AsRawFd for UserptrStream<B> {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl<B: UserptrBuffer> AsFd for UserptrStream<B> {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+/// Immutable view into a dequeued (filled) buffer of a [`UserptrStream`].
+///
+/// Dereferences to the used portion of the buffer.
+pub struct UserptrBufferView<'a, B> {
+    index: usize,
+    buffer: &'a B,
+    flags: BufFlag,
+    bytesused: usize,
+    sequence: u32,
+    timestamp: Option<Duration>,
+}
+
+impl<'a, B: UserptrBuffer> UserptrBufferView<'a, B> {
+    /// Returns the index of the buffer in the list of buffers passed to the stream.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns a reference to the user buffer holding the data.
+    #[inline]
+    pub fn buffer(&self) -> &'a B {
+        self.buffer
+    }
+
+    buffer_metadata!();
+}
+
+impl<B: UserptrBuffer> Deref for UserptrBufferView<'_, B> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.buffer.as_slice()[..self.bytesused]
+    }
+}
+
+/// A stream that reads data from a V4L2 device into imported DMA buffers (`DMABUF`).
+///
+/// The DMA buffer file descriptors are typically exported by a GPU or display driver, and
+/// imported here without copying. They are kept open for the lifetime of the stream, and returned
+/// by [`DmabufStream::into_fds`].
+///
+/// Since the buffers are not mapped into our address space, dequeuing only reports which buffer
+/// was filled, and how many bytes of it are used.
+pub struct DmabufStream {
+    file: File,
+    buf_type: BufType,
+    fds: Vec<OwnedFd>,
+}
+
+impl DmabufStream {
+    pub(crate) fn new(file: File, buf_type: BufType, fds: Vec<OwnedFd>) -> io::Result<Self> {
+        if fds.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "at least one buffer is required",
+            ));
+        }
+
+        request_app_buffers(&file, buf_type, Memory::DMABUF, fds.len() as u32)?;
+
+        let this = Self {
+            file,
+            buf_type,
+            fds,
+        };
+        for i in 0..this.fds.len() {
+            this.enqueue(i)?;
+        }
+
+        unsafe {
+            raw::streamon(this.file.as_raw_fd(), &this.buf_type)?;
+        }
+
+        Ok(this)
+    }
+
+    fn enqueue(&self, index: usize) -> io::Result<()> {
+        let mut buf: raw::Buffer = unsafe { mem::zeroed() };
+        buf.type_ = self.buf_type;
+        buf.memory = Memory::DMABUF;
+        buf.index = index as u32;
+        buf.m.fd = self.fds[index].as_raw_fd();
+        // A length of 0 makes the driver use the size of the DMA buffer.
+        buf.length = 0;
+
+        unsafe {
+            raw::qbuf(self.file.as_raw_fd(), &mut buf)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of buffers used by the stream.
+    pub fn num_buffers(&self) -> usize {
+        self.fds.len()
+    }
+
+    /// Dequeues a buffer, passes it to `cb`, then enqueues it again.
+    ///
+    /// If `cb` returns an error, this function will still try to enqueue the buffer again. If that
+    /// fails, the error that occurred during enqueuing will be returned, if it succeeds, the error
+    /// returned by `cb` will be returned.
+    pub fn dequeue<T>(
+        &mut self,
+        cb: impl FnOnce(DmabufBufferView<'_>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut buf: raw::Buffer = unsafe { mem::zeroed() };
+        buf.type_ = self.buf_type;
+        buf.memory = Memory::DMABUF;
+
+        unsafe {
+            raw::dqbuf(self.file.as_raw_fd(), &mut buf)?;
+        }
+
+        let index = buf.index as usize;
+        let view = DmabufBufferView {
+            index,
+            fd: self.fds[index].as_fd(),
+            flags: buf.flags,
+            bytesused: buf.bytesused as usize,
+            sequence: buf.sequence,
+            timestamp: buffer_timestamp(&buf),
+        };
+
+        let res = cb(view);
+
+        self.enqueue(index)?;
+
+        res
+    }
+
+    /// Stops streaming and returns the DMA buffer file descriptors, in the order they were passed
+    /// in.
+    pub fn into_fds(mut self) -> io::Result<Vec<OwnedFd>> {
+        release_app_buffers(&self.file, self.buf_type, Memory::DMABUF)?;
+        Ok(mem::take(&mut self.fds))
+    }
+}
+
+impl Drop for DmabufStream {
+    fn drop(&mut self) {
+        if !self.fds.is_empty() {
+            if let Err(e) = release_app_buffers(&self.file, self.buf_type, Memory::DMABUF) {
+                log::error!("failed to stop DMABUF stream: {}", e);
+            }
+        }
+    }
+}
+
+impl AsRawFd for DmabufStream {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl AsFd for DmabufStream {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+/// Information about a dequeued (filled) buffer of a [`DmabufStream`].
+pub struct DmabufBufferView<'a> {
+    index: usize,
+    fd: BorrowedFd<'a>,
+    flags: BufFlag,
+    bytesused: usize,
+    sequence: u32,
+    timestamp: Option<Duration>,
+}
+
+impl<'a> DmabufBufferView<'a> {
+    /// Returns the index of the buffer in the list of file descriptors passed to the stream.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the DMA buffer file descriptor of the buffer.
+    #[inline]
+    pub fn fd(&self) -> BorrowedFd<'a> {
+        self.fd
+    }
+
+    /// Returns the number of bytes of the buffer that contain frame data.
+    #[inline]
+    pub fn bytes_used(&self) -> usize {
+        self.bytesused
+    }
+
+    buffer_metadata!();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn sequence_gaps() {
+        let mut tracker = SequenceTracker::default();
+        assert_eq!(tracker.observe(5), 0);
+        assert_eq!(tracker.observe(6), 0);
+        assert_eq!(tracker.observe(9), 2);
+        assert_eq!(tracker.observe(9), 0);
+        assert_eq!(tracker.observe(4), 0);
+        assert_eq!(tracker.dropped, 2);
+
+        // Wrapping around is not a drop.
+        assert_eq!(tracker.observe(u32::MAX), 0);
+        assert_eq!(tracker.observe(0), 0);
+        assert_eq!(tracker.observe(2), 1);
+        assert_eq!(tracker.dropped, 3);
+
+        tracker.reset();
+        assert_eq!(tracker.observe(100), 0);
+        assert_eq!(tracker.dropped, 0);
+    }
+
+    #[test]
+    fn view_excludes_unused_data() {
+        let data = [0xffu8; 16];
+        let mut view = ReadBufferView {
+            index: 0,
+            flags: BufFlag::ERROR,
+            data: &data,
+            bytesused: 0,
+            sequence: 0,
+            timestamp: None,
+            gap: 0,
+        };
+        assert!(view.is_empty());
+        assert_eq!(view.capacity(), 16);
+        assert_eq!(view.raw_buffer(), &data);
+        assert!(view.to_owned_frame().is_empty());
+
+        view.bytesused = 4;
+        assert_eq!(&*view, &data[..4]);
+    }
+
+    #[test]
+    fn bytes_used_is_clamped() {
+        let buffer = Buffer {
+            ptr: std::ptr::null_mut(),
+            length: 100,
+            queued: AtomicBool::new(false),
+        };
+        let mut buf: raw::Buffer = unsafe { mem::zeroed() };
+        buf.bytesused = 60;
+        assert_eq!(bytes_used(&buf, &buffer), 60);
+        buf.bytesused = 200;
+        assert_eq!(bytes_used(&buf, &buffer), 100);
+    }
+
+    fn buffers(queued: &[bool]) -> Vec<Buffer> {
+        queued
+            .iter()
+            .map(|&queued| Buffer {
+                ptr: std::ptr::null_mut(),
+                length: 100,
+                queued: AtomicBool::new(queued),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn queued_buffers_are_counted() {
+        assert_eq!(queued_count(&[]), 0);
+        assert_eq!(queued_count(&buffers(&[false, false])), 0);
+
+        let buffers = buffers(&[true, false, true]);
+        assert_eq!(queued_count(&buffers), 2);
+        buffers[1].set_queued(true);
+        assert_eq!(queued_count(&buffers), 3);
+        buffers[0].set_queued(false);
+        buffers[2].set_queued(false);
+        assert_eq!(queued_count(&buffers), 1);
+
+        // Clones track their state separately.
+        let clone = buffers.clone();
+        clone[1].set_queued(false);
+        assert_eq!(queued_count(&buffers), 1);
+        assert_eq!(queued_count(&clone), 0);
+    }
+
+    #[test]
+    fn created_buffer_indices() {
+        let mut create: raw::CreateBuffers = unsafe { mem::zeroed() };
+        create.index = 4;
+        create.count = 3;
+        assert_eq!(created_indices(&create, 4).unwrap(), 4..7);
+
+        let e = created_indices(&create, 3).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "driver created buffers starting at index 4, expected 3"
+        );
+
+        // The driver may create fewer buffers than requested, or none at all.
+        create.count = 0;
+        assert!(created_indices(&create, 4).unwrap().is_empty());
+    }
+
+    #[test]
+    fn all_buffers_held() {
+        let e = io::Error::from(AllBuffersHeld { buffers: 3 });
+        assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(e.to_string(), "all 3 buffers are held by the application");
+        let held = AllBuffersHeld::from_io_error(&e).unwrap();
+        assert_eq!(held.buffers(), 3);
+
+        let e = io::Error::new(io::ErrorKind::WouldBlock, "other error");
+        assert_eq!(AllBuffersHeld::from_io_error(&e), None);
+        assert_eq!(AllBuffersHeld::from_io_error(&Errno::EAGAIN.into()), None);
+    }
+
+    #[test]
+    fn zero_timestamp_is_none() {
+        let mut buf: raw::Buffer = unsafe { mem::zeroed() };
+        assert_eq!(buffer_timestamp(&buf), None);
+
+        buf.timestamp.tv_sec = 12;
+        buf.timestamp.tv_usec = 345_678;
+        assert_eq!(buffer_timestamp(&buf), Some(Duration::new(12, 345_678_000)));
+    }
+
     #[test]
     fn stream_types_are_send_sync() {
         fn assert<T: Send + Sync>() {}
@@ -564,5 +2348,12 @@ mod tests {
         assert::<ReadStream>();
         assert::<WriteBufferView<'_>>();
         assert::<ReadBufferView<'_>>();
+        assert::<UserptrStream<Vec<u8>>>();
+        assert::<DmabufStream>();
+        assert::<OwnedFrame>();
+        assert::<FrameGuard<'_>>();
+        assert::<MplaneReadStream>();
+        assert::<SharedStream>();
+        assert::<FrameBuffer>();
     }
 }
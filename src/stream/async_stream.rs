@@ -0,0 +1,88 @@
+//! Integration with the tokio runtime.
+
+use std::io;
+use std::os::unix::prelude::AsRawFd;
+
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use tokio::io::unix::AsyncFd;
+
+use super::{OwnedFrame, ReadBufferView, ReadStream};
+
+/// A [`ReadStream`] that is driven by the tokio reactor instead of blocking a thread.
+///
+/// All methods are cancellation safe: a buffer is only dequeued once it is ready, and it is
+/// processed and enqueued again without yielding to the runtime. Dropping a pending future leaves
+/// the stream as it was, so the next call will pick up the frame that became available.
+///
+/// This works with video and metadata capture streams alike, so a UVC camera's frames and
+/// metadata can be awaited concurrently (eg. with `tokio::select!`).
+pub struct AsyncStream {
+    inner: AsyncFd<ReadStream>,
+}
+
+impl AsyncStream {
+    /// Registers `stream` with the tokio reactor.
+    ///
+    /// This puts the stream's file descriptor into non-blocking mode. Since that mode is shared
+    /// between all duplicates of the file descriptor, other handles to the same [`Device`] will
+    /// also become non-blocking.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if called outside of a tokio runtime, or if the runtime's I/O driver is
+    /// not enabled.
+    ///
+    /// [`Device`]: crate::Device
+    pub fn new(stream: ReadStream) -> io::Result<Self> {
+        if !stream.is_nonblocking()? {
+            let flags = fcntl(stream.file.as_raw_fd(), FcntlArg::F_GETFL)?;
+            let flags = OFlag::from_bits_truncate(flags) | OFlag::O_NONBLOCK;
+            fcntl(stream.file.as_raw_fd(), FcntlArg::F_SETFL(flags))?;
+        }
+
+        Ok(Self {
+            inner: AsyncFd::new(stream)?,
+        })
+    }
+
+    /// Returns a reference to the wrapped stream.
+    pub fn get_ref(&self) -> &ReadStream {
+        self.inner.get_ref()
+    }
+
+    /// Returns a mutable reference to the wrapped stream.
+    ///
+    /// The stream's file descriptor is in non-blocking mode, so [`ReadStream::dequeue`] will
+    /// return an error of kind [`io::ErrorKind::WouldBlock`] instead of blocking.
+    pub fn get_mut(&mut self) -> &mut ReadStream {
+        self.inner.get_mut()
+    }
+
+    /// Deregisters the stream from the tokio reactor and returns it.
+    ///
+    /// The stream's file descriptor stays in non-blocking mode.
+    pub fn into_inner(self) -> ReadStream {
+        self.inner.into_inner()
+    }
+
+    /// Waits for a filled buffer, passes it to `cb`, then enqueues it again.
+    ///
+    /// See [`ReadStream::dequeue`] for how errors returned by `cb` are handled.
+    pub async fn dequeue<T>(
+        &mut self,
+        cb: impl FnOnce(ReadBufferView<'_>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        loop {
+            let mut guard = self.inner.readable_mut().await?;
+            match guard.get_inner_mut().try_dqbuf()? {
+                Some(buf) => return guard.get_inner_mut().process_dequeued(buf, cb),
+                None => guard.clear_ready(),
+            }
+        }
+    }
+
+    /// Waits for the next frame and copies it out of the stream's buffer.
+    pub async fn next_frame(&mut self) -> io::Result<OwnedFrame> {
+        self.dequeue(|view| Ok(view.to_owned_frame())).await
+    }
+}
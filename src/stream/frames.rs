@@ -0,0 +1,119 @@
+//! An [`Iterator`] over the frames of a [`ReadStream`].
+
+use std::io;
+use std::os::unix::prelude::AsRawFd;
+
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+
+use super::{OwnedFrame, ReadStream};
+
+/// Number of frame allocations a [`Frames`] iterator keeps for reuse by default.
+const DEFAULT_POOL_SIZE: usize = 4;
+
+/// An iterator over the frames captured by a [`ReadStream`], returned by [`ReadStream::frames`].
+///
+/// Every frame is copied out of the stream's buffer, so that the buffer can be enqueued again
+/// immediately. Frames that are no longer needed can be handed back with [`Frames::recycle`], so
+/// that their allocation is reused for a later frame.
+///
+/// The iterator ends when an error occurs (for example, `ENODEV` when the device is unplugged).
+/// The error can be retrieved afterwards with [`Frames::error`] or [`Frames::take_error`].
+pub struct Frames<'a> {
+    stream: &'a mut ReadStream,
+    pool: Vec<Vec<u8>>,
+    pool_size: usize,
+    error: Option<io::Error>,
+}
+
+impl<'a> Frames<'a> {
+    pub(super) fn new(stream: &'a mut ReadStream) -> Self {
+        Self {
+            stream,
+            pool: Vec::new(),
+            pool_size: DEFAULT_POOL_SIZE,
+            error: None,
+        }
+    }
+
+    /// Sets the maximum number of recycled frame allocations to keep around for reuse.
+    ///
+    /// Frames passed to [`Frames::recycle`] while the pool is full are freed. A pool size of 0
+    /// disables reuse.
+    pub fn with_pool_size(mut self, pool_size: usize) -> Self {
+        self.pool_size = pool_size;
+        self.pool.truncate(pool_size);
+        self
+    }
+
+    /// Waits for the next frame and copies it out of the stream's buffer.
+    ///
+    /// Unlike the [`Iterator`] implementation, this returns errors directly, and does not end the
+    /// iteration when one occurs.
+    pub fn next_frame(&mut self) -> io::Result<OwnedFrame> {
+        let mut data = self.pool.pop().unwrap_or_default();
+        loop {
+            if let Some(buf) = self.stream.try_dqbuf()? {
+                return self.stream.process_dequeued(buf, |view| {
+                    data.clear();
+                    data.extend_from_slice(&view);
+                    Ok(OwnedFrame {
+                        index: view.index,
+                        flags: view.flags,
+                        sequence: view.sequence,
+                        timestamp: view.timestamp,
+                        data,
+                    })
+                });
+            }
+
+            // The device is in non-blocking mode and no buffer is ready yet.
+            let mut fds = [PollFd::new(self.stream.as_raw_fd(), PollFlags::POLLIN)];
+            match poll(&mut fds, -1) {
+                Ok(_) | Err(Errno::EINTR) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Hands a frame back to the iterator, so that its allocation can be reused.
+    pub fn recycle(&mut self, frame: OwnedFrame) {
+        if self.pool.len() < self.pool_size {
+            self.pool.push(frame.into_vec());
+        }
+    }
+
+    /// Returns the error that ended the iteration, if any.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Takes the error that ended the iteration, if any.
+    ///
+    /// Once the error has been taken, the iterator can be resumed.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+impl Iterator for Frames<'_> {
+    type Item = OwnedFrame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        loop {
+            match self.next_frame() {
+                Ok(frame) => return Some(frame),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    log::debug!("ending frame iteration: {}", e);
+                    self.error = Some(e);
+                    return None;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,344 @@
+//! Streaming I/O with multi-planar buffers.
+
+use std::ffi::c_void;
+use std::fs::File;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::os::unix::prelude::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::time::Duration;
+use std::{io, mem, slice};
+
+use nix::sys::mman::{mmap, munmap, MapFlags};
+
+use super::BufFlag;
+use super::{buffer_timestamp, file_into_device, free_buffers, mmap_protection};
+use crate::buf_type::BufType;
+use crate::format::PixFormatMplane;
+use crate::raw::{self, VIDEO_MAX_PLANES};
+use crate::shared::Memory;
+use crate::Device;
+
+/// A single memory plane of a buffer, mapped into our address space.
+struct PlaneMapping {
+    ptr: *mut c_void,
+    length: u32,
+}
+
+/// A stream that reads data from a multi-planar V4L2 device
+/// ([`VIDEO_CAPTURE_MPLANE`][BufType::VIDEO_CAPTURE_MPLANE]).
+///
+/// Every buffer of a multi-planar stream consists of one or more memory planes, which are mapped
+/// separately. The number of memory planes is determined by the negotiated format (see
+/// [`PixFormatMplane::num_planes`]).
+pub struct MplaneReadStream {
+    file: File,
+    buf_type: BufType,
+    format: PixFormatMplane,
+    /// The buffer index equals its index in this vector.
+    buffers: Vec<Vec<PlaneMapping>>,
+}
+
+// The mappings are only accessed through `&self` or `&mut self`, like any owned memory.
+unsafe impl Send for MplaneReadStream {}
+unsafe impl Sync for MplaneReadStream {}
+
+impl MplaneReadStream {
+    pub(crate) fn new(
+        file: File,
+        buf_type: BufType,
+        format: PixFormatMplane,
+        buffer_count: u32,
+    ) -> io::Result<Self> {
+        let fd = file.as_raw_fd();
+
+        let mut req_bufs: raw::RequestBuffers = unsafe { mem::zeroed() };
+        req_bufs.count = buffer_count;
+        req_bufs.type_ = buf_type;
+        req_bufs.memory = Memory::MMAP;
+        unsafe {
+            raw::reqbufs(fd, &mut req_bufs)?;
+        }
+        log::debug!("{:?}", req_bufs);
+
+        let mut this = Self {
+            file,
+            buf_type,
+            format,
+            buffers: Vec::with_capacity(req_bufs.count as usize),
+        };
+        let protection = mmap_protection(fd)?;
+        for i in 0..req_bufs.count {
+            let mut planes: [raw::Plane; VIDEO_MAX_PLANES] = unsafe { mem::zeroed() };
+            let mut buf = this.raw_buffer(&mut planes);
+            buf.index = i;
+
+            unsafe {
+                raw::querybuf(fd, &mut buf)?;
+            }
+
+            let mut mappings = Vec::with_capacity(buf.length as usize);
+            for plane in &planes[..buf.length as usize] {
+                let ptr = unsafe {
+                    mmap(
+                        None,
+                        NonZeroUsize::try_from(plane.length as usize)
+                            .expect("V4L2 returned plane size of 0"),
+                        protection,
+                        MapFlags::MAP_SHARED,
+                        fd,
+                        plane.m.mem_offset.into(),
+                    )?
+                };
+                mappings.push(PlaneMapping {
+                    ptr,
+                    length: plane.length,
+                });
+            }
+            this.buffers.push(mappings);
+        }
+
+        for i in 0..this.buffers.len() {
+            this.enqueue(i as u32)?;
+        }
+
+        unsafe {
+            raw::streamon(this.file.as_raw_fd(), &this.buf_type)?;
+        }
+
+        Ok(this)
+    }
+
+    /// Returns a `v4l2_buffer` for this stream that uses `planes` as its plane array.
+    fn raw_buffer(&self, planes: &mut [raw::Plane; VIDEO_MAX_PLANES]) -> raw::Buffer {
+        let mut buf: raw::Buffer = unsafe { mem::zeroed() };
+        buf.type_ = self.buf_type;
+        buf.memory = Memory::MMAP;
+        buf.m.planes = planes.as_mut_ptr();
+        buf.length = VIDEO_MAX_PLANES as u32;
+        buf
+    }
+
+    fn enqueue(&self, index: u32) -> io::Result<()> {
+        let mut planes: [raw::Plane; VIDEO_MAX_PLANES] = unsafe { mem::zeroed() };
+        let mut buf = self.raw_buffer(&mut planes);
+        buf.index = index;
+
+        unsafe {
+            raw::qbuf(self.file.as_raw_fd(), &mut buf)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the format that was in effect when the stream was created.
+    pub fn format(&self) -> &PixFormatMplane {
+        &self.format
+    }
+
+    /// Returns the number of buffers the driver allocated for this stream.
+    pub fn num_buffers(&self) -> u32 {
+        self.buffers.len() as u32
+    }
+
+    /// Dequeues a buffer, passes it to `cb`, then enqueues it again.
+    ///
+    /// If `cb` returns an error, this function will still try to enqueue the buffer again. If that
+    /// fails, the error that occurred during enqueuing will be returned, if it succeeds, the error
+    /// returned by `cb` will be returned.
+    pub fn dequeue<T>(
+        &mut self,
+        cb: impl FnOnce(MplaneBufferView<'_>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut planes: [raw::Plane; VIDEO_MAX_PLANES] = unsafe { mem::zeroed() };
+        let mut buf = self.raw_buffer(&mut planes);
+
+        unsafe {
+            raw::dqbuf(self.file.as_raw_fd(), &mut buf)?;
+        }
+
+        let mappings = &self.buffers[buf.index as usize];
+        let view = MplaneBufferView {
+            index: buf.index,
+            flags: buf.flags,
+            sequence: buf.sequence,
+            timestamp: buffer_timestamp(&buf),
+            mappings,
+            planes,
+        };
+
+        let res = cb(view);
+
+        self.enqueue(buf.index)?;
+
+        res
+    }
+}
+
+impl MplaneReadStream {
+    /// Stops streaming and frees all buffers, reporting any errors.
+    ///
+    /// Dropping the stream does the same, but can only log errors.
+    pub fn close(mut self) -> io::Result<()> {
+        self.teardown()
+    }
+
+    /// Stops streaming, frees all buffers, and returns the [`Device`] the stream was created from.
+    ///
+    /// See [`ReadStream::into_device`][super::ReadStream::into_device].
+    pub fn into_device(mut self) -> io::Result<Device> {
+        self.teardown()?;
+        file_into_device(&self.file)
+    }
+
+    /// Turns off the stream, unmaps all planes, and makes the driver free the buffers.
+    ///
+    /// All steps are attempted, even if an earlier one fails. The first error is returned.
+    fn teardown(&mut self) -> io::Result<()> {
+        if self.buffers.is_empty() {
+            return Ok(());
+        }
+
+        // Stop streaming so that the driver no longer accesses the buffers.
+        let mut res = Ok(());
+        if let Err(e) = unsafe { raw::streamoff(self.file.as_raw_fd(), &self.buf_type) } {
+            res = Err(e.into());
+        }
+        for mapping in self.buffers.drain(..).flatten() {
+            let r = unsafe { munmap(mapping.ptr, mapping.length as usize) };
+            if let (Ok(()), Err(e)) = (&res, r) {
+                res = Err(e.into());
+            }
+        }
+        res.and(free_buffers(&self.file, self.buf_type, Memory::MMAP))
+    }
+}
+
+impl Drop for MplaneReadStream {
+    fn drop(&mut self) {
+        if let Err(e) = self.teardown() {
+            log::error!("failed to tear down capture stream: {}", e);
+        }
+    }
+}
+
+/// The file descriptor of the stream's device.
+///
+/// The file descriptor becomes readable when a filled buffer can be dequeued.
+impl AsRawFd for MplaneReadStream {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl AsFd for MplaneReadStream {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+/// Immutable view into a dequeued (filled) buffer of a [`MplaneReadStream`].
+pub struct MplaneBufferView<'a> {
+    index: u32,
+    flags: BufFlag,
+    sequence: u32,
+    timestamp: Option<Duration>,
+    mappings: &'a [PlaneMapping],
+    planes: [raw::Plane; VIDEO_MAX_PLANES],
+}
+
+impl<'a> MplaneBufferView<'a> {
+    /// Returns the index of the buffer in the stream.
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    buffer_metadata!();
+
+    /// Returns the number of memory planes in this buffer.
+    #[inline]
+    pub fn num_planes(&self) -> usize {
+        self.mappings.len()
+    }
+
+    /// Returns the data of memory plane `plane`.
+    ///
+    /// This excludes any data the driver placed in front of the image data (the plane's
+    /// `data_offset`), and any unused space at the end of the plane.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `plane` is not less than [`MplaneBufferView::num_planes`].
+    pub fn plane(&self, plane: usize) -> &'a [u8] {
+        let range = self.plane_range(plane);
+        &self.plane_raw(plane)[range]
+    }
+
+    /// Returns the number of bytes of memory plane `plane` that are used, including the
+    /// `data_offset`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `plane` is not less than [`MplaneBufferView::num_planes`].
+    #[inline]
+    pub fn plane_bytes_used(&self, plane: usize) -> usize {
+        assert!(plane < self.num_planes());
+        self.planes[plane].bytesused as usize
+    }
+
+    /// Returns the *entire* memory plane `plane`, including unused space.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `plane` is not less than [`MplaneBufferView::num_planes`].
+    pub fn plane_raw(&self, plane: usize) -> &'a [u8] {
+        let mapping = &self.mappings[plane];
+        unsafe { slice::from_raw_parts(mapping.ptr as *const u8, mapping.length as usize) }
+    }
+
+    fn plane_range(&self, plane: usize) -> Range<usize> {
+        let length = self.mappings[plane].length as usize;
+        let raw = &self.planes[plane];
+        let end = (raw.bytesused as usize).min(length);
+        let start = (raw.data_offset as usize).min(end);
+        start..end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plane_range_is_clamped() {
+        let mappings = [
+            PlaneMapping {
+                ptr: std::ptr::null_mut(),
+                length: 100,
+            },
+            PlaneMapping {
+                ptr: std::ptr::null_mut(),
+                length: 50,
+            },
+        ];
+        let mut planes: [raw::Plane; VIDEO_MAX_PLANES] = unsafe { mem::zeroed() };
+        planes[0].bytesused = 80;
+        planes[0].data_offset = 16;
+        planes[1].bytesused = 60;
+        planes[1].data_offset = 70;
+        let view = MplaneBufferView {
+            index: 0,
+            flags: BufFlag::empty(),
+            sequence: 0,
+            timestamp: None,
+            mappings: &mappings,
+            planes,
+        };
+
+        assert_eq!(view.num_planes(), 2);
+        assert_eq!(view.plane_range(0), 16..80);
+        assert_eq!(view.plane_range(1), 50..50);
+    }
+}
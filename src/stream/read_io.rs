@@ -0,0 +1,294 @@
+//! Capture via `read(2)`, for devices that do not support streaming I/O.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::os::unix::prelude::{AsFd, AsRawFd, BorrowedFd, RawFd};
+
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+
+use super::{ReadBufferView, ReadStream};
+use crate::format::Format;
+use crate::shared::BufFlag;
+
+/// The I/O method to use for capturing data from a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IoMethod {
+    /// Use streaming I/O if the device supports it, and fall back to `read(2)` otherwise.
+    #[default]
+    Auto,
+    /// Always use streaming I/O with memory-mapped buffers.
+    Streaming,
+    /// Always use `read(2)`.
+    Read,
+}
+
+/// A stream that reads frames from a V4L2 device using `read(2)`.
+///
+/// This is intended for simple devices that only support the
+/// [`READWRITE`][crate::CapabilityFlags::READWRITE] capability. It copies every frame into an
+/// internal buffer, so it is slower than a [`ReadStream`].
+pub struct ReadIoStream {
+    file: File,
+    format: Format,
+    buf: Vec<u8>,
+    /// Whether frames have a fixed size, so that short reads have to be continued until the buffer
+    /// is full. Compressed formats return one variably-sized frame per `read`.
+    fixed_size: bool,
+    sequence: u32,
+}
+
+impl ReadIoStream {
+    pub(crate) fn new(file: File, format: Format, frame_size: usize, fixed_size: bool) -> Self {
+        Self {
+            file,
+            format,
+            buf: vec![0; frame_size],
+            fixed_size,
+            sequence: 0,
+        }
+    }
+
+    /// Returns the format that was in effect when the stream was created.
+    ///
+    /// See [`ReadStream::format`].
+    pub fn format(&self) -> &Format {
+        &self.format
+    }
+
+    /// Reads the next frame, passes it to `cb`, and returns the result of `cb`.
+    ///
+    /// Since the driver does not report sequence numbers in this mode, the
+    /// [`ReadBufferView::sequence`] is a counter of the frames read from this stream.
+    ///
+    /// If the device was opened in non-blocking mode, this returns an error of kind
+    /// [`io::ErrorKind::WouldBlock`] when no data is available. Once a frame has been partially
+    /// read, this will wait for the rest of it, even in non-blocking mode.
+    pub fn dequeue<T>(
+        &mut self,
+        cb: impl FnOnce(ReadBufferView<'_>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let len = self.read_frame()?;
+        let view = ReadBufferView {
+            index: 0,
+            flags: BufFlag::empty(),
+            data: &self.buf,
+            bytesused: len,
+            sequence: self.sequence,
+            timestamp: None,
+            gap: 0,
+        };
+        self.sequence = self.sequence.wrapping_add(1);
+
+        cb(view)
+    }
+
+    fn read_frame(&mut self) -> io::Result<usize> {
+        let mut filled = 0;
+        loop {
+            match self.file.read(&mut self.buf[filled..]) {
+                Ok(0) if filled == 0 => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "device returned no data",
+                    ))
+                }
+                Ok(0) => return Ok(filled),
+                Ok(n) => {
+                    filled += n;
+                    if !self.fixed_size || filled == self.buf.len() {
+                        return Ok(filled);
+                    }
+                    log::trace!("short read ({}/{} bytes)", filled, self.buf.len());
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock && filled != 0 => {
+                    // Don't discard the partial frame, wait for the rest.
+                    let mut fds = [PollFd::new(self.file.as_raw_fd(), PollFlags::POLLIN)];
+                    match poll(&mut fds, -1) {
+                        Ok(_) | Err(Errno::EINTR) => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl AsRawFd for ReadIoStream {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl AsFd for ReadIoStream {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+/// A capture stream using either streaming I/O or `read(2)`.
+///
+/// Returned by `into_capture_stream`, which selects the I/O method according to an [`IoMethod`].
+pub enum CaptureStream {
+    Streaming(ReadStream),
+    Read(ReadIoStream),
+}
+
+impl CaptureStream {
+    /// Returns the I/O method used by this stream ([`IoMethod::Streaming`] or [`IoMethod::Read`]).
+    pub fn io_method(&self) -> IoMethod {
+        match self {
+            CaptureStream::Streaming(_) => IoMethod::Streaming,
+            CaptureStream::Read(_) => IoMethod::Read,
+        }
+    }
+
+    /// Waits for the next frame and passes it to `cb`.
+    ///
+    /// See [`ReadStream::dequeue`] and [`ReadIoStream::dequeue`] for details.
+    pub fn dequeue<T>(
+        &mut self,
+        cb: impl FnOnce(ReadBufferView<'_>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        match self {
+            CaptureStream::Streaming(s) => s.dequeue(cb),
+            CaptureStream::Read(s) => s.dequeue(cb),
+        }
+    }
+
+    /// Returns the format that was in effect when the stream was created.
+    ///
+    /// See [`ReadStream::format`].
+    pub fn format(&self) -> &Format {
+        match self {
+            CaptureStream::Streaming(s) => s.format(),
+            CaptureStream::Read(s) => s.format(),
+        }
+    }
+
+    /// Returns the number of buffers currently held by the application.
+    ///
+    /// See [`ReadStream::held_buffers`]. Streams using `read(2)` never hold buffers.
+    pub fn held_buffers(&self) -> u32 {
+        match self {
+            CaptureStream::Streaming(s) => s.held_buffers(),
+            CaptureStream::Read(_) => 0,
+        }
+    }
+
+    /// Enqueues all buffers that are held by the application.
+    ///
+    /// See [`ReadStream::requeue_all`]. This does nothing for streams using `read(2)`.
+    pub fn requeue_all(&mut self) -> io::Result<()> {
+        match self {
+            CaptureStream::Streaming(s) => s.requeue_all(),
+            CaptureStream::Read(_) => Ok(()),
+        }
+    }
+
+    /// Allocates `count` additional buffers for the stream.
+    ///
+    /// See [`ReadStream::create_buffers`]. Streams using `read(2)` have no buffers, so this
+    /// returns an error of kind [`io::ErrorKind::Unsupported`] for them.
+    pub fn create_buffers(&mut self, count: u32) -> io::Result<Range<u32>> {
+        match self {
+            CaptureStream::Streaming(s) => s.create_buffers(count),
+            CaptureStream::Read(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "streams using `read(2)` have no buffers",
+            )),
+        }
+    }
+}
+
+impl AsRawFd for CaptureStream {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            CaptureStream::Streaming(s) => s.as_raw_fd(),
+            CaptureStream::Read(s) => s.as_raw_fd(),
+        }
+    }
+}
+
+impl AsFd for CaptureStream {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        match self {
+            CaptureStream::Streaming(s) => s.as_fd(),
+            CaptureStream::Read(s) => s.as_fd(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::os::unix::prelude::FromRawFd;
+
+    use crate::format::{PixFormat, PixelFormat};
+
+    use super::*;
+
+    fn test_format() -> Format {
+        Format::VideoCapture(PixFormat::new(4, 1, PixelFormat::YUYV))
+    }
+
+    fn pipe() -> (File, File) {
+        let (r, w) = nix::unistd::pipe().unwrap();
+        unsafe { (File::from_raw_fd(r), File::from_raw_fd(w)) }
+    }
+
+    #[test]
+    fn short_reads_are_continued() {
+        let (r, mut w) = pipe();
+        let mut stream = ReadIoStream::new(r, test_format(), 8, true);
+
+        w.write_all(&[1, 2, 3]).unwrap();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            w.write_all(&[4, 5, 6, 7, 8, 9]).unwrap();
+            w
+        });
+
+        stream
+            .dequeue(|view| {
+                assert_eq!(&*view, &[1, 2, 3, 4, 5, 6, 7, 8]);
+                assert_eq!(view.sequence(), 0);
+                Ok(())
+            })
+            .unwrap();
+        drop(writer.join().unwrap());
+
+        // The rest of the data is returned as a partial frame at EOF.
+        stream
+            .dequeue(|view| {
+                assert_eq!(&*view, &[9]);
+                assert_eq!(view.sequence(), 1);
+                Ok(())
+            })
+            .unwrap();
+        let err = stream.dequeue(|_| Ok(())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn variable_size_frames() {
+        let (r, mut w) = pipe();
+        let mut stream = ReadIoStream::new(r, test_format(), 8, false);
+
+        w.write_all(&[1, 2, 3]).unwrap();
+        stream
+            .dequeue(|view| {
+                assert_eq!(&*view, &[1, 2, 3]);
+                assert_eq!(view.raw_buffer().len(), 8);
+                Ok(())
+            })
+            .unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+//! Waiting for frames from multiple streams on a single thread.
+
+use std::os::unix::prelude::AsRawFd;
+use std::time::Duration;
+use std::{fmt, io};
+
+use nix::poll::{PollFd, PollFlags};
+
+use super::{poll_timeout, ReadBufferView, ReadStream};
+
+/// A set of capture streams that are waited on together.
+///
+/// This allows capturing from several devices (eg. multiple cameras, or a camera's video and
+/// metadata nodes) on a single thread. Streams are identified by the index returned by
+/// [`StreamSet::push`].
+pub struct StreamSet<'a> {
+    streams: Vec<&'a mut ReadStream>,
+    /// Index of the stream that is serviced first by the next [`StreamSet::poll`].
+    next: usize,
+}
+
+impl<'a> StreamSet<'a> {
+    /// Creates an empty stream set.
+    pub fn new() -> Self {
+        Self {
+            streams: Vec::new(),
+            next: 0,
+        }
+    }
+
+    /// Adds a stream to the set and returns its index.
+    pub fn push(&mut self, stream: &'a mut ReadStream) -> usize {
+        self.streams.push(stream);
+        self.streams.len() - 1
+    }
+
+    /// Returns the number of streams in the set.
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Returns whether the set contains no streams.
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    /// Waits at most `timeout` for any of the streams to have a filled buffer, then dequeues one
+    /// buffer from every stream that has one ready and passes it to `cb`, together with the index
+    /// of its stream.
+    ///
+    /// Returns the number of buffers passed to `cb`, which is 0 if the timeout elapsed.
+    ///
+    /// Every call dequeues at most one buffer per stream, and the stream that is serviced first
+    /// rotates between calls, so a fast stream cannot starve a slow one.
+    ///
+    /// # Errors
+    ///
+    /// If dequeuing from a stream fails (for example, with `ENODEV` because the device was
+    /// unplugged), or `cb` returns an error, this returns the error wrapped in a
+    /// [`StreamSetError`] that identifies the stream. The streams after it are not serviced
+    /// anymore during this call. The kind of the returned [`io::Error`] is that of the original
+    /// error.
+    pub fn poll(
+        &mut self,
+        timeout: Duration,
+        mut cb: impl FnMut(usize, ReadBufferView<'_>) -> io::Result<()>,
+    ) -> io::Result<usize> {
+        if self.streams.is_empty() {
+            return Ok(0);
+        }
+
+        let mut fds: Vec<_> = self
+            .streams
+            .iter()
+            .map(|s| PollFd::new(s.as_raw_fd(), PollFlags::POLLIN))
+            .collect();
+        if poll_timeout(&mut fds, timeout)? == 0 {
+            return Ok(0);
+        }
+
+        let count = self.streams.len();
+        let first = self.next;
+        self.next = (self.next + 1) % count;
+
+        let mut dequeued = 0;
+        for index in (first..count).chain(0..first) {
+            let ready = fds[index]
+                .revents()
+                .is_some_and(|ev| !ev.is_empty() && ev != PollFlags::POLLNVAL);
+            if !ready {
+                continue;
+            }
+
+            // `POLLERR` is reported by the following `VIDIOC_DQBUF`.
+            let stream = &mut *self.streams[index];
+            let res = match stream.try_dqbuf() {
+                Ok(Some(buf)) => stream.process_dequeued(buf, |view| cb(index, view)),
+                Ok(None) => continue,
+                Err(e) => Err(e),
+            };
+            if let Err(error) = res {
+                return Err(StreamSetError {
+                    stream: index,
+                    error,
+                }
+                .into());
+            }
+            dequeued += 1;
+        }
+
+        Ok(dequeued)
+    }
+}
+
+impl Default for StreamSet<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An error that occurred while servicing one of the streams of a [`StreamSet`].
+///
+/// [`StreamSet::poll`] returns this wrapped in an [`io::Error`]. Use
+/// [`StreamSetError::from_io_error`] to retrieve it.
+#[derive(Debug)]
+pub struct StreamSetError {
+    stream: usize,
+    error: io::Error,
+}
+
+impl StreamSetError {
+    /// Returns the [`StreamSetError`] wrapped in `error`, if any.
+    pub fn from_io_error(error: &io::Error) -> Option<&StreamSetError> {
+        error.get_ref()?.downcast_ref()
+    }
+
+    /// Returns the index of the stream that failed.
+    pub fn stream(&self) -> usize {
+        self.stream
+    }
+
+    /// Returns the error that occurred.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+}
+
+impl fmt::Display for StreamSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stream {}: {}", self.stream, self.error)
+    }
+}
+
+impl std::error::Error for StreamSetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<StreamSetError> for io::Error {
+    fn from(e: StreamSetError) -> Self {
+        io::Error::new(e.error.kind(), e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_identifies_stream() {
+        let error: io::Error = StreamSetError {
+            stream: 1,
+            error: io::Error::from_raw_os_error(nix::libc::ENODEV),
+        }
+        .into();
+        assert_eq!(
+            error.kind(),
+            io::Error::from_raw_os_error(nix::libc::ENODEV).kind()
+        );
+
+        let inner = StreamSetError::from_io_error(&error).unwrap();
+        assert_eq!(inner.stream(), 1);
+        assert_eq!(inner.error().raw_os_error(), Some(nix::libc::ENODEV));
+    }
+}
@@ -0,0 +1,125 @@
+//! Sharing captured frames between threads without copying them.
+
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{io, slice};
+
+use super::{buffer_timestamp, bytes_used, BufFlag, ReadStream};
+
+/// A capture stream whose frames are handed out as reference-counted [`FrameBuffer`]s.
+///
+/// Created with [`ReadStream::into_shared`]. A [`FrameBuffer`] refers directly to the stream's
+/// memory-mapped buffer, and can be cloned and sent to other threads (as `Arc<FrameBuffer>`)
+/// without copying the frame data. The buffer is enqueued again once the last reference to it is
+/// dropped.
+///
+/// While frames are outstanding, the driver has fewer buffers to capture into, so it may drop
+/// frames. If every buffer is held, [`SharedStream::dequeue`] fails with an
+/// [`AllBuffersHeld`][super::AllBuffersHeld] error.
+pub struct SharedStream {
+    stream: Arc<ReadStream>,
+}
+
+impl SharedStream {
+    pub(super) fn new(stream: ReadStream) -> Self {
+        Self {
+            stream: Arc::new(stream),
+        }
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &ReadStream {
+        &self.stream
+    }
+
+    /// Returns the underlying stream, if no [`FrameBuffer`] is outstanding anymore.
+    pub fn try_into_inner(self) -> Result<ReadStream, Self> {
+        Arc::try_unwrap(self.stream).map_err(|stream| Self { stream })
+    }
+
+    /// Waits for the next frame and returns it as a shared [`FrameBuffer`].
+    ///
+    /// # Errors
+    ///
+    /// If every buffer of the stream is held by a [`FrameBuffer`], this returns an
+    /// [`AllBuffersHeld`][super::AllBuffersHeld] error instead of waiting for a buffer that might
+    /// never be released.
+    pub fn dequeue(&mut self) -> io::Result<Arc<FrameBuffer>> {
+        let stream = &self.stream;
+        let buf = stream.dqbuf_held()?;
+        let buffer = &stream.buffers.buffers[buf.index as usize];
+
+        Ok(Arc::new(FrameBuffer {
+            stream: self.stream.clone(),
+            index: buf.index,
+            flags: buf.flags,
+            ptr: buffer.ptr as *const u8,
+            bytesused: bytes_used(&buf, buffer),
+            sequence: buf.sequence,
+            timestamp: buffer_timestamp(&buf),
+            gap: stream.sequence.lock().unwrap().observe(buf.sequence),
+            dequeued_at: stream.stats_dequeued(&buf),
+        }))
+    }
+}
+
+/// A dequeued buffer of a [`SharedStream`], which is enqueued again when dropped.
+///
+/// Dereferences to the used portion of the buffer.
+pub struct FrameBuffer {
+    /// Keeps the mapping alive.
+    stream: Arc<ReadStream>,
+    index: u32,
+    flags: BufFlag,
+    ptr: *const u8,
+    bytesused: usize,
+    sequence: u32,
+    timestamp: Option<Duration>,
+    gap: u32,
+    dequeued_at: Option<Instant>,
+}
+
+// Safety: the buffer's mapping stays valid as long as the `ReadStream` exists, which `stream`
+// guarantees. The buffer is dequeued for the whole lifetime of the `FrameBuffer`, so the driver does
+// not write to it, and only shared access to the data is ever handed out, so concurrent reads from
+// several threads are fine.
+unsafe impl Send for FrameBuffer {}
+unsafe impl Sync for FrameBuffer {}
+
+impl FrameBuffer {
+    /// Returns the index of the buffer in the stream.
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    buffer_metadata!();
+
+    /// Returns the number of frames the driver dropped between the previously dequeued frame and
+    /// this one.
+    ///
+    /// See [`ReadBufferView::gap_since_previous`][super::ReadBufferView::gap_since_previous].
+    #[inline]
+    pub fn gap_since_previous(&self) -> u32 {
+        self.gap
+    }
+}
+
+impl Deref for FrameBuffer {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.ptr, self.bytesused) }
+    }
+}
+
+impl Drop for FrameBuffer {
+    fn drop(&mut self) {
+        match self.stream.enqueue(self.index) {
+            Ok(()) => self.stream.stats_requeued(self.dequeued_at),
+            Err(e) => log::error!("failed to enqueue buffer {}: {}", self.index, e),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Capture statistics collected by a [`ReadStream`][super::ReadStream].
+
+use std::time::{Duration, Instant};
+
+/// Weight of the newest sample in the moving averages.
+const SMOOTHING: f64 = 1.0 / 16.0;
+
+/// Statistics about a capture stream, returned by [`ReadStream::stats`][super::ReadStream::stats].
+///
+/// Collection has to be enabled with
+/// [`ReadStream::enable_stats`][super::ReadStream::enable_stats]. The statistics are reset when
+/// streaming is (re)started with [`ReadStream::stream_on`][super::ReadStream::stream_on].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StreamStats {
+    /// The number of frames dequeued since statistics collection was enabled or streaming was
+    /// restarted.
+    pub frames: u64,
+    /// The number of frames the driver dropped (see
+    /// [`ReadStream::dropped_frames`][super::ReadStream::dropped_frames]).
+    pub dropped_frames: u64,
+    /// Moving average of the frame rate, in frames per second.
+    ///
+    /// This is computed from the timestamps the driver recorded for the buffers (see
+    /// [`ReadBufferView::timestamp`][super::ReadBufferView::timestamp]), not from the time the
+    /// frames were dequeued, so it is not affected by scheduling delays in the application. It is
+    /// `None` until two frames with timestamps have been dequeued.
+    pub fps: Option<f64>,
+    /// Moving average of the time between dequeuing a buffer and enqueuing it again, which is the
+    /// time the application holds on to a buffer.
+    ///
+    /// This is measured with [`Instant`] (`CLOCK_MONOTONIC`). It is `None` until the first buffer
+    /// has been enqueued again.
+    pub avg_latency: Option<Duration>,
+    /// The longest time between dequeuing a buffer and enqueuing it again.
+    pub max_latency: Duration,
+    /// The number of buffers currently dequeued by the application.
+    pub buffers_in_userspace: u32,
+    /// The number of buffers currently enqueued in the driver, waiting to be filled or dequeued.
+    pub buffers_in_driver: u32,
+}
+
+/// Collects the per-frame parts of [`StreamStats`].
+///
+/// This is updated for every frame, so it must not allocate or perform system calls.
+#[derive(Debug, Default)]
+pub(super) struct StatsCollector {
+    frames: u64,
+    last_timestamp: Option<Duration>,
+    /// Moving average of the interval between buffer timestamps, in seconds.
+    avg_interval: Option<f64>,
+    /// Moving average of the dequeue-to-requeue latency, in seconds.
+    avg_latency: Option<f64>,
+    max_latency: Duration,
+}
+
+impl StatsCollector {
+    /// Records a dequeued frame and returns the time it was dequeued at.
+    pub(super) fn frame_dequeued(&mut self, timestamp: Option<Duration>) -> Instant {
+        self.frames += 1;
+        if let Some(timestamp) = timestamp {
+            if let Some(interval) = self
+                .last_timestamp
+                .and_then(|last| timestamp.checked_sub(last))
+                .filter(|interval| !interval.is_zero())
+            {
+                self.avg_interval = Some(moving_average(self.avg_interval, interval));
+            }
+            self.last_timestamp = Some(timestamp);
+        }
+
+        Instant::now()
+    }
+
+    /// Records that a buffer dequeued at `dequeued_at` was enqueued again.
+    pub(super) fn frame_requeued(&mut self, dequeued_at: Instant) {
+        let latency = dequeued_at.elapsed();
+        self.avg_latency = Some(moving_average(self.avg_latency, latency));
+        self.max_latency = self.max_latency.max(latency);
+    }
+
+    pub(super) fn to_stats(&self) -> StreamStats {
+        StreamStats {
+            frames: self.frames,
+            fps: self.avg_interval.map(|interval| 1.0 / interval),
+            avg_latency: self.avg_latency.map(Duration::from_secs_f64),
+            max_latency: self.max_latency,
+            ..StreamStats::default()
+        }
+    }
+}
+
+fn moving_average(avg: Option<f64>, sample: Duration) -> f64 {
+    let sample = sample.as_secs_f64();
+    match avg {
+        Some(avg) => avg + (sample - avg) * SMOOTHING,
+        None => sample,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fps_from_timestamps() {
+        let mut collector = StatsCollector::default();
+        assert_eq!(collector.to_stats().fps, None);
+
+        for i in 0..100 {
+            collector.frame_dequeued(Some(Duration::from_millis(1000 + i * 40)));
+        }
+        // Frames without timestamps, or with repeated ones, do not affect the frame rate.
+        collector.frame_dequeued(None);
+        collector.frame_dequeued(Some(Duration::from_millis(1000 + 99 * 40)));
+
+        let stats = collector.to_stats();
+        assert_eq!(stats.frames, 102);
+        assert!((stats.fps.unwrap() - 25.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn latency() {
+        let mut collector = StatsCollector::default();
+        let dequeued_at = collector.frame_dequeued(None);
+        collector.frame_requeued(dequeued_at - Duration::from_millis(10));
+
+        let stats = collector.to_stats();
+        assert!(stats.avg_latency.unwrap() >= Duration::from_millis(10));
+        assert!(stats.max_latency >= Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,162 @@
+//! Interpretation of buffer timestamps.
+
+use std::time::{Duration, SystemTime};
+
+use nix::time::{clock_gettime, ClockId};
+
+use super::BufFlag;
+
+/// The point in time at which the driver took a buffer's timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampSource {
+    /// The timestamp was taken when the last byte of the frame was received (or, for output
+    /// devices, when it was sent). This is the default.
+    EndOfFrame,
+    /// The timestamp was taken at the start of exposure of the frame.
+    StartOfExposure,
+}
+
+/// A buffer timestamp, along with the clock it was taken from.
+///
+/// The clock and source are signaled by the driver with the [`BufFlag::TIMESTAMP_MASK`] and
+/// [`BufFlag::TIMESTAMP_SRC_MASK`] bits of the buffer flags. Timestamps of different kinds cannot
+/// be compared with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameTimestamp {
+    /// The timestamp was taken from `CLOCK_MONOTONIC`.
+    ///
+    /// This is the same clock used by [`std::time::Instant`], so it can be compared with
+    /// timestamps of other devices and with the application's own measurements.
+    Monotonic(Duration, TimestampSource),
+    /// The driver did not specify which clock the timestamp was taken from.
+    Unknown(Duration, TimestampSource),
+    /// The timestamp was copied from the output buffer this buffer was produced from, by a
+    /// mem-to-mem device.
+    Copied(Duration, TimestampSource),
+}
+
+impl FrameTimestamp {
+    /// Interprets `timestamp` according to the timestamp bits in `flags`.
+    pub fn from_flags(flags: BufFlag, timestamp: Duration) -> Self {
+        let source = if flags & BufFlag::TIMESTAMP_SRC_MASK == BufFlag::TIMESTAMP_SRC_SOE {
+            TimestampSource::StartOfExposure
+        } else {
+            TimestampSource::EndOfFrame
+        };
+
+        let kind = flags & BufFlag::TIMESTAMP_MASK;
+        if kind == BufFlag::TIMESTAMP_MONOTONIC {
+            Self::Monotonic(timestamp, source)
+        } else if kind == BufFlag::TIMESTAMP_COPY {
+            Self::Copied(timestamp, source)
+        } else {
+            Self::Unknown(timestamp, source)
+        }
+    }
+
+    /// Returns the raw timestamp value.
+    pub fn duration(&self) -> Duration {
+        match *self {
+            Self::Monotonic(d, _) | Self::Unknown(d, _) | Self::Copied(d, _) => d,
+        }
+    }
+
+    /// Returns the point in time at which the timestamp was taken.
+    pub fn source(&self) -> TimestampSource {
+        match *self {
+            Self::Monotonic(_, s) | Self::Unknown(_, s) | Self::Copied(_, s) => s,
+        }
+    }
+
+    /// Estimates the wall-clock time corresponding to a [`FrameTimestamp::Monotonic`] timestamp.
+    ///
+    /// This uses the current offset between `CLOCK_REALTIME` and `CLOCK_MONOTONIC`, so the result
+    /// is off by however much the system clock was adjusted since the frame was captured. It is
+    /// meant for correlating frames with logs, not for precise synchronization.
+    ///
+    /// Returns `None` for other kinds of timestamps, or if the clocks cannot be read.
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        let Self::Monotonic(timestamp, _) = *self else {
+            return None;
+        };
+
+        // Read the monotonic clock before and after the real-time clock, and use the midpoint to
+        // minimize the error caused by being preempted in between.
+        let before = Duration::from(clock_gettime(ClockId::CLOCK_MONOTONIC).ok()?);
+        let realtime = Duration::from(clock_gettime(ClockId::CLOCK_REALTIME).ok()?);
+        let after = Duration::from(clock_gettime(ClockId::CLOCK_MONOTONIC).ok()?);
+        let now_monotonic = before + (after - before) / 2;
+
+        monotonic_to_system_time(timestamp, now_monotonic, SystemTime::UNIX_EPOCH + realtime)
+    }
+}
+
+/// Converts the monotonic `timestamp` to wall-clock time, given a pair of simultaneous readings of
+/// both clocks.
+fn monotonic_to_system_time(
+    timestamp: Duration,
+    now_monotonic: Duration,
+    now_realtime: SystemTime,
+) -> Option<SystemTime> {
+    if timestamp <= now_monotonic {
+        now_realtime.checked_sub(now_monotonic - timestamp)
+    } else {
+        now_realtime.checked_add(timestamp - now_monotonic)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_flags() {
+        let ts = Duration::from_millis(1234);
+        assert_eq!(
+            FrameTimestamp::from_flags(BufFlag::TIMESTAMP_MONOTONIC | BufFlag::ERROR, ts),
+            FrameTimestamp::Monotonic(ts, TimestampSource::EndOfFrame)
+        );
+        assert_eq!(
+            FrameTimestamp::from_flags(BufFlag::TIMESTAMP_COPY | BufFlag::TIMESTAMP_SRC_SOE, ts),
+            FrameTimestamp::Copied(ts, TimestampSource::StartOfExposure)
+        );
+        assert_eq!(
+            FrameTimestamp::from_flags(BufFlag::empty(), ts),
+            FrameTimestamp::Unknown(ts, TimestampSource::EndOfFrame)
+        );
+        // Reserved timestamp types are treated as unknown.
+        assert_eq!(
+            FrameTimestamp::from_flags(BufFlag::from_bits_truncate(0x6000), ts),
+            FrameTimestamp::Unknown(ts, TimestampSource::EndOfFrame)
+        );
+    }
+
+    #[test]
+    fn system_time_conversion() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mono = Duration::from_secs(100);
+        assert_eq!(
+            monotonic_to_system_time(Duration::from_secs(98), mono, now),
+            Some(now - Duration::from_secs(2))
+        );
+        assert_eq!(
+            monotonic_to_system_time(Duration::from_secs(101), mono, now),
+            Some(now + Duration::from_secs(1))
+        );
+
+        let unknown = FrameTimestamp::Unknown(mono, TimestampSource::EndOfFrame);
+        assert_eq!(unknown.to_system_time(), None);
+
+        let converted = FrameTimestamp::Monotonic(
+            Duration::from(clock_gettime(ClockId::CLOCK_MONOTONIC).unwrap()),
+            TimestampSource::EndOfFrame,
+        )
+        .to_system_time()
+        .unwrap();
+        let diff = match SystemTime::now().duration_since(converted) {
+            Ok(d) => d,
+            Err(e) => e.duration(),
+        };
+        assert!(diff < Duration::from_secs(1));
+    }
+}
@@ -1,43 +1,306 @@
 //! USB Video Class extensions.
 
+mod clock;
+mod descriptors;
+pub mod h264;
+pub mod logitech;
+mod paired;
+mod quirks;
 mod raw;
 
 use std::{
-    io, mem,
+    collections::HashMap,
+    fmt, io, mem,
     os::unix::prelude::{AsRawFd, RawFd},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
 };
 
 use bitflags::bitflags;
+use nix::errno::Errno;
 
+use crate::raw::controls::{Cid, CtrlWhich, ExtControl, ExtControlValue};
+use crate::raw::Rect;
 use crate::Device;
 
-use self::raw::{XuControlQuery, XuQuery};
+use self::raw::XuControlQuery;
 
-const HFLIP_UNIT_SELECTOR: u8 = 0x0c;
-const VFLIP_UNIT_SELECTOR: u8 = 0x0d;
-const UVC_EXTENSION_UNIT: u8 = 0x03;
-const EXPOSURE_WEIGHTS_UNIT_SELECTOR: u8 = 0x09;
+pub use self::clock::{ClockEstimate, ClockMapper};
+pub use self::descriptors::ExtensionUnitDescriptor;
+pub use self::paired::PairedStream;
+pub use self::quirks::{UvcQuirks, XuControl};
+pub use self::raw::XuQuery;
 
 /// `UVCH` meta capture format.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy)]
 pub struct UvcMetadata {
-    #[allow(dead_code)]
     raw: RawMetadata,
 }
 
 impl UvcMetadata {
+    /// The size of the decoded part of a metadata record (the kernel-provided fields and the
+    /// standard UVC payload header fields).
+    ///
+    /// Records in a metadata buffer can be larger than this, since they can contain additional
+    /// vendor-specific data, and a buffer can contain several records. The size of the buffers is
+    /// given by [`MetaFormat::buffer_size`][crate::format::MetaFormat::buffer_size].
     pub const MAX_SIZE: usize = mem::size_of::<RawMetadata>();
 
+    /// Size of the fields added by the kernel (`ts` and `sof`).
+    const KERNEL_HEADER_SIZE: usize = 10;
+
+    /// Decodes a metadata record, panicking if it is malformed.
+    ///
+    /// # Panics
+    ///
+    /// This panics if [`UvcMetadata::try_from_bytes`] returns an error.
     pub fn from_bytes(bytes: &[u8]) -> Self {
-        assert!(bytes.len() <= Self::MAX_SIZE);
+        match Self::try_from_bytes(bytes) {
+            Ok(meta) => meta,
+            Err(e) => panic!("invalid UVC metadata: {}", e),
+        }
+    }
+
+    /// Decodes a metadata record.
+    ///
+    /// A record consists of the kernel-provided timestamp and SOF counter, followed by the UVC
+    /// payload header, whose length is given by its first byte. Only the first record in `bytes`
+    /// is decoded, any data following it is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is too short to contain the record, or if the payload header is
+    /// too short to contain the fields its `bmHeaderInfo` claims are present.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, MetadataError> {
+        if bytes.len() < Self::KERNEL_HEADER_SIZE + 1 {
+            return Err(MetadataError::TooShort { len: bytes.len() });
+        }
+
+        let header_length = bytes[Self::KERNEL_HEADER_SIZE];
+        let available = bytes.len() - Self::KERNEL_HEADER_SIZE;
+        if header_length < 2 || usize::from(header_length) > available {
+            return Err(MetadataError::InvalidHeaderLength {
+                header_length,
+                available,
+            });
+        }
+
+        let header_info = HeaderInfo::from_bits_truncate(bytes[Self::KERNEL_HEADER_SIZE + 1]);
+        let mut required = 2;
+        if header_info.contains(HeaderInfo::PRESENTATION_TIME) {
+            required += 4;
+        }
+        if header_info.contains(HeaderInfo::SOURCE_CLOCK_REFERENCE) {
+            required += 6;
+        }
+        if header_length < required {
+            return Err(MetadataError::HeaderTooShort {
+                header_length,
+                required,
+            });
+        }
 
+        // Vendor-specific data following the standard header fields is not retained.
+        let len = (Self::KERNEL_HEADER_SIZE + usize::from(header_length)).min(Self::MAX_SIZE);
         unsafe {
             // Safety: all-zero is valid for this type.
             let mut raw: RawMetadata = mem::zeroed();
-            // Safety: the assert guarantees that `bytes.len()` bytes fit, and arbitrary bytes are
-            // valid for the type.
-            std::ptr::copy(bytes.as_ptr(), &mut raw as *mut _ as _, bytes.len());
-            Self { raw }
+            // Safety: `len` is at most the size of `RawMetadata` and no larger than `bytes`, and
+            // arbitrary bytes are valid for the type.
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), &mut raw as *mut _ as _, len);
+            Ok(Self { raw })
+        }
+    }
+
+    /// Returns an iterator over all metadata records in `bytes`.
+    ///
+    /// The `UVCH` format stores one record per received payload, so a single metadata buffer can
+    /// contain several records. Iteration stops at the first zero-length or truncated record.
+    /// Records whose payload header is inconsistent are yielded as errors, but do not stop the
+    /// iteration.
+    pub fn parse_all(bytes: &[u8]) -> UvcMetadataIter<'_> {
+        UvcMetadataIter { remaining: bytes }
+    }
+
+    /// Returns the host timestamp of the start of the frame, in nanoseconds.
+    ///
+    /// The timestamp is taken from `CLOCK_MONOTONIC`.
+    #[inline]
+    pub fn ts(&self) -> u64 {
+        self.raw.ts
+    }
+
+    /// Returns the USB frame number (SOF counter) at which the payload was received by the host.
+    #[inline]
+    pub fn sof(&self) -> u16 {
+        self.raw.sof
+    }
+
+    /// Returns the `bmHeaderInfo` field of the UVC payload header.
+    #[inline]
+    pub fn header_info(&self) -> HeaderInfo {
+        self.raw.header_info
+    }
+
+    /// Returns the presentation time stamp (PTS) of the payload, in units of the camera's device
+    /// clock.
+    ///
+    /// Returns `None` if the payload header does not contain a PTS.
+    pub fn presentation_time(&self) -> Option<u32> {
+        if self.has_pts() {
+            // Unlike the fields added by the kernel, the payload header is little-endian.
+            Some(u32::from_le(self.raw.presentation_time))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the source clock reference (SCR) of the payload.
+    ///
+    /// Returns `None` if the payload header does not contain an SCR.
+    pub fn source_clock(&self) -> Option<SourceClock> {
+        let pts_len = if self.has_pts() { 4 } else { 0 };
+        if !self
+            .raw
+            .header_info
+            .contains(HeaderInfo::SOURCE_CLOCK_REFERENCE)
+            || usize::from(self.raw.header_length) < 2 + pts_len + 6
+        {
+            return None;
+        }
+
+        // The SCR directly follows the PTS, or takes its place if there is no PTS.
+        let mut scr = [0; 6];
+        if self.has_pts() {
+            scr = self.raw.source_clock;
+        } else {
+            scr[..4].copy_from_slice(&self.raw.presentation_time.to_ne_bytes());
+            scr[4..].copy_from_slice(&self.raw.source_clock[..2]);
+        }
+        Some(SourceClock::from_bytes(scr))
+    }
+
+    fn has_pts(&self) -> bool {
+        self.raw.header_info.contains(HeaderInfo::PRESENTATION_TIME) && self.raw.header_length >= 6
+    }
+
+    /// Returns whether the payload belongs to a still image.
+    ///
+    /// After [`UvcExt::trigger_still_image`] has been called, the camera will set this bit on the
+    /// payload header of the frame carrying the still image.
+    pub fn is_still_image(&self) -> bool {
+        self.raw.header_info.is_still_image()
+    }
+}
+
+impl fmt::Debug for UvcMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UvcMetadata")
+            .field("ts", &self.ts())
+            .field("sof", &self.sof())
+            .field("header_info", &self.header_info())
+            .field("presentation_time", &self.presentation_time())
+            .field("source_clock", &self.source_clock())
+            .finish()
+    }
+}
+
+/// Iterator over the metadata records of a buffer, returned by [`UvcMetadata::parse_all`].
+pub struct UvcMetadataIter<'a> {
+    remaining: &'a [u8],
+}
+
+impl Iterator for UvcMetadataIter<'_> {
+    type Item = Result<UvcMetadata, MetadataError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header_length = *self.remaining.get(UvcMetadata::KERNEL_HEADER_SIZE)?;
+        let len = UvcMetadata::KERNEL_HEADER_SIZE + usize::from(header_length);
+        if header_length == 0 || len > self.remaining.len() {
+            if header_length != 0 {
+                log::debug!(
+                    "truncated UVC metadata record ({} of {} bytes)",
+                    self.remaining.len(),
+                    len,
+                );
+            }
+            self.remaining = &[];
+            return None;
+        }
+
+        let (record, rest) = self.remaining.split_at(len);
+        self.remaining = rest;
+        Some(UvcMetadata::try_from_bytes(record))
+    }
+}
+
+/// Error returned by [`UvcMetadata::try_from_bytes`] when decoding a malformed record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataError {
+    /// The buffer is too short to contain the kernel-provided fields and the header length.
+    TooShort { len: usize },
+    /// The header length is smaller than the minimum of 2, or larger than the remaining buffer.
+    InvalidHeaderLength { header_length: u8, available: usize },
+    /// The header is too short to contain the fields flagged as present in `bmHeaderInfo`.
+    HeaderTooShort { header_length: u8, required: u8 },
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooShort { len } => write!(f, "metadata buffer too short ({} bytes)", len),
+            Self::InvalidHeaderLength {
+                header_length,
+                available,
+            } => write!(
+                f,
+                "invalid payload header length {} ({} bytes available)",
+                header_length, available
+            ),
+            Self::HeaderTooShort {
+                header_length,
+                required,
+            } => write!(
+                f,
+                "payload header of length {} is too short for the flagged fields ({} bytes required)",
+                header_length, required
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {}
+
+impl From<MetadataError> for io::Error {
+    fn from(e: MetadataError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// A source clock reference (SCR) from a UVC payload header.
+///
+/// The SCR relates the camera's device clock to the USB bus clock: it contains the value of the
+/// device clock (the source time clock, STC) sampled at the start of the USB frame with the given
+/// SOF counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceClock {
+    /// Value of the camera's source time clock, in device clock units.
+    pub stc: u32,
+    /// The 11-bit USB SOF counter value of the frame in which `stc` was sampled.
+    pub sof_counter: u16,
+}
+
+impl SourceClock {
+    /// Decodes the 6-byte `scrSourceClock` field of a UVC payload header.
+    ///
+    /// The field consists of the 32-bit source time clock, followed by a 16-bit word whose lower 11
+    /// bits hold the SOF counter. Both are little-endian. The remaining bits are reserved and
+    /// ignored.
+    pub fn from_bytes(bytes: [u8; 6]) -> Self {
+        Self {
+            stc: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            sof_counter: u16::from_le_bytes([bytes[4], bytes[5]]) & 0x7ff,
         }
     }
 }
@@ -55,126 +318,1477 @@ struct RawMetadata {
 }
 
 bitflags! {
+    /// The `bmHeaderInfo` field of a UVC payload header.
     #[repr(transparent)]
-    struct HeaderInfo: u8 {
+    pub struct HeaderInfo: u8 {
+        /// Toggles between 0 and 1 at the start of every new video frame.
         const FRAME_ID               = 1 << 0;
+        /// The payload marks the end of the current video frame.
         const END_OF_FRAME           = 1 << 1;
+        /// The header contains a presentation time stamp.
         const PRESENTATION_TIME      = 1 << 2;
+        /// The header contains a source clock reference.
         const SOURCE_CLOCK_REFERENCE = 1 << 3;
         /// Payload-specific bit.
         const PAYLOAD                = 1 << 4;
+        /// The payload belongs to a still image.
         const STILL_IMAGE            = 1 << 5;
+        /// An error occurred in the device while streaming the payload.
         const ERROR                  = 1 << 6;
+        /// This is the last header in the payload header chain.
         const END_OF_HEADER          = 1 << 7;
     }
 }
 
+impl HeaderInfo {
+    /// Returns the value of the frame ID bit (0 or 1).
+    ///
+    /// The frame ID toggles with every new video frame, so consecutive payloads with the same frame
+    /// ID belong to the same frame.
+    #[inline]
+    pub fn frame_id(&self) -> u8 {
+        self.bits() & Self::FRAME_ID.bits()
+    }
+
+    /// Returns whether the payload marks the end of a video frame.
+    #[inline]
+    pub fn is_end_of_frame(&self) -> bool {
+        self.contains(Self::END_OF_FRAME)
+    }
+
+    /// Returns whether the device reported an error while streaming the payload.
+    #[inline]
+    pub fn has_error(&self) -> bool {
+        self.contains(Self::ERROR)
+    }
+
+    /// Returns whether the payload belongs to a still image.
+    #[inline]
+    pub fn is_still_image(&self) -> bool {
+        self.contains(Self::STILL_IMAGE)
+    }
+}
+
 /// Grants access to operations that are specific to UVC devices.
+///
+/// Queries that only read device state take `&self`, so any number of `UvcExt`s and
+/// [`ExtensionUnit`]s can be created for the same [`Device`] and used side by side.
+#[derive(Clone)]
 pub struct UvcExt<'a> {
     device: &'a Device,
+    retry: RetryPolicy,
+    quirks: UvcQuirks,
+    preflight: Option<InfoCache>,
 }
 
 impl<'a> UvcExt<'a> {
+    /// Creates a `UvcExt` for `device`.
+    ///
+    /// The [`UvcQuirks`] are selected based on the USB vendor and product ID of the device.
+    /// Cameras that are not in the built-in table, and devices whose USB ID cannot be determined,
+    /// use [`UvcQuirks::DEFAULT`].
     pub fn new(device: &'a Device) -> Self {
-        Self { device }
+        let quirks = match descriptors::usb_id(device) {
+            Ok(Some((vid, pid))) => match UvcQuirks::for_usb_id(vid, pid) {
+                Some(quirks) => {
+                    log::debug!("using quirks '{}' for {:04x}:{:04x}", quirks.name, vid, pid);
+                    *quirks
+                }
+                None => UvcQuirks::DEFAULT,
+            },
+            Ok(None) => UvcQuirks::DEFAULT,
+            Err(e) => {
+                log::debug!("failed to read USB ID: {}", e);
+                UvcQuirks::DEFAULT
+            }
+        };
+        Self::with_quirks(device, quirks)
+    }
+
+    /// Creates a `UvcExt` for `device` that uses the control mapping described by `quirks`.
+    pub fn with_quirks(device: &'a Device, quirks: UvcQuirks) -> Self {
+        Self {
+            device,
+            retry: RetryPolicy::NONE,
+            quirks,
+            preflight: None,
+        }
+    }
+
+    /// Returns the [`UvcQuirks`] in use.
+    pub fn quirks(&self) -> &UvcQuirks {
+        &self.quirks
+    }
+
+    /// Returns the USB vendor and product ID of the device.
+    ///
+    /// Returns `Ok(None)` if the device is not a USB device.
+    pub fn usb_id(&self) -> io::Result<Option<(u16, u16)>> {
+        descriptors::usb_id(self.device)
+    }
+
+    /// Retries control queries that fail with a transient error up to `retries` times.
+    ///
+    /// See [`ExtensionUnit::with_retries`] for details. The policy is inherited by all extension
+    /// units obtained via [`UvcExt::extension_unit`].
+    pub fn with_retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.retry = RetryPolicy { retries, delay };
+        self
+    }
+
+    /// Checks controls with `UVC_GET_INFO` before writing to them.
+    ///
+    /// See [`ExtensionUnit::with_preflight`] for details. All extension units obtained via
+    /// [`UvcExt::extension_unit`] share the same cache of control information.
+    pub fn with_preflight(mut self) -> Self {
+        self.preflight = Some(InfoCache::default());
+        self
     }
 
-    pub fn extension_unit(&self, unit_id: u8) -> ExtensionUnit<'_> {
+    /// Returns a handle to the extension unit with ID `unit_id`.
+    ///
+    /// The returned handle borrows the underlying [`Device`], not this `UvcExt`.
+    pub fn extension_unit(&self, unit_id: u8) -> ExtensionUnit<'a> {
         ExtensionUnit {
             unit_id,
             device: self.device,
+            retry: self.retry,
+            preflight: self.preflight.clone(),
         }
     }
 
+    /// Enables or disables horizontal flipping of the image.
+    pub fn set_horizontal_flip(&mut self, enabled: bool) -> io::Result<()> {
+        let ctrl = self.quirks.horizontal_flip;
+        self.extension_unit(ctrl.unit_id)
+            .set_u16(ctrl.selector, enabled.into())
+    }
+
+    /// Enables or disables vertical flipping of the image.
+    pub fn set_vertical_flip(&mut self, enabled: bool) -> io::Result<()> {
+        let ctrl = self.quirks.vertical_flip;
+        self.extension_unit(ctrl.unit_id)
+            .set_u16(ctrl.selector, enabled.into())
+    }
+
+    /// Returns whether horizontal flipping is currently enabled.
+    pub fn horizontal_flip_enabled(&self) -> io::Result<bool> {
+        let ctrl = self.quirks.horizontal_flip;
+        Ok(self.extension_unit(ctrl.unit_id).get_u16(ctrl.selector)? != 0)
+    }
+
+    /// Returns whether vertical flipping is currently enabled.
+    pub fn vertical_flip_enabled(&self) -> io::Result<bool> {
+        let ctrl = self.quirks.vertical_flip;
+        Ok(self.extension_unit(ctrl.unit_id).get_u16(ctrl.selector)? != 0)
+    }
+
+    #[deprecated(note = "use `set_horizontal_flip(true)` instead")]
     pub fn horizontal_flip(&mut self) -> io::Result<()> {
-        self.control_query(
-            UVC_EXTENSION_UNIT,
-            HFLIP_UNIT_SELECTOR,
-            XuQuery::SET_CUR,
-            &mut [1, 0],
-        )
+        self.set_horizontal_flip(true)
     }
 
+    #[deprecated(note = "use `set_vertical_flip(true)` instead")]
     pub fn vertical_flip(&mut self) -> io::Result<()> {
-        self.control_query(
-            UVC_EXTENSION_UNIT,
-            VFLIP_UNIT_SELECTOR,
-            XuQuery::SET_CUR,
-            &mut [1, 0],
-        )
+        self.set_vertical_flip(true)
     }
 
-    pub fn set_auto_exposure_weights(&mut self, weights: &mut [u8; 17]) -> io::Result<()> {
-        self.control_query(
-            UVC_EXTENSION_UNIT,
-            EXPOSURE_WEIGHTS_UNIT_SELECTOR,
-            XuQuery::SET_CUR,
-            weights,
-        )
+    /// Negotiates the format used for still images captured via [`UvcExt::trigger_still_image`].
+    ///
+    /// This performs the still probe/commit sequence: `config` is written to the probe control,
+    /// the camera's adjusted configuration is read back and committed, and then returned.
+    ///
+    /// Note that `uvcvideo` only grants userspace access to extension units, so this only works on
+    /// cameras that mirror the still image controls of the video streaming interface on their
+    /// extension unit.
+    pub fn set_still_image_config(
+        &mut self,
+        config: &StillImageConfig,
+    ) -> io::Result<StillImageConfig> {
+        let (probe, commit) = (self.quirks.still_probe, self.quirks.still_commit);
+        let mut data = config.to_bytes();
+        let probe_xu = self.extension_unit(probe.unit_id);
+        probe_xu.query(probe.selector, XuQuery::SET_CUR, &mut data)?;
+        probe_xu.query(probe.selector, XuQuery::GET_CUR, &mut data)?;
+        self.extension_unit(commit.unit_id)
+            .query(commit.selector, XuQuery::SET_CUR, &mut data)?;
+        Ok(StillImageConfig::from_bytes(&data))
     }
 
-    pub fn get_auto_exposure_weights(&mut self, out: &mut [u8; 17]) -> io::Result<()> {
-        self.control_query(
-            UVC_EXTENSION_UNIT,
-            EXPOSURE_WEIGHTS_UNIT_SELECTOR,
-            XuQuery::GET_CUR,
-            out,
-        )
+    /// Reads the committed still image format.
+    pub fn still_image_config(&self) -> io::Result<StillImageConfig> {
+        let mut data = [0; StillImageConfig::SIZE];
+        let ctrl = self.quirks.still_commit;
+        self.extension_unit(ctrl.unit_id)
+            .query(ctrl.selector, XuQuery::GET_CUR, &mut data)?;
+        Ok(StillImageConfig::from_bytes(&data))
     }
 
-    fn control_query<const SIZE: usize>(
-        &self,
-        unit: u8,
-        selector: u8,
-        query: XuQuery,
-        data: &mut [u8; SIZE],
-    ) -> io::Result<()> {
-        let mut query = XuControlQuery {
-            unit,
-            selector,
-            query,
-            size: SIZE as u16,
-            data: data.as_mut_ptr(),
-        };
+    /// Instructs the camera to transmit a still image (still image capture method 2).
+    ///
+    /// The still image is delivered through the regular video stream, in the format configured
+    /// with [`UvcExt::set_still_image_config`]. The frame carrying it can be identified via
+    /// [`UvcMetadata::is_still_image`] on the corresponding metadata buffer, which has the same
+    /// [`sequence`][crate::stream::ReadBufferView::sequence] number as the video buffer.
+    pub fn trigger_still_image(&mut self) -> io::Result<()> {
+        let ctrl = self.quirks.still_trigger;
+        self.extension_unit(ctrl.unit_id).set_u8(ctrl.selector, 1)
+    }
 
-        unsafe {
-            raw::ctrl_query(self.device.file.as_raw_fd(), &mut query)?;
+    /// Lists the extension units of the device, as described by its USB descriptors.
+    ///
+    /// The descriptors are read from sysfs. For devices that aren't USB devices (like `vivid`), an
+    /// empty list is returned.
+    pub fn extension_units(&self) -> io::Result<Vec<ExtensionUnitDescriptor>> {
+        descriptors::read(self.device)
+    }
+
+    /// Looks up the extension unit identified by `guid` (in USB byte order).
+    ///
+    /// Returns `Ok(None)` if the device has no such extension unit.
+    pub fn find_by_guid(&self, guid: &[u8; 16]) -> io::Result<Option<ExtensionUnit<'a>>> {
+        Ok(self
+            .extension_units()?
+            .iter()
+            .find(|unit| unit.guid() == guid)
+            .map(|unit| self.extension_unit(unit.unit_id())))
+    }
+
+    /// Points the camera's automatic controls at a region of interest.
+    ///
+    /// `auto_controls` selects which automatic controls should take the region into account. This
+    /// uses the UVC 1.5 `CT_REGION_OF_INTEREST_CONTROL`, which the `uvcvideo` driver exposes as a
+    /// set of V4L2 controls starting with Linux 6.16.
+    ///
+    /// # Errors
+    ///
+    /// If `rect` is empty or exceeds the maximum region reported by the camera, an error of kind
+    /// [`io::ErrorKind::InvalidInput`] is returned without changing the device state.
+    pub fn set_roi(&mut self, rect: RoiRect, auto_controls: RoiAutoFlags) -> io::Result<()> {
+        if rect.top > rect.bottom || rect.left > rect.right {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid region of interest {:?}", rect),
+            ));
+        }
+        match self.read_roi_rect(CtrlWhich::MAX_VAL) {
+            Ok(max) => {
+                if rect.bottom > max.bottom || rect.right > max.right {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("region of interest {:?} exceeds maximum of {:?}", rect, max,),
+                    ));
+                }
+            }
+            // Kernels without support for querying the maximum value clamp the region instead.
+            Err(e) if e.raw_os_error() == Some(Errno::EINVAL as i32) => {
+                log::debug!("could not query maximum region of interest: {}", e);
+            }
+            Err(e) => return Err(e),
         }
 
-        Ok(())
+        let mut raw_rect = rect.to_raw();
+        let mut controls = [
+            roi_rect_control(&mut raw_rect),
+            ExtControl {
+                id: Cid::UVC_REGION_OF_INTEREST_AUTO,
+                size: 0,
+                reserved2: [0],
+                value: ExtControlValue {
+                    value: auto_controls.bits().into(),
+                },
+            },
+        ];
+        self.device.write_ext_controls_raw(&mut controls)
+    }
+
+    /// Returns the current region of interest and the automatic controls that use it.
+    pub fn roi(&self) -> io::Result<(RoiRect, RoiAutoFlags)> {
+        let mut raw_rect = RoiRect::default().to_raw();
+        let mut controls = [
+            roi_rect_control(&mut raw_rect),
+            ExtControl {
+                id: Cid::UVC_REGION_OF_INTEREST_AUTO,
+                size: 0,
+                reserved2: [0],
+                value: ExtControlValue { value: 0 },
+            },
+        ];
+        self.device
+            .read_ext_controls_raw(CtrlWhich::CUR_VAL, &mut controls)?;
+
+        let auto = unsafe { controls[1].value.value };
+        Ok((
+            RoiRect::from_raw(&raw_rect),
+            RoiAutoFlags::from_bits_truncate(auto as u16),
+        ))
+    }
+
+    fn read_roi_rect(&self, which: CtrlWhich) -> io::Result<RoiRect> {
+        let mut raw_rect = RoiRect::default().to_raw();
+        self.device
+            .read_ext_controls_raw(which, &mut [roi_rect_control(&mut raw_rect)])?;
+        Ok(RoiRect::from_raw(&raw_rect))
+    }
+
+    /// Returns whether the camera's privacy shutter is closed.
+    ///
+    /// # Errors
+    ///
+    /// If the camera doesn't report the state of a privacy shutter, an error of kind
+    /// [`io::ErrorKind::Unsupported`] is returned.
+    pub fn privacy(&self) -> io::Result<bool> {
+        match self.device.read_control_raw(Cid::PRIVACY) {
+            Ok(value) => Ok(value != 0),
+            Err(e) if e.raw_os_error() == Some(Errno::EINVAL as i32) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "camera does not have a privacy control",
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns an iterator that reports changes of the privacy shutter state.
+    ///
+    /// The first call to [`Iterator::next`] returns the current state. Subsequent calls block,
+    /// reading the state every `interval`, until it differs from the previously returned one.
+    ///
+    /// The iterator ends after returning an error.
+    pub fn poll_privacy(&self, interval: Duration) -> PrivacyPoller<'a> {
+        PrivacyPoller {
+            uvc: self.clone(),
+            interval,
+            last: None,
+            done: false,
+        }
+    }
+
+    /// Configures the weight grid used by the camera's auto-exposure algorithm.
+    pub fn set_auto_exposure_weights(&mut self, weights: &ExposureWeights) -> io::Result<()> {
+        let mut data = weights.to_bytes();
+        let ctrl = self.quirks.exposure_weights;
+        self.extension_unit(ctrl.unit_id)
+            .query(ctrl.selector, XuQuery::SET_CUR, &mut data)
+    }
+
+    /// Reads the weight grid used by the camera's auto-exposure algorithm.
+    pub fn get_auto_exposure_weights(&self) -> io::Result<ExposureWeights> {
+        let mut data = [0; ExposureWeights::SIZE];
+        let ctrl = self.quirks.exposure_weights;
+        self.extension_unit(ctrl.unit_id)
+            .query(ctrl.selector, XuQuery::GET_CUR, &mut data)?;
+        Ok(ExposureWeights::from_bytes(&data))
+    }
+}
+
+fn roi_rect_control(rect: &mut Rect) -> ExtControl {
+    ExtControl {
+        id: Cid::UVC_REGION_OF_INTEREST_RECT,
+        size: mem::size_of::<Rect>() as u32,
+        reserved2: [0],
+        value: ExtControlValue {
+            ptr: (rect as *mut Rect).cast(),
+        },
+    }
+}
+
+/// Iterator returned by [`UvcExt::poll_privacy`].
+pub struct PrivacyPoller<'a> {
+    uvc: UvcExt<'a>,
+    interval: Duration,
+    last: Option<bool>,
+    done: bool,
+}
+
+impl Iterator for PrivacyPoller<'_> {
+    type Item = io::Result<bool>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            if self.last.is_some() {
+                thread::sleep(self.interval);
+            }
+            match self.uvc.privacy() {
+                Ok(closed) if self.last == Some(closed) => continue,
+                Ok(closed) => {
+                    self.last = Some(closed);
+                    return Some(Ok(closed));
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// A rectangular region of interest, in pixel coordinates of the sensor.
+///
+/// All coordinates are inclusive, so `bottom` and `right` refer to the last row and column inside
+/// the region.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RoiRect {
+    pub top: u16,
+    pub left: u16,
+    pub bottom: u16,
+    pub right: u16,
+}
+
+impl RoiRect {
+    pub fn new(top: u16, left: u16, bottom: u16, right: u16) -> Self {
+        Self {
+            top,
+            left,
+            bottom,
+            right,
+        }
+    }
+
+    fn to_raw(self) -> Rect {
+        Rect {
+            left: self.left.into(),
+            top: self.top.into(),
+            width: u32::from(self.right.saturating_sub(self.left)) + 1,
+            height: u32::from(self.bottom.saturating_sub(self.top)) + 1,
+        }
+    }
+
+    fn from_raw(rect: &Rect) -> Self {
+        let clamp = |v: i64| v.clamp(0, u16::MAX.into()) as u16;
+        let left = i64::from(rect.left);
+        let top = i64::from(rect.top);
+        Self {
+            top: clamp(top),
+            left: clamp(left),
+            bottom: clamp(top + i64::from(rect.height) - 1),
+            right: clamp(left + i64::from(rect.width) - 1),
+        }
+    }
+}
+
+bitflags! {
+    /// Automatic controls that can be directed at a region of interest (`bmAutoControls`).
+    pub struct RoiAutoFlags: u16 {
+        const AUTO_EXPOSURE         = 1 << 0;
+        const AUTO_IRIS             = 1 << 1;
+        const AUTO_WHITE_BALANCE    = 1 << 2;
+        const AUTO_FOCUS            = 1 << 3;
+        const AUTO_FACE_DETECT      = 1 << 4;
+        const AUTO_DETECT_AND_TRACK = 1 << 5;
+        const IMAGE_STABILIZATION   = 1 << 6;
+        const HIGHER_QUALITY        = 1 << 7;
+    }
+}
+
+/// Still image format, negotiated via [`UvcExt::set_still_image_config`].
+///
+/// The indices refer to the format and frame descriptors of the camera's video streaming
+/// interface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StillImageConfig {
+    /// Index of the format descriptor to use.
+    pub format_index: u8,
+    /// Index of the still image frame descriptor (resolution) to use.
+    pub frame_index: u8,
+    /// Index of the compression quality to use.
+    pub compression_index: u8,
+    /// Maximum size of a still image in bytes (set by the camera).
+    pub max_frame_size: u32,
+    /// Maximum number of bytes the camera transmits in a single payload transfer (set by the
+    /// camera).
+    pub max_payload_transfer_size: u32,
+}
+
+impl StillImageConfig {
+    const SIZE: usize = 11;
+
+    /// Creates a still image configuration for the given format and frame descriptors.
+    pub fn new(format_index: u8, frame_index: u8) -> Self {
+        Self {
+            format_index,
+            frame_index,
+            compression_index: 0,
+            max_frame_size: 0,
+            max_payload_transfer_size: 0,
+        }
+    }
+
+    fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        Self {
+            format_index: bytes[0],
+            frame_index: bytes[1],
+            compression_index: bytes[2],
+            max_frame_size: u32::from_le_bytes([bytes[3], bytes[4], bytes[5], bytes[6]]),
+            max_payload_transfer_size: u32::from_le_bytes([
+                bytes[7], bytes[8], bytes[9], bytes[10],
+            ]),
+        }
     }
+
+    fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0] = self.format_index;
+        bytes[1] = self.frame_index;
+        bytes[2] = self.compression_index;
+        bytes[3..7].copy_from_slice(&self.max_frame_size.to_le_bytes());
+        bytes[7..11].copy_from_slice(&self.max_payload_transfer_size.to_le_bytes());
+        bytes
+    }
+}
+
+/// Grid of auto-exposure weights.
+///
+/// The image is divided into a grid of [`ExposureWeights::WIDTH`] by [`ExposureWeights::HEIGHT`]
+/// cells, and each cell is assigned a 4-bit weight that determines how much it contributes to the
+/// auto-exposure algorithm. Additionally, a mode byte is transmitted alongside the grid.
+///
+/// On the wire, the grid is stored row by row, with two cells packed into each byte (the left cell
+/// in the low nibble), followed by the mode byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExposureWeights {
+    weights: [[u8; Self::WIDTH]; Self::HEIGHT],
+    mode: u8,
 }
 
+impl ExposureWeights {
+    /// Number of columns in the weight grid.
+    pub const WIDTH: usize = 8;
+    /// Number of rows in the weight grid.
+    pub const HEIGHT: usize = 4;
+    /// Size of the encoded control value in bytes.
+    pub const SIZE: usize = Self::WIDTH * Self::HEIGHT / 2 + 1;
+    /// Largest allowed weight.
+    pub const MAX_WEIGHT: u8 = 0xf;
+
+    /// Creates an [`ExposureWeights`] value from a grid of weights and a mode byte.
+    ///
+    /// If any weight is larger than [`ExposureWeights::MAX_WEIGHT`], an error of kind
+    /// [`io::ErrorKind::InvalidInput`] is returned.
+    pub fn new(weights: [[u8; Self::WIDTH]; Self::HEIGHT], mode: u8) -> io::Result<Self> {
+        for (y, row) in weights.iter().enumerate() {
+            for (x, weight) in row.iter().enumerate() {
+                if *weight > Self::MAX_WEIGHT {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "exposure weight at ({}, {}) does not fit in 4 bits: {}",
+                            x, y, weight
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Ok(Self { weights, mode })
+    }
+
+    /// Decodes the raw control value.
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        let mut weights = [[0; Self::WIDTH]; Self::HEIGHT];
+        for (i, byte) in bytes[..Self::SIZE - 1].iter().enumerate() {
+            let (y, x) = (i * 2 / Self::WIDTH, i * 2 % Self::WIDTH);
+            weights[y][x] = byte & 0xf;
+            weights[y][x + 1] = byte >> 4;
+        }
+
+        Self {
+            weights,
+            mode: bytes[Self::SIZE - 1],
+        }
+    }
+
+    /// Encodes `self` as a raw control value.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        for (i, byte) in bytes[..Self::SIZE - 1].iter_mut().enumerate() {
+            let (y, x) = (i * 2 / Self::WIDTH, i * 2 % Self::WIDTH);
+            *byte = self.weights[y][x] | self.weights[y][x + 1] << 4;
+        }
+        bytes[Self::SIZE - 1] = self.mode;
+        bytes
+    }
+
+    /// Returns the weight grid, indexed by row, then column.
+    #[inline]
+    pub fn weights(&self) -> &[[u8; Self::WIDTH]; Self::HEIGHT] {
+        &self.weights
+    }
+
+    /// Returns the mode byte.
+    #[inline]
+    pub fn mode(&self) -> u8 {
+        self.mode
+    }
+}
+
+/// How often to retry control queries that fail with a transient error.
+#[derive(Clone, Copy, Debug)]
+struct RetryPolicy {
+    retries: u32,
+    delay: Duration,
+}
+
+impl RetryPolicy {
+    const NONE: Self = Self {
+        retries: 0,
+        delay: Duration::ZERO,
+    };
+}
+
+/// Cache of the `UVC_GET_INFO` results of controls, keyed by unit ID and selector.
+type InfoCache = Arc<Mutex<HashMap<(u8, u8), ControlInfo>>>;
+
+#[derive(Clone)]
 pub struct ExtensionUnit<'a> {
     unit_id: u8,
     device: &'a Device,
+    retry: RetryPolicy,
+    preflight: Option<InfoCache>,
 }
 
 impl<'a> ExtensionUnit<'a> {
+    fn new(device: &'a Device, unit_id: u8) -> Self {
+        Self {
+            unit_id,
+            device,
+            retry: RetryPolicy::NONE,
+            preflight: None,
+        }
+    }
+
+    /// Checks controls with `UVC_GET_INFO` before writing to them.
+    ///
+    /// Without preflight checks, writing to a control that doesn't exist, is read-only, or is
+    /// currently disabled typically fails with a nondescript `EINVAL` or `EIO`. With preflight
+    /// checks enabled, `SET_CUR` queries instead fail with an [`XuError`] describing the cause
+    /// (wrapped in an [`io::Error`]).
+    ///
+    /// The control information is cached, so that repeated writes to the same control don't
+    /// require additional queries. Since the "disabled" bits can change at runtime (eg. when an
+    /// automatic mode is toggled), cached information is refreshed whenever it reports the
+    /// control as disabled, or when a write fails.
+    pub fn with_preflight(mut self) -> Self {
+        self.preflight = Some(InfoCache::default());
+        self
+    }
+
+    /// Retries control queries that fail with a transient error up to `retries` times.
+    ///
+    /// The USB stack occasionally fails control transfers with `EPIPE` or `EPROTO` while it is
+    /// busy. With a retry policy configured, queries failing with one of those errors are repeated
+    /// after sleeping for `delay`. Other errors (like `EINVAL` or `ENOENT`) are returned
+    /// immediately.
+    ///
+    /// By default, no retries are performed. If all attempts fail, the returned error reports the
+    /// number of attempts made.
+    pub fn with_retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.retry = RetryPolicy { retries, delay };
+        self
+    }
+
     fn fd(&self) -> RawFd {
         self.device.file.as_raw_fd()
     }
 
+    /// Returns the capabilities and status of a control (`UVC_GET_INFO`).
+    ///
+    /// Bits that are reserved by the UVC specification are discarded. Use
+    /// [`ExtensionUnit::control_info_raw`] to inspect them.
     pub fn control_info(&self, selector: u8) -> io::Result<ControlInfo> {
-        let mut info = 0;
+        self.control_info_raw(selector)
+            .map(ControlInfo::from_bits_truncate)
+    }
+
+    /// Returns the unmodified `UVC_GET_INFO` byte of a control, including any reserved bits set
+    /// by the device firmware.
+    pub fn control_info_raw(&self, selector: u8) -> io::Result<u8> {
+        let mut info = [0];
+        self.query(selector, XuQuery::GET_INFO, &mut info)?;
+        Ok(info[0])
+    }
+
+    /// Returns the minimum value of a control (`UVC_GET_MIN`).
+    ///
+    /// The size of the returned value is determined by issuing `UVC_GET_LEN` first.
+    pub fn control_min(&self, selector: u8) -> io::Result<Vec<u8>> {
+        self.query_sized(selector, XuQuery::GET_MIN)
+    }
+
+    /// Returns the maximum value of a control (`UVC_GET_MAX`).
+    ///
+    /// The size of the returned value is determined by issuing `UVC_GET_LEN` first.
+    pub fn control_max(&self, selector: u8) -> io::Result<Vec<u8>> {
+        self.query_sized(selector, XuQuery::GET_MAX)
+    }
+
+    /// Returns the resolution (step size) of a control (`UVC_GET_RES`).
+    ///
+    /// The size of the returned value is determined by issuing `UVC_GET_LEN` first.
+    pub fn control_res(&self, selector: u8) -> io::Result<Vec<u8>> {
+        self.query_sized(selector, XuQuery::GET_RES)
+    }
+
+    /// Returns the default value of a control (`UVC_GET_DEF`).
+    ///
+    /// The size of the returned value is determined by issuing `UVC_GET_LEN` first.
+    pub fn control_default(&self, selector: u8) -> io::Result<Vec<u8>> {
+        self.query_sized(selector, XuQuery::GET_DEF)
+    }
+
+    /// Returns the size of a control's value in bytes (`UVC_GET_LEN`).
+    pub fn control_len(&self, selector: u8) -> io::Result<u16> {
+        let mut len = [0; 2];
+        self.query(selector, XuQuery::GET_LEN, &mut len)?;
+        Ok(u16::from_le_bytes(len))
+    }
+
+    /// Reads the current value of a control (`UVC_GET_CUR`).
+    ///
+    /// The size of the returned value is determined by issuing `UVC_GET_LEN` first.
+    pub fn get_current(&self, selector: u8) -> io::Result<Vec<u8>> {
+        self.query_sized(selector, XuQuery::GET_CUR)
+    }
+
+    /// Writes a new value to a control (`UVC_SET_CUR`).
+    ///
+    /// # Errors
+    ///
+    /// If the length of `data` does not match the control size reported by `UVC_GET_LEN`, an error
+    /// of kind [`io::ErrorKind::InvalidInput`] is returned without issuing the `SET_CUR` query.
+    pub fn set_current(&self, selector: u8, data: &[u8]) -> io::Result<()> {
+        let len = self.control_len(selector)?;
+        if data.len() != usize::from(len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "control {:#04x} has a size of {} bytes, but {} bytes were provided",
+                    selector,
+                    len,
+                    data.len()
+                ),
+            ));
+        }
+
+        let mut data = data.to_vec();
+        self.query(selector, XuQuery::SET_CUR, &mut data)
+    }
+
+    /// Writes a new value to a control and reads it back to check that it was applied.
+    ///
+    /// Some camera firmware acknowledges `SET_CUR` requests but clamps or ignores the written
+    /// value. This method detects that by issuing `GET_CUR` after `SET_CUR`.
+    ///
+    /// # Errors
+    ///
+    /// In addition to the errors returned by [`ExtensionUnit::set_current`], this returns an error
+    /// of kind [`io::ErrorKind::InvalidData`] wrapping a [`VerifyMismatch`] if the value read back
+    /// differs from `data`. It can be obtained via [`io::Error::get_ref`] and downcasting, which
+    /// allows callers of controls that legitimately quantize values to inspect the applied value.
+    pub fn set_current_verified(&self, selector: u8, data: &[u8]) -> io::Result<()> {
+        self.set_current(selector, data)?;
+
+        let mut actual = vec![0; data.len()];
+        self.query(selector, XuQuery::GET_CUR, &mut actual)?;
+        if actual != data {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                VerifyMismatch {
+                    unit_id: self.unit_id,
+                    selector,
+                    requested: data.to_vec(),
+                    actual,
+                },
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reads the current value of a 1-byte control.
+    pub fn get_u8(&self, selector: u8) -> io::Result<u8> {
+        self.get_int(selector).map(u8::from_le_bytes)
+    }
+
+    /// Reads the current value of a 2-byte little-endian control.
+    pub fn get_u16(&self, selector: u8) -> io::Result<u16> {
+        self.get_int(selector).map(u16::from_le_bytes)
+    }
+
+    /// Reads the current value of a 4-byte little-endian control.
+    pub fn get_u32(&self, selector: u8) -> io::Result<u32> {
+        self.get_int(selector).map(u32::from_le_bytes)
+    }
+
+    /// Reads the current value of a 4-byte little-endian signed control.
+    pub fn get_i32(&self, selector: u8) -> io::Result<i32> {
+        self.get_int(selector).map(i32::from_le_bytes)
+    }
+
+    /// Writes a new value to a 1-byte control.
+    pub fn set_u8(&self, selector: u8, value: u8) -> io::Result<()> {
+        self.set_int(selector, value.to_le_bytes())
+    }
+
+    /// Writes a new value to a 2-byte little-endian control.
+    pub fn set_u16(&self, selector: u8, value: u16) -> io::Result<()> {
+        self.set_int(selector, value.to_le_bytes())
+    }
+
+    /// Writes a new value to a 4-byte little-endian control.
+    pub fn set_u32(&self, selector: u8, value: u32) -> io::Result<()> {
+        self.set_int(selector, value.to_le_bytes())
+    }
+
+    /// Writes a new value to a 4-byte little-endian signed control.
+    pub fn set_i32(&self, selector: u8, value: i32) -> io::Result<()> {
+        self.set_int(selector, value.to_le_bytes())
+    }
+
+    /// Reads the current value of a control and decodes it as a `T`.
+    ///
+    /// # Errors
+    ///
+    /// If the size of the control (as reported by `UVC_GET_LEN`) differs from
+    /// [`FromXuBytes::SIZE`], an error of kind [`io::ErrorKind::InvalidData`] is returned.
+    pub fn read_struct<T: FromXuBytes>(&self, selector: u8) -> io::Result<T> {
+        self.check_len(selector, T::SIZE)?;
+        let mut data = vec![0; T::SIZE];
+        self.query(selector, XuQuery::GET_CUR, &mut data)?;
+        Ok(T::from_xu_bytes(&data))
+    }
+
+    fn get_int<const N: usize>(&self, selector: u8) -> io::Result<[u8; N]> {
+        self.check_len(selector, N)?;
+        let mut data = [0; N];
+        self.query(selector, XuQuery::GET_CUR, &mut data)?;
+        Ok(data)
+    }
+
+    fn set_int<const N: usize>(&self, selector: u8, mut data: [u8; N]) -> io::Result<()> {
+        self.check_len(selector, N)?;
+        self.query(selector, XuQuery::SET_CUR, &mut data)
+    }
+
+    /// Checks that the control's `UVC_GET_LEN` matches the size of the type used to access it.
+    fn check_len(&self, selector: u8, expected: usize) -> io::Result<()> {
+        let len = self.control_len(selector)?;
+        if usize::from(len) != expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "control {:#04x} has a size of {} bytes, cannot access it as a {}-byte value",
+                    selector, len, expected
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Performs `query` with a buffer sized according to `UVC_GET_LEN`.
+    fn query_sized(&self, selector: u8, query: XuQuery) -> io::Result<Vec<u8>> {
+        let len = self.control_len(selector)?;
+        let mut data = vec![0; len.into()];
+        self.query(selector, query, &mut data)?;
+        Ok(data)
+    }
+
+    /// Issues an arbitrary control query.
+    ///
+    /// For `GET_*` queries, `data` receives the control value. For `SET_CUR`, `data` holds the
+    /// value to write. The length of `data` must match the size the device expects for this query
+    /// (see [`ExtensionUnit::control_len`]).
+    ///
+    /// # Errors
+    ///
+    /// If `data` is longer than `u16::MAX` bytes, an error of kind
+    /// [`io::ErrorKind::InvalidInput`] is returned.
+    ///
+    /// If [preflight checks][ExtensionUnit::with_preflight] are enabled, `SET_CUR` queries may
+    /// also fail with an [`XuError`].
+    pub fn query(&self, selector: u8, query: XuQuery, data: &mut [u8]) -> io::Result<()> {
+        let Some(cache) = self
+            .preflight
+            .as_ref()
+            .filter(|_| query == XuQuery::SET_CUR)
+        else {
+            return self.query_raw(selector, query, data);
+        };
+
+        self.preflight_check(cache, selector, false)?;
+        match self.query_raw(selector, query, data) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                // The cached information might be outdated, check whether it explains the error.
+                self.preflight_check(cache, selector, true)?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Checks that the control with selector `selector` can be written to.
+    fn preflight_check(&self, cache: &InfoCache, selector: u8, refresh: bool) -> io::Result<()> {
+        let key = (self.unit_id, selector);
+        let cached = match refresh {
+            true => None,
+            false => cache.lock().unwrap().get(&key).copied(),
+        };
+        let info = match cached {
+            // Only the "disabled" bits can change, so a control that was writable before is
+            // assumed to still be writable.
+            Some(info) if info.is_writable() || !info.contains(ControlInfo::SUPPORTS_SET) => info,
+            _ => {
+                let info = match self.control_info(selector) {
+                    Ok(info) => info,
+                    Err(e)
+                        if matches!(
+                            e.raw_os_error().map(Errno::from_i32),
+                            Some(Errno::EINVAL | Errno::ENOENT)
+                        ) =>
+                    {
+                        return Err(XuError::Unsupported.into());
+                    }
+                    Err(e) => return Err(e),
+                };
+                cache.lock().unwrap().insert(key, info);
+                info
+            }
+        };
+
+        if !info.contains(ControlInfo::SUPPORTS_SET) {
+            Err(XuError::ReadOnly.into())
+        } else if info.contains(ControlInfo::DISABLED_BY_AUTOMATIC_MODE) {
+            Err(XuError::DisabledByAutoMode.into())
+        } else if info.contains(ControlInfo::DISABLED_DUE_TO_STATE) {
+            Err(XuError::DisabledDueToState.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn query_raw(&self, selector: u8, query: XuQuery, data: &mut [u8]) -> io::Result<()> {
+        let size = u16::try_from(data.len()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("control data too large ({} bytes)", data.len()),
+            )
+        })?;
         let mut query = XuControlQuery {
             unit: self.unit_id,
             selector,
-            query: XuQuery::GET_INFO,
-            size: 1,
-            data: &mut info,
+            query,
+            size,
+            data: data.as_mut_ptr(),
         };
 
-        unsafe {
-            raw::ctrl_query(self.fd(), &mut query)?;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match unsafe { raw::ctrl_query(self.fd(), &mut query) } {
+                Ok(_) => return Ok(()),
+                Err(Errno::EPIPE | Errno::EPROTO) if attempts <= self.retry.retries => {
+                    log::debug!(
+                        "XU query {:?} of selector {} on unit {} failed (attempt {}), retrying",
+                        query.query,
+                        selector,
+                        self.unit_id,
+                        attempts,
+                    );
+                    thread::sleep(self.retry.delay);
+                }
+                Err(e) if attempts > 1 => {
+                    let e = io::Error::from(e);
+                    return Err(io::Error::new(
+                        e.kind(),
+                        format!("{} (failed after {} attempts)", e, attempts),
+                    ));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Queries information about every selector of this extension unit.
+    ///
+    /// Selectors the device doesn't implement (for which `UVC_GET_INFO` fails with `EINVAL` or
+    /// `ENOENT`) are skipped. The values in the returned [`SelectorReport`]s are `None` if the
+    /// respective query failed.
+    pub fn scan(&self) -> io::Result<Vec<SelectorReport>> {
+        let mut reports = Vec::new();
+        for selector in 0..=0xff {
+            let info = match self.control_info(selector) {
+                Ok(info) => info,
+                Err(e)
+                    if matches!(
+                        e.raw_os_error().map(Errno::from_i32),
+                        Some(Errno::EINVAL | Errno::ENOENT)
+                    ) =>
+                {
+                    continue
+                }
+                Err(e) => return Err(e),
+            };
+            reports.push(SelectorReport {
+                selector,
+                info,
+                len: self.control_len(selector).ok(),
+                min: self.control_min(selector).ok(),
+                max: self.control_max(selector).ok(),
+                default: self.control_default(selector).ok(),
+                current: self.get_current(selector).ok(),
+            });
+        }
+
+        Ok(reports)
+    }
+}
+
+/// Information about an extension unit control, as returned by [`ExtensionUnit::scan`].
+#[derive(Debug, Clone)]
+pub struct SelectorReport {
+    /// The control selector.
+    pub selector: u8,
+    /// Capabilities and status of the control (`UVC_GET_INFO`).
+    pub info: ControlInfo,
+    /// The size of the control value in bytes (`UVC_GET_LEN`).
+    pub len: Option<u16>,
+    /// The minimum value (`UVC_GET_MIN`).
+    pub min: Option<Vec<u8>>,
+    /// The maximum value (`UVC_GET_MAX`).
+    pub max: Option<Vec<u8>>,
+    /// The default value (`UVC_GET_DEF`).
+    pub default: Option<Vec<u8>>,
+    /// The current value (`UVC_GET_CUR`).
+    pub current: Option<Vec<u8>>,
+}
+
+/// Describes why writing to an extension unit control failed.
+///
+/// These errors are produced by the [preflight checks][ExtensionUnit::with_preflight]. Since the
+/// extension unit API returns [`io::Error`]s, they are wrapped in one (except for
+/// [`XuError::Io`], which is unwrapped). Use [`XuError::from_io_error`] to retrieve them.
+#[derive(Debug)]
+pub enum XuError {
+    /// The extension unit does not have a control with the given selector.
+    Unsupported,
+    /// The control does not support `SET_CUR`.
+    ReadOnly,
+    /// The control is disabled because an automatic mode is enabled.
+    DisabledByAutoMode,
+    /// The control is temporarily disabled due to other device state.
+    DisabledDueToState,
+    /// An I/O error occurred.
+    Io(io::Error),
+}
+
+impl XuError {
+    /// Returns the [`XuError`] wrapped in `error`, if any.
+    pub fn from_io_error(error: &io::Error) -> Option<&XuError> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for XuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XuError::Unsupported => f.write_str("control is not supported by the extension unit"),
+            XuError::ReadOnly => f.write_str("control is read-only"),
+            XuError::DisabledByAutoMode => f.write_str("control is disabled by an automatic mode"),
+            XuError::DisabledDueToState => f.write_str("control is disabled due to device state"),
+            XuError::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for XuError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            XuError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for XuError {
+    fn from(e: io::Error) -> Self {
+        XuError::Io(e)
+    }
+}
+
+impl From<XuError> for io::Error {
+    fn from(e: XuError) -> Self {
+        let kind = match e {
+            XuError::Io(e) => return e,
+            XuError::Unsupported => io::ErrorKind::Unsupported,
+            XuError::ReadOnly => io::ErrorKind::PermissionDenied,
+            XuError::DisabledByAutoMode | XuError::DisabledDueToState => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+/// Error returned by [`ExtensionUnit::set_current_verified`] when the value read back from a
+/// control differs from the written one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyMismatch {
+    pub unit_id: u8,
+    pub selector: u8,
+    /// The value written with `SET_CUR`.
+    pub requested: Vec<u8>,
+    /// The value returned by `GET_CUR` afterwards.
+    pub actual: Vec<u8>,
+}
+
+impl fmt::Display for VerifyMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "control {:#04x} on unit {} was set to {:02x?}, but reads back as {:02x?}",
+            self.selector, self.unit_id, self.requested, self.actual
+        )
+    }
+}
+
+impl std::error::Error for VerifyMismatch {}
+
+/// Types that can be decoded from the raw value of an extension unit control.
+///
+/// This is used by [`ExtensionUnit::read_struct`].
+pub trait FromXuBytes: Sized {
+    /// The size of the encoded value in bytes.
+    const SIZE: usize;
 
-            Ok(ControlInfo::from_bits_unchecked(info))
+    /// Decodes a value from `bytes`.
+    ///
+    /// `bytes` is guaranteed to be exactly [`Self::SIZE`] bytes long.
+    fn from_xu_bytes(bytes: &[u8]) -> Self;
+}
+
+/// A firmware or hardware version, encoded as major, minor and patch bytes followed by a
+/// little-endian 16-bit build number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VersionInfo {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+    pub build: u16,
+}
+
+impl FromXuBytes for VersionInfo {
+    const SIZE: usize = 5;
+
+    fn from_xu_bytes(bytes: &[u8]) -> Self {
+        Self {
+            major: bytes[0],
+            minor: bytes[1],
+            patch: bytes[2],
+            build: u16::from_le_bytes([bytes[3], bytes[4]]),
         }
     }
 }
 
+impl fmt::Display for VersionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}+{}",
+            self.major, self.minor, self.patch, self.build
+        )
+    }
+}
+
+impl FromXuBytes for ExposureWeights {
+    const SIZE: usize = Self::SIZE;
+
+    fn from_xu_bytes(bytes: &[u8]) -> Self {
+        Self::from_bytes(bytes.try_into().unwrap())
+    }
+}
+
 bitflags! {
+    /// Capabilities and status of an extension unit control, as returned by `UVC_GET_INFO`.
+    ///
+    /// Reserved bits are not representable and get dropped when decoding the firmware's response.
     pub struct ControlInfo: u8 {
+        /// The control supports `GET_CUR` and the other GET requests.
+        const SUPPORTS_GET               = 1 << 0;
+        /// The control supports `SET_CUR`.
+        const SUPPORTS_SET               = 1 << 1;
+        /// The control is temporarily disabled because an automatic mode is active.
+        const DISABLED_BY_AUTOMATIC_MODE = 1 << 2;
+        /// The control's value may change without the host writing to it.
+        const AUTOUPDATE                 = 1 << 3;
+        /// Setting the control completes asynchronously.
+        const ASYNCHRONOUS               = 1 << 4;
+        /// The control is disabled because it is incompatible with the current commit state of the
+        /// video streaming interface.
+        const DISABLED_DUE_TO_STATE      = 1 << 5;
+    }
+}
+
+impl ControlInfo {
+    /// Returns whether the control's value can be read.
+    #[inline]
+    pub fn is_readable(&self) -> bool {
+        self.contains(Self::SUPPORTS_GET)
+    }
+
+    /// Returns whether the control's value can currently be written.
+    ///
+    /// This returns `false` if the control does not support `SET_CUR`, or if it is temporarily
+    /// disabled.
+    #[inline]
+    pub fn is_writable(&self) -> bool {
+        self.contains(Self::SUPPORTS_SET)
+            && !self.intersects(Self::DISABLED_BY_AUTOMATIC_MODE | Self::DISABLED_DUE_TO_STATE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exposure_weights_roundtrip() {
+        let mut grid = [[0; ExposureWeights::WIDTH]; ExposureWeights::HEIGHT];
+        for (y, row) in grid.iter_mut().enumerate() {
+            for (x, weight) in row.iter_mut().enumerate() {
+                *weight = ((x + y * 3) % 16) as u8;
+            }
+        }
+        let weights = ExposureWeights::new(grid, 0x42).unwrap();
+        let bytes = weights.to_bytes();
+        assert_eq!(bytes[0], 0x10);
+        assert_eq!(bytes[ExposureWeights::SIZE - 1], 0x42);
+        assert_eq!(ExposureWeights::from_bytes(&bytes), weights);
+        assert_eq!(ExposureWeights::from_bytes(&bytes).weights(), &grid);
+    }
+
+    #[test]
+    fn exposure_weights_out_of_range() {
+        let mut grid = [[0; ExposureWeights::WIDTH]; ExposureWeights::HEIGHT];
+        grid[1][2] = 16;
+        let e = ExposureWeights::new(grid, 0).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            e.to_string(),
+            "exposure weight at (2, 1) does not fit in 4 bits: 16"
+        );
+    }
+
+    #[test]
+    fn roi_rect_raw_roundtrip() {
+        let rect = RoiRect::new(10, 20, 109, 219);
+        let raw = rect.to_raw();
+        assert_eq!(
+            (raw.left, raw.top, raw.width, raw.height),
+            (20, 10, 200, 100)
+        );
+        assert_eq!(RoiRect::from_raw(&raw), rect);
+    }
+
+    #[test]
+    fn version_info_from_bytes() {
+        let version = VersionInfo::from_xu_bytes(&[1, 2, 3, 0x34, 0x12]);
+        assert_eq!(
+            version,
+            VersionInfo {
+                major: 1,
+                minor: 2,
+                patch: 3,
+                build: 0x1234,
+            }
+        );
+        assert_eq!(version.to_string(), "1.2.3+4660");
+    }
+
+    fn metadata_record(header: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; UvcMetadata::KERNEL_HEADER_SIZE];
+        bytes[..8].copy_from_slice(&123u64.to_ne_bytes());
+        bytes[8..10].copy_from_slice(&45u16.to_ne_bytes());
+        bytes.extend_from_slice(header);
+        bytes
+    }
+
+    #[test]
+    fn metadata_all_header_bits() {
+        let mut header = [0; 12];
+        header[0] = 12;
+        header[1] = 0xff;
+        let meta = UvcMetadata::try_from_bytes(&metadata_record(&header)).unwrap();
+        assert_eq!(meta.header_info(), HeaderInfo::all());
+        assert_eq!(meta.header_info().bits(), 0xff);
+        assert_eq!(meta.header_info().frame_id(), 1);
+    }
+
+    #[test]
+    fn control_info_reserved_bits() {
+        let info = ControlInfo::from_bits_truncate(0b1100_0011);
+        assert_eq!(info, ControlInfo::SUPPORTS_GET | ControlInfo::SUPPORTS_SET);
+        assert_eq!(info.bits(), 0b11);
+    }
+
+    #[test]
+    fn metadata_full_header() {
+        let bytes = metadata_record(&[
+            12,
+            0b0000_1110,
+            0x04,
+            0x03,
+            0x02,
+            0x01,
+            0x0a,
+            0x0b,
+            0x0c,
+            0x0d,
+            0x01,
+            0xf8,
+        ]);
+        let meta = UvcMetadata::try_from_bytes(&bytes).unwrap();
+        assert_eq!(meta.ts(), 123);
+        assert_eq!(meta.sof(), 45);
+        assert!(meta.header_info().contains(HeaderInfo::END_OF_FRAME));
+        assert_eq!(meta.presentation_time(), Some(0x01020304));
+        let scr = meta.source_clock().unwrap();
+        assert_eq!(scr.stc, 0x0d0c0b0a);
+        assert_eq!(scr.sof_counter, 0x001);
+
+        let debug = format!("{:?}", meta);
+        assert!(
+            debug
+                .contains("header_info: END_OF_FRAME | PRESENTATION_TIME | SOURCE_CLOCK_REFERENCE"),
+            "{}",
+            debug
+        );
+        assert!(
+            debug.contains("presentation_time: Some(16909060)"),
+            "{}",
+            debug
+        );
+    }
+
+    #[test]
+    fn metadata_scr_without_pts() {
+        let bytes = metadata_record(&[8, 0b0000_1000, 0x0a, 0x0b, 0x0c, 0x0d, 0x02, 0x00]);
+        let meta = UvcMetadata::try_from_bytes(&bytes).unwrap();
+        assert_eq!(meta.presentation_time(), None);
+        let scr = meta.source_clock().unwrap();
+        assert_eq!(scr.stc, 0x0d0c0b0a);
+        assert_eq!(scr.sof_counter, 2);
+    }
+
+    #[test]
+    fn metadata_truncated() {
+        assert_eq!(
+            UvcMetadata::try_from_bytes(&[0; 10]).unwrap_err(),
+            MetadataError::TooShort { len: 10 },
+        );
+        assert_eq!(
+            UvcMetadata::try_from_bytes(&metadata_record(&[12, 0b0000_1100, 0, 0])).unwrap_err(),
+            MetadataError::InvalidHeaderLength {
+                header_length: 12,
+                available: 4,
+            },
+        );
+        assert_eq!(
+            UvcMetadata::try_from_bytes(&metadata_record(&[1])).unwrap_err(),
+            MetadataError::InvalidHeaderLength {
+                header_length: 1,
+                available: 1,
+            },
+        );
+        assert_eq!(
+            UvcMetadata::try_from_bytes(&metadata_record(&[6, 0b0000_1100, 0, 0, 0, 0]))
+                .unwrap_err(),
+            MetadataError::HeaderTooShort {
+                header_length: 6,
+                required: 12,
+            },
+        );
+    }
+
+    #[test]
+    fn metadata_oversized() {
+        // Vendor-specific header data and trailing records are ignored.
+        let mut bytes = metadata_record(&[
+            16,
+            0b0000_0110,
+            0x04,
+            0x03,
+            0x02,
+            0x01,
+            0xaa,
+            0xbb,
+            0xcc,
+            0xdd,
+            0xee,
+            0xff,
+            1,
+            2,
+            3,
+            4,
+        ]);
+        bytes.extend_from_slice(&metadata_record(&[2, 0]));
+        let meta = UvcMetadata::try_from_bytes(&bytes).unwrap();
+        assert_eq!(meta.presentation_time(), Some(0x01020304));
+        assert_eq!(meta.source_clock(), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn metadata_from_bytes_panics() {
+        UvcMetadata::from_bytes(&[0; 4]);
+    }
+
+    #[test]
+    fn metadata_parse_all() {
+        let mut bytes = metadata_record(&[6, 0b0000_0100, 1, 0, 0, 0]);
+        bytes.extend(metadata_record(&[2, 0b0000_0110]));
+        bytes.extend(metadata_record(&[6, 0b0000_0100, 3, 0, 0, 0]));
+        // Truncated trailing record
+        bytes.extend(metadata_record(&[12, 0]));
+
+        let records = UvcMetadata::parse_all(&bytes).collect::<Vec<_>>();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].unwrap().presentation_time(), Some(1));
+        assert_eq!(
+            records[1].unwrap_err(),
+            MetadataError::HeaderTooShort {
+                header_length: 2,
+                required: 6,
+            },
+        );
+        assert_eq!(records[2].unwrap().presentation_time(), Some(3));
+
+        let mut bytes = metadata_record(&[2, 0]);
+        bytes.extend(metadata_record(&[0]));
+        bytes.extend(metadata_record(&[2, 0]));
+        assert_eq!(UvcMetadata::parse_all(&bytes).count(), 1);
+    }
 
+    #[test]
+    fn source_clock_layout() {
+        assert_eq!(
+            SourceClock::from_bytes([0x5e, 0x1c, 0x3b, 0x9a, 0xd3, 0x06]),
+            SourceClock {
+                stc: 0x9a3b1c5e,
+                sof_counter: 0x6d3,
+            },
+        );
+        // The 5 reserved bits in the upper byte are ignored
+        assert_eq!(
+            SourceClock::from_bytes([0x00, 0x00, 0x00, 0x80, 0xff, 0xff]),
+            SourceClock {
+                stc: 0x80000000,
+                sof_counter: 0x7ff,
+            },
+        );
+        assert_eq!(
+            SourceClock::from_bytes([0x01, 0x00, 0x00, 0x00, 0x00, 0xf8]).sof_counter,
+            0,
+        );
     }
 }
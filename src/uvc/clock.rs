@@ -0,0 +1,291 @@
+//! Mapping of the camera's device clock onto the host clock.
+
+use std::collections::VecDeque;
+
+use super::UvcMetadata;
+
+/// Number of SOF counter values (the counter is 11 bits wide and increments every millisecond).
+const SOF_PERIOD: u16 = 2048;
+const NS_PER_SOF: i64 = 1_000_000;
+
+/// Estimates host timestamps from the device clock values in UVC payload headers.
+///
+/// Every payload header carrying a source clock reference ([`UvcMetadata::source_clock`]) relates
+/// a device clock value (STC) to a USB frame number. Together with the host timestamp and USB frame
+/// number recorded by the kernel when the payload arrived, this yields a sample of device clock vs.
+/// host clock (`CLOCK_MONOTONIC`). [`ClockMapper`] keeps a window of recent samples and fits a
+/// linear function to them, similar to what the `uvcvideo` driver does internally, which can then
+/// be used to convert a frame's presentation time stamp into a host timestamp.
+///
+/// Wraparound of the 32-bit device clock and the 11-bit SOF counter is handled transparently, as
+/// long as samples are pushed at least once per wraparound period of either.
+///
+/// Since the host only records the USB frame number at which a payload arrived, the absolute
+/// accuracy of the mapping is limited to one USB frame (1 ms): estimated timestamps are offset by
+/// the (unknown) delay between the start of a USB frame and the host timestamp of the payloads
+/// received in it. Differences between estimated timestamps are much more accurate.
+#[derive(Debug, Clone)]
+pub struct ClockMapper {
+    window: usize,
+    samples: VecDeque<Sample>,
+    /// Last raw device clock value and its unwrapped counterpart.
+    last_stc: Option<(u32, i64)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    /// Unwrapped device clock value.
+    stc: i64,
+    /// Host time in nanoseconds at which the device clock was sampled.
+    host_ns: i64,
+}
+
+/// A host timestamp estimated by [`ClockMapper::map_pts`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockEstimate {
+    /// The estimated host timestamp (`CLOCK_MONOTONIC`) in nanoseconds.
+    pub host_ns: u64,
+    /// The root mean square deviation of the samples from the fitted clock mapping, in
+    /// nanoseconds.
+    ///
+    /// This is a measure of the jitter of the samples and thus of the accuracy of the estimate.
+    pub rms_error_ns: f64,
+    /// The number of samples the estimate is based on.
+    pub samples: usize,
+}
+
+impl Default for ClockMapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClockMapper {
+    /// The default number of samples to keep, matching the `uvcvideo` driver.
+    pub const DEFAULT_WINDOW: usize = 32;
+
+    /// The minimum number of samples required before timestamps can be estimated.
+    pub const MIN_SAMPLES: usize = 2;
+
+    /// Creates a mapper that keeps the last [`ClockMapper::DEFAULT_WINDOW`] samples.
+    pub fn new() -> Self {
+        Self::with_window(Self::DEFAULT_WINDOW)
+    }
+
+    /// Creates a mapper that keeps the last `window` samples.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `window` is less than [`ClockMapper::MIN_SAMPLES`].
+    pub fn with_window(window: usize) -> Self {
+        assert!(
+            window >= Self::MIN_SAMPLES,
+            "clock mapper window must hold at least {} samples",
+            Self::MIN_SAMPLES
+        );
+        Self {
+            window,
+            samples: VecDeque::with_capacity(window),
+            last_stc: None,
+        }
+    }
+
+    /// Returns the number of samples currently used for the clock mapping.
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Discards all collected samples.
+    ///
+    /// This should be called when the stream is restarted, since the device clock might have been
+    /// reset.
+    pub fn reset(&mut self) {
+        self.samples.clear();
+        self.last_stc = None;
+    }
+
+    /// Adds the clock sample contained in `meta`.
+    ///
+    /// Records without a source clock reference are ignored. Returns whether a sample was added.
+    pub fn push(&mut self, meta: &UvcMetadata) -> bool {
+        let Some(scr) = meta.source_clock() else {
+            return false;
+        };
+        self.push_raw(meta.ts(), meta.sof(), scr.stc, scr.sof_counter);
+        true
+    }
+
+    /// Adds a clock sample from its raw components.
+    ///
+    /// - `host_ns` and `host_sof` are the host timestamp and USB frame number recorded when the
+    ///   payload was received ([`UvcMetadata::ts`] and [`UvcMetadata::sof`]).
+    /// - `stc` and `scr_sof` are the device clock value and USB frame number at which it was
+    ///   sampled by the device (from [`UvcMetadata::source_clock`]).
+    pub fn push_raw(&mut self, host_ns: u64, host_sof: u16, stc: u32, scr_sof: u16) {
+        let stc = self.unwrap_stc(stc);
+
+        // The device sampled its clock at the start of frame `scr_sof`, the host timestamp was taken
+        // `host_sof - scr_sof` frames later.
+        let frames = (host_sof % SOF_PERIOD + SOF_PERIOD - scr_sof % SOF_PERIOD) % SOF_PERIOD;
+        let host_ns = host_ns as i64 - i64::from(frames) * NS_PER_SOF;
+
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample { stc, host_ns });
+    }
+
+    /// Converts a presentation time stamp ([`UvcMetadata::presentation_time`]) to a host
+    /// timestamp.
+    ///
+    /// Returns `None` if fewer than [`ClockMapper::MIN_SAMPLES`] samples have been collected, or if
+    /// the samples do not allow fitting a clock mapping (eg. because the device clock did not
+    /// advance).
+    pub fn map_pts(&self, pts: u32) -> Option<ClockEstimate> {
+        let (last_stc, last_unwrapped) = self.last_stc?;
+        let (slope, intercept, rms_error_ns) = self.fit()?;
+
+        // The PTS is close to the most recent device clock samples, so it can be unwrapped relative
+        // to them.
+        let pts = last_unwrapped + i64::from(pts.wrapping_sub(last_stc) as i32);
+        let base = self.samples[0];
+        let host_ns = base.host_ns as f64 + intercept + slope * (pts - base.stc) as f64;
+        if host_ns < 0.0 {
+            return None;
+        }
+
+        Some(ClockEstimate {
+            host_ns: host_ns.round() as u64,
+            rms_error_ns,
+            samples: self.samples.len(),
+        })
+    }
+
+    /// Returns the estimated frequency of the device clock in Hz.
+    pub fn device_clock_frequency(&self) -> Option<f64> {
+        let (slope, _, _) = self.fit()?;
+        Some(1e9 / slope)
+    }
+
+    fn unwrap_stc(&mut self, stc: u32) -> i64 {
+        let unwrapped = match self.last_stc {
+            // The device clock might go backwards by a small amount due to jitter, so compute the
+            // signed difference to the last value.
+            Some((last, last_unwrapped)) => {
+                last_unwrapped + i64::from(stc.wrapping_sub(last) as i32)
+            }
+            None => i64::from(stc),
+        };
+        self.last_stc = Some((stc, unwrapped));
+        unwrapped
+    }
+
+    /// Performs a least-squares fit of host time vs. device clock.
+    ///
+    /// Coordinates are relative to the first sample to retain precision. Returns the slope (in
+    /// nanoseconds per device clock tick), the intercept and the RMS residual.
+    fn fit(&self) -> Option<(f64, f64, f64)> {
+        if self.samples.len() < Self::MIN_SAMPLES {
+            return None;
+        }
+
+        let base = self.samples[0];
+        let points = || {
+            self.samples
+                .iter()
+                .map(move |s| ((s.stc - base.stc) as f64, (s.host_ns - base.host_ns) as f64))
+        };
+
+        let n = self.samples.len() as f64;
+        let (sum_x, sum_y) = points().fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+        let (mean_x, mean_y) = (sum_x / n, sum_y / n);
+        let (sxx, sxy) = points().fold((0.0, 0.0), |(sxx, sxy), (x, y)| {
+            let dx = x - mean_x;
+            (sxx + dx * dx, sxy + dx * (y - mean_y))
+        });
+        if sxx == 0.0 {
+            return None;
+        }
+
+        let slope = sxy / sxx;
+        let intercept = mean_y - slope * mean_x;
+        let sq_error = points()
+            .map(|(x, y)| {
+                let residual = y - (intercept + slope * x);
+                residual * residual
+            })
+            .sum::<f64>();
+
+        Some((slope, intercept, (sq_error / n).sqrt()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FREQ: i64 = 48_000_000;
+    const HOST_START: i64 = 5_000_000_000;
+    const STC_START: u32 = u32::MAX - 10_000_000;
+    const SOF_START: u16 = 2040;
+
+    /// Device clock value at host time `t`.
+    fn stc_at(t: i64) -> u32 {
+        (i64::from(STC_START) + (t - HOST_START) * FREQ / 1_000_000_000) as u32
+    }
+
+    fn sof_at(t: i64) -> u16 {
+        ((i64::from(SOF_START) + (t - HOST_START) / NS_PER_SOF) % i64::from(SOF_PERIOD)) as u16
+    }
+
+    #[test]
+    fn too_few_samples() {
+        let mut mapper = ClockMapper::new();
+        assert_eq!(mapper.map_pts(0), None);
+        mapper.push_raw(HOST_START as u64, SOF_START, STC_START, SOF_START);
+        assert_eq!(mapper.map_pts(0), None);
+    }
+
+    #[test]
+    fn wrapping_samples() {
+        let mut mapper = ClockMapper::with_window(8);
+
+        // One sample every 33 ms for 2 seconds. This wraps the SOF counter and the device clock.
+        for k in 0..60 {
+            // The device samples its clock at the start of a USB frame, the payload arrives 2 frames
+            // and 300 µs (plus some jitter) later.
+            let sampled = HOST_START + k * 33 * NS_PER_SOF;
+            let arrived = sampled + 2 * NS_PER_SOF + 300_000 + (k % 3 - 1) * 1000;
+            mapper.push_raw(
+                arrived as u64,
+                sof_at(arrived),
+                stc_at(sampled),
+                sof_at(sampled),
+            );
+        }
+        assert_eq!(mapper.sample_count(), 8);
+
+        let last_sample = HOST_START + 59 * 33 * NS_PER_SOF;
+        assert!(stc_at(last_sample) < STC_START, "device clock did not wrap");
+
+        let frequency = mapper.device_clock_frequency().unwrap();
+        assert!((frequency - FREQ as f64).abs() < 1000.0, "{}", frequency);
+
+        let captured = last_sample + 12_345_678;
+        let estimate = mapper.map_pts(stc_at(captured)).unwrap();
+        assert_eq!(estimate.samples, 8);
+        assert!(estimate.rms_error_ns < 1000.0, "{:?}", estimate);
+        // The absolute error is dominated by the offset of the host timestamps within the USB frame.
+        let error = estimate.host_ns as i64 - captured - 300_000;
+        assert!(error.abs() < 2000, "error of {} ns", error);
+
+        // Time differences are accurate.
+        let later = mapper.map_pts(stc_at(captured + 10_000_000)).unwrap();
+        let diff = later.host_ns as i64 - estimate.host_ns as i64;
+        assert!(
+            (diff - 10_000_000).abs() < 1000,
+            "difference of {} ns",
+            diff
+        );
+    }
+}
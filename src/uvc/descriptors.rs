@@ -0,0 +1,260 @@
+//! Information about the USB device backing a UVC camera, read from sysfs.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::Device;
+
+const USB_DT_INTERFACE: u8 = 0x04;
+const USB_DT_CS_INTERFACE: u8 = 0x24;
+const USB_CLASS_VIDEO: u8 = 0x0e;
+const UVC_SC_VIDEOCONTROL: u8 = 0x01;
+const UVC_VC_EXTENSION_UNIT: u8 = 0x06;
+
+/// Describes an extension unit of a UVC device, as listed in its USB descriptors.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ExtensionUnitDescriptor {
+    unit_id: u8,
+    guid: [u8; 16],
+    num_controls: u8,
+    control_bitmap: Vec<u8>,
+}
+
+impl ExtensionUnitDescriptor {
+    /// Returns the ID of the unit, which can be passed to [`UvcExt::extension_unit`].
+    ///
+    /// [`UvcExt::extension_unit`]: super::UvcExt::extension_unit
+    #[inline]
+    pub fn unit_id(&self) -> u8 {
+        self.unit_id
+    }
+
+    /// Returns the GUID identifying the vendor-specific interface of this unit, in the byte order
+    /// used by USB descriptors.
+    #[inline]
+    pub fn guid(&self) -> &[u8; 16] {
+        &self.guid
+    }
+
+    /// Returns the number of controls the unit claims to implement.
+    #[inline]
+    pub fn num_controls(&self) -> u8 {
+        self.num_controls
+    }
+
+    /// Returns the raw control bitmap.
+    ///
+    /// Bit `n` (counting from the least significant bit of the first byte) is set if the control
+    /// with selector `n + 1` is available.
+    #[inline]
+    pub fn control_bitmap(&self) -> &[u8] {
+        &self.control_bitmap
+    }
+
+    /// Returns whether the control bitmap marks the control with selector `selector` as available.
+    pub fn has_control(&self, selector: u8) -> bool {
+        let Some(bit) = usize::from(selector).checked_sub(1) else {
+            return false;
+        };
+        self.control_bitmap
+            .get(bit / 8)
+            .is_some_and(|byte| byte & (1 << (bit % 8)) != 0)
+    }
+}
+
+impl fmt::Debug for ExtensionUnitDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtensionUnitDescriptor")
+            .field("unit_id", &self.unit_id)
+            .field("guid", &GuidDisplay(&self.guid))
+            .field("num_controls", &self.num_controls)
+            .field(
+                "control_bitmap",
+                &format_args!("{:02x?}", self.control_bitmap),
+            )
+            .finish()
+    }
+}
+
+/// Formats a GUID stored in USB byte order in its canonical textual representation.
+struct GuidDisplay<'a>(&'a [u8; 16]);
+
+impl fmt::Debug for GuidDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let g = self.0;
+        write!(
+            f,
+            "{:02X}{:02X}{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-",
+            g[3], g[2], g[1], g[0], g[5], g[4], g[7], g[6], g[8], g[9],
+        )?;
+        for b in &g[10..] {
+            write!(f, "{:02X}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the sysfs directory of the USB device backing `device`.
+///
+/// Returns `None` if the device is not a USB device.
+fn usb_device_dir(device: &Device) -> io::Result<Option<PathBuf>> {
+    // The `device` link of a USB video device points to its USB interface, whose parent is the USB
+    // device.
+    let interface = match fs::canonicalize(device.sysfs_path()?.join("device")) {
+        Ok(path) => path,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if !interface.join("bInterfaceClass").exists() {
+        return Ok(None);
+    }
+    Ok(interface.parent().map(Path::to_path_buf))
+}
+
+/// Reads the USB vendor and product ID of the device backing `device`.
+///
+/// Returns `None` if the device is not a USB device.
+pub(super) fn usb_id(device: &Device) -> io::Result<Option<(u16, u16)>> {
+    let Some(dir) = usb_device_dir(device)? else {
+        return Ok(None);
+    };
+    let read_id = |file: &str| -> io::Result<u16> {
+        let id = fs::read_to_string(dir.join(file))?;
+        u16::from_str_radix(id.trim(), 16).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid USB ID {:?} in {}: {}", id.trim(), file, e),
+            )
+        })
+    };
+    Ok(Some((read_id("idVendor")?, read_id("idProduct")?)))
+}
+
+/// Reads the extension unit descriptors of the USB device backing `device`.
+///
+/// Returns an empty list if the device is not a USB device.
+pub(super) fn read(device: &Device) -> io::Result<Vec<ExtensionUnitDescriptor>> {
+    // The raw descriptors of all interfaces are stored in the USB device.
+    let Some(dir) = usb_device_dir(device)? else {
+        return Ok(Vec::new());
+    };
+    let descriptors = match fs::read(dir.join("descriptors")) {
+        Ok(descriptors) => descriptors,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    Ok(parse(&descriptors))
+}
+
+/// Extracts the extension unit descriptors from a list of raw USB descriptors.
+///
+/// Only extension units belonging to a video control interface are returned.
+fn parse(mut descriptors: &[u8]) -> Vec<ExtensionUnitDescriptor> {
+    let mut units = Vec::new();
+    let mut in_video_control = false;
+
+    while let [len, ty, ..] = *descriptors {
+        let len = usize::from(len);
+        if len < 2 || len > descriptors.len() {
+            log::warn!("malformed USB descriptor (length {})", len);
+            break;
+        }
+        let desc = &descriptors[..len];
+        descriptors = &descriptors[len..];
+
+        match (ty, desc) {
+            (USB_DT_INTERFACE, &[_, _, _, _, _, class, subclass, ..]) => {
+                in_video_control = class == USB_CLASS_VIDEO && subclass == UVC_SC_VIDEOCONTROL;
+            }
+            (USB_DT_CS_INTERFACE, &[_, _, UVC_VC_EXTENSION_UNIT, ..]) if in_video_control => {
+                match parse_extension_unit(desc) {
+                    Some(unit) => units.push(unit),
+                    None => log::warn!("malformed extension unit descriptor: {:02x?}", desc),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    units
+}
+
+fn parse_extension_unit(desc: &[u8]) -> Option<ExtensionUnitDescriptor> {
+    // bLength, bDescriptorType, bDescriptorSubtype, bUnitID, guidExtensionCode[16], bNumControls,
+    // bNrInPins (p), baSourceID[p], bControlSize (n), bmControls[n], iExtension
+    let unit_id = *desc.get(3)?;
+    let guid = desc.get(4..20)?.try_into().ok()?;
+    let num_controls = *desc.get(20)?;
+    let num_pins = usize::from(*desc.get(21)?);
+    let control_size_pos = 22 + num_pins;
+    let control_size = usize::from(*desc.get(control_size_pos)?);
+    let control_bitmap = desc
+        .get(control_size_pos + 1..control_size_pos + 1 + control_size)?
+        .to_vec();
+
+    Some(ExtensionUnitDescriptor {
+        unit_id,
+        guid,
+        num_controls,
+        control_bitmap,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_extension_units() {
+        #[rustfmt::skip]
+        let descriptors = [
+            // Video control interface
+            9, USB_DT_INTERFACE, 0, 0, 1, USB_CLASS_VIDEO, UVC_SC_VIDEOCONTROL, 0, 0,
+            // Extension unit 4, 1 input pin, 2 byte bitmap
+            27, USB_DT_CS_INTERFACE, UVC_VC_EXTENSION_UNIT, 4,
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+            3, 1, 2, 2, 0b0000_0101, 0b1000_0000, 0,
+            // Video streaming interface with a descriptor that shares the extension unit subtype
+            9, USB_DT_INTERFACE, 1, 0, 1, USB_CLASS_VIDEO, 0x02, 0, 0,
+            4, USB_DT_CS_INTERFACE, UVC_VC_EXTENSION_UNIT, 0,
+        ];
+
+        let units = parse(&descriptors);
+        assert_eq!(units.len(), 1);
+        let unit = &units[0];
+        assert_eq!(unit.unit_id(), 4);
+        assert_eq!(
+            unit.guid(),
+            &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+        );
+        assert_eq!(unit.num_controls(), 3);
+        assert_eq!(unit.control_bitmap(), &[0b0000_0101, 0b1000_0000]);
+        assert!(unit.has_control(1));
+        assert!(!unit.has_control(2));
+        assert!(unit.has_control(3));
+        assert!(unit.has_control(16));
+        assert!(!unit.has_control(0));
+        assert!(!unit.has_control(17));
+    }
+
+    #[test]
+    fn parse_truncated() {
+        let descriptors = [
+            9,
+            USB_DT_INTERFACE,
+            0,
+            0,
+            1,
+            USB_CLASS_VIDEO,
+            UVC_SC_VIDEOCONTROL,
+            0,
+            0,
+            30,
+            USB_DT_CS_INTERFACE,
+        ];
+        assert!(parse(&descriptors).is_empty());
+    }
+}
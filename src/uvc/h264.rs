@@ -0,0 +1,309 @@
+//! UVC H.264 encoder extension unit (UVCX), as implemented by cameras like the Logitech C920.
+//!
+//! The H.264 encoder is configured through a probe/commit sequence similar to the one used by the
+//! video streaming interface: a [`VideoConfig`] is written to the probe control, the camera adjusts
+//! it to what it supports, and the result is written to the commit control to take effect.
+//!
+//! The unit ID of the H.264 extension unit differs between cameras. It can be found by looking for
+//! the extension unit with GUID [`GUID`].
+
+use std::io;
+
+use super::{ExtensionUnit, XuQuery};
+
+/// GUID of the UVCX H.264 extension unit (`A29E7641-DE04-47E3-8B2B-F4341AFF003B`), in the byte
+/// order used by USB descriptors.
+pub const GUID: [u8; 16] = [
+    0x41, 0x76, 0x9e, 0xa2, 0x04, 0xde, 0xe3, 0x47, 0x8b, 0x2b, 0xf4, 0x34, 0x1a, 0xff, 0x00, 0x3b,
+];
+
+const VIDEO_CONFIG_PROBE: u8 = 0x01;
+const VIDEO_CONFIG_COMMIT: u8 = 0x02;
+const RATE_CONTROL_MODE: u8 = 0x03;
+const BITRATE_LAYERS: u8 = 0x0e;
+
+ffi_enum! {
+    /// Rate control mode of the H.264 encoder.
+    pub enum RateControlMode: u8 {
+        /// Constant bitrate.
+        CBR      = 0x01,
+        /// Variable bitrate.
+        VBR      = 0x02,
+        /// Constant quantization parameter.
+        CONST_QP = 0x03,
+    }
+}
+
+ffi_enum! {
+    /// Determines how the encoder divides frames into slices.
+    pub enum SliceMode: u16 {
+        /// Each frame is a single slice.
+        OFF              = 0x0000,
+        /// `slice_units` specifies the number of bits per slice.
+        BITS_PER_SLICE   = 0x0001,
+        /// `slice_units` specifies the number of macroblocks per slice.
+        MBS_PER_SLICE    = 0x0002,
+        /// `slice_units` specifies the number of slices per frame.
+        SLICES_PER_FRAME = 0x0003,
+    }
+}
+
+ffi_enum! {
+    /// H.264 profile (`profile_idc` in the upper byte, constraint flags in the lower byte).
+    pub enum Profile: u16 {
+        CONSTRAINED_BASELINE = 0x4240,
+        BASELINE             = 0x4200,
+        MAIN                 = 0x4d00,
+        CONSTRAINED_HIGH     = 0x640c,
+        HIGH                 = 0x6400,
+        SCALABLE_BASELINE    = 0x5300,
+        SCALABLE_HIGH        = 0x5600,
+        MULTIVIEW_HIGH       = 0x7600,
+        STEREO_HIGH          = 0x8000,
+    }
+}
+
+/// H.264 encoder configuration (`uvcx_video_config_probe_commit_t`).
+///
+/// The layout matches the wire format of the probe and commit controls. All multi-byte fields are
+/// transmitted in little-endian byte order.
+#[derive(Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct VideoConfig {
+    /// Frame interval in 100 ns units.
+    pub frame_interval: u32,
+    /// Bitrate in bits per second.
+    pub bitrate: u32,
+    /// Bitmask indicating which fields the camera should keep fixed during probing.
+    pub hints: u16,
+    pub configuration_index: u16,
+    pub width: u16,
+    pub height: u16,
+    pub slice_units: u16,
+    pub slice_mode: SliceMode,
+    pub profile: Profile,
+    /// Number of milliseconds between IDR frames.
+    pub i_frame_period: u16,
+    pub estimated_video_delay: u16,
+    pub estimated_max_config_delay: u16,
+    pub usage_type: u8,
+    pub rate_control_mode: RateControlMode,
+    pub temporal_scale_mode: u8,
+    pub spatial_scale_mode: u8,
+    pub snr_scale_mode: u8,
+    pub stream_mux_option: u8,
+    pub stream_format: u8,
+    pub entropy_cabac: u8,
+    pub timestamp: u8,
+    pub num_of_reorder_frames: u8,
+    pub preview_flipped: u8,
+    pub view: u8,
+    pub reserved: [u8; 2],
+    pub stream_id: u8,
+    pub spatial_layer_ratio: u8,
+    pub leaky_bucket_size: u16,
+}
+
+impl VideoConfig {
+    /// Size of the encoded configuration in bytes.
+    pub const SIZE: usize = 46;
+
+    /// Decodes a configuration from the raw control value.
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let u32_at =
+            |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+
+        Self {
+            frame_interval: u32_at(0),
+            bitrate: u32_at(4),
+            hints: u16_at(8),
+            configuration_index: u16_at(10),
+            width: u16_at(12),
+            height: u16_at(14),
+            slice_units: u16_at(16),
+            slice_mode: SliceMode(u16_at(18)),
+            profile: Profile(u16_at(20)),
+            i_frame_period: u16_at(22),
+            estimated_video_delay: u16_at(24),
+            estimated_max_config_delay: u16_at(26),
+            usage_type: bytes[28],
+            rate_control_mode: RateControlMode(bytes[29]),
+            temporal_scale_mode: bytes[30],
+            spatial_scale_mode: bytes[31],
+            snr_scale_mode: bytes[32],
+            stream_mux_option: bytes[33],
+            stream_format: bytes[34],
+            entropy_cabac: bytes[35],
+            timestamp: bytes[36],
+            num_of_reorder_frames: bytes[37],
+            preview_flipped: bytes[38],
+            view: bytes[39],
+            reserved: [bytes[40], bytes[41]],
+            stream_id: bytes[42],
+            spatial_layer_ratio: bytes[43],
+            leaky_bucket_size: u16_at(44),
+        }
+    }
+
+    /// Encodes the configuration as a raw control value.
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..4].copy_from_slice(&{ self.frame_interval }.to_le_bytes());
+        bytes[4..8].copy_from_slice(&{ self.bitrate }.to_le_bytes());
+        bytes[8..10].copy_from_slice(&{ self.hints }.to_le_bytes());
+        bytes[10..12].copy_from_slice(&{ self.configuration_index }.to_le_bytes());
+        bytes[12..14].copy_from_slice(&{ self.width }.to_le_bytes());
+        bytes[14..16].copy_from_slice(&{ self.height }.to_le_bytes());
+        bytes[16..18].copy_from_slice(&{ self.slice_units }.to_le_bytes());
+        bytes[18..20].copy_from_slice(&{ self.slice_mode }.0.to_le_bytes());
+        bytes[20..22].copy_from_slice(&{ self.profile }.0.to_le_bytes());
+        bytes[22..24].copy_from_slice(&{ self.i_frame_period }.to_le_bytes());
+        bytes[24..26].copy_from_slice(&{ self.estimated_video_delay }.to_le_bytes());
+        bytes[26..28].copy_from_slice(&{ self.estimated_max_config_delay }.to_le_bytes());
+        bytes[28] = self.usage_type;
+        bytes[29] = self.rate_control_mode.0;
+        bytes[30] = self.temporal_scale_mode;
+        bytes[31] = self.spatial_scale_mode;
+        bytes[32] = self.snr_scale_mode;
+        bytes[33] = self.stream_mux_option;
+        bytes[34] = self.stream_format;
+        bytes[35] = self.entropy_cabac;
+        bytes[36] = self.timestamp;
+        bytes[37] = self.num_of_reorder_frames;
+        bytes[38] = self.preview_flipped;
+        bytes[39] = self.view;
+        bytes[40..42].copy_from_slice(&self.reserved);
+        bytes[42] = self.stream_id;
+        bytes[43] = self.spatial_layer_ratio;
+        bytes[44..46].copy_from_slice(&{ self.leaky_bucket_size }.to_le_bytes());
+        bytes
+    }
+}
+
+const _: () = assert!(std::mem::size_of::<VideoConfig>() == VideoConfig::SIZE);
+
+/// Peak and average bitrate of an encoder layer (`uvcx_bitrate_layers_t`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitrateLayers {
+    /// The layer the bitrates apply to (0 for the base layer of non-scalable streams).
+    pub layer_id: u16,
+    /// Peak bitrate in bits per second.
+    pub peak_bitrate: u32,
+    /// Average bitrate in bits per second.
+    pub average_bitrate: u32,
+}
+
+/// Access to the H.264 encoder of a UVC camera.
+pub struct H264Ext<'a> {
+    xu: ExtensionUnit<'a>,
+}
+
+impl<'a> H264Ext<'a> {
+    /// Wraps the extension unit implementing the UVCX H.264 controls.
+    ///
+    /// `xu` must refer to the extension unit with GUID [`GUID`].
+    pub fn new(xu: ExtensionUnit<'a>) -> Self {
+        Self { xu }
+    }
+
+    /// Writes `config` to the probe control and returns the configuration the camera would use.
+    pub fn probe(&self, config: &VideoConfig) -> io::Result<VideoConfig> {
+        let mut data = config.to_bytes();
+        self.xu
+            .query(VIDEO_CONFIG_PROBE, XuQuery::SET_CUR, &mut data)?;
+        self.probe_query(XuQuery::GET_CUR)
+    }
+
+    /// Returns the minimum configuration values supported by the camera.
+    pub fn probe_min(&self) -> io::Result<VideoConfig> {
+        self.probe_query(XuQuery::GET_MIN)
+    }
+
+    /// Returns the maximum configuration values supported by the camera.
+    pub fn probe_max(&self) -> io::Result<VideoConfig> {
+        self.probe_query(XuQuery::GET_MAX)
+    }
+
+    /// Returns the camera's default configuration.
+    pub fn probe_default(&self) -> io::Result<VideoConfig> {
+        self.probe_query(XuQuery::GET_DEF)
+    }
+
+    /// Commits a configuration, making the encoder use it.
+    ///
+    /// `config` should be the result of a previous call to [`H264Ext::probe`].
+    pub fn commit(&self, config: &VideoConfig) -> io::Result<()> {
+        let mut data = config.to_bytes();
+        self.xu
+            .query(VIDEO_CONFIG_COMMIT, XuQuery::SET_CUR, &mut data)
+    }
+
+    /// Probes `config` and commits the adjusted configuration, which is returned.
+    pub fn negotiate(&self, config: &VideoConfig) -> io::Result<VideoConfig> {
+        let config = self.probe(config)?;
+        self.commit(&config)?;
+        Ok(config)
+    }
+
+    /// Reads the committed configuration.
+    pub fn current(&self) -> io::Result<VideoConfig> {
+        let mut data = [0; VideoConfig::SIZE];
+        self.xu
+            .query(VIDEO_CONFIG_COMMIT, XuQuery::GET_CUR, &mut data)?;
+        Ok(VideoConfig::from_bytes(&data))
+    }
+
+    /// Changes the rate control mode of an encoder layer while streaming.
+    pub fn set_rate_control_mode(&self, layer_id: u16, mode: RateControlMode) -> io::Result<()> {
+        let [a, b] = layer_id.to_le_bytes();
+        self.xu
+            .query(RATE_CONTROL_MODE, XuQuery::SET_CUR, &mut [a, b, mode.0])
+    }
+
+    /// Changes the bitrate of an encoder layer while streaming.
+    pub fn set_bitrate_layers(&self, bitrates: &BitrateLayers) -> io::Result<()> {
+        let mut data = [0; 10];
+        data[0..2].copy_from_slice(&bitrates.layer_id.to_le_bytes());
+        data[2..6].copy_from_slice(&bitrates.peak_bitrate.to_le_bytes());
+        data[6..10].copy_from_slice(&bitrates.average_bitrate.to_le_bytes());
+        self.xu.query(BITRATE_LAYERS, XuQuery::SET_CUR, &mut data)
+    }
+
+    /// Reads the current bitrate settings.
+    pub fn bitrate_layers(&self) -> io::Result<BitrateLayers> {
+        let mut data = [0; 10];
+        self.xu.query(BITRATE_LAYERS, XuQuery::GET_CUR, &mut data)?;
+        Ok(BitrateLayers {
+            layer_id: u16::from_le_bytes([data[0], data[1]]),
+            peak_bitrate: u32::from_le_bytes([data[2], data[3], data[4], data[5]]),
+            average_bitrate: u32::from_le_bytes([data[6], data[7], data[8], data[9]]),
+        })
+    }
+
+    fn probe_query(&self, query: XuQuery) -> io::Result<VideoConfig> {
+        let mut data = [0; VideoConfig::SIZE];
+        self.xu.query(VIDEO_CONFIG_PROBE, query, &mut data)?;
+        Ok(VideoConfig::from_bytes(&data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn video_config_roundtrip() {
+        let mut bytes = [0; VideoConfig::SIZE];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+
+        let config = VideoConfig::from_bytes(&bytes);
+        assert_eq!({ config.frame_interval }, 0x03020100);
+        assert_eq!({ config.width }, 0x0d0c);
+        assert_eq!(config.rate_control_mode, RateControlMode(29));
+        assert_eq!({ config.leaky_bucket_size }, 0x2d2c);
+        assert_eq!(config.to_bytes(), bytes);
+    }
+}
@@ -0,0 +1,173 @@
+//! Vendor extension units of Logitech webcams.
+//!
+//! Logitech cameras expose their LED, the pan/tilt motors and the focus motor through vendor
+//! specific extension units. The IDs of those units vary between models, so [`LogitechExt::new`]
+//! looks them up by GUID, and falls back to probing a range of candidate unit IDs for controls
+//! with the expected sizes if the USB descriptors are unavailable.
+
+use std::io;
+
+use super::{ExtensionUnit, UvcExt, XuQuery};
+use crate::Device;
+
+/// GUID of the user hardware control unit (contains the LED controls).
+pub const USER_HW_CONTROL_GUID: [u8; 16] = [
+    0x82, 0x06, 0x61, 0x63, 0x70, 0x50, 0xab, 0x49, 0xb8, 0xcc, 0xb3, 0x85, 0x5e, 0x8d, 0x22, 0x1f,
+];
+
+/// GUID of the motor control unit (contains the pan/tilt and focus controls).
+pub const MOTOR_CONTROL_GUID: [u8; 16] = [
+    0x82, 0x06, 0x61, 0x63, 0x70, 0x50, 0xab, 0x49, 0xb8, 0xcc, 0xb3, 0x85, 0x5e, 0x8d, 0x22, 0x56,
+];
+
+/// Range of unit IDs that are probed for the vendor controls.
+const CANDIDATE_UNITS: std::ops::RangeInclusive<u8> = 1..=16;
+
+const LED_SELECTOR: u8 = 0x01;
+const LED_SIZE: u16 = 3;
+const PAN_TILT_RELATIVE_SELECTOR: u8 = 0x01;
+const PAN_TILT_RELATIVE_SIZE: u16 = 4;
+const PAN_TILT_RESET_SELECTOR: u8 = 0x02;
+const FOCUS_SELECTOR: u8 = 0x03;
+
+ffi_enum! {
+    /// Operating mode of the camera's LED.
+    pub enum LedMode: u8 {
+        OFF   = 0,
+        ON    = 1,
+        BLINK = 2,
+        /// The camera controls the LED (it is lit while streaming).
+        AUTO  = 3,
+    }
+}
+
+/// Access to the vendor controls of a Logitech webcam.
+pub struct LogitechExt<'a> {
+    device: &'a Device,
+    led_unit: Option<u8>,
+    motor_unit: Option<u8>,
+}
+
+impl<'a> LogitechExt<'a> {
+    /// Probes `device` for the Logitech vendor extension units.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Unsupported`] if none of the vendor controls could
+    /// be found, which typically means that the device is not a Logitech camera.
+    pub fn new(device: &'a Device) -> io::Result<Self> {
+        let mut this = Self {
+            device,
+            led_unit: None,
+            motor_unit: None,
+        };
+
+        let units = UvcExt::new(device).extension_units().unwrap_or_else(|e| {
+            log::debug!("failed to read extension unit descriptors: {}", e);
+            Vec::new()
+        });
+        for unit in &units {
+            match *unit.guid() {
+                USER_HW_CONTROL_GUID => this.led_unit = Some(unit.unit_id()),
+                MOTOR_CONTROL_GUID => this.motor_unit = Some(unit.unit_id()),
+                _ => {}
+            }
+        }
+        if !units.is_empty() {
+            return this.check_found();
+        }
+
+        for unit_id in CANDIDATE_UNITS {
+            let xu = this.unit(unit_id);
+            if this.led_unit.is_none() && probe(&xu, LED_SELECTOR, LED_SIZE) {
+                log::debug!("found Logitech LED control on unit {}", unit_id);
+                this.led_unit = Some(unit_id);
+            } else if this.motor_unit.is_none()
+                && probe(&xu, PAN_TILT_RELATIVE_SELECTOR, PAN_TILT_RELATIVE_SIZE)
+            {
+                log::debug!("found Logitech motor controls on unit {}", unit_id);
+                this.motor_unit = Some(unit_id);
+            }
+        }
+
+        this.check_found()
+    }
+
+    fn check_found(self) -> io::Result<Self> {
+        if self.led_unit.is_none() && self.motor_unit.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "device does not have Logitech vendor controls (not a Logitech camera?)",
+            ));
+        }
+
+        Ok(self)
+    }
+
+    /// Returns the unit ID of the user hardware control unit, if found.
+    pub fn led_unit(&self) -> Option<u8> {
+        self.led_unit
+    }
+
+    /// Returns the unit ID of the motor control unit, if found.
+    pub fn motor_unit(&self) -> Option<u8> {
+        self.motor_unit
+    }
+
+    /// Sets the LED mode and, for [`LedMode::BLINK`], the blinking frequency.
+    ///
+    /// `frequency` is in units of 0.05 Hz.
+    pub fn set_led(&self, mode: LedMode, frequency: u8) -> io::Result<()> {
+        let xu = self.unit(require(self.led_unit, "LED")?);
+        xu.query(LED_SELECTOR, XuQuery::SET_CUR, &mut [mode.0, 0, frequency])
+    }
+
+    /// Moves the camera relative to its current position.
+    ///
+    /// The movement is specified in 1/64th of a degree. Positive values pan right and tilt up.
+    pub fn pan_tilt_relative(&self, pan: i16, tilt: i16) -> io::Result<()> {
+        let xu = self.unit(require(self.motor_unit, "pan/tilt")?);
+        let [p0, p1] = pan.to_le_bytes();
+        let [t0, t1] = tilt.to_le_bytes();
+        xu.query(
+            PAN_TILT_RELATIVE_SELECTOR,
+            XuQuery::SET_CUR,
+            &mut [p0, p1, t0, t1],
+        )
+    }
+
+    /// Moves the camera back to its center position.
+    pub fn pan_tilt_reset(&self) -> io::Result<()> {
+        let xu = self.unit(require(self.motor_unit, "pan/tilt")?);
+        // Bit 0 resets pan, bit 1 resets tilt.
+        xu.set_u8(PAN_TILT_RESET_SELECTOR, 0b11)
+    }
+
+    /// Moves the focus motor to an absolute position (0 is infinity, 255 is closest).
+    pub fn set_focus(&self, value: u8) -> io::Result<()> {
+        let xu = self.unit(require(self.motor_unit, "focus")?);
+        let mut data = vec![0; xu.control_len(FOCUS_SELECTOR)?.into()];
+        if let Some(first) = data.first_mut() {
+            *first = value;
+        }
+        xu.query(FOCUS_SELECTOR, XuQuery::SET_CUR, &mut data)
+    }
+
+    fn unit(&self, unit_id: u8) -> ExtensionUnit<'a> {
+        ExtensionUnit::new(self.device, unit_id)
+    }
+}
+
+/// Returns whether `selector` on `xu` is accessible and has the expected size.
+fn probe(xu: &ExtensionUnit<'_>, selector: u8, size: u16) -> bool {
+    xu.control_info(selector).is_ok() && xu.control_len(selector).ok() == Some(size)
+}
+
+fn require(unit: Option<u8>, what: &str) -> io::Result<u8> {
+    unit.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("camera does not have Logitech {} controls", what),
+        )
+    })
+}
@@ -0,0 +1,112 @@
+//! Pairing of video frames with their UVC metadata.
+
+use std::{collections::VecDeque, io};
+
+use super::UvcMetadata;
+use crate::stream::{ReadBufferView, ReadStream};
+
+/// Maximum number of metadata buffers kept while waiting for the matching video frame.
+const WINDOW: usize = 8;
+
+/// A video capture stream combined with the metadata stream of the same UVC camera.
+///
+/// The `uvcvideo` driver exposes the payload headers of a camera's video stream on a separate
+/// metadata capture device. Both streams assign the same sequence number to the buffers belonging
+/// to the same frame, which [`PairedStream`] uses to match up frames and their metadata.
+pub struct PairedStream {
+    video: ReadStream,
+    meta: ReadStream,
+    /// Sequence numbers and decoded metadata of buffers that were dequeued ahead of their frame.
+    /// Metadata that failed to decode is kept as `None`.
+    pending: VecDeque<(u32, Option<UvcMetadata>)>,
+}
+
+impl PairedStream {
+    /// Creates a paired stream from a video capture stream and the metadata stream of the same
+    /// camera.
+    ///
+    /// The metadata stream has to use the [`UVC`][crate::format::PixelFormat::UVC] format.
+    pub fn new(video: ReadStream, meta: ReadStream) -> Self {
+        Self {
+            video,
+            meta,
+            pending: VecDeque::with_capacity(WINDOW),
+        }
+    }
+
+    /// Returns the contained video and metadata streams.
+    pub fn into_inner(self) -> (ReadStream, ReadStream) {
+        (self.video, self.meta)
+    }
+
+    /// Dequeues a video frame and its metadata, and passes both to `cb`.
+    ///
+    /// The metadata is that of the first payload of the frame. If the metadata buffer belonging to
+    /// the frame was dropped, or could not be decoded, `cb` is passed `None` instead.
+    ///
+    /// Like [`ReadStream::dequeue`], this blocks until a frame is available, unless the video
+    /// stream was opened in non-blocking mode. Dequeuing the metadata blocks until the metadata for
+    /// the frame (or a later one) has arrived, which normally happens before the frame itself.
+    pub fn dequeue<T>(
+        &mut self,
+        cb: impl FnOnce(ReadBufferView<'_>, Option<UvcMetadata>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let meta = &mut self.meta;
+        let pending = &mut self.pending;
+        self.video.dequeue(|view| {
+            let metadata = take_metadata(meta, pending, view.sequence())?;
+            cb(view, metadata)
+        })
+    }
+}
+
+fn take_metadata(
+    meta: &mut ReadStream,
+    pending: &mut VecDeque<(u32, Option<UvcMetadata>)>,
+    sequence: u32,
+) -> io::Result<Option<UvcMetadata>> {
+    loop {
+        while let Some(&(seq, metadata)) = pending.front() {
+            if seq == sequence {
+                pending.pop_front();
+                return Ok(metadata);
+            }
+            if is_before(seq, sequence) {
+                log::debug!(
+                    "discarding metadata of frame #{}, which was not received",
+                    seq
+                );
+                pending.pop_front();
+            } else {
+                // Metadata for later frames is already there, so the one for this frame was lost.
+                log::debug!("no metadata received for frame #{}", sequence);
+                return Ok(None);
+            }
+        }
+
+        let (seq, metadata) = meta.dequeue(|view| {
+            let metadata = match UvcMetadata::parse_all(&view).next() {
+                Some(Ok(metadata)) => Some(metadata),
+                Some(Err(e)) => {
+                    log::warn!("invalid metadata for frame #{}: {}", view.sequence(), e);
+                    None
+                }
+                None => {
+                    log::debug!("empty metadata buffer for frame #{}", view.sequence());
+                    None
+                }
+            };
+            Ok((view.sequence(), metadata))
+        })?;
+
+        if pending.len() == WINDOW {
+            pending.pop_front();
+        }
+        pending.push_back((seq, metadata));
+    }
+}
+
+/// Returns whether sequence number `a` comes before `b`, accounting for wraparound.
+fn is_before(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
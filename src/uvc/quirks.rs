@@ -0,0 +1,107 @@
+//! Camera-specific locations of the controls used by [`UvcExt`][super::UvcExt].
+
+/// Identifies a control on an extension unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XuControl {
+    pub unit_id: u8,
+    pub selector: u8,
+}
+
+impl XuControl {
+    pub const fn new(unit_id: u8, selector: u8) -> Self {
+        Self { unit_id, selector }
+    }
+}
+
+/// Describes where a camera exposes the vendor-specific controls used by [`UvcExt`].
+///
+/// Different camera models place flip, exposure and still image controls on different extension
+/// units and selectors. [`UvcExt::new`] picks the quirks matching the camera's USB vendor and
+/// product ID from a built-in table, falling back to [`UvcQuirks::DEFAULT`]. Use
+/// [`UvcExt::with_quirks`] to specify them manually.
+///
+/// [`UvcExt`]: super::UvcExt
+/// [`UvcExt::new`]: super::UvcExt::new
+/// [`UvcExt::with_quirks`]: super::UvcExt::with_quirks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UvcQuirks {
+    /// A human-readable name of the camera model (or family) these quirks apply to.
+    pub name: &'static str,
+    pub horizontal_flip: XuControl,
+    pub vertical_flip: XuControl,
+    pub exposure_weights: XuControl,
+    pub still_probe: XuControl,
+    pub still_commit: XuControl,
+    pub still_trigger: XuControl,
+}
+
+impl UvcQuirks {
+    /// The control mapping used for cameras that are not in the built-in table.
+    pub const DEFAULT: Self = Self {
+        name: "default",
+        horizontal_flip: XuControl::new(0x03, 0x0c),
+        vertical_flip: XuControl::new(0x03, 0x0d),
+        exposure_weights: XuControl::new(0x03, 0x09),
+        still_probe: XuControl::new(0x03, 0x03),
+        still_commit: XuControl::new(0x03, 0x04),
+        still_trigger: XuControl::new(0x03, 0x05),
+    };
+
+    /// Looks up the quirks of the camera with the given USB vendor and product ID in the built-in
+    /// table.
+    pub fn for_usb_id(vendor_id: u16, product_id: u16) -> Option<&'static Self> {
+        lookup(KNOWN_CAMERAS, vendor_id, product_id)
+    }
+}
+
+impl Default for UvcQuirks {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Cameras whose control mapping differs from [`UvcQuirks::DEFAULT`], keyed by USB VID:PID.
+///
+/// Only add entries that have been verified on real hardware.
+const KNOWN_CAMERAS: &[(u16, u16, UvcQuirks)] = &[];
+
+fn lookup(
+    table: &'static [(u16, u16, UvcQuirks)],
+    vendor_id: u16,
+    product_id: u16,
+) -> Option<&'static UvcQuirks> {
+    table
+        .iter()
+        .find(|(vid, pid, _)| *vid == vendor_id && *pid == product_id)
+        .map(|(_, _, quirks)| quirks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIT_4: UvcQuirks = UvcQuirks {
+        name: "unit 4",
+        horizontal_flip: XuControl::new(0x04, 0x01),
+        vertical_flip: XuControl::new(0x04, 0x02),
+        ..UvcQuirks::DEFAULT
+    };
+
+    const TABLE: &[(u16, u16, UvcQuirks)] = &[
+        (0x1234, 0x0001, UvcQuirks::DEFAULT),
+        (0x1234, 0x0002, UNIT_4),
+    ];
+
+    #[test]
+    fn lookup_by_usb_id() {
+        assert_eq!(lookup(TABLE, 0x1234, 0x0002), Some(&UNIT_4));
+        assert_eq!(lookup(TABLE, 0x1234, 0x0001), Some(&UvcQuirks::DEFAULT));
+        assert_eq!(lookup(TABLE, 0x1234, 0x0003), None);
+        assert_eq!(lookup(TABLE, 0x4321, 0x0002), None);
+    }
+
+    #[test]
+    fn unknown_camera() {
+        assert_eq!(UvcQuirks::for_usb_id(0xffff, 0xffff), None);
+    }
+}
@@ -2,14 +2,23 @@ use nix::ioctl_readwrite;
 
 // These are from `linux/usb/video.h`
 ffi_enum! {
+    /// A UVC control request, issued via [`ExtensionUnit::query`][crate::uvc::ExtensionUnit::query].
     pub enum XuQuery: u8 {
+        /// Sets the current value of the control.
         SET_CUR  = 0x01,
+        /// Reads the current value of the control.
         GET_CUR  = 0x81,
+        /// Reads the minimum value of the control.
         GET_MIN  = 0x82,
+        /// Reads the maximum value of the control.
         GET_MAX  = 0x83,
+        /// Reads the resolution (step size) of the control.
         GET_RES  = 0x84,
+        /// Reads the size of the control's value in bytes (as a 2-byte little-endian integer).
         GET_LEN  = 0x85,
+        /// Reads the control's capabilities (a 1-byte [`ControlInfo`][crate::uvc::ControlInfo]).
         GET_INFO = 0x86,
+        /// Reads the default value of the control.
         GET_DEF  = 0x87,
     }
 }
@@ -0,0 +1,804 @@
+//! Tests against the `vivid` virtual video driver.
+//!
+//! These are ignored by default, since they need the driver to be loaded (`modprobe vivid`). Run
+//! them with `cargo test --test vivid -- --ignored`.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use std::{io, mem, thread};
+
+use linuxvideo::controls::{
+    Area, Cid, ControlBatch, ControlChanges, ControlDesc, ControlValue, CtrlClass, CtrlType,
+    PowerLineFrequency,
+};
+use linuxvideo::format::{Format, FrameSizes, PixFormat, PixelFormat};
+use linuxvideo::stream::{AllBuffersHeld, ReadStream, StreamSet};
+use linuxvideo::{AccessMode, BufType, CapabilityFlags, Device, OpenOptions};
+
+/// Serializes the tests, since they all change the inputs, formats and controls of the same
+/// device nodes.
+static VIVID: Mutex<()> = Mutex::new(());
+
+/// Opens the first vivid node whose device capabilities contain `caps`.
+///
+/// The returned guard keeps other tests from using vivid until it is dropped.
+fn vivid(caps: CapabilityFlags) -> (MutexGuard<'static, ()>, Device) {
+    // A failing test poisons the lock, which shouldn't fail the remaining ones.
+    let guard = VIVID.lock().unwrap_or_else(PoisonError::into_inner);
+    let info = linuxvideo::list()
+        .unwrap()
+        .into_iter()
+        .find(|info| {
+            info.capabilities()
+                .is_some_and(|c| c.driver() == "vivid" && c.device_capabilities().contains(caps))
+        })
+        .unwrap_or_else(|| panic!("no vivid device with {:?}", caps));
+    (guard, info.open().unwrap())
+}
+
+fn capture() -> (MutexGuard<'static, ()>, Device) {
+    vivid(CapabilityFlags::VIDEO_CAPTURE)
+}
+
+/// Starts a stream with the device's current capture format.
+fn capture_stream(device: Device) -> io::Result<ReadStream> {
+    let Format::VideoCapture(fmt) = device.format(BufType::VIDEO_CAPTURE)? else {
+        unreachable!()
+    };
+    device.video_capture(fmt)?.into_stream()
+}
+
+fn all_controls(device: &Device) -> Vec<ControlDesc> {
+    device.controls().collect::<io::Result<_>>().unwrap()
+}
+
+fn find_control(controls: &[ControlDesc], ty: CtrlType) -> &ControlDesc {
+    controls
+        .iter()
+        .find(|desc| desc.control_type() == ty)
+        .unwrap_or_else(|| panic!("no control of type {:?}", ty))
+}
+
+/// Switches to an input with stepwise frame sizes, returning the previously selected input.
+///
+/// The default webcam input only supports a few discrete sizes.
+fn select_stepwise_input(device: &mut Device) -> u32 {
+    let prev = device.input().unwrap();
+    let input = device
+        .inputs()
+        .map(Result::unwrap)
+        .position(|input| !input.name().starts_with("Webcam"))
+        .expect("no non-webcam input");
+    device.set_input(input as u32).unwrap();
+    prev
+}
+
+/// Exporting buffers must not invalidate the mappings used for dequeuing.
+#[test]
+#[ignore = "requires vivid"]
+fn export_keeps_mappings() -> io::Result<()> {
+    let (_lock, device) = capture();
+    let mut stream = capture_stream(device)?;
+    let fds = stream.export_all()?;
+    assert_eq!(fds.len(), stream.num_buffers() as usize);
+
+    let mut mappings = HashMap::new();
+    for _ in 0..stream.num_buffers() * 3 {
+        stream.dequeue(|view| {
+            let ptr = view.raw_buffer().as_ptr();
+            assert_eq!(*mappings.entry(view.index()).or_insert(ptr), ptr);
+            // Touch the entire mapping; this faults if it was invalidated.
+            let sum = view
+                .raw_buffer()
+                .iter()
+                .fold(0u8, |a, b| a.wrapping_add(*b));
+            std::hint::black_box(sum);
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
+/// Frames must keep arriving after stopping and restarting a stream.
+#[test]
+#[ignore = "requires vivid"]
+fn stream_off_on_roundtrip() -> io::Result<()> {
+    let (_lock, device) = capture();
+    let mut stream = capture_stream(device)?;
+
+    for _ in 0..3 {
+        for _ in 0..stream.num_buffers() * 2 {
+            stream.dequeue(|_| Ok(()))?;
+        }
+        stream.stream_off()?;
+        assert!(stream.dequeue(|_| Ok(())).is_err());
+        stream.stream_on()?;
+        assert_eq!(stream.held_buffers(), 0);
+    }
+    Ok(())
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn slow_consumer_drops_frames() -> io::Result<()> {
+    let (_lock, device) = capture();
+    let mut stream = capture_stream(device)?;
+
+    let mut gaps = 0;
+    for _ in 0..stream.num_buffers() * 4 {
+        stream.dequeue(|view| {
+            gaps += u64::from(view.gap_since_previous());
+            // Hold the buffer long enough for the driver to run out of queued buffers.
+            thread::sleep(Duration::from_millis(200));
+            Ok(())
+        })?;
+    }
+    assert_ne!(stream.dropped_frames(), 0);
+    assert_eq!(stream.dropped_frames(), gaps);
+
+    // Restarting a running stream keeps the count.
+    stream.stream_on()?;
+    assert_eq!(stream.dropped_frames(), gaps);
+
+    stream.stream_off()?;
+    assert_eq!(stream.dropped_frames(), 0);
+    Ok(())
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn frames_are_recycled() -> io::Result<()> {
+    let (_lock, device) = capture();
+    let mut stream = capture_stream(device)?;
+    let mut frames = stream.frames().with_pool_size(1);
+
+    let first = frames.next().unwrap();
+    let ptr = first.as_ptr();
+    frames.recycle(first);
+    let second = frames.next().unwrap();
+    assert_eq!(second.as_ptr(), ptr);
+    assert!(frames.error().is_none());
+    Ok(())
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn into_device_allows_new_stream() -> io::Result<()> {
+    let (_lock, device) = capture();
+    let mut stream = capture_stream(device)?;
+    stream.dequeue(|_| Ok(()))?;
+
+    // Without freeing the buffers, setting the format would fail with `EBUSY`.
+    let mut stream = capture_stream(stream.into_device()?)?;
+    stream.dequeue(|_| Ok(()))?;
+    stream.close()
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn poll_single_stream() -> io::Result<()> {
+    let (_lock, device) = capture();
+    let mut stream = capture_stream(device)?;
+
+    let mut set = StreamSet::new();
+    assert_eq!(set.push(&mut stream), 0);
+    let mut frames = 0;
+    while frames < 5 {
+        frames += set.poll(Duration::from_secs(1), |index, _| {
+            assert_eq!(index, 0);
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn hold_all_buffers() -> io::Result<()> {
+    let (_lock, device) = capture();
+    let mut stream = capture_stream(device)?;
+
+    {
+        let guards = (0..stream.num_buffers())
+            .map(|_| stream.dequeue_frame())
+            .collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(stream.held_buffers(), stream.num_buffers());
+
+        let err = stream.dequeue_frame().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        let held = AllBuffersHeld::from_io_error(&err).unwrap();
+        assert_eq!(held.buffers(), stream.num_buffers());
+
+        let mut guards = guards.into_iter();
+        guards.next().unwrap().requeue()?;
+        assert_eq!(stream.held_buffers(), stream.num_buffers() - 1);
+        // Leak the remaining guards, so their buffers are not enqueued again.
+        guards.for_each(mem::forget);
+    }
+
+    assert_eq!(stream.held_buffers(), stream.num_buffers() - 1);
+    stream.requeue_all()?;
+    assert_eq!(stream.held_buffers(), 0);
+    stream.dequeue(|_| Ok(()))
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn output_prequeue() -> io::Result<()> {
+    let (_lock, device) = vivid(CapabilityFlags::VIDEO_OUTPUT);
+    let Format::VideoOutput(fmt) = device.format(BufType::VIDEO_OUTPUT)? else {
+        unreachable!()
+    };
+    let mut stream = device.video_output(fmt)?.into_stream_num_buffers(4)?;
+    stream.set_min_queued(stream.num_buffers());
+
+    for i in 1..stream.num_buffers() {
+        stream.enqueue(|_| Ok(()))?;
+        assert_eq!(stream.queued_buffers(), i);
+        assert!(!stream.is_streaming());
+    }
+    stream.enqueue(|_| Ok(()))?;
+    assert!(stream.is_streaming());
+
+    for _ in 0..stream.num_buffers() * 2 {
+        stream.enqueue(|_| Ok(()))?;
+    }
+
+    stream.stream_off()?;
+    stream.set_auto_start(false);
+    for _ in 0..stream.num_buffers() {
+        stream.enqueue(|_| Ok(()))?;
+    }
+    let err = stream.enqueue(|_| Ok(())).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+    stream.stream_on()?;
+    assert!(stream.is_streaming());
+    stream.enqueue(|_| Ok(()))
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn share_across_threads() -> io::Result<()> {
+    let (_lock, device) = capture();
+    let mut stream = capture_stream(device)?.into_shared();
+
+    let frames = (0..stream.get_ref().num_buffers())
+        .map(|_| stream.dequeue())
+        .collect::<io::Result<Vec<_>>>()?;
+    let err = stream.dequeue().err().unwrap();
+    assert!(AllBuffersHeld::from_io_error(&err).is_some());
+
+    let consumers: Vec<_> = frames
+        .iter()
+        .flat_map(|frame| [frame.clone(), frame.clone()])
+        .map(|frame| thread::spawn(move || frame.iter().fold(0u8, |a, b| a.wrapping_add(*b))))
+        .collect();
+    drop(frames);
+    for consumer in consumers {
+        consumer.join().unwrap();
+    }
+
+    // All references are gone, so the buffers were enqueued again.
+    assert_eq!(stream.get_ref().held_buffers(), 0);
+    stream.dequeue()?;
+    Ok(())
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn create_buffers_while_streaming() -> io::Result<()> {
+    let (_lock, device) = capture();
+    let mut stream = capture_stream(device)?;
+    let before = stream.num_buffers();
+    stream.dequeue(|_| Ok(()))?;
+
+    let created = stream.create_buffers(2)?;
+    assert_eq!(created.start, before);
+    assert_eq!(stream.num_buffers(), created.end);
+    assert_eq!(stream.held_buffers(), 0);
+
+    // Every buffer, including the new ones, gets filled eventually.
+    let mut seen = vec![false; stream.num_buffers() as usize];
+    for _ in 0..stream.num_buffers() * 2 {
+        stream.dequeue(|view| {
+            seen[view.index() as usize] = true;
+            Ok(())
+        })?;
+    }
+    assert!(seen.iter().all(|&s| s));
+    Ok(())
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn meta_output_refill() -> io::Result<()> {
+    let (_lock, device) = vivid(CapabilityFlags::META_OUTPUT);
+    let Format::MetaOutput(fmt) = device.format(BufType::META_OUTPUT)? else {
+        unreachable!()
+    };
+    let device = device.meta_output(fmt)?;
+    let buffer_size = device.format().buffer_size() as usize;
+    let mut stream = device.into_stream_num_buffers(2)?;
+
+    for i in 0..stream.num_buffers() * 3 {
+        stream.enqueue(|mut buf| {
+            assert_eq!(buf.len(), buffer_size);
+            buf.fill(i as u8);
+            Ok(())
+        })?;
+    }
+    assert!(stream.is_streaming());
+    Ok(())
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn stepwise_sizes() {
+    let (_lock, mut device) = capture();
+    let prev_input = select_stepwise_input(&mut device);
+
+    let sizes = device.frame_sizes(PixelFormat::YUYV).unwrap();
+    let FrameSizes::Stepwise(range) = &sizes else {
+        panic!("expected stepwise sizes, got {}", sizes);
+    };
+    assert!(range.step_width() > 1 || range.step_height() > 1);
+    let (width, height) = range.clamp(641, 481);
+    assert!(sizes.contains(width, height));
+    assert!(!sizes.contains(range.max_width() + 1, height));
+
+    let format = Format::VideoCapture(PixFormat::new(width, height, PixelFormat::YUYV));
+    let Format::VideoCapture(tried) = device.try_format(format).unwrap() else {
+        unreachable!()
+    };
+    assert_eq!((tried.width(), tried.height()), (width, height));
+    device
+        .frame_intervals(PixelFormat::YUYV, width, height)
+        .unwrap();
+
+    device.set_input(prev_input).unwrap();
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn aligned_stride() {
+    let (_lock, mut device) = capture();
+    let path = device.path().unwrap();
+    let prev_input = select_stepwise_input(&mut device);
+
+    // 650 YUYV pixels take 1300 bytes, so a 256-byte aligned stride needs padding.
+    let format = PixFormat::new(650, 480, PixelFormat::YUYV).with_bytes_per_line(1536);
+    let capture = device.video_capture(format).unwrap();
+    let format = capture.format();
+    assert_eq!(format.width(), 650);
+    assert_eq!(format.bytes_per_line(), 1536);
+    assert!(format.size_image() >= 1536 * 480);
+    assert!(!capture.format_adjusted());
+
+    let mut stream = capture.into_stream().unwrap();
+    let len = stream.dequeue(|view| Ok(view.len())).unwrap();
+    assert!(len >= 1536 * 480);
+
+    drop(stream);
+    Device::open(path).unwrap().set_input(prev_input).unwrap();
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn compound_controls() {
+    let (_lock, device) = capture();
+    let controls = all_controls(&device);
+
+    // The first control and the user class itself must not be skipped.
+    assert!(controls.iter().any(|desc| desc.id() == Cid::BRIGHTNESS));
+    let user_class = find_control(&controls, CtrlType::CTRL_CLASS);
+    assert_eq!(user_class.class(), CtrlClass::USER);
+
+    let int64 = find_control(&controls, CtrlType::INTEGER64);
+    assert!(int64.maximum() > i64::from(i32::MAX));
+
+    // vivid has a 2-dimensional array control, which `VIDIOC_QUERYCTRL` can't report.
+    let array = controls
+        .iter()
+        .find(|desc| desc.dims().len() == 2)
+        .expect("no 2-dimensional array control");
+    assert_eq!(
+        array.elems(),
+        array.dims().iter().product::<u32>(),
+        "{:?}",
+        array
+    );
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn menus() {
+    let (_lock, device) = capture();
+    let controls = all_controls(&device);
+
+    // vivid's "Menu" control skips some of its indices.
+    let menu = find_control(&controls, CtrlType::MENU);
+    let items = device
+        .menu_items(menu)
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap();
+    assert!(items.iter().all(|item| item.name().is_some()));
+    assert!((items.len() as i64) < menu.maximum() - menu.minimum() + 1);
+    assert_eq!(items.last().unwrap().index() as i64, menu.maximum());
+
+    let menu = find_control(&controls, CtrlType::INTEGER_MENU);
+    for item in device.menu_items(menu) {
+        assert!(item.unwrap().value().is_some());
+    }
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn integer_controls() {
+    let (_lock, mut device) = capture();
+    let controls = all_controls(&device);
+
+    for (ty, value) in [
+        (CtrlType::INTEGER, None),
+        (CtrlType::INTEGER64, Some(i64::from(i32::MAX) + 1)),
+        (CtrlType::BOOLEAN, None),
+        (CtrlType::MENU, None),
+    ] {
+        let desc = find_control(&controls, ty);
+        let prev = device.control(desc.id()).unwrap();
+        let value = value.unwrap_or(desc.maximum());
+        device.set_control(desc.id(), value).unwrap();
+        assert_eq!(device.control(desc.id()).unwrap(), value, "{:?}", desc);
+        device.set_control(desc.id(), prev).unwrap();
+    }
+
+    let menu = find_control(&controls, CtrlType::MENU);
+    let e = device
+        .set_control(menu.id(), menu.maximum() + 1)
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput, "{}", e);
+
+    let string = find_control(&controls, CtrlType::STRING);
+    let e = device.control(string.id()).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput, "{}", e);
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn payload_controls() {
+    let (_lock, mut device) = capture();
+    let controls = all_controls(&device);
+
+    let string = find_control(&controls, CtrlType::STRING);
+    let prev = device.control_value(string).unwrap();
+    let value = ControlValue::String("ab".repeat(string.maximum() as usize / 2));
+    device.set_control_value(string, &value).unwrap();
+    assert_eq!(device.control_value(string).unwrap(), value);
+    device.set_control_value(string, &prev).unwrap();
+
+    // vivid's `U16` control is a 2-dimensional matrix.
+    let matrix = find_control(&controls, CtrlType::U16);
+    let ControlValue::U16Array(elems) = device.control_value(matrix).unwrap() else {
+        panic!("expected a U16 array");
+    };
+    assert_eq!(elems.len(), matrix.dims().iter().product::<u32>() as usize);
+    let value = ControlValue::U16Array(elems.iter().map(|&e| e ^ 1).collect());
+    device.set_control_value(matrix, &value).unwrap();
+    assert_eq!(device.control_value(matrix).unwrap(), value);
+
+    let array = find_control(&controls, CtrlType::U32);
+    let e = device
+        .set_control_value(array, &ControlValue::Integer(0))
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn batch() {
+    let (_lock, mut device) = capture();
+    let cids = [Cid::BRIGHTNESS, Cid::CONTRAST];
+    let prev = device.read_controls(cids).unwrap();
+    assert_eq!(prev.len(), 2);
+
+    let batch = ControlBatch::new()
+        .with(Cid::BRIGHTNESS, 10)
+        .with(Cid::CONTRAST, 20);
+    device.apply_controls(&batch).unwrap();
+    assert_eq!(device.control(Cid::BRIGHTNESS).unwrap(), 10);
+    assert_eq!(device.control(Cid::CONTRAST).unwrap(), 20);
+
+    // The menu control rejects out-of-range values (integer controls are clamped instead).
+    let menu = find_control(&all_controls(&device), CtrlType::MENU).clone();
+    let batch = ControlBatch::new()
+        .with(Cid::BRIGHTNESS, 30)
+        .with(menu.id(), menu.maximum() + 1);
+    let e = device.apply_controls(&batch).unwrap_err();
+    assert_eq!((e.index(), e.cid()), (Some(1), Some(menu.id())), "{}", e);
+    assert!(!e.partially_applied());
+    assert_eq!(device.control(Cid::BRIGHTNESS).unwrap(), 10);
+
+    device.apply_controls(&prev).unwrap();
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn control_events() {
+    let (_lock, mut device) = capture();
+    let mut other = Device::open(device.path().unwrap()).unwrap();
+    device.subscribe_control_events(Cid::BRIGHTNESS).unwrap();
+    assert_eq!(device.try_dequeue_event().unwrap(), None);
+
+    let prev = other.control(Cid::BRIGHTNESS).unwrap();
+    let value = if prev == 100 { 101 } else { 100 };
+    other.set_control(Cid::BRIGHTNESS, value).unwrap();
+    let ev = device.dequeue_event().unwrap();
+    assert_eq!((ev.id(), ev.value()), (Cid::BRIGHTNESS, value));
+    assert!(ev.changes().contains(ControlChanges::VALUE));
+
+    device.unsubscribe_control_events(Cid::BRIGHTNESS).unwrap();
+    other.set_control(Cid::BRIGHTNESS, prev).unwrap();
+    assert_eq!(device.try_dequeue_event().unwrap(), None);
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn reset() {
+    let (_lock, mut device) = capture();
+    let desc = device.query_control(Cid::BRIGHTNESS).unwrap();
+    device
+        .set_control(Cid::BRIGHTNESS, desc.default_value() + 1)
+        .unwrap();
+    device.reset_control(Cid::BRIGHTNESS).unwrap();
+    assert_eq!(
+        device.control(Cid::BRIGHTNESS).unwrap(),
+        desc.default_value()
+    );
+
+    let contrast = device.query_control(Cid::CONTRAST).unwrap();
+    device
+        .set_control(Cid::CONTRAST, contrast.default_value() + 1)
+        .unwrap();
+    let report = device.reset_controls().unwrap();
+    assert!(report.reset().contains(&Cid::CONTRAST), "{}", report);
+    assert_eq!(
+        device.control(Cid::CONTRAST).unwrap(),
+        contrast.default_value()
+    );
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn defaults() {
+    let (_lock, mut device) = capture();
+    let desc = device.query_control(Cid::BRIGHTNESS).unwrap();
+    let prev = device.control(Cid::BRIGHTNESS).unwrap();
+    device
+        .set_control(Cid::BRIGHTNESS, desc.default_value() + 1)
+        .unwrap();
+    let default = device.control_default(Cid::BRIGHTNESS).unwrap();
+    assert_eq!(default.as_i64(), Some(desc.default_value()));
+
+    let string = find_control(&all_controls(&device), CtrlType::STRING).id();
+    let default = device.control_default(string).unwrap();
+    assert!(matches!(default, ControlValue::String(_)), "{:?}", default);
+
+    let defaults = device.read_class_defaults(CtrlClass::USER).unwrap();
+    assert_eq!(
+        defaults.get(Cid::BRIGHTNESS).and_then(ControlValue::as_i64),
+        Some(desc.default_value())
+    );
+    assert_eq!(
+        device.control(Cid::BRIGHTNESS).unwrap(),
+        desc.default_value() + 1
+    );
+    device.set_control(Cid::BRIGHTNESS, prev).unwrap();
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn menu_holes() {
+    let (_lock, mut device) = capture();
+    let menu = find_control(&all_controls(&device), CtrlType::MENU).clone();
+    let indices = device
+        .menu_items(&menu)
+        .map(|item| i64::from(item.unwrap().index()))
+        .collect::<Vec<_>>();
+    let hole = (menu.minimum()..=menu.maximum())
+        .find(|index| !indices.contains(index))
+        .expect("vivid menu control has no holes");
+
+    assert_eq!(menu.validate(hole), Ok(()));
+    let e = device.validate_control(&menu, hole).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+    let prev = device.control(menu.id()).unwrap();
+    let clamped = device
+        .set_control_clamped(menu.id(), hole)
+        .unwrap()
+        .unwrap();
+    assert!(indices.contains(&clamped));
+    assert_eq!(device.control(menu.id()).unwrap(), clamped);
+    assert_eq!(device.set_control_clamped(menu.id(), prev).unwrap(), None);
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn ptz_unsupported() {
+    // vivid has no PTZ controls.
+    let (_lock, mut device) = capture();
+    let mut ptz = device.ptz();
+    assert_eq!(ptz.pan().unwrap_err().kind(), io::ErrorKind::Unsupported);
+    assert_eq!(
+        ptz.nudge_tilt(1.0).unwrap_err().kind(),
+        io::ErrorKind::Unsupported
+    );
+    assert_eq!(ptz.reset().unwrap_err().kind(), io::ErrorKind::Unsupported);
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn white_balance() {
+    // vivid has no white balance controls.
+    let (_lock, mut device) = capture();
+    let e = device.white_balance().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::Unsupported, "{}", e);
+    let e = device.set_white_balance_temperature(5000).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::Unsupported, "{}", e);
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn focus() {
+    // vivid has no focus controls.
+    let (_lock, mut device) = capture();
+    assert_eq!(device.autofocus_status().unwrap(), None);
+    let e = device.nudge_focus(10).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::Unsupported, "{}", e);
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn power_line_frequency() {
+    let (_lock, mut device) = capture();
+    let supported = device
+        .supported_menu_values::<PowerLineFrequency>()
+        .unwrap();
+    if supported.is_empty() {
+        let e = device.power_line_frequency().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::Unsupported, "{}", e);
+        return;
+    }
+
+    let prev = device.power_line_frequency().unwrap();
+    for &freq in &supported {
+        device.set_power_line_frequency(freq).unwrap();
+        assert_eq!(device.power_line_frequency().unwrap(), freq);
+    }
+    device.set_power_line_frequency(prev).unwrap();
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn mixed_class_batch() {
+    let (_lock, mut device) = capture();
+    let user = device
+        .controls_in_class(CtrlClass::USER)
+        .map(Result::unwrap)
+        .find(|desc| desc.id() == Cid::BRIGHTNESS)
+        .unwrap();
+    // vivid's own test controls are in a driver-specific class.
+    let other = all_controls(&device)
+        .into_iter()
+        .find(|desc| {
+            desc.class() != user.class()
+                && desc.control_type() == CtrlType::BOOLEAN
+                && desc.is_actionable()
+        })
+        .unwrap();
+    let prev = device.read_controls([user.id(), other.id()]).unwrap();
+
+    let value = prev.get(other.id()).and_then(ControlValue::as_i64).unwrap();
+    let batch = ControlBatch::new()
+        .with(user.id(), user.minimum())
+        .with(other.id(), 1 - value);
+    device.apply_controls(&batch).unwrap();
+    assert_eq!(device.control(user.id()).unwrap(), user.minimum());
+    assert_eq!(device.control(other.id()).unwrap(), 1 - value);
+
+    device.apply_controls(&prev).unwrap();
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn area_control() {
+    let (_lock, mut device) = capture();
+    let desc = find_control(&all_controls(&device), CtrlType::AREA).clone();
+    assert_eq!(desc.elem_size(), 8);
+
+    let ControlValue::Area(prev) = device.control_value(&desc).unwrap() else {
+        panic!("expected an area");
+    };
+    let value = ControlValue::Area(Area {
+        width: prev.width + 1,
+        height: prev.height + 1,
+    });
+    device.set_control_value(&desc, &value).unwrap();
+    assert_eq!(device.control_value(&desc).unwrap(), value);
+
+    let raw = ControlValue::Compound {
+        elem_size: 8,
+        data: [prev.width.to_ne_bytes(), prev.height.to_ne_bytes()].concat(),
+    };
+    device.set_control_value(&desc, &raw).unwrap();
+    assert_eq!(
+        device.control_value(&desc).unwrap(),
+        ControlValue::Area(prev)
+    );
+    let wrong = ControlValue::Compound {
+        elem_size: 4,
+        data: vec![0; 8],
+    };
+    let e = device.set_control_value(&desc, &wrong).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn button() {
+    let (_lock, mut device) = capture();
+    let button = all_controls(&device)
+        .into_iter()
+        .find(ControlDesc::is_button)
+        .expect("no button control");
+    device.press_button(button.id()).unwrap();
+
+    let e = device.press_button(Cid::BRIGHTNESS).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    assert!(e.to_string().contains("not a button control"), "{}", e);
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn snapshot() {
+    let (_lock, mut device) = capture();
+    let snapshot = device.control_snapshot().unwrap();
+    assert!(snapshot
+        .iter()
+        .any(|(_, value)| matches!(value, ControlValue::String(_))));
+
+    let brightness = snapshot.get(Cid::BRIGHTNESS).unwrap().as_i64().unwrap();
+    device
+        .set_control(Cid::BRIGHTNESS, if brightness == 0 { 1 } else { 0 })
+        .unwrap();
+    let report = device.apply_snapshot(&snapshot).unwrap();
+    assert!(report.is_complete(), "{}", report);
+    assert_eq!(device.control(Cid::BRIGHTNESS).unwrap(), brightness);
+    assert_eq!(device.control_snapshot().unwrap(), snapshot);
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn list() {
+    let (_lock, device) = capture();
+    let path = device.path().unwrap();
+    let devices = linuxvideo::list().unwrap();
+    let info = devices.iter().find(|info| info.path() == path).unwrap();
+    assert_eq!(info.capabilities().unwrap().driver(), "vivid");
+    assert!(info.error().is_none());
+}
+
+#[test]
+#[ignore = "requires vivid"]
+fn open_with() {
+    let (_lock, device) = capture();
+    let path = device.path().unwrap();
+    assert_eq!(device.open_options().unwrap(), OpenOptions::new());
+
+    let options = OpenOptions::new()
+        .with_access(AccessMode::ReadOnly)
+        .with_non_blocking(true);
+    let device = Device::open_with(&path, options).unwrap();
+    assert_eq!(device.open_options().unwrap(), options);
+    assert!(device.is_nonblocking().unwrap());
+}